@@ -0,0 +1,190 @@
+// Copyright (C) 2019-2020 Boyu Yang
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+use std::{fmt, str::FromStr};
+
+use bech32::{FromBase32 as _, ToBase32 as _};
+use property::Property;
+use uckb_jsonrpc_core::types::{bytes::Bytes, packed, prelude::*};
+
+use crate::error::{Error, Result};
+
+const FORMAT_SHORT: u8 = 0x01;
+const FORMAT_FULL_DATA: u8 = 0x02;
+const FORMAT_FULL_TYPE: u8 = 0x04;
+
+const HASH_TYPE_DATA: u8 = 0;
+const HASH_TYPE_TYPE: u8 = 1;
+
+// The code hashes which have indexes in the short format, all of them use the hash type "type"
+// and 20 bytes args.
+const SHORT_CODE_HASHES: &[[u8; 32]] = &[
+    // SECP256K1/blake160
+    [
+        0x9b, 0xd7, 0xe0, 0x6f, 0x3e, 0xcf, 0x4b, 0xe0, 0xf2, 0xfc, 0xd2, 0x18, 0x8b, 0x23, 0xf1,
+        0xb9, 0xfc, 0xc8, 0x8e, 0x5d, 0x4b, 0x65, 0xa8, 0x63, 0x7b, 0x17, 0x72, 0x3b, 0xbd, 0xa3,
+        0xcc, 0xe8,
+    ],
+    // SECP256K1/multisig
+    [
+        0x5c, 0x50, 0x69, 0xeb, 0x08, 0x57, 0xef, 0xc6, 0x5e, 0x1b, 0xca, 0x0c, 0x07, 0xdf, 0x34,
+        0xc3, 0x16, 0x63, 0xb3, 0x62, 0x2f, 0xd3, 0x87, 0x6c, 0x87, 0x63, 0x20, 0xfc, 0x96, 0x34,
+        0xe2, 0xa8,
+    ],
+];
+const SHORT_ARGS_LEN: usize = 20;
+
+/// The network of an address, which decides the human-readable prefix.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Network {
+    Mainnet,
+    Testnet,
+}
+
+/// A lock script in the form of an address.
+///
+/// An address could be in the full format, which includes the whole script, or in the short
+/// format, which only works for the well-known locks and includes the index of the code hash.
+#[derive(Property, Debug, Clone, PartialEq, Eq)]
+#[property(get(public), set(disable), mut(disable))]
+pub struct Address {
+    network: Network,
+    code_hash: Vec<u8>,
+    hash_type: u8,
+    args: Vec<u8>,
+}
+
+impl Network {
+    pub fn prefix(self) -> &'static str {
+        match self {
+            Self::Mainnet => "ckb",
+            Self::Testnet => "ckt",
+        }
+    }
+
+    fn from_prefix(prefix: &str) -> Option<Self> {
+        match prefix {
+            "ckb" => Some(Self::Mainnet),
+            "ckt" => Some(Self::Testnet),
+            _ => None,
+        }
+    }
+}
+
+impl fmt::Display for Network {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Self::Mainnet => write!(f, "mainnet"),
+            Self::Testnet => write!(f, "testnet"),
+        }
+    }
+}
+
+impl FromStr for Network {
+    type Err = Error;
+    fn from_str(s: &str) -> Result<Self> {
+        match s {
+            "mainnet" => Ok(Self::Mainnet),
+            "testnet" => Ok(Self::Testnet),
+            _ => Err(Error::Address(format!("unknown network {}", s))),
+        }
+    }
+}
+
+impl Address {
+    /// Creates an address from the fields of a script, the hash type is `0` for "data" and `1`
+    /// for "type".
+    pub fn new(network: Network, code_hash: &[u8], hash_type: u8, args: &[u8]) -> Result<Self> {
+        if code_hash.len() != 32 {
+            return Err(Error::Address(format!(
+                "incorrect length of code hash {}",
+                code_hash.len()
+            )));
+        }
+        if hash_type != HASH_TYPE_DATA && hash_type != HASH_TYPE_TYPE {
+            return Err(Error::Address(format!("unknown hash type {}", hash_type)));
+        }
+        Ok(Self {
+            network,
+            code_hash: code_hash.to_vec(),
+            hash_type,
+            args: args.to_vec(),
+        })
+    }
+
+    /// Encodes the address in the full format.
+    pub fn to_full(&self) -> String {
+        let format = if self.hash_type == HASH_TYPE_TYPE {
+            FORMAT_FULL_TYPE
+        } else {
+            FORMAT_FULL_DATA
+        };
+        let mut payload = vec![format];
+        payload.extend_from_slice(&self.code_hash);
+        payload.extend_from_slice(&self.args);
+        self.encode(&payload)
+    }
+
+    /// Encodes the address in the short format, if the lock is a well-known one.
+    pub fn to_short(&self) -> Option<String> {
+        if self.hash_type != HASH_TYPE_TYPE || self.args.len() != SHORT_ARGS_LEN {
+            return None;
+        }
+        SHORT_CODE_HASHES
+            .iter()
+            .position(|code_hash| &code_hash[..] == self.code_hash.as_slice())
+            .map(|index| {
+                let mut payload = vec![FORMAT_SHORT, index as u8];
+                payload.extend_from_slice(&self.args);
+                self.encode(&payload)
+            })
+    }
+
+    /// The hash of the lock script, as `lock_hash` in the storage.
+    pub fn script_hash(&self) -> Vec<u8> {
+        packed::Script::new_builder()
+            .code_hash(packed::Byte32::from_slice(&self.code_hash).expect("checked length"))
+            .hash_type(self.hash_type.into())
+            .args(Bytes::from(self.args.clone()).pack())
+            .build()
+            .calc_script_hash()
+            .raw_data()
+            .to_vec()
+    }
+
+    fn encode(&self, payload: &[u8]) -> String {
+        bech32::encode(self.network.prefix(), payload.to_base32()).expect("the prefix is valid")
+    }
+}
+
+impl FromStr for Address {
+    type Err = Error;
+    fn from_str(s: &str) -> Result<Self> {
+        let (prefix, data) =
+            bech32::decode(s).map_err(|err| Error::Address(format!("{} ({})", s, err)))?;
+        let network = Network::from_prefix(&prefix)
+            .ok_or_else(|| Error::Address(format!("unknown prefix {}", prefix)))?;
+        let payload = Vec::<u8>::from_base32(&data)
+            .map_err(|err| Error::Address(format!("{} ({})", s, err)))?;
+        match payload.split_first() {
+            Some((&FORMAT_SHORT, rest)) if rest.len() == 1 + SHORT_ARGS_LEN => {
+                let code_hash = SHORT_CODE_HASHES.get(rest[0] as usize).ok_or_else(|| {
+                    Error::Address(format!("unknown code hash index {}", rest[0]))
+                })?;
+                Self::new(network, code_hash, HASH_TYPE_TYPE, &rest[1..])
+            }
+            Some((&FORMAT_FULL_DATA, rest)) if rest.len() >= 32 => {
+                Self::new(network, &rest[..32], HASH_TYPE_DATA, &rest[32..])
+            }
+            Some((&FORMAT_FULL_TYPE, rest)) if rest.len() >= 32 => {
+                Self::new(network, &rest[..32], HASH_TYPE_TYPE, &rest[32..])
+            }
+            _ => Err(Error::Address(format!("unsupported format of {}", s))),
+        }
+    }
+}
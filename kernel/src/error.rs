@@ -6,7 +6,10 @@
 // option. This file may not be copied, modified, or distributed
 // except according to those terms.
 
+use std::io;
+
 use thiserror::Error;
+use uckb_jsonrpc_client::error::Error as RpcError;
 use uckb_jsonrpc_core::types::fixed::H256;
 
 use crate::postgres as pg;
@@ -15,9 +18,19 @@ use crate::postgres as pg;
 pub enum Error {
     #[error("inner db error: {0}")]
     InnerDB(#[from] pg::Error),
+    #[error("inner sqlite error: {0}")]
+    InnerSqlite(#[from] rusqlite::Error),
+    #[error("rpc error: {0}")]
+    Rpc(#[from] RpcError),
+    #[error("io error: {0}")]
+    IO(#[from] io::Error),
+    #[error("json error: {0}")]
+    Json(#[from] serde_json::Error),
 
     #[error("data error: {0}")]
     Data(String),
+    #[error("address error: {0}")]
+    Address(String),
 
     #[error("data error: unknown parent block ({number}, {hash:#x})")]
     UnknownParentBlock { number: u64, hash: H256 },
@@ -13,10 +13,24 @@ use tokio::runtime::Runtime as RawRuntime;
 pub use tokio_postgres as postgres;
 
 pub mod error;
+pub mod traits;
+pub mod utilities;
 
+mod address;
+mod registry;
+mod report;
+mod source;
 mod storage;
-mod utilities;
+mod syncer;
 
-pub use storage::{traits, Storage};
+pub use address::{Address, Network};
+pub use registry::{KnownCellDep, KnownScript, Registry};
+pub use report::Report;
+pub use source::{FileSource, MemorySource, Subscription};
+pub use storage::{
+    BlockId, CellFilter, Column, ColumnType, Dataset, Excluded, FromValue, IndexerCell,
+    IndexerPage, IndexerTx, Order, Row, ScriptType, SearchKey, Storage, Value,
+};
+pub use syncer::{SyncPolicy, Syncer};
 
 pub(crate) type Runtime = Arc<RawRuntime>;
@@ -0,0 +1,424 @@
+// Copyright (C) 2019-2020 Boyu Yang
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+use std::{fs, path::Path};
+
+use property::Property;
+use serde_json::Value as JsonValue;
+
+use crate::{
+    error::{Error, Result},
+    utilities::hex_to_bytes,
+    Network,
+};
+
+const HASH_TYPE_DATA: u8 = 0;
+const HASH_TYPE_TYPE: u8 = 1;
+
+const DEP_TYPE_CODE: u8 = 0;
+const DEP_TYPE_DEP_GROUP: u8 = 1;
+
+// A well-known script: the code hash with the hash type "type", the code hash with the hash
+// type "data" if the binary is widely referred by its data hash, and the cell deps as
+// `(tx hash, index, dep type)`.
+struct Preset {
+    name: &'static str,
+    type_hash: &'static str,
+    data_hash: Option<&'static str>,
+    cell_deps: &'static [(&'static str, u32, u8)],
+}
+
+// A chain, which is recognized by the hash of its genesis block.
+struct Chain {
+    network: Network,
+    genesis_hash: &'static str,
+    presets: &'static [Preset],
+}
+
+const SECP256K1_BLAKE160_TYPE_HASH: &str =
+    "0x9bd7e06f3ecf4be0f2fcd2188b23f1b9fcc88e5d4b65a8637b17723bbda3cce8";
+const SECP256K1_BLAKE160_DATA_HASH: &str =
+    "0x709f3fda12f561cfacf92273c57a98fede188a3f1a59b1f888d113f9cce08649";
+const SECP256K1_MULTISIG_TYPE_HASH: &str =
+    "0x5c5069eb0857efc65e1bca0c07df34c31663b3622fd3876c876320fc9634e2a8";
+const SECP256K1_MULTISIG_DATA_HASH: &str =
+    "0x43400de165f0821abf63dcac299bbdf7fd73898675ee4ddb099b0a0d8db63bfb";
+const DAO_TYPE_HASH: &str = "0x82d76d1b75fe2fd9a27dfbaa65a039221a380d76c926f378d3f81cf3e7e13f2e";
+const DAO_DATA_HASH: &str = "0x32064a14ce10d95d4b7343054cc19d73b25b16ae61a6c681011ca781a60c7923";
+// Type ID is built in the CKB VM, so it has no cell deps.
+const TYPE_ID_TYPE_HASH: &str =
+    "0x00000000000000000000000000000000000000000000000000545950455f4944";
+
+const CHAINS: &[Chain] = &[
+    Chain {
+        network: Network::Mainnet,
+        genesis_hash: "0x92b197aa1fba0f63633922c61c92375c9c074a93e85963554f5499fe1450d0e5",
+        presets: &[
+            Preset {
+                name: "secp256k1_blake160",
+                type_hash: SECP256K1_BLAKE160_TYPE_HASH,
+                data_hash: Some(SECP256K1_BLAKE160_DATA_HASH),
+                cell_deps: &[
+                    (
+                        "0x71a7ba8fc96349fea0ed3a5c47992e3b4084b031a42264a018e0072e8172e46c",
+                        0,
+                        DEP_TYPE_DEP_GROUP,
+                    ),
+                    (
+                        "0xe2fb199810d49a4d8beec56718ba2593b665db9d52299a0f9e6e75416d73ff5c",
+                        1,
+                        DEP_TYPE_CODE,
+                    ),
+                ],
+            },
+            Preset {
+                name: "secp256k1_multisig",
+                type_hash: SECP256K1_MULTISIG_TYPE_HASH,
+                data_hash: Some(SECP256K1_MULTISIG_DATA_HASH),
+                cell_deps: &[
+                    (
+                        "0x71a7ba8fc96349fea0ed3a5c47992e3b4084b031a42264a018e0072e8172e46c",
+                        1,
+                        DEP_TYPE_DEP_GROUP,
+                    ),
+                    (
+                        "0xe2fb199810d49a4d8beec56718ba2593b665db9d52299a0f9e6e75416d73ff5c",
+                        4,
+                        DEP_TYPE_CODE,
+                    ),
+                ],
+            },
+            Preset {
+                name: "dao",
+                type_hash: DAO_TYPE_HASH,
+                data_hash: Some(DAO_DATA_HASH),
+                cell_deps: &[(
+                    "0xe2fb199810d49a4d8beec56718ba2593b665db9d52299a0f9e6e75416d73ff5c",
+                    2,
+                    DEP_TYPE_CODE,
+                )],
+            },
+            Preset {
+                name: "type_id",
+                type_hash: TYPE_ID_TYPE_HASH,
+                data_hash: None,
+                cell_deps: &[],
+            },
+            Preset {
+                name: "anyone_can_pay",
+                type_hash: "0xd369597ff47f29fbc0d47d2e3775370d1250b85140c670e4718af712983a2354",
+                data_hash: None,
+                cell_deps: &[(
+                    "0x4153a2014952d7cac45f285ce9a7c5c0c0e1b21f2d378b82ac1433cb11c25c4d",
+                    0,
+                    DEP_TYPE_DEP_GROUP,
+                )],
+            },
+            Preset {
+                name: "sudt",
+                type_hash: "0x5e7a36a77e68eecc013dfa2fe6a23f3b6c344b04005808694ae6dd45eea4cfd5",
+                data_hash: None,
+                cell_deps: &[(
+                    "0xc7813f6a415144643970c2e88e0bb6ca6a8edc5dd7c1022746f628284a9936d5",
+                    0,
+                    DEP_TYPE_CODE,
+                )],
+            },
+            Preset {
+                name: "cheque",
+                type_hash: "0xe4d4ecc6e5f9a059bf2f7a82cca292083aebc0c421566a52484fe2ec51a9fb0c",
+                data_hash: None,
+                cell_deps: &[(
+                    "0x04632cc459459cf5c9d384b43dee3e36f542a464bdd4127be7d6618ac6f8d268",
+                    0,
+                    DEP_TYPE_DEP_GROUP,
+                )],
+            },
+        ],
+    },
+    Chain {
+        network: Network::Testnet,
+        genesis_hash: "0x10639e0895502b5688a6be8cf69460d76541bfa4821629d86d62ba0aae3f9606",
+        presets: &[
+            Preset {
+                name: "secp256k1_blake160",
+                type_hash: SECP256K1_BLAKE160_TYPE_HASH,
+                data_hash: Some(SECP256K1_BLAKE160_DATA_HASH),
+                cell_deps: &[
+                    (
+                        "0xf8de3bb47d055cdf460d93a2a6e1b05f7432f9777c8c474abf4eec1d4aee5d37",
+                        0,
+                        DEP_TYPE_DEP_GROUP,
+                    ),
+                    (
+                        "0x8f8c79eb6671709633fe6a46de93c0fedc9c1b8a6527a18d3983879542635c9f",
+                        1,
+                        DEP_TYPE_CODE,
+                    ),
+                ],
+            },
+            Preset {
+                name: "secp256k1_multisig",
+                type_hash: SECP256K1_MULTISIG_TYPE_HASH,
+                data_hash: Some(SECP256K1_MULTISIG_DATA_HASH),
+                cell_deps: &[
+                    (
+                        "0xf8de3bb47d055cdf460d93a2a6e1b05f7432f9777c8c474abf4eec1d4aee5d37",
+                        1,
+                        DEP_TYPE_DEP_GROUP,
+                    ),
+                    (
+                        "0x8f8c79eb6671709633fe6a46de93c0fedc9c1b8a6527a18d3983879542635c9f",
+                        4,
+                        DEP_TYPE_CODE,
+                    ),
+                ],
+            },
+            Preset {
+                name: "dao",
+                type_hash: DAO_TYPE_HASH,
+                data_hash: Some(DAO_DATA_HASH),
+                cell_deps: &[(
+                    "0x8f8c79eb6671709633fe6a46de93c0fedc9c1b8a6527a18d3983879542635c9f",
+                    2,
+                    DEP_TYPE_CODE,
+                )],
+            },
+            Preset {
+                name: "type_id",
+                type_hash: TYPE_ID_TYPE_HASH,
+                data_hash: None,
+                cell_deps: &[],
+            },
+            Preset {
+                name: "anyone_can_pay",
+                type_hash: "0x3419a1c09eb2567f6552ee7a8ecffd64155cffe0f1796e6e61ec088d740c1356",
+                data_hash: None,
+                cell_deps: &[(
+                    "0xec26b0f85ed839ece5f11c4c4e837ec359f5adc4420410f6453b1f6b60fb96a6",
+                    0,
+                    DEP_TYPE_DEP_GROUP,
+                )],
+            },
+            Preset {
+                name: "sudt",
+                type_hash: "0xc5e5dcf215925f7ef4dfaf5f4b4f105bc321c02776d6e7d52a1db3fcd9d011a4",
+                data_hash: None,
+                cell_deps: &[(
+                    "0xe12877ebd2c3c364dc46c5c992bcfaf4986f6390ac2c6bc9b5fd1d0c05e0fa2e",
+                    0,
+                    DEP_TYPE_CODE,
+                )],
+            },
+            Preset {
+                name: "cheque",
+                type_hash: "0x60d5f39efce409c587cb9ea359cefdead650ca128f0bd9cb3855348f98c70d5b",
+                data_hash: None,
+                cell_deps: &[(
+                    "0x7f96858be0a9d584b4a9ea190e0420835156a6010a5fde15ffcdc9d9c721ccab",
+                    0,
+                    DEP_TYPE_DEP_GROUP,
+                )],
+            },
+        ],
+    },
+];
+
+/// A script which is known by a name.
+#[derive(Property, Debug, Clone, PartialEq, Eq)]
+#[property(get(public), set(disable), mut(disable))]
+pub struct KnownScript {
+    name: String,
+    code_hash: Vec<u8>,
+    hash_type: u8,
+}
+
+/// A cell dep which provides the code of a known script, directly or through a dep group.
+#[derive(Property, Debug, Clone, PartialEq, Eq)]
+#[property(get(public), set(disable), mut(disable))]
+pub struct KnownCellDep {
+    name: String,
+    tx_hash: Vec<u8>,
+    index: u32,
+    dep_type: u8,
+}
+
+/// The names of scripts, so reports could show names instead of code hashes.
+///
+/// It starts with the well-known scripts of a network, and could be extended from a file.
+/// A script could be known by several code hashes, since it could be referred by the hash of
+/// its type script or by the hash of its data.
+#[derive(Debug, Clone, Default)]
+pub struct Registry {
+    scripts: Vec<KnownScript>,
+    cell_deps: Vec<KnownCellDep>,
+}
+
+impl KnownScript {
+    /// The hash type is `0` for "data" and `1` for "type".
+    pub fn new(name: &str, code_hash: &[u8], hash_type: u8) -> Self {
+        Self {
+            name: name.to_owned(),
+            code_hash: code_hash.to_vec(),
+            hash_type,
+        }
+    }
+}
+
+impl KnownCellDep {
+    /// The dep type is `0` for "code" and `1` for "dep_group".
+    pub fn new(name: &str, tx_hash: &[u8], index: u32, dep_type: u8) -> Self {
+        Self {
+            name: name.to_owned(),
+            tx_hash: tx_hash.to_vec(),
+            index,
+            dep_type,
+        }
+    }
+}
+
+impl Registry {
+    /// The well-known scripts of the network.
+    pub fn builtin(network: Network) -> Self {
+        let chain = CHAINS
+            .iter()
+            .find(|chain| chain.network == network)
+            .expect("all networks have presets");
+        let hash = |hex| hex_to_bytes(hex).expect("a builtin hash");
+        let mut registry = Self::default();
+        for preset in chain.presets {
+            let type_hash = hash(preset.type_hash);
+            registry.insert(KnownScript::new(preset.name, &type_hash, HASH_TYPE_TYPE));
+            if let Some(data_hash) = preset.data_hash {
+                let data_hash = hash(data_hash);
+                registry.insert(KnownScript::new(preset.name, &data_hash, HASH_TYPE_DATA));
+            }
+            for (tx_hash, index, dep_type) in preset.cell_deps {
+                let tx_hash = hash(tx_hash);
+                let cell_dep = KnownCellDep::new(preset.name, &tx_hash, *index, *dep_type);
+                registry.insert_cell_dep(cell_dep);
+            }
+        }
+        registry
+    }
+
+    /// Detects the network by the hash of the genesis block, `None` for other chains, such as
+    /// the development chains.
+    pub fn detect_network(genesis_hash: &[u8]) -> Option<Network> {
+        CHAINS
+            .iter()
+            .find(|chain| hex_to_bytes(chain.genesis_hash).expect("a builtin hash") == genesis_hash)
+            .map(|chain| chain.network)
+    }
+
+    /// Adds the scripts in a JSON file, a script replaces the known one with the same code hash
+    /// and hash type, and a cell dep replaces the known one with the same out point.
+    ///
+    /// The file is an array of `{"name": .., "code_hash": "0x..", "hash_type": "data|type"}`,
+    /// each item could have `"cell_deps": [{"tx_hash": "0x..", "index": .., "dep_type":
+    /// "code|dep_group"}]`.
+    pub fn load(&mut self, path: &Path) -> Result<()> {
+        log::trace!("load scripts from {}", path.display());
+        let content = fs::read_to_string(path)?;
+        let items = match serde_json::from_str::<JsonValue>(&content)? {
+            JsonValue::Array(items) => items,
+            _ => return Err(Error::Data("the registry should be an array".to_owned())),
+        };
+        for item in items {
+            let name = string_field(&item, "name")?;
+            let code_hash = hash_field(&item, "code_hash")?;
+            let hash_type = match string_field(&item, "hash_type")? {
+                "data" => HASH_TYPE_DATA,
+                "type" => HASH_TYPE_TYPE,
+                _ => return Err(Error::Data(format!("unknown hash type in {}", item))),
+            };
+            let cell_deps = match item.get("cell_deps") {
+                Some(JsonValue::Array(cell_deps)) => cell_deps.as_slice(),
+                Some(_) => return Err(Error::Data(format!("incorrect cell deps in {}", item))),
+                None => &[],
+            };
+            for cell_dep in cell_deps {
+                let tx_hash = hash_field(cell_dep, "tx_hash")?;
+                let index = cell_dep
+                    .get("index")
+                    .and_then(JsonValue::as_u64)
+                    .filter(|index| *index <= u64::from(u32::MAX))
+                    .ok_or_else(|| Error::Data(format!("incorrect index in {}", cell_dep)))?;
+                let dep_type = match string_field(cell_dep, "dep_type")? {
+                    "code" => DEP_TYPE_CODE,
+                    "dep_group" => DEP_TYPE_DEP_GROUP,
+                    _ => return Err(Error::Data(format!("unknown dep type in {}", cell_dep))),
+                };
+                self.insert_cell_dep(KnownCellDep::new(name, &tx_hash, index as u32, dep_type));
+            }
+            self.insert(KnownScript::new(name, &code_hash, hash_type));
+        }
+        Ok(())
+    }
+
+    pub fn insert(&mut self, script: KnownScript) {
+        self.scripts.retain(|known| {
+            known.code_hash != script.code_hash || known.hash_type != script.hash_type
+        });
+        self.scripts.push(script);
+    }
+
+    pub fn insert_cell_dep(&mut self, cell_dep: KnownCellDep) {
+        self.cell_deps
+            .retain(|known| known.tx_hash != cell_dep.tx_hash || known.index != cell_dep.index);
+        self.cell_deps.push(cell_dep);
+    }
+
+    pub fn name(&self, code_hash: &[u8], hash_type: u8) -> Option<&str> {
+        self.scripts
+            .iter()
+            .find(|known| known.code_hash == code_hash && known.hash_type == hash_type)
+            .map(|known| known.name.as_str())
+    }
+
+    /// The name of the script which a cell dep provides, the out point could be the cell of
+    /// the code or a dep group which includes it.
+    pub fn cell_dep_name(&self, tx_hash: &[u8], index: u32) -> Option<&str> {
+        self.cell_deps
+            .iter()
+            .find(|known| known.tx_hash == tx_hash && known.index == index)
+            .map(|known| known.name.as_str())
+    }
+
+    /// All variants of the scripts with the name, as `(code hash, hash type)`.
+    pub fn code_hashes(&self, name: &str) -> Vec<(&[u8], u8)> {
+        self.scripts
+            .iter()
+            .filter(|known| known.name == name)
+            .map(|known| (known.code_hash.as_slice(), known.hash_type))
+            .collect()
+    }
+
+    pub fn scripts(&self) -> &[KnownScript] {
+        &self.scripts
+    }
+
+    pub fn cell_deps(&self) -> &[KnownCellDep] {
+        &self.cell_deps
+    }
+}
+
+fn string_field<'a>(item: &'a JsonValue, name: &str) -> Result<&'a str> {
+    item.get(name)
+        .and_then(JsonValue::as_str)
+        .ok_or_else(|| Error::Data(format!("no string field {} in {}", name, item)))
+}
+
+fn hash_field(item: &JsonValue, name: &str) -> Result<Vec<u8>> {
+    let hash = hex_to_bytes(string_field(item, name)?)?;
+    if hash.len() == 32 {
+        Ok(hash)
+    } else {
+        Err(Error::Data(format!("incorrect {} in {}", name, item)))
+    }
+}
@@ -0,0 +1,52 @@
+// Copyright (C) 2019-2020 Boyu Yang
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+use property::Property;
+
+use crate::storage::{Row, Value};
+
+/// A table of results, for statistics reports and ad-hoc queries.
+#[derive(Property, Debug, Clone, Default)]
+#[property(get(public), set(disable), mut(disable))]
+pub struct Report {
+    columns: Vec<String>,
+    rows: Vec<Vec<Value>>,
+}
+
+impl Report {
+    pub fn new(columns: &[&str]) -> Self {
+        Self {
+            columns: columns.iter().map(|column| (*column).to_owned()).collect(),
+            rows: Vec::new(),
+        }
+    }
+
+    /// Creates a report with the columns, the columns of the rows are ignored.
+    pub fn with_rows(columns: &[&str], rows: Vec<Row>) -> Self {
+        let mut report = Self::new(columns);
+        report.rows = rows.into_iter().map(Row::into_values).collect();
+        report
+    }
+
+    pub fn push(&mut self, row: Vec<Value>) -> &mut Self {
+        self.rows.push(row);
+        self
+    }
+}
+
+impl From<Vec<Row>> for Report {
+    /// The columns are taken from the first row, so a report without rows has no columns.
+    fn from(rows: Vec<Row>) -> Self {
+        let columns = rows
+            .first()
+            .map(|row| row.columns().to_owned())
+            .unwrap_or_default();
+        let rows = rows.into_iter().map(Row::into_values).collect();
+        Self { columns, rows }
+    }
+}
@@ -0,0 +1,115 @@
+// Copyright (C) 2019-2020 Boyu Yang
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+use std::{collections::BTreeMap, fs, io, path::Path};
+
+use uckb_jsonrpc_core::types::{core, packed, prelude::*, rpc};
+
+use super::BlockSource;
+use crate::{
+    error::{Error, Result},
+    utilities::hex_to_bytes,
+};
+
+/// A block source which serves the blocks loaded from a dump file.
+///
+/// Each non-empty line of the file is a block, either in the JSON format which is returned by
+/// the JSON-RPC method `get_block`, or a molecule-encoded block in `0x`-prefixed hex.
+/// When there are more than one block at the same height, the last one wins. The heights should
+/// be contiguous and each block should be the child of the previous one.
+pub struct FileSource {
+    blocks: BTreeMap<core::BlockNumber, core::BlockView>,
+}
+
+impl FileSource {
+    pub fn open<P: AsRef<Path>>(path: P) -> Result<Self> {
+        log::info!("load blocks from {}", path.as_ref().display());
+        let file = fs::File::open(path)?;
+        Self::load(io::BufReader::new(file))
+    }
+
+    pub fn load<R: io::BufRead>(reader: R) -> Result<Self> {
+        let mut blocks = BTreeMap::new();
+        for (index, line) in reader.lines().enumerate() {
+            let line = line?;
+            let line = line.trim();
+            if line.is_empty() {
+                continue;
+            }
+            let block = parse_block(line).map_err(|err| {
+                Error::Data(format!(
+                    "failed to parse block at line {}: {}",
+                    index + 1,
+                    err
+                ))
+            })?;
+            log::trace!("load block {} {:#}", block.number(), block.hash());
+            blocks.insert(block.number(), block);
+        }
+        if blocks.is_empty() {
+            return Err(Error::Data("no block in the dump file".to_owned()));
+        }
+        check_chain(&blocks)?;
+        Ok(Self { blocks })
+    }
+}
+
+// Checks that the blocks are one chain, otherwise the syncer could never reach the tip.
+fn check_chain(blocks: &BTreeMap<core::BlockNumber, core::BlockView>) -> Result<()> {
+    let mut parent: Option<&core::BlockView> = None;
+    for (number, block) in blocks {
+        if let Some(parent) = parent {
+            if *number != parent.number() + 1 {
+                return Err(Error::Data(format!(
+                    "no block between {} and {} in the dump file",
+                    parent.number(),
+                    number
+                )));
+            }
+            if block.parent_hash() != parent.hash() {
+                return Err(Error::Data(format!(
+                    "block {} is not the child of block {:#} in the dump file",
+                    number,
+                    parent.hash()
+                )));
+            }
+        }
+        parent = Some(block);
+    }
+    Ok(())
+}
+
+fn parse_block(line: &str) -> Result<core::BlockView> {
+    if line.starts_with('{') {
+        let block: rpc::BlockView = serde_json::from_str(line)?;
+        Ok(block.into())
+    } else {
+        let bytes = hex_to_bytes(line)?;
+        packed::Block::from_slice(&bytes)
+            .map(|block| block.into_view())
+            .map_err(|err| Error::Data(err.to_string()))
+    }
+}
+
+impl BlockSource for FileSource {
+    fn tip_number(&self) -> Result<core::BlockNumber> {
+        self.blocks
+            .keys()
+            .next_back()
+            .cloned()
+            .ok_or_else(|| Error::Data("no block in the dump file".to_owned()))
+    }
+
+    fn block_by_number(&self, number: core::BlockNumber) -> Result<Option<core::BlockView>> {
+        Ok(self.blocks.get(&number).cloned())
+    }
+
+    fn block_hash_by_number(&self, number: core::BlockNumber) -> Result<Option<packed::Byte32>> {
+        Ok(self.blocks.get(&number).map(|block| block.hash()))
+    }
+}
@@ -0,0 +1,44 @@
+// Copyright (C) 2019-2020 Boyu Yang
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+use std::sync::mpsc;
+
+use uckb_jsonrpc_client::Client;
+use uckb_jsonrpc_core::types::{core, packed, prelude::*, rpc};
+
+use super::{BlockSource, Subscription};
+use crate::error::Result;
+
+impl BlockSource for Client {
+    fn tip_number(&self) -> Result<core::BlockNumber> {
+        self.get_tip_block_number().map_err(Into::into)
+    }
+
+    fn block_by_number(&self, number: core::BlockNumber) -> Result<Option<core::BlockView>> {
+        self.get_block_by_number(number, None).map_err(Into::into)
+    }
+
+    fn block_hash_by_number(&self, number: core::BlockNumber) -> Result<Option<packed::Byte32>> {
+        self.get_block_hash(number)
+            .map(|hash_opt| hash_opt.map(|hash| hash.pack()))
+            .map_err(Into::into)
+    }
+
+    fn subscribe(&self) -> Result<Option<Subscription>> {
+        let (sender, receiver) = mpsc::channel();
+        self.subscribe_new_tip_header(move |message| {
+            let header: rpc::HeaderView = serde_json::from_str(message).map_err(|err| {
+                log::error!("failed to parse the new tip header since {}", err);
+            })?;
+            sender.send(header.inner.number.value()).map_err(|_| {
+                log::trace!("the subscription of new tip headers is closed");
+            })
+        })?;
+        Ok(Some(receiver))
+    }
+}
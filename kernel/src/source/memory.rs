@@ -0,0 +1,95 @@
+// Copyright (C) 2019-2020 Boyu Yang
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+use std::sync::{mpsc, Arc};
+
+use parking_lot::RwLock;
+use uckb_jsonrpc_core::types::{core, packed};
+
+use super::{BlockSource, Subscription};
+use crate::error::{Error, Result};
+
+/// A block source which serves an in-memory chain.
+///
+/// All clones share the same chain, so a chain could be changed while a syncer is using it.
+#[derive(Clone, Default)]
+pub struct MemorySource {
+    inner: Arc<RwLock<Inner>>,
+}
+
+#[derive(Default)]
+struct Inner {
+    blocks: Vec<core::BlockView>,
+    subscribers: Vec<mpsc::Sender<core::BlockNumber>>,
+}
+
+impl MemorySource {
+    pub fn new() -> Self {
+        Default::default()
+    }
+
+    /// Appends a block to the chain, its number should be the length of the chain.
+    pub fn push(&self, block: core::BlockView) -> Result<()> {
+        let mut inner = self.inner.write();
+        let expected = inner.blocks.len() as core::BlockNumber;
+        if block.number() != expected {
+            return Err(Error::Data(format!(
+                "incorrect block number {} (expected: {})",
+                block.number(),
+                expected
+            )));
+        }
+        inner.blocks.push(block);
+        inner
+            .subscribers
+            .retain(|sender| sender.send(expected).is_ok());
+        Ok(())
+    }
+
+    /// Removes all blocks whose number is not less than the input number.
+    pub fn truncate(&self, number: core::BlockNumber) {
+        self.inner.write().blocks.truncate(number as usize);
+    }
+
+    /// Returns the block at the height.
+    pub fn get(&self, number: core::BlockNumber) -> Option<core::BlockView> {
+        self.inner.read().blocks.get(number as usize).cloned()
+    }
+
+    /// Returns the tip block.
+    pub fn tip(&self) -> Option<core::BlockView> {
+        self.inner.read().blocks.last().cloned()
+    }
+
+    /// Returns all blocks of the chain.
+    pub fn blocks(&self) -> Vec<core::BlockView> {
+        self.inner.read().blocks.clone()
+    }
+}
+
+impl BlockSource for MemorySource {
+    fn tip_number(&self) -> Result<core::BlockNumber> {
+        self.tip()
+            .map(|block| block.number())
+            .ok_or_else(|| Error::Data("no block in the memory chain".to_owned()))
+    }
+
+    fn block_by_number(&self, number: core::BlockNumber) -> Result<Option<core::BlockView>> {
+        Ok(self.get(number))
+    }
+
+    fn block_hash_by_number(&self, number: core::BlockNumber) -> Result<Option<packed::Byte32>> {
+        Ok(self.get(number).map(|block| block.hash()))
+    }
+
+    fn subscribe(&self) -> Result<Option<Subscription>> {
+        let (sender, receiver) = mpsc::channel();
+        self.inner.write().subscribers.push(sender);
+        Ok(Some(receiver))
+    }
+}
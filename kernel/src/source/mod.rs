@@ -0,0 +1,37 @@
+// Copyright (C) 2019-2020 Boyu Yang
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+use std::sync::mpsc;
+
+use uckb_jsonrpc_core::types::{core, packed};
+
+use crate::error::Result;
+
+mod file;
+mod jsonrpc;
+mod memory;
+
+pub use file::FileSource;
+pub use memory::MemorySource;
+
+/// Receives the numbers of the new tip blocks.
+pub type Subscription = mpsc::Receiver<core::BlockNumber>;
+
+/// A source which provides the blocks of a chain.
+pub trait BlockSource {
+    /// Returns the number of the tip block.
+    fn tip_number(&self) -> Result<core::BlockNumber>;
+    /// Returns the block of the chain at the height, or `None` if there is no such block.
+    fn block_by_number(&self, number: core::BlockNumber) -> Result<Option<core::BlockView>>;
+    /// Returns the hash of the block at the height, or `None` if there is no such block.
+    fn block_hash_by_number(&self, number: core::BlockNumber) -> Result<Option<packed::Byte32>>;
+    /// Subscribes the new tip blocks, returns `None` if the source doesn't support it.
+    fn subscribe(&self) -> Result<Option<Subscription>> {
+        Ok(None)
+    }
+}
@@ -0,0 +1,72 @@
+// Copyright (C) 2019-2020 Boyu Yang
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+use super::{Row, Storage, Value};
+use crate::error::Result;
+
+pub(super) mod operations;
+
+/// The balances of locks and their changes, which are maintained when blocks are inserted and
+/// removed, so the queries on locks don't aggregate the cells.
+pub trait Accounts {
+    /// Returns the lock script, the balance, the count of live cells, and the first and the last
+    /// blocks which change the balance or the cells.
+    fn account(&self, lock_hash: &[u8]) -> Result<Option<Row>>;
+    /// Returns the changes of a lock by transactions, from the latest, before the position
+    /// `(block_number, tx_index)`.
+    fn account_changes(
+        &self,
+        lock_hash: &[u8],
+        before: Option<(u64, u32)>,
+        limit: u32,
+    ) -> Result<Vec<Row>>;
+}
+
+impl Accounts for Storage {
+    fn account(&self, lock_hash: &[u8]) -> Result<Option<Row>> {
+        log::trace!("query an account");
+        let sql = r#"
+            SELECT a.lock_hash, s.code_hash, s.hash_type, s.args, a.balance, a.live_cells,
+                   a.first_block_number, a.last_block_number
+              FROM accounts a
+              JOIN scripts s ON s.hash = a.lock_hash
+             WHERE a.lock_hash = $1
+        ;"#;
+        let cli = self.backend();
+        self.block_on(cli.query_opt(sql, &[lock_hash.into()]))
+    }
+
+    fn account_changes(
+        &self,
+        lock_hash: &[u8],
+        before: Option<(u64, u32)>,
+        limit: u32,
+    ) -> Result<Vec<Row>> {
+        log::trace!("query changes of an account before {:?}", before);
+        let (block_number, tx_index) = before
+            .map(|(block_number, tx_index)| (block_number as i64, i64::from(tx_index)))
+            .unwrap_or((i64::MAX, 0));
+        let sql = r#"
+            SELECT block_number, tx_index, tx_hash, capacity_change, cells_change, balance
+              FROM account_changes
+             WHERE 1 = 1
+               AND lock_hash = $1
+               AND (block_number < $2 OR (block_number = $2 AND tx_index < $3))
+             ORDER BY block_number DESC, tx_index DESC
+             LIMIT $4
+        ;"#;
+        let params: &[Value] = &[
+            lock_hash.into(),
+            block_number.into(),
+            tx_index.into(),
+            i64::from(limit).into(),
+        ];
+        let cli = self.backend();
+        self.block_on(cli.query(sql, params))
+    }
+}
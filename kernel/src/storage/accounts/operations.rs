@@ -0,0 +1,227 @@
+// Copyright (C) 2019-2020 Boyu Yang
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+use std::collections::BTreeMap;
+
+use uckb_jsonrpc_core::types::packed;
+
+use crate::{
+    error::Result,
+    storage::backend::{Backend, Transaction},
+};
+
+pub(in crate::storage) const TABLES: &[&str] = &["accounts", "account_changes"];
+
+// Creates the tables if they don't exist, and fills them from the stored cells.
+pub(in crate::storage) async fn create_tables(cli: &mut dyn Backend) -> Result<()> {
+    log::trace!("create tables of accounts");
+    if cli.table_exists("accounts").await? {
+        return Ok(());
+    }
+    let sqls = &[
+        r#"
+        CREATE TABLE IF NOT EXISTS accounts (
+            lock_hash           BYTEA       NOT NULL PRIMARY KEY,
+            balance             BIGINT      NOT NULL,
+            live_cells          BIGINT      NOT NULL,
+            first_block_number  BIGINT      NOT NULL,
+            last_block_number   BIGINT      NOT NULL
+        );"#,
+        r#"
+        CREATE TABLE IF NOT EXISTS account_changes (
+            lock_hash           BYTEA       NOT NULL,
+            block_number        BIGINT      NOT NULL,
+            tx_index            INTEGER     NOT NULL,
+            tx_hash             BYTEA       NOT NULL,
+            capacity_change     BIGINT      NOT NULL,
+            cells_change        BIGINT      NOT NULL,
+            balance             BIGINT      NOT NULL,
+            PRIMARY KEY (lock_hash, block_number, tx_index)
+        );"#,
+        r#"
+        INSERT INTO account_changes (
+            lock_hash, block_number, tx_index, tx_hash, capacity_change, cells_change, balance
+        )
+        SELECT lock_hash, block_number, tx_index, tx_hash, capacity_change, cells_change,
+               SUM(capacity_change) OVER (
+                   PARTITION BY lock_hash
+                   ORDER BY block_number, tx_index
+               )
+          FROM (
+               SELECT ch.lock_hash, ch.block_number, bt."index" AS tx_index, ch.tx_hash,
+                      CAST(SUM(ch.capacity) AS BIGINT) AS capacity_change,
+                      CAST(SUM(ch.cells) AS BIGINT) AS cells_change
+                 FROM (
+                      SELECT lock_hash, created_block_number AS block_number, tx_hash,
+                             capacity, 1 AS cells
+                        FROM cells
+                       UNION ALL
+                      SELECT lock_hash, consumed_block_number, consumed_tx_hash,
+                             -capacity, -1
+                        FROM cells
+                       WHERE consumed_tx_hash IS NOT NULL
+                      ) ch
+                 JOIN block_transactions bt ON bt.tx_hash = ch.tx_hash
+                GROUP BY ch.lock_hash, ch.block_number, bt."index", ch.tx_hash
+               ) c
+        ;"#,
+        r#"
+        INSERT INTO accounts (
+            lock_hash, balance, live_cells, first_block_number, last_block_number
+        )
+        SELECT lock_hash, CAST(SUM(capacity_change) AS BIGINT),
+               CAST(SUM(cells_change) AS BIGINT), MIN(block_number), MAX(block_number)
+          FROM account_changes
+         GROUP BY lock_hash
+        ;"#,
+    ];
+    let txn = cli.transaction().await?;
+    for sql in sqls {
+        txn.execute(sql, &[]).await?;
+    }
+    txn.commit().await
+}
+
+pub(in crate::storage) const INDEXES: &[&str] = &[
+    r#"CREATE INDEX IF NOT EXISTS accounts_balance_idx ON accounts (balance);"#,
+    r#"CREATE INDEX IF NOT EXISTS account_changes_block_number_idx
+           ON account_changes (block_number);"#,
+];
+
+// Records the changes of the locks which the transaction touches, the cells of the transaction
+// should be inserted and consumed already.
+pub(in crate::storage) async fn insert_changes(
+    txn: &dyn Transaction,
+    tx_hash: &packed::Byte32,
+    block_number: u64,
+    tx_index: usize,
+) -> Result<()> {
+    log::trace!("insert account changes for transaction {:#}", tx_hash);
+    let sqls = &[
+        r#"
+        SELECT lock_hash, CAST(SUM(capacity) AS BIGINT), COUNT(*)
+          FROM cells
+         WHERE tx_hash = $1
+         GROUP BY lock_hash
+        ;"#,
+        r#"
+        SELECT lock_hash, -CAST(SUM(capacity) AS BIGINT), -COUNT(*)
+          FROM cells
+         WHERE consumed_tx_hash = $1
+         GROUP BY lock_hash
+        ;"#,
+    ];
+    let mut changes = BTreeMap::new();
+    for sql in sqls {
+        for row in txn
+            .query(sql, &[tx_hash.raw_data().as_ref().into()])
+            .await?
+        {
+            let lock_hash = row.try_get::<Vec<u8>>(0)?;
+            let capacity = row.try_get::<i64>(1)?;
+            let cells = row.try_get::<i64>(2)?;
+            let change = changes.entry(lock_hash).or_insert((0, 0));
+            change.0 += capacity;
+            change.1 += cells;
+        }
+    }
+    let upsert_sql = r#"
+        INSERT INTO accounts (
+            lock_hash, balance, live_cells, first_block_number, last_block_number
+        ) VALUES (
+            $1, $2, $3, $4, $4
+        )
+        ON CONFLICT (lock_hash) DO UPDATE
+           SET
+               balance = accounts.balance + excluded.balance,
+               live_cells = accounts.live_cells + excluded.live_cells,
+               last_block_number = excluded.last_block_number
+     RETURNING balance
+    ;"#;
+    let insert_sql = r#"
+        INSERT INTO account_changes (
+            lock_hash, block_number, tx_index, tx_hash, capacity_change, cells_change, balance
+        ) VALUES (
+            $1, $2, $3, $4, $5, $6, $7
+        )
+    ;"#;
+    for (lock_hash, (capacity, cells)) in changes.into_iter() {
+        let balance = txn
+            .query_one(
+                upsert_sql,
+                &[
+                    lock_hash.as_slice().into(),
+                    capacity.into(),
+                    cells.into(),
+                    (block_number as i64).into(),
+                ],
+            )
+            .await?
+            .try_get::<i64>(0)?;
+        txn.execute(
+            insert_sql,
+            &[
+                lock_hash.as_slice().into(),
+                (block_number as i64).into(),
+                (tx_index as i32).into(),
+                tx_hash.raw_data().as_ref().into(),
+                capacity.into(),
+                cells.into(),
+                balance.into(),
+            ],
+        )
+        .await?;
+    }
+    Ok(())
+}
+
+// Reverts the changes in the block, the accounts which are first seen in it are removed.
+pub(in crate::storage) async fn remove_changes(
+    txn: &dyn Transaction,
+    block_number: u64,
+) -> Result<()> {
+    log::trace!("remove account changes for block {}", block_number);
+    let sql = r#"
+        SELECT lock_hash, CAST(SUM(capacity_change) AS BIGINT), CAST(SUM(cells_change) AS BIGINT)
+          FROM account_changes
+         WHERE block_number = $1
+         GROUP BY lock_hash
+    ;"#;
+    let changes = txn.query(sql, &[(block_number as i64).into()]).await?;
+    let sql = "DELETE FROM account_changes WHERE block_number = $1;";
+    txn.execute(sql, &[(block_number as i64).into()]).await?;
+    let remove_sql = r#"
+        DELETE FROM accounts
+         WHERE 1 = 1
+           AND lock_hash = $1
+           AND first_block_number = $2
+    ;"#;
+    let update_sql = r#"
+        UPDATE accounts
+           SET
+               balance = balance - $2,
+               live_cells = live_cells - $3,
+               last_block_number = (
+                   SELECT MAX(block_number)
+                     FROM account_changes
+                    WHERE lock_hash = $1
+               )
+         WHERE lock_hash = $1
+    ;"#;
+    for row in changes {
+        let lock_hash = row.try_get::<Vec<u8>>(0)?;
+        let capacity = row.try_get::<i64>(1)?;
+        let cells = row.try_get::<i64>(2)?;
+        let params = &[lock_hash.as_slice().into(), (block_number as i64).into()];
+        if txn.execute(remove_sql, params).await? == 0 {
+            let params = &[lock_hash.as_slice().into(), capacity.into(), cells.into()];
+            txn.execute(update_sql, params).await?;
+        }
+    }
+    Ok(())
+}
@@ -0,0 +1,271 @@
+// Copyright (C) 2019-2020 Boyu Yang
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+use std::{convert::TryFrom, fmt};
+
+use async_trait::async_trait;
+
+use crate::error::{Error, Result};
+
+mod postgres;
+mod sqlite;
+
+pub(crate) use self::{postgres::Postgres, sqlite::Sqlite};
+
+/// A value of a column, or a parameter of a statement.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Value {
+    Null,
+    Integer(i64),
+    Float(f64),
+    Text(String),
+    Bytes(Vec<u8>),
+}
+
+/// A row of the results of a query.
+#[derive(Debug, Clone)]
+pub struct Row {
+    columns: Vec<String>,
+    values: Vec<Value>,
+}
+
+/// Converts a value of a column into a Rust type.
+pub trait FromValue: Sized {
+    fn from_value(value: &Value) -> Result<Self>;
+}
+
+/// The statements which could be executed in a storage or in a transaction.
+///
+/// The placeholders of parameters are `$1`, `$2`, etc, and the statements should be written in
+/// the common subset of the SQL dialects of all backends.
+#[async_trait(?Send)]
+pub(crate) trait Executor {
+    async fn execute(&self, sql: &str, params: &[Value]) -> Result<u64>;
+    async fn query(&self, sql: &str, params: &[Value]) -> Result<Vec<Row>>;
+
+    async fn query_opt(&self, sql: &str, params: &[Value]) -> Result<Option<Row>> {
+        let mut rows = self.query(sql, params).await?;
+        if rows.len() > 1 {
+            Err(Error::Data(format!(
+                "query returns {} rows but expect at most one",
+                rows.len()
+            )))
+        } else {
+            Ok(rows.pop())
+        }
+    }
+
+    async fn query_one(&self, sql: &str, params: &[Value]) -> Result<Row> {
+        self.query_opt(sql, params)
+            .await?
+            .ok_or_else(|| Error::Data("query returns no rows but expect one".to_owned()))
+    }
+}
+
+/// A database which stores the data.
+#[async_trait(?Send)]
+pub(crate) trait Backend: Executor + Send {
+    async fn table_exists(&self, table: &str) -> Result<bool>;
+    async fn column_exists(&self, table: &str, column: &str) -> Result<bool>;
+    async fn transaction<'a>(&'a mut self) -> Result<Box<dyn Transaction + 'a>>;
+}
+
+/// A database transaction, it will be rolled back if it is dropped without committed.
+#[async_trait(?Send)]
+pub(crate) trait Transaction: Executor {
+    async fn commit(self: Box<Self>) -> Result<()>;
+}
+
+impl Row {
+    pub(crate) fn new(columns: Vec<String>, values: Vec<Value>) -> Self {
+        Self { columns, values }
+    }
+
+    pub fn columns(&self) -> &[String] {
+        &self.columns
+    }
+
+    pub fn values(&self) -> &[Value] {
+        &self.values
+    }
+
+    pub fn into_values(self) -> Vec<Value> {
+        self.values
+    }
+
+    pub fn try_get<T: FromValue>(&self, index: usize) -> Result<T> {
+        self.values
+            .get(index)
+            .ok_or_else(|| Error::Data(format!("no column at index {}", index)))
+            .and_then(T::from_value)
+            .map_err(|err| {
+                let column = self.columns.get(index).map(AsRef::as_ref).unwrap_or("?");
+                Error::Data(format!("failed to get column {}: {}", column, err))
+            })
+    }
+}
+
+impl fmt::Display for Value {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Self::Null => write!(f, "null"),
+            Self::Integer(value) => write!(f, "{}", value),
+            Self::Float(value) => write!(f, "{}", value),
+            Self::Text(value) => write!(f, "{}", value),
+            Self::Bytes(value) => {
+                write!(f, "0x")?;
+                for byte in value {
+                    write!(f, "{:02x}", byte)?;
+                }
+                Ok(())
+            }
+        }
+    }
+}
+
+impl Value {
+    fn type_name(&self) -> &'static str {
+        match self {
+            Self::Null => "null",
+            Self::Integer(_) => "integer",
+            Self::Float(_) => "float",
+            Self::Text(_) => "text",
+            Self::Bytes(_) => "bytes",
+        }
+    }
+
+    fn mismatch<T>(&self, expected: &str) -> Result<T> {
+        Err(Error::Data(format!(
+            "expect {} but the value is {}",
+            expected,
+            self.type_name()
+        )))
+    }
+}
+
+macro_rules! impl_integer {
+    ($type:ty) => {
+        impl From<$type> for Value {
+            fn from(value: $type) -> Self {
+                Self::Integer(value.into())
+            }
+        }
+
+        impl FromValue for $type {
+            fn from_value(value: &Value) -> Result<Self> {
+                if let Value::Integer(value) = value {
+                    <$type>::try_from(*value)
+                        .map_err(|_| Error::Data(format!("integer {} is out of range", value)))
+                } else {
+                    value.mismatch("integer")
+                }
+            }
+        }
+    };
+}
+
+impl_integer!(i16);
+impl_integer!(i32);
+impl_integer!(i64);
+impl_integer!(u8);
+impl_integer!(u16);
+impl_integer!(u32);
+
+impl From<bool> for Value {
+    fn from(value: bool) -> Self {
+        Self::Integer(value.into())
+    }
+}
+
+impl FromValue for bool {
+    fn from_value(value: &Value) -> Result<Self> {
+        i64::from_value(value).map(|value| value != 0)
+    }
+}
+
+impl From<f64> for Value {
+    fn from(value: f64) -> Self {
+        Self::Float(value)
+    }
+}
+
+impl FromValue for f64 {
+    fn from_value(value: &Value) -> Result<Self> {
+        match value {
+            Value::Float(value) => Ok(*value),
+            Value::Integer(value) => Ok(*value as f64),
+            _ => value.mismatch("float"),
+        }
+    }
+}
+
+impl From<&str> for Value {
+    fn from(value: &str) -> Self {
+        Self::Text(value.to_owned())
+    }
+}
+
+impl From<String> for Value {
+    fn from(value: String) -> Self {
+        Self::Text(value)
+    }
+}
+
+impl FromValue for String {
+    fn from_value(value: &Value) -> Result<Self> {
+        if let Value::Text(value) = value {
+            Ok(value.clone())
+        } else {
+            value.mismatch("text")
+        }
+    }
+}
+
+impl From<&[u8]> for Value {
+    fn from(value: &[u8]) -> Self {
+        Self::Bytes(value.to_owned())
+    }
+}
+
+impl From<Vec<u8>> for Value {
+    fn from(value: Vec<u8>) -> Self {
+        Self::Bytes(value)
+    }
+}
+
+impl FromValue for Vec<u8> {
+    fn from_value(value: &Value) -> Result<Self> {
+        if let Value::Bytes(value) = value {
+            Ok(value.clone())
+        } else {
+            value.mismatch("bytes")
+        }
+    }
+}
+
+impl<T: Into<Value>> From<Option<T>> for Value {
+    fn from(value: Option<T>) -> Self {
+        value.map(Into::into).unwrap_or(Self::Null)
+    }
+}
+
+impl<T: FromValue> FromValue for Option<T> {
+    fn from_value(value: &Value) -> Result<Self> {
+        if let Value::Null = value {
+            Ok(None)
+        } else {
+            T::from_value(value).map(Some)
+        }
+    }
+}
+
+impl FromValue for Value {
+    fn from_value(value: &Value) -> Result<Self> {
+        Ok(value.clone())
+    }
+}
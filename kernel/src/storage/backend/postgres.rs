@@ -0,0 +1,221 @@
+// Copyright (C) 2019-2020 Boyu Yang
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+use std::{cell::RefCell, collections::HashMap};
+
+use async_trait::async_trait;
+
+use super::{Backend, Executor, FromValue, Row, Transaction, Value};
+use crate::{
+    error::{Error, Result},
+    postgres as pg, Runtime,
+};
+
+type Statements = RefCell<HashMap<String, pg::Statement>>;
+
+pub(crate) struct Postgres {
+    client: pg::Client,
+    statements: Statements,
+}
+
+struct PostgresTransaction<'a> {
+    txn: pg::Transaction<'a>,
+    statements: &'a Statements,
+}
+
+impl Postgres {
+    pub(crate) fn connect(rt: &Runtime, uri: &str) -> Result<Self> {
+        let (client, connection) = rt.block_on(pg::connect(uri, pg::NoTls))?;
+        rt.spawn(async {
+            if let Err(err) = connection.await {
+                log::error!("connection error: {}", err);
+            }
+        });
+        Ok(Self {
+            client,
+            statements: Default::default(),
+        })
+    }
+}
+
+#[async_trait(?Send)]
+impl Executor for Postgres {
+    async fn execute(&self, sql: &str, params: &[Value]) -> Result<u64> {
+        execute(&self.client, &self.statements, sql, params).await
+    }
+
+    async fn query(&self, sql: &str, params: &[Value]) -> Result<Vec<Row>> {
+        query(&self.client, &self.statements, sql, params).await
+    }
+}
+
+#[async_trait(?Send)]
+impl Backend for Postgres {
+    async fn table_exists(&self, table: &str) -> Result<bool> {
+        let sql = r#"
+            SELECT 1
+              FROM information_schema.tables
+             WHERE 1 = 1
+               AND table_schema = current_schema()
+               AND table_name::TEXT = $1
+        ;"#;
+        self.query_opt(sql, &[table.into()])
+            .await
+            .map(|row_opt| row_opt.is_some())
+    }
+
+    async fn column_exists(&self, table: &str, column: &str) -> Result<bool> {
+        let sql = r#"
+            SELECT 1
+              FROM information_schema.columns
+             WHERE 1 = 1
+               AND table_schema = current_schema()
+               AND table_name::TEXT = $1
+               AND column_name::TEXT = $2
+        ;"#;
+        self.query_opt(sql, &[table.into(), column.into()])
+            .await
+            .map(|row_opt| row_opt.is_some())
+    }
+
+    async fn transaction<'a>(&'a mut self) -> Result<Box<dyn Transaction + 'a>> {
+        let txn = self.client.transaction().await?;
+        Ok(Box::new(PostgresTransaction {
+            txn,
+            statements: &self.statements,
+        }))
+    }
+}
+
+#[async_trait(?Send)]
+impl<'a> Executor for PostgresTransaction<'a> {
+    async fn execute(&self, sql: &str, params: &[Value]) -> Result<u64> {
+        execute(&self.txn, self.statements, sql, params).await
+    }
+
+    async fn query(&self, sql: &str, params: &[Value]) -> Result<Vec<Row>> {
+        query(&self.txn, self.statements, sql, params).await
+    }
+}
+
+#[async_trait(?Send)]
+impl<'a> Transaction for PostgresTransaction<'a> {
+    async fn commit(self: Box<Self>) -> Result<()> {
+        self.txn.commit().await.map_err(Into::into)
+    }
+}
+
+async fn prepare<C: pg::GenericClient>(
+    cli: &C,
+    statements: &Statements,
+    sql: &str,
+) -> Result<pg::Statement> {
+    let cached = statements.borrow().get(sql).cloned();
+    if let Some(stmt) = cached {
+        Ok(stmt)
+    } else {
+        let stmt = cli.prepare(sql).await?;
+        statements.borrow_mut().insert(sql.to_owned(), stmt.clone());
+        Ok(stmt)
+    }
+}
+
+async fn execute<C: pg::GenericClient>(
+    cli: &C,
+    statements: &Statements,
+    sql: &str,
+    params: &[Value],
+) -> Result<u64> {
+    let stmt = prepare(cli, statements, sql).await?;
+    let params = to_sql_params(&stmt, params)?;
+    let params_ref = params.iter().map(AsRef::as_ref).collect::<Vec<_>>();
+    cli.execute(&stmt, &params_ref).await.map_err(Into::into)
+}
+
+async fn query<C: pg::GenericClient>(
+    cli: &C,
+    statements: &Statements,
+    sql: &str,
+    params: &[Value],
+) -> Result<Vec<Row>> {
+    let stmt = prepare(cli, statements, sql).await?;
+    let params = to_sql_params(&stmt, params)?;
+    let params_ref = params.iter().map(AsRef::as_ref).collect::<Vec<_>>();
+    cli.query(&stmt, &params_ref)
+        .await?
+        .iter()
+        .map(from_pg_row)
+        .collect()
+}
+
+type SqlParam = Box<dyn pg::types::ToSql + Sync>;
+
+fn to_sql_params(stmt: &pg::Statement, params: &[Value]) -> Result<Vec<SqlParam>> {
+    if stmt.params().len() != params.len() {
+        return Err(Error::Data(format!(
+            "expect {} parameters but got {}",
+            stmt.params().len(),
+            params.len()
+        )));
+    }
+    stmt.params()
+        .iter()
+        .zip(params.iter())
+        .map(|(ty, value)| to_sql_param(ty, value))
+        .collect()
+}
+
+fn to_sql_param(ty: &pg::types::Type, value: &Value) -> Result<SqlParam> {
+    use pg::types::Type;
+    let param: SqlParam = match *ty {
+        Type::BOOL => Box::new(Option::<bool>::from_value(value)?),
+        Type::INT2 => Box::new(Option::<i16>::from_value(value)?),
+        Type::INT4 => Box::new(Option::<i32>::from_value(value)?),
+        Type::INT8 => Box::new(Option::<i64>::from_value(value)?),
+        Type::FLOAT4 => Box::new(Option::<f64>::from_value(value)?.map(|value| value as f32)),
+        Type::FLOAT8 => Box::new(Option::<f64>::from_value(value)?),
+        Type::TEXT | Type::VARCHAR | Type::BPCHAR | Type::NAME => {
+            Box::new(Option::<String>::from_value(value)?)
+        }
+        Type::BYTEA => Box::new(Option::<Vec<u8>>::from_value(value)?),
+        _ => {
+            return Err(Error::Data(format!("unsupported parameter type {}", ty)));
+        }
+    };
+    Ok(param)
+}
+
+fn from_pg_row(row: &pg::Row) -> Result<Row> {
+    use pg::types::Type;
+    let mut columns = Vec::with_capacity(row.len());
+    let mut values = Vec::with_capacity(row.len());
+    for (index, column) in row.columns().iter().enumerate() {
+        let value = match *column.type_() {
+            Type::BOOL => row.try_get::<_, Option<bool>>(index)?.into(),
+            Type::INT2 => row.try_get::<_, Option<i16>>(index)?.into(),
+            Type::INT4 => row.try_get::<_, Option<i32>>(index)?.into(),
+            Type::INT8 => row.try_get::<_, Option<i64>>(index)?.into(),
+            Type::FLOAT4 => row.try_get::<_, Option<f32>>(index)?.map(f64::from).into(),
+            Type::FLOAT8 => row.try_get::<_, Option<f64>>(index)?.into(),
+            Type::TEXT | Type::VARCHAR | Type::BPCHAR | Type::NAME => {
+                row.try_get::<_, Option<String>>(index)?.into()
+            }
+            Type::BYTEA => row.try_get::<_, Option<Vec<u8>>>(index)?.into(),
+            ref ty => {
+                return Err(Error::Data(format!(
+                    "unsupported type {} of column {}, please cast it",
+                    ty,
+                    column.name()
+                )));
+            }
+        };
+        columns.push(column.name().to_owned());
+        values.push(value);
+    }
+    Ok(Row::new(columns, values))
+}
@@ -0,0 +1,157 @@
+// Copyright (C) 2019-2020 Boyu Yang
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+use async_trait::async_trait;
+use rusqlite::types::{ToSql, ToSqlOutput, ValueRef};
+
+use super::{Backend, Executor, Row, Transaction, Value};
+use crate::error::Result;
+
+pub(crate) struct Sqlite {
+    conn: rusqlite::Connection,
+}
+
+struct SqliteTransaction<'a> {
+    txn: rusqlite::Transaction<'a>,
+}
+
+impl Sqlite {
+    /// Opens a database file, or an in-memory database if the path is empty or `:memory:`.
+    pub(crate) fn open(path: &str) -> Result<Self> {
+        let conn = if path.is_empty() || path == ":memory:" {
+            rusqlite::Connection::open_in_memory()?
+        } else {
+            rusqlite::Connection::open(path)?
+        };
+        conn.pragma_update(None, "journal_mode", &"WAL")?;
+        conn.pragma_update(None, "synchronous", &"NORMAL")?;
+        Ok(Self { conn })
+    }
+}
+
+#[async_trait(?Send)]
+impl Executor for Sqlite {
+    async fn execute(&self, sql: &str, params: &[Value]) -> Result<u64> {
+        execute(&self.conn, sql, params)
+    }
+
+    async fn query(&self, sql: &str, params: &[Value]) -> Result<Vec<Row>> {
+        query(&self.conn, sql, params)
+    }
+}
+
+#[async_trait(?Send)]
+impl Backend for Sqlite {
+    async fn table_exists(&self, table: &str) -> Result<bool> {
+        let sql = r#"
+            SELECT 1
+              FROM sqlite_master
+             WHERE 1 = 1
+               AND type = 'table'
+               AND name = $1
+        ;"#;
+        self.query_opt(sql, &[table.into()])
+            .await
+            .map(|row_opt| row_opt.is_some())
+    }
+
+    async fn column_exists(&self, table: &str, column: &str) -> Result<bool> {
+        let sql = r#"
+            SELECT 1
+              FROM pragma_table_info($1)
+             WHERE name = $2
+        ;"#;
+        self.query_opt(sql, &[table.into(), column.into()])
+            .await
+            .map(|row_opt| row_opt.is_some())
+    }
+
+    async fn transaction<'a>(&'a mut self) -> Result<Box<dyn Transaction + 'a>> {
+        let txn = self.conn.transaction()?;
+        Ok(Box::new(SqliteTransaction { txn }))
+    }
+}
+
+#[async_trait(?Send)]
+impl<'a> Executor for SqliteTransaction<'a> {
+    async fn execute(&self, sql: &str, params: &[Value]) -> Result<u64> {
+        execute(&self.txn, sql, params)
+    }
+
+    async fn query(&self, sql: &str, params: &[Value]) -> Result<Vec<Row>> {
+        query(&self.txn, sql, params)
+    }
+}
+
+#[async_trait(?Send)]
+impl<'a> Transaction for SqliteTransaction<'a> {
+    async fn commit(self: Box<Self>) -> Result<()> {
+        self.txn.commit().map_err(Into::into)
+    }
+}
+
+impl ToSql for Value {
+    fn to_sql(&self) -> rusqlite::Result<ToSqlOutput<'_>> {
+        let value = match self {
+            Self::Null => ValueRef::Null,
+            Self::Integer(value) => ValueRef::Integer(*value),
+            Self::Float(value) => ValueRef::Real(*value),
+            Self::Text(value) => ValueRef::Text(value.as_bytes()),
+            Self::Bytes(value) => ValueRef::Blob(&value[..]),
+        };
+        Ok(ToSqlOutput::Borrowed(value))
+    }
+}
+
+// Rewrites the placeholders `$N` into `?N`.
+fn convert_placeholders(sql: &str) -> String {
+    let mut converted = String::with_capacity(sql.len());
+    let mut chars = sql.chars().peekable();
+    while let Some(ch) = chars.next() {
+        if ch == '$' && chars.peek().map(char::is_ascii_digit).unwrap_or(false) {
+            converted.push('?');
+        } else {
+            converted.push(ch);
+        }
+    }
+    converted
+}
+
+fn execute(conn: &rusqlite::Connection, sql: &str, params: &[Value]) -> Result<u64> {
+    let sql = convert_placeholders(sql);
+    let mut stmt = conn.prepare_cached(&sql)?;
+    let changed = stmt.execute(rusqlite::params_from_iter(params.iter()))?;
+    Ok(changed as u64)
+}
+
+fn query(conn: &rusqlite::Connection, sql: &str, params: &[Value]) -> Result<Vec<Row>> {
+    let sql = convert_placeholders(sql);
+    let mut stmt = conn.prepare_cached(&sql)?;
+    let columns = stmt
+        .column_names()
+        .into_iter()
+        .map(ToOwned::to_owned)
+        .collect::<Vec<_>>();
+    let mut rows = stmt.query(rusqlite::params_from_iter(params.iter()))?;
+    let mut result = Vec::new();
+    while let Some(row) = rows.next()? {
+        let values = (0..columns.len())
+            .map(|index| {
+                row.get_ref(index).map(|value| match value {
+                    ValueRef::Null => Value::Null,
+                    ValueRef::Integer(value) => Value::Integer(value),
+                    ValueRef::Real(value) => Value::Float(value),
+                    ValueRef::Text(value) => Value::Text(String::from_utf8_lossy(value).into()),
+                    ValueRef::Blob(value) => Value::Bytes(value.to_owned()),
+                })
+            })
+            .collect::<rusqlite::Result<Vec<_>>>()?;
+        result.push(Row::new(columns.clone(), values));
+    }
+    Ok(result)
+}
@@ -6,9 +6,13 @@
 // option. This file may not be copied, modified, or distributed
 // except according to those terms.
 
-use uckb_jsonrpc_core::types::{core, prelude::*};
+use uckb_jsonrpc_core::types::{core, packed, prelude::*};
 
-use super::Storage;
+use super::{
+    accounts::operations as accounts_ops, epochs::operations as epochs_ops,
+    mining::operations as mining_ops, proposals::operations as proposals_ops,
+    rollups::operations as rollups_ops, udt::operations as udt_ops, Storage,
+};
 use crate::error::{Error, Result};
 
 mod operations;
@@ -16,28 +20,46 @@ mod operations;
 use self::operations as ops;
 
 pub trait BaseData {
-    fn initialize(&self) -> Result<Option<u64>>;
+    fn initialize(&mut self) -> Result<Option<u64>>;
     fn destory(&self) -> Result<Vec<u64>>;
     fn insert_block(&mut self, block: &core::BlockView) -> Result<()>;
     fn remove_block(&mut self, number: u64) -> Result<()>;
     fn verify_block(&self, header: &core::HeaderView) -> Result<bool>;
+    fn block_hash(&self, number: u64) -> Result<Option<packed::Byte32>>;
 }
 
 impl BaseData for Storage {
-    fn initialize(&self) -> Result<Option<u64>> {
+    fn initialize(&mut self) -> Result<Option<u64>> {
         log::trace!("initialize the storage");
-        let cli = self.client();
-        self.block_on(async {
-            if ops::is_first_run(cli).await? {
-                ops::create_tables(cli).await?;
+        let rt = self.runtime();
+        rt.block_on(async {
+            let cli = self.mut_backend();
+            if ops::is_first_run(cli.as_ref()).await? {
+                ops::create_tables(cli.as_ref()).await?;
+            } else {
+                ops::upgrade_tables(cli.as_mut()).await?;
             }
-            ops::check_current_block(cli).await
+            // The tables which are maintained along with the blocks are created at every start
+            // if they don't exist, and filled from the stored blocks, so the storages which are
+            // created before them have them too.
+            accounts_ops::create_tables(cli.as_mut()).await
+        })?;
+        let udt_scripts = self.udt_scripts(None)?;
+        let cli = self.mut_backend();
+        rt.block_on(async {
+            udt_ops::create_tables(cli.as_mut(), &udt_scripts).await?;
+            mining_ops::create_tables(cli.as_mut()).await?;
+            proposals_ops::create_tables(cli.as_mut()).await?;
+            epochs_ops::create_tables(cli.as_mut()).await?;
+            rollups_ops::create_tables(cli.as_mut()).await?;
+            ops::create_indexes(cli.as_ref()).await?;
+            ops::check_current_block(cli.as_ref()).await
         })
     }
 
     fn destory(&self) -> Result<Vec<u64>> {
         log::trace!("destory the storage");
-        let cli = self.client();
+        let cli = self.backend().as_ref();
         self.block_on(ops::drop_tables(cli))
     }
 
@@ -49,33 +71,49 @@ impl BaseData for Storage {
                 hash: block.parent_hash().unpack(),
             });
         }
+        let genesis_hash = if block.number() == 0 {
+            Some(block.hash())
+        } else {
+            None
+        };
+        let udt_scripts = self.udt_scripts(genesis_hash)?;
         let rt = self.runtime();
-        let cli = self.mut_client();
-        let txn = rt.block_on(cli.transaction())?;
+        let cli = self.mut_backend();
         rt.block_on(async {
-            ops::insert_block_header(&txn, &block.header()).await?;
+            let txn = cli.transaction().await?;
+            ops::insert_block_header(&*txn, &block.header()).await?;
+            if block.number() == 0 {
+                // The network is detected from the genesis block, when no registry is provided.
+                udt_ops::record_scripts(&*txn, &udt_scripts).await?;
+            }
             let uncle_hashes = block.uncle_hashes().into_iter();
-            ops::insert_block_uncles(&txn, &block.hash(), uncle_hashes).await?;
+            ops::insert_block_uncles(&*txn, &block.hash(), uncle_hashes).await?;
             for uncle in block.uncles().into_iter() {
-                ops::insert_uncle_header(&txn, &uncle.header()).await?;
+                ops::insert_uncle_header(&*txn, &uncle.header()).await?;
                 let proposals = uncle.data().proposals().into_iter();
-                ops::insert_block_proposals(&txn, &uncle.hash(), proposals).await?;
+                ops::insert_block_proposals(&*txn, &uncle.hash(), proposals).await?;
             }
             let proposals = block.data().proposals().into_iter();
-            ops::insert_block_proposals(&txn, &block.hash(), proposals).await?;
+            ops::insert_block_proposals(&*txn, &block.hash(), proposals).await?;
             let tx_hashes = block.tx_hashes().to_owned().into_iter();
-            ops::insert_block_transactions(&txn, &block.hash(), tx_hashes).await?;
+            ops::insert_block_transactions(&*txn, &block.hash(), tx_hashes).await?;
             for (tx_index, tx) in block.transactions().into_iter().enumerate() {
-                ops::insert_transaction(&txn, &tx, tx_index).await?;
+                ops::insert_transaction(&*txn, &tx, tx_index).await?;
                 if tx_index != 0 {
                     let inputs = tx.data().raw().inputs().into_iter();
-                    ops::consume_cells(&txn, &tx.hash(), inputs).await?;
+                    ops::consume_cells(&*txn, &tx.hash(), block.number(), inputs).await?;
                 }
                 let outputs = tx.data().raw().outputs().into_iter();
                 let outputs_data = tx.data().raw().outputs_data().into_iter();
-                ops::insert_cells(&txn, &tx.hash(), outputs, outputs_data).await?;
+                ops::insert_cells(&*txn, &tx.hash(), block.number(), outputs, outputs_data).await?;
+                accounts_ops::insert_changes(&*txn, &tx.hash(), block.number(), tx_index).await?;
+                udt_ops::insert_changes(&*txn, &tx, block.number(), tx_index, &udt_scripts).await?;
             }
-            txn.commit().await.map_err(Into::<Error>::into)
+            mining_ops::insert_changes(&*txn, block).await?;
+            proposals_ops::insert_changes(&*txn, block.number()).await?;
+            epochs_ops::insert_changes(&*txn, block.number()).await?;
+            rollups_ops::insert_changes(&*txn, block.number()).await?;
+            txn.commit().await
         })?;
         Ok(())
     }
@@ -83,26 +121,32 @@ impl BaseData for Storage {
     fn remove_block(&mut self, number: u64) -> Result<()> {
         log::trace!("remove block {}", number);
         let rt = self.runtime();
-        let cli = self.mut_client();
-        let block_hash_opt = rt.block_on(ops::query_block_hash(&cli, number))?;
+        let cli = self.mut_backend();
+        let block_hash_opt = rt.block_on(ops::query_block_hash(cli.as_ref(), number))?;
         if let Some(block_hash) = block_hash_opt {
             log::trace!("remove block {:#}", block_hash);
-            let txn = rt.block_on(cli.transaction())?;
             rt.block_on(async {
-                let tx_hashes = ops::remove_block_transactions(&txn, &block_hash).await?;
+                let txn = cli.transaction().await?;
+                rollups_ops::remove_changes(&*txn, number).await?;
+                accounts_ops::remove_changes(&*txn, number).await?;
+                udt_ops::remove_changes(&*txn, number).await?;
+                mining_ops::remove_changes(&*txn, number).await?;
+                proposals_ops::remove_changes(&*txn, number).await?;
+                epochs_ops::remove_changes(&*txn, number).await?;
+                let tx_hashes = ops::remove_block_transactions(&*txn, &block_hash).await?;
                 for tx_hash in tx_hashes.into_iter() {
-                    ops::remove_transaction(&txn, &tx_hash).await?;
-                    ops::restore_cells(&txn, &tx_hash).await?;
-                    ops::remove_cells(&txn, &tx_hash).await?;
+                    ops::remove_transaction(&*txn, &tx_hash).await?;
+                    ops::restore_cells(&*txn, &tx_hash).await?;
+                    ops::remove_cells(&*txn, &tx_hash).await?;
                 }
-                ops::remove_block_proposals(&txn, &block_hash).await?;
-                let uncle_hashes = ops::remove_block_uncles(&txn, &block_hash).await?;
+                let uncle_hashes = ops::remove_block_uncles(&*txn, &block_hash).await?;
                 for uncle_hash in uncle_hashes.into_iter() {
-                    ops::remove_uncle_header(&txn, &uncle_hash).await?;
-                    ops::remove_block_proposals(&txn, &uncle_hash).await?;
+                    ops::remove_uncle_header(&*txn, &uncle_hash).await?;
+                    ops::remove_block_proposals(&*txn, &uncle_hash).await?;
                 }
-                ops::remove_block_header(&txn, &block_hash).await?;
-                txn.commit().await.map_err(Into::<Error>::into)
+                ops::remove_block_header(&*txn, &block_hash).await?;
+                ops::remove_block_proposals(&*txn, &block_hash).await?;
+                txn.commit().await
             })?;
         }
         Ok(())
@@ -110,7 +154,7 @@ impl BaseData for Storage {
 
     fn verify_block(&self, header: &core::HeaderView) -> Result<bool> {
         log::trace!("verify block {:#}", header.hash());
-        let cli = self.client();
+        let cli = self.backend();
         let sql = r#"
             SELECT 1
               FROM block_headers
@@ -122,20 +166,22 @@ impl BaseData for Storage {
             cli.query_opt(
                 sql,
                 &[
-                    &(header.number() as i64 - 1),
-                    &(header.parent_hash().raw_data().as_ref()),
+                    (header.number() as i64 - 1).into(),
+                    header.parent_hash().raw_data().as_ref().into(),
                 ],
             )
             .await
             .and_then(|row_opt| {
                 row_opt
-                    .map(|row| {
-                        row.try_get::<_, Option<i32>>(0)
-                            .map(|value| value.is_some())
-                    })
+                    .map(|row| row.try_get::<Option<i32>>(0).map(|value| value.is_some()))
                     .unwrap_or(Ok(false))
             })
         })
-        .map_err(Into::into)
+    }
+
+    fn block_hash(&self, number: u64) -> Result<Option<packed::Byte32>> {
+        log::trace!("query block hash {}", number);
+        let cli = self.backend().as_ref();
+        self.block_on(ops::query_block_hash(cli, number))
     }
 }
@@ -9,29 +9,25 @@
 use futures::future::try_join_all;
 use uckb_jsonrpc_core::types::{core, packed, prelude::*};
 
-use super::super::operations as ops;
-use crate::{error::Result, postgres as pg, utilities::Dao};
+use super::super::{
+    accounts::operations as accounts_ops, epochs::operations as epochs_ops,
+    mining::operations as mining_ops, operations as ops, proposals::operations as proposals_ops,
+    rollups::operations as rollups_ops, script_usage, udt::operations as udt_ops,
+};
+use crate::{
+    error::Result,
+    storage::backend::{Backend, Transaction},
+    utilities::Dao,
+};
 
-pub(super) async fn is_first_run(cli: &pg::Client) -> Result<bool> {
+pub(super) async fn is_first_run(cli: &dyn Backend) -> Result<bool> {
     log::trace!("check if is the first run");
-    cli.query("SELECT 1 FROM block_headers;", &[])
+    cli.table_exists("block_headers")
         .await
-        .map(|_| false)
-        .or_else(|err| {
-            let undefined = err
-                .code()
-                .map(|s| *s == pg::error::SqlState::UNDEFINED_TABLE)
-                .unwrap_or(false);
-            if undefined {
-                Ok(true)
-            } else {
-                Err(err)
-            }
-        })
-        .map_err(Into::into)
+        .map(|exists| !exists)
 }
 
-pub(super) async fn create_tables(cli: &pg::Client) -> Result<Vec<u64>> {
+pub(super) async fn create_tables(cli: &dyn Backend) -> Result<Vec<u64>> {
     log::trace!("create all tables");
     let mut sqls = Vec::new();
     {
@@ -62,7 +58,7 @@ pub(super) async fn create_tables(cli: &pg::Client) -> Result<Vec<u64>> {
             CREATE TABLE IF NOT EXISTS block_uncles (
                 block_hash          BYTEA       NOT NULL,
                 uncle_hash          BYTEA       NOT NULL,
-                index               INTEGER     NOT NULL,
+                "index"             INTEGER     NOT NULL,
                 PRIMARY KEY (block_hash, uncle_hash)
             );"#;
         sqls.push(sql);
@@ -95,7 +91,7 @@ pub(super) async fn create_tables(cli: &pg::Client) -> Result<Vec<u64>> {
             CREATE TABLE IF NOT EXISTS block_proposals (
                 block_hash          BYTEA       NOT NULL,
                 short_id            BYTEA       NOT NULL,
-                index               INTEGER     NOT NULL,
+                "index"             INTEGER     NOT NULL,
                 PRIMARY KEY (block_hash, short_id)
             );"#;
         sqls.push(sql);
@@ -105,7 +101,7 @@ pub(super) async fn create_tables(cli: &pg::Client) -> Result<Vec<u64>> {
             CREATE TABLE IF NOT EXISTS block_transactions (
                 block_hash          BYTEA       NOT NULL,
                 tx_hash             BYTEA       NOT NULL,
-                index               INTEGER     NOT NULL,
+                "index"             INTEGER     NOT NULL,
                 PRIMARY KEY (block_hash, tx_hash)
             );"#;
         sqls.push(sql);
@@ -125,7 +121,7 @@ pub(super) async fn create_tables(cli: &pg::Client) -> Result<Vec<u64>> {
                 ref_index           INTEGER     NOT NULL,
                 ref_dep_index       INTEGER     NOT NULL,
                 tx_hash             BYTEA       NOT NULL,
-                index               INTEGER     NOT NULL,
+                "index"             INTEGER     NOT NULL,
                 dep_type            SMALLINT    NOT NULL,
                 PRIMARY KEY (ref_tx_hash, ref_index, ref_dep_index)
             );"#;
@@ -157,7 +153,7 @@ pub(super) async fn create_tables(cli: &pg::Client) -> Result<Vec<u64>> {
         let sql = r#"
             CREATE TABLE IF NOT EXISTS cells (
                 tx_hash             BYTEA       NOT NULL,
-                index               INTEGER     NOT NULL,
+                "index"             INTEGER     NOT NULL,
                 capacity            BIGINT      NOT NULL,
                 lock_hash           BYTEA       NOT NULL,
                 type_hash           BYTEA,
@@ -165,7 +161,9 @@ pub(super) async fn create_tables(cli: &pg::Client) -> Result<Vec<u64>> {
                 consumed_tx_hash    BYTEA,
                 consumed_index      INTEGER,
                 consumed_since      BYTEA,
-                PRIMARY KEY (tx_hash, index)
+                created_block_number    BIGINT  NOT NULL,
+                consumed_block_number   BIGINT,
+                PRIMARY KEY (tx_hash, "index")
             );"#;
         sqls.push(sql);
     }
@@ -191,10 +189,77 @@ pub(super) async fn create_tables(cli: &pg::Client) -> Result<Vec<u64>> {
         .into_iter()
         .map(|sql| cli.execute(sql, &[]))
         .collect::<Vec<_>>();
-    try_join_all(futures).await.map_err(Into::into)
+    try_join_all(futures).await
+}
+
+// Adds the columns which are introduced after the tables are created, and fills them from the
+// stored blocks.
+pub(super) async fn upgrade_tables(cli: &mut dyn Backend) -> Result<()> {
+    log::trace!("upgrade all tables");
+    if cli.column_exists("cells", "created_block_number").await? {
+        return Ok(());
+    }
+    log::info!("add the block numbers to the stored cells");
+    let sqls = &[
+        r#"ALTER TABLE cells ADD COLUMN created_block_number BIGINT NOT NULL DEFAULT 0;"#,
+        r#"
+        UPDATE cells
+           SET created_block_number = (
+               SELECT bh.number
+                 FROM block_transactions bt
+                 JOIN block_headers bh ON bh.hash = bt.block_hash
+                WHERE bt.tx_hash = cells.tx_hash
+           )
+        ;"#,
+        r#"ALTER TABLE cells ADD COLUMN consumed_block_number BIGINT;"#,
+        r#"
+        UPDATE cells
+           SET consumed_block_number = (
+               SELECT bh.number
+                 FROM block_transactions bt
+                 JOIN block_headers bh ON bh.hash = bt.block_hash
+                WHERE bt.tx_hash = cells.consumed_tx_hash
+           )
+         WHERE consumed_tx_hash IS NOT NULL
+        ;"#,
+    ];
+    let txn = cli.transaction().await?;
+    for sql in sqls {
+        txn.execute(sql, &[]).await?;
+    }
+    txn.commit().await
+}
+
+// The indexes are created at every start, so they are added to the storages which are created
+// before them.
+pub(super) async fn create_indexes(cli: &dyn Backend) -> Result<Vec<u64>> {
+    log::trace!("create all indexes");
+    let sqls = &[
+        r#"CREATE INDEX IF NOT EXISTS block_transactions_tx_hash_idx
+               ON block_transactions (tx_hash);"#,
+        r#"CREATE INDEX IF NOT EXISTS cells_lock_hash_idx ON cells (lock_hash);"#,
+        r#"CREATE INDEX IF NOT EXISTS cells_type_hash_idx ON cells (type_hash);"#,
+        r#"CREATE INDEX IF NOT EXISTS cells_consumed_tx_hash_idx ON cells (consumed_tx_hash);"#,
+        r#"CREATE INDEX IF NOT EXISTS cells_created_block_number_idx
+               ON cells (created_block_number);"#,
+        r#"CREATE INDEX IF NOT EXISTS cells_consumed_block_number_idx
+               ON cells (consumed_block_number);"#,
+        r#"CREATE INDEX IF NOT EXISTS scripts_code_hash_idx ON scripts (code_hash);"#,
+    ];
+    let mut ret = Vec::with_capacity(sqls.len() + accounts_ops::INDEXES.len());
+    for sql in sqls
+        .iter()
+        .chain(accounts_ops::INDEXES)
+        .chain(udt_ops::INDEXES)
+        .chain(mining_ops::INDEXES)
+        .chain(proposals_ops::INDEXES)
+    {
+        ret.push(cli.execute(sql, &[]).await?);
+    }
+    Ok(ret)
 }
 
-pub(super) async fn drop_tables(cli: &pg::Client) -> Result<Vec<u64>> {
+pub(super) async fn drop_tables(cli: &dyn Backend) -> Result<Vec<u64>> {
     log::trace!("drop all tables");
     let tables = &[
         "block_headers",
@@ -212,24 +277,30 @@ pub(super) async fn drop_tables(cli: &pg::Client) -> Result<Vec<u64>> {
     ];
     let futures = tables
         .iter()
+        .chain(accounts_ops::TABLES)
+        .chain(script_usage::TABLES)
+        .chain(udt_ops::TABLES)
+        .chain(mining_ops::TABLES)
+        .chain(proposals_ops::TABLES)
+        .chain(epochs_ops::TABLES)
+        .chain(rollups_ops::TABLES)
         .map(|name| ops::drop_table(cli, name))
         .collect::<Vec<_>>();
     try_join_all(futures).await
 }
 
-pub(super) async fn check_current_block(cli: &pg::Client) -> Result<Option<u64>> {
+pub(super) async fn check_current_block(cli: &dyn Backend) -> Result<Option<u64>> {
     log::trace!("check the number of current block");
     cli.query_one("SELECT MAX(number) FROM block_headers;", &[])
         .await
         .and_then(|row| {
-            row.try_get::<_, Option<i64>>(0)
+            row.try_get::<Option<i64>>(0)
                 .map(|num_opt| num_opt.map(|num| num as u64))
         })
-        .map_err(Into::into)
 }
 
 pub(super) async fn query_block_hash(
-    cli: &pg::Client,
+    cli: &dyn Backend,
     number: u64,
 ) -> Result<Option<packed::Byte32>> {
     log::trace!("query block by number {}", number);
@@ -239,14 +310,12 @@ pub(super) async fn query_block_hash(
          WHERE 1 = 1
            AND number = $1
     ;"#;
-    cli.query_opt(sql, &[&(number as i64)])
+    cli.query_opt(sql, &[(number as i64).into()])
         .await
-        .map_err(Into::into)
         .and_then(|row_opt| {
             row_opt
                 .and_then(|row| {
-                    row.try_get::<_, Option<Vec<u8>>>(0)
-                        .map_err(Into::into)
+                    row.try_get::<Option<Vec<u8>>>(0)
                         .transpose()
                         .map(|value_opt| value_opt.and_then(ops::hash_from_value))
                 })
@@ -255,7 +324,7 @@ pub(super) async fn query_block_hash(
 }
 
 async fn insert_header(
-    txn: &pg::Transaction<'_>,
+    txn: &dyn Transaction,
     table_name: &str,
     header: &core::HeaderView,
 ) -> Result<u64> {
@@ -277,31 +346,30 @@ async fn insert_header(
     txn.execute(
         sql.as_str(),
         &[
-            &header.hash().raw_data().as_ref(),
-            &(header.version() as i32),
-            &(header.compact_target() as i64),
-            &(header.timestamp() as i64),
-            &(header.number() as i64),
-            &(header.epoch().number() as i32),
-            &(header.epoch().index() as i32),
-            &(header.epoch().length() as i32),
-            &header.parent_hash().raw_data().as_ref(),
-            &header.transactions_root().raw_data().as_ref(),
-            &header.proposals_hash().raw_data().as_ref(),
-            &header.uncles_hash().raw_data().as_ref(),
-            &(dao.c() as i64),
-            &(dao.ar() as i64),
-            &(dao.s() as i64),
-            &(dao.u() as i64),
-            &header.data().nonce().raw_data().as_ref(),
+            header.hash().raw_data().as_ref().into(),
+            (header.version() as i32).into(),
+            (header.compact_target() as i64).into(),
+            (header.timestamp() as i64).into(),
+            (header.number() as i64).into(),
+            (header.epoch().number() as i32).into(),
+            (header.epoch().index() as i32).into(),
+            (header.epoch().length() as i32).into(),
+            header.parent_hash().raw_data().as_ref().into(),
+            header.transactions_root().raw_data().as_ref().into(),
+            header.proposals_hash().raw_data().as_ref().into(),
+            header.uncles_hash().raw_data().as_ref().into(),
+            (dao.c() as i64).into(),
+            (dao.ar() as i64).into(),
+            (dao.s() as i64).into(),
+            (dao.u() as i64).into(),
+            header.data().nonce().raw_data().as_ref().into(),
         ],
     )
     .await
-    .map_err(Into::into)
 }
 
 pub(super) async fn insert_block_header(
-    txn: &pg::Transaction<'_>,
+    txn: &dyn Transaction,
     header: &core::HeaderView,
 ) -> Result<u64> {
     log::trace!("insert block header {:#}", header.hash());
@@ -309,38 +377,36 @@ pub(super) async fn insert_block_header(
 }
 
 pub(super) async fn remove_block_header(
-    txn: &pg::Transaction<'_>,
+    txn: &dyn Transaction,
     block_hash: &packed::Byte32,
 ) -> Result<u64> {
     log::trace!("remove block header {:#}", block_hash);
     let sql = r#"DELETE FROM block_headers WHERE hash = $1;"#;
-    txn.execute(sql, &[&block_hash.raw_data().as_ref()])
+    txn.execute(sql, &[block_hash.raw_data().as_ref().into()])
         .await
-        .map_err(Into::into)
 }
 
 pub(super) async fn insert_block_uncles(
-    txn: &pg::Transaction<'_>,
+    txn: &dyn Transaction,
     block_hash: &packed::Byte32,
     uncle_hashes: impl Iterator<Item = packed::Byte32>,
 ) -> Result<()> {
     log::trace!("insert uncles for block {:#}", block_hash);
     let sql = r#"
         INSERT INTO block_uncles (
-            block_hash, uncle_hash, index
+            block_hash, uncle_hash, "index"
         ) VALUES (
             $1, $2, $3
         )
         ON CONFLICT DO NOTHING
     ;"#;
-    let stmt = txn.prepare(sql).await?;
     for (index, uncle_hash) in uncle_hashes.enumerate() {
         txn.execute(
-            &stmt,
+            sql,
             &[
-                &block_hash.raw_data().as_ref(),
-                &uncle_hash.raw_data().as_ref(),
-                &(index as i32),
+                block_hash.raw_data().as_ref().into(),
+                uncle_hash.raw_data().as_ref().into(),
+                (index as i32).into(),
             ],
         )
         .await?;
@@ -349,7 +415,7 @@ pub(super) async fn insert_block_uncles(
 }
 
 pub(super) async fn remove_block_uncles(
-    txn: &pg::Transaction<'_>,
+    txn: &dyn Transaction,
     block_hash: &packed::Byte32,
 ) -> Result<Vec<packed::Byte32>> {
     log::trace!("remove uncles for block {:#}", block_hash);
@@ -358,22 +424,17 @@ pub(super) async fn remove_block_uncles(
          WHERE block_hash = $1
      RETURNING uncle_hash
     ;"#;
-    txn.query(sql, &[&block_hash.raw_data().as_ref()])
+    txn.query(sql, &[block_hash.raw_data().as_ref().into()])
         .await
-        .map_err(Into::into)
         .and_then(|ref rows| {
             rows.iter()
-                .map(|ref row| {
-                    row.try_get::<_, Vec<u8>>(0)
-                        .map_err(Into::into)
-                        .and_then(ops::hash_from_value)
-                })
+                .map(|row| row.try_get::<Vec<u8>>(0).and_then(ops::hash_from_value))
                 .collect::<Result<Vec<packed::Byte32>>>()
         })
 }
 
 pub(super) async fn insert_uncle_header(
-    txn: &pg::Transaction<'_>,
+    txn: &dyn Transaction,
     header: &core::HeaderView,
 ) -> Result<u64> {
     log::trace!("insert uncle header {:#}", header.hash());
@@ -381,12 +442,12 @@ pub(super) async fn insert_uncle_header(
 }
 
 pub(super) async fn remove_uncle_header(
-    txn: &pg::Transaction<'_>,
+    txn: &dyn Transaction,
     uncle_hash: &packed::Byte32,
 ) -> Result<u64> {
     log::trace!("remove uncle header {:#}", uncle_hash);
     let sql = r#"
-        DELETE FROM uncle_headers uh
+        DELETE FROM uncle_headers AS uh
               WHERE 1 = 1
                 AND hash = $1
                 AND NOT EXISTS (
@@ -394,33 +455,31 @@ pub(super) async fn remove_uncle_header(
                       FROM block_uncles bu
                      WHERE bu.uncle_hash = uh.hash)
     ;"#;
-    txn.execute(sql, &[&uncle_hash.raw_data().as_ref()])
+    txn.execute(sql, &[uncle_hash.raw_data().as_ref().into()])
         .await
-        .map_err(Into::into)
 }
 
 pub(super) async fn insert_block_proposals(
-    txn: &pg::Transaction<'_>,
+    txn: &dyn Transaction,
     block_hash: &packed::Byte32,
     proposals: impl Iterator<Item = packed::ProposalShortId>,
 ) -> Result<()> {
     log::trace!("insert proposals for block {:#}", block_hash);
     let sql = r#"
         INSERT INTO block_proposals (
-            block_hash, short_id, index
+            block_hash, short_id, "index"
         ) VALUES (
             $1, $2, $3
         )
         ON CONFLICT DO NOTHING
     ;"#;
-    let stmt = txn.prepare(sql).await?;
     for (index, proposal) in proposals.enumerate() {
         txn.execute(
-            &stmt,
+            sql,
             &[
-                &block_hash.raw_data().as_ref(),
-                &proposal.raw_data().as_ref(),
-                &(index as i32),
+                block_hash.raw_data().as_ref().into(),
+                proposal.raw_data().as_ref().into(),
+                (index as i32).into(),
             ],
         )
         .await?;
@@ -429,12 +488,12 @@ pub(super) async fn insert_block_proposals(
 }
 
 pub(super) async fn remove_block_proposals(
-    txn: &pg::Transaction<'_>,
+    txn: &dyn Transaction,
     block_hash: &packed::Byte32,
 ) -> Result<u64> {
     log::trace!("remove proposals for block {:#}", block_hash);
     let sql = r#"
-        DELETE FROM block_proposals bp
+        DELETE FROM block_proposals AS bp
               WHERE 1 = 1
                 AND block_hash = $1
                 AND NOT EXISTS (
@@ -446,33 +505,31 @@ pub(super) async fn remove_block_proposals(
                       FROM block_uncles bu
                      WHERE bu.uncle_hash = bp.block_hash)
     ;"#;
-    txn.execute(sql, &[&block_hash.raw_data().as_ref()])
+    txn.execute(sql, &[block_hash.raw_data().as_ref().into()])
         .await
-        .map_err(Into::into)
 }
 
 pub(super) async fn insert_block_transactions(
-    txn: &pg::Transaction<'_>,
+    txn: &dyn Transaction,
     block_hash: &packed::Byte32,
     tx_hashes: impl Iterator<Item = packed::Byte32>,
 ) -> Result<()> {
     log::trace!("insert transactions for block {:#}", block_hash);
     let sql = r#"
         INSERT INTO block_transactions (
-            block_hash, tx_hash, index
+            block_hash, tx_hash, "index"
         ) VALUES (
             $1, $2, $3
         )
         ON CONFLICT DO NOTHING
     ;"#;
-    let stmt = txn.prepare(sql).await?;
     for (index, tx_hash) in tx_hashes.enumerate() {
         txn.execute(
-            &stmt,
+            sql,
             &[
-                &block_hash.raw_data().as_ref(),
-                &tx_hash.raw_data().as_ref(),
-                &(index as i32),
+                block_hash.raw_data().as_ref().into(),
+                tx_hash.raw_data().as_ref().into(),
+                (index as i32).into(),
             ],
         )
         .await?;
@@ -481,7 +538,7 @@ pub(super) async fn insert_block_transactions(
 }
 
 pub(super) async fn remove_block_transactions(
-    txn: &pg::Transaction<'_>,
+    txn: &dyn Transaction,
     block_hash: &packed::Byte32,
 ) -> Result<Vec<packed::Byte32>> {
     log::trace!("remove transactions for block {:#}", block_hash);
@@ -490,22 +547,17 @@ pub(super) async fn remove_block_transactions(
          WHERE block_hash = $1
      RETURNING tx_hash
     ;"#;
-    txn.query(sql, &[&block_hash.raw_data().as_ref()])
+    txn.query(sql, &[block_hash.raw_data().as_ref().into()])
         .await
-        .map_err(Into::into)
         .and_then(|ref rows| {
             rows.iter()
-                .map(|ref row| {
-                    row.try_get::<_, Vec<u8>>(0)
-                        .map_err(Into::into)
-                        .and_then(ops::hash_from_value)
-                })
+                .map(|row| row.try_get::<Vec<u8>>(0).and_then(ops::hash_from_value))
                 .collect::<Result<Vec<packed::Byte32>>>()
         })
 }
 
 pub(super) async fn insert_transaction(
-    txn: &pg::Transaction<'_>,
+    txn: &dyn Transaction,
     tx: &core::TransactionView,
     ref_index: usize,
 ) -> Result<u64> {
@@ -513,25 +565,24 @@ pub(super) async fn insert_transaction(
     {
         let sql = r#"
             INSERT INTO tx_cell_deps (
-                ref_tx_hash, ref_index, ref_dep_index, tx_hash, index, dep_type
+                ref_tx_hash, ref_index, ref_dep_index, tx_hash, "index", dep_type
             ) VALUES (
                 $1, $2, $3, $4, $5, $6
             )
             ON CONFLICT DO NOTHING
         ;"#;
-        let stmt = txn.prepare(sql).await?;
         for (index, cell_dep) in tx.cell_deps().into_iter().enumerate() {
             let tmp: u32 = cell_dep.out_point().index().unpack();
             let dep_type: u8 = cell_dep.dep_type().into();
             txn.execute(
-                &stmt,
+                sql,
                 &[
-                    &tx.hash().raw_data().as_ref(),
-                    &(ref_index as i32),
-                    &(index as i32),
-                    &cell_dep.out_point().tx_hash().raw_data().as_ref(),
-                    &(tmp as i32),
-                    &(dep_type as i16),
+                    tx.hash().raw_data().as_ref().into(),
+                    (ref_index as i32).into(),
+                    (index as i32).into(),
+                    cell_dep.out_point().tx_hash().raw_data().as_ref().into(),
+                    (tmp as i32).into(),
+                    (dep_type as i16).into(),
                 ],
             )
             .await?;
@@ -546,15 +597,14 @@ pub(super) async fn insert_transaction(
             )
             ON CONFLICT DO NOTHING
         ;"#;
-        let stmt = txn.prepare(sql).await?;
         for (index, header_dep) in tx.header_deps().into_iter().enumerate() {
             txn.execute(
-                &stmt,
+                sql,
                 &[
-                    &tx.hash().raw_data().as_ref(),
-                    &(ref_index as i32),
-                    &(index as i32),
-                    &header_dep.raw_data().as_ref(),
+                    tx.hash().raw_data().as_ref().into(),
+                    (ref_index as i32).into(),
+                    (index as i32).into(),
+                    header_dep.raw_data().as_ref().into(),
                 ],
             )
             .await?;
@@ -569,15 +619,14 @@ pub(super) async fn insert_transaction(
             )
             ON CONFLICT DO NOTHING
         ;"#;
-        let stmt = txn.prepare(sql).await?;
         for (index, witness) in tx.witnesses().into_iter().enumerate() {
             txn.execute(
-                &stmt,
+                sql,
                 &[
-                    &tx.hash().raw_data().as_ref(),
-                    &(ref_index as i32),
-                    &(index as i32),
-                    &witness.raw_data().as_ref(),
+                    tx.hash().raw_data().as_ref().into(),
+                    (ref_index as i32).into(),
+                    (index as i32).into(),
+                    witness.raw_data().as_ref().into(),
                 ],
             )
             .await?;
@@ -593,14 +642,16 @@ pub(super) async fn insert_transaction(
     ;"#;
     txn.execute(
         sql,
-        &[&tx.hash().raw_data().as_ref(), &(tx.version() as i32)],
+        &[
+            tx.hash().raw_data().as_ref().into(),
+            (tx.version() as i32).into(),
+        ],
     )
     .await
-    .map_err(Into::into)
 }
 
 pub(super) async fn remove_transaction(
-    txn: &pg::Transaction<'_>,
+    txn: &dyn Transaction,
     tx_hash: &packed::Byte32,
 ) -> Result<Vec<u64>> {
     log::trace!("remove transaction {:#}", tx_hash);
@@ -612,14 +663,16 @@ pub(super) async fn remove_transaction(
     ];
     let mut ret = Vec::with_capacity(sqls.len());
     for sql in sqls {
-        let code = txn.execute(*sql, &[&tx_hash.raw_data().as_ref()]).await?;
+        let code = txn
+            .execute(sql, &[tx_hash.raw_data().as_ref().into()])
+            .await?;
         ret.push(code);
     }
     Ok(ret)
 }
 
 async fn insert_cell_data(
-    txn: &pg::Transaction<'_>,
+    txn: &dyn Transaction,
     data_hash: &packed::Byte32,
     data: &packed::Bytes,
 ) -> Result<u64> {
@@ -634,16 +687,18 @@ async fn insert_cell_data(
     ;"#;
     txn.execute(
         sql,
-        &[&data_hash.raw_data().as_ref(), &data.raw_data().as_ref()],
+        &[
+            data_hash.raw_data().as_ref().into(),
+            data.raw_data().as_ref().into(),
+        ],
     )
     .await
-    .map_err(Into::into)
 }
 
-async fn remove_cell_data(txn: &pg::Transaction<'_>, data_hash: &packed::Byte32) -> Result<u64> {
+async fn remove_cell_data(txn: &dyn Transaction, data_hash: &packed::Byte32) -> Result<u64> {
     log::trace!("remove cell data {:#}", data_hash);
     let sql = r#"
-        DELETE FROM cells_data cd
+        DELETE FROM cells_data AS cd
          WHERE 1 = 1
            AND hash = $1
            AND NOT EXISTS (
@@ -651,13 +706,12 @@ async fn remove_cell_data(txn: &pg::Transaction<'_>, data_hash: &packed::Byte32)
                  FROM cells c
                 WHERE c.data_hash = cd.hash)
     ;"#;
-    txn.execute(sql, &[&data_hash.raw_data().as_ref()])
+    txn.execute(sql, &[data_hash.raw_data().as_ref().into()])
         .await
-        .map_err(Into::into)
 }
 
 async fn insert_script(
-    txn: &pg::Transaction<'_>,
+    txn: &dyn Transaction,
     script_hash: &packed::Byte32,
     script: &packed::Script,
 ) -> Result<u64> {
@@ -674,20 +728,19 @@ async fn insert_script(
     txn.execute(
         sql,
         &[
-            &script_hash.raw_data().as_ref(),
-            &script.code_hash().raw_data().as_ref(),
-            &(hash_type as i16),
-            &script.args().raw_data().as_ref(),
+            script_hash.raw_data().as_ref().into(),
+            script.code_hash().raw_data().as_ref().into(),
+            (hash_type as i16).into(),
+            script.args().raw_data().as_ref().into(),
         ],
     )
     .await
-    .map_err(Into::into)
 }
 
-async fn remove_script(txn: &pg::Transaction<'_>, script_hash: &packed::Byte32) -> Result<u64> {
+async fn remove_script(txn: &dyn Transaction, script_hash: &packed::Byte32) -> Result<u64> {
     log::trace!("remove script {:#}", script_hash);
     let sql = r#"
-        DELETE FROM scripts s
+        DELETE FROM scripts AS s
          WHERE 1 = 1
            AND hash = $1
            AND NOT EXISTS (
@@ -696,27 +749,26 @@ async fn remove_script(txn: &pg::Transaction<'_>, script_hash: &packed::Byte32)
                 WHERE c.lock_hash = s.hash
                    OR c.type_hash = s.hash)
     ;"#;
-    txn.execute(sql, &[&script_hash.raw_data().as_ref()])
+    txn.execute(sql, &[script_hash.raw_data().as_ref().into()])
         .await
-        .map_err(Into::into)
 }
 
 pub(super) async fn insert_cells(
-    txn: &pg::Transaction<'_>,
+    txn: &dyn Transaction,
     tx_hash: &packed::Byte32,
+    block_number: u64,
     outputs: impl Iterator<Item = packed::CellOutput>,
     outputs_data: impl Iterator<Item = packed::Bytes>,
 ) -> Result<()> {
     log::trace!("insert cells for transaction {:#}", tx_hash);
     let sql = r#"
         INSERT INTO cells (
-            tx_hash, index, capacity, lock_hash, type_hash, data_hash
+            tx_hash, "index", capacity, lock_hash, type_hash, data_hash, created_block_number
         ) VALUES (
-            $1, $2, $3, $4, $5, $6
+            $1, $2, $3, $4, $5, $6, $7
         )
         ON CONFLICT DO NOTHING
     ;"#;
-    let stmt = txn.prepare(sql).await?;
     for (index, (output, data)) in outputs.zip(outputs_data).enumerate() {
         let data_hash = packed::CellOutput::calc_data_hash(data.raw_data().as_ref());
         let lock_hash = output.lock().calc_script_hash();
@@ -731,17 +783,17 @@ pub(super) async fn insert_cells(
             None
         };
         txn.execute(
-            &stmt,
+            sql,
             &[
-                &tx_hash.raw_data().as_ref(),
-                &(index as i32),
-                &(capacity.as_u64() as i64),
-                &lock_hash.raw_data().as_ref(),
-                &type_hash_opt
-                    .map(|type_hash| type_hash.raw_data())
-                    .as_ref()
-                    .map(AsRef::as_ref),
-                &data_hash.raw_data().as_ref(),
+                tx_hash.raw_data().as_ref().into(),
+                (index as i32).into(),
+                (capacity.as_u64() as i64).into(),
+                lock_hash.raw_data().as_ref().into(),
+                type_hash_opt
+                    .map(|type_hash| type_hash.raw_data().to_vec())
+                    .into(),
+                data_hash.raw_data().as_ref().into(),
+                (block_number as i64).into(),
             ],
         )
         .await?;
@@ -749,10 +801,7 @@ pub(super) async fn insert_cells(
     Ok(())
 }
 
-pub(super) async fn remove_cells(
-    txn: &pg::Transaction<'_>,
-    tx_hash: &packed::Byte32,
-) -> Result<()> {
+pub(super) async fn remove_cells(txn: &dyn Transaction, tx_hash: &packed::Byte32) -> Result<()> {
     log::trace!("remove cells for transaction {:#}", tx_hash);
     let sql = r#"
         DELETE FROM cells
@@ -760,22 +809,15 @@ pub(super) async fn remove_cells(
      RETURNING data_hash, lock_hash, type_hash
     ;"#;
     let hashes = txn
-        .query(sql, &[&tx_hash.raw_data().as_ref()])
+        .query(sql, &[tx_hash.raw_data().as_ref().into()])
         .await
-        .map_err(Into::into)
         .and_then(|ref rows| {
             rows.iter()
-                .map(|ref row| {
-                    let data_hash = row
-                        .try_get::<_, Vec<u8>>(0)
-                        .map_err(Into::into)
-                        .and_then(ops::hash_from_value)?;
-                    let lock_hash = row
-                        .try_get::<_, Vec<u8>>(1)
-                        .map_err(Into::into)
-                        .and_then(ops::hash_from_value)?;
+                .map(|row| {
+                    let data_hash = row.try_get::<Vec<u8>>(0).and_then(ops::hash_from_value)?;
+                    let lock_hash = row.try_get::<Vec<u8>>(1).and_then(ops::hash_from_value)?;
                     let type_hash_opt = row
-                        .try_get::<_, Option<Vec<u8>>>(2)?
+                        .try_get::<Option<Vec<u8>>>(2)?
                         .map(ops::hash_from_value)
                         .transpose()?;
                     Ok((data_hash, lock_hash, type_hash_opt))
@@ -793,8 +835,9 @@ pub(super) async fn remove_cells(
 }
 
 pub(super) async fn consume_cells(
-    txn: &pg::Transaction<'_>,
+    txn: &dyn Transaction,
     consumed_tx_hash: &packed::Byte32,
+    block_number: u64,
     inputs: impl Iterator<Item = packed::CellInput>,
 ) -> Result<()> {
     log::trace!("consume cells for transaction {:#}", consumed_tx_hash);
@@ -803,12 +846,12 @@ pub(super) async fn consume_cells(
            SET
                consumed_tx_hash = $1,
                consumed_index = $2,
-               consumed_since = $3
+               consumed_since = $3,
+               consumed_block_number = $6
          WHERE 1 = 1
            AND tx_hash = $4
-           AND index = $5
+           AND "index" = $5
     ;"#;
-    let stmt = txn.prepare(sql).await?;
     for (consumed_index, input) in inputs.enumerate() {
         let since: u64 = input.since().unpack();
         let prev_output = input.previous_output();
@@ -816,13 +859,14 @@ pub(super) async fn consume_cells(
         let tx_hash = prev_output.tx_hash();
         let index: u32 = prev_output.index().unpack();
         txn.execute(
-            &stmt,
+            sql,
             &[
-                &consumed_tx_hash.raw_data().as_ref(),
-                &(consumed_index as i32),
-                &(&since.to_le_bytes()[..]),
-                &tx_hash.raw_data().as_ref(),
-                &(index as i32),
+                consumed_tx_hash.raw_data().as_ref().into(),
+                (consumed_index as i32).into(),
+                (&since.to_le_bytes()[..]).into(),
+                tx_hash.raw_data().as_ref().into(),
+                (index as i32).into(),
+                (block_number as i64).into(),
             ],
         )
         .await?;
@@ -831,7 +875,7 @@ pub(super) async fn consume_cells(
 }
 
 pub(super) async fn restore_cells(
-    txn: &pg::Transaction<'_>,
+    txn: &dyn Transaction,
     restored_tx_hash: &packed::Byte32,
 ) -> Result<u64> {
     log::trace!("restore cells for transaction {:#}", restored_tx_hash);
@@ -840,11 +884,11 @@ pub(super) async fn restore_cells(
            SET
                consumed_tx_hash = null,
                consumed_index = null,
-               consumed_since = null
+               consumed_since = null,
+               consumed_block_number = null
          WHERE 1 = 1
            AND consumed_tx_hash = $1
     ;"#;
-    txn.execute(sql, &[&restored_tx_hash.raw_data().as_ref()])
+    txn.execute(sql, &[restored_tx_hash.raw_data().as_ref().into()])
         .await
-        .map_err(Into::into)
 }
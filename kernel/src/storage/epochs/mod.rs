@@ -0,0 +1,69 @@
+// Copyright (C) 2019-2020 Boyu Yang
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+use super::Storage;
+use crate::{error::Result, utilities::compact_to_difficulty, Report};
+
+pub(super) mod operations;
+
+/// The summaries of epochs, which are maintained when blocks are inserted and removed.
+///
+/// The uncles are counted in the epochs of the blocks which include them. The issuance is the
+/// increment of the total issuance in the `Dao` fields, the secondary issuance is the rest of
+/// the computed primary issuance.
+pub trait Epochs {
+    /// The summaries of the latest epochs.
+    fn epoch_summaries(&self, epochs: u32) -> Result<Report>;
+}
+
+const SUMMARY_COLUMNS: &[&str] = &[
+    "epoch_number",
+    "start_number",
+    "end_number",
+    "start_timestamp",
+    "end_timestamp",
+    "blocks",
+    "transactions",
+    "uncles",
+    "fees",
+    "primary_issuance",
+    "secondary_issuance",
+    "issuance",
+    "compact_target",
+];
+
+impl Epochs for Storage {
+    fn epoch_summaries(&self, epochs: u32) -> Result<Report> {
+        log::trace!("report the summaries of {} epochs", epochs);
+        let sql = format!(
+            r#"
+            SELECT {}
+              FROM (
+                   SELECT *, primary_issuance + secondary_issuance AS issuance
+                     FROM epochs
+                   ) e
+             WHERE epoch_number > (SELECT MAX(epoch_number) FROM epochs) - $1
+             ORDER BY epoch_number
+        ;"#,
+            SUMMARY_COLUMNS.join(", ")
+        );
+        let columns = SUMMARY_COLUMNS
+            .iter()
+            .chain(&["difficulty"])
+            .copied()
+            .collect::<Vec<_>>();
+        let mut report = Report::new(&columns);
+        for row in self.query(&sql, &[i64::from(epochs).into()])? {
+            let compact_target = row.try_get::<i64>(SUMMARY_COLUMNS.len() - 1)? as u32;
+            let mut row = row.into_values();
+            row.push(compact_to_difficulty(compact_target).into());
+            report.push(row);
+        }
+        Ok(report)
+    }
+}
@@ -0,0 +1,243 @@
+// Copyright (C) 2019-2020 Boyu Yang
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+use crate::{
+    error::{Error, Result},
+    storage::{
+        backend::{Backend, Transaction},
+        mining::operations::{primary_reward, query_fees, BLOCK_FEES},
+        Value,
+    },
+};
+
+pub(in crate::storage) const TABLES: &[&str] = &["epochs"];
+
+// What a block adds to its epoch.
+struct Changes {
+    epoch_number: i64,
+    number: i64,
+    timestamp: i64,
+    compact_target: i64,
+    transactions: i64,
+    uncles: i64,
+    fees: i64,
+    primary_issuance: i64,
+    secondary_issuance: i64,
+}
+
+// Creates the table if it doesn't exist, and summarizes the stored blocks by epoch.
+pub(in crate::storage) async fn create_tables(cli: &mut dyn Backend) -> Result<()> {
+    log::trace!("create tables of epochs");
+    if cli.table_exists("epochs").await? {
+        return Ok(());
+    }
+    let sql = r#"
+        CREATE TABLE IF NOT EXISTS epochs (
+            epoch_number        BIGINT      NOT NULL PRIMARY KEY,
+            start_number        BIGINT      NOT NULL,
+            end_number          BIGINT      NOT NULL,
+            start_timestamp     BIGINT      NOT NULL,
+            end_timestamp       BIGINT      NOT NULL,
+            blocks              BIGINT      NOT NULL,
+            transactions        BIGINT      NOT NULL,
+            uncles              BIGINT      NOT NULL,
+            fees                BIGINT      NOT NULL,
+            primary_issuance    BIGINT      NOT NULL,
+            secondary_issuance  BIGINT      NOT NULL,
+            compact_target      BIGINT      NOT NULL
+        );"#;
+    let txn = cli.transaction().await?;
+    txn.execute(sql, &[]).await?;
+    fill_table(&*txn).await?;
+    txn.commit().await
+}
+
+// Summarizes all stored blocks by epoch at once, as `insert_changes` block by block.
+async fn fill_table(txn: &dyn Transaction) -> Result<()> {
+    let sql = "SELECT 1 FROM block_headers LIMIT 1;";
+    if txn.query_opt(sql, &[]).await?.is_none() {
+        return Ok(());
+    }
+    log::info!("summarize the epochs of the stored blocks");
+    // The issuance of the blocks in an epoch is the increment of the total issuance since the
+    // parent of the first block, it is the secondary issuance until the primary one is known.
+    let sql = format!(
+        r#"
+        INSERT INTO epochs (
+            epoch_number, start_number, end_number, start_timestamp, end_timestamp, blocks,
+            transactions, uncles, fees, primary_issuance, secondary_issuance, compact_target
+        )
+        SELECT e.epoch_number, e.start_number, e.end_number, s.timestamp, t.timestamp, e.blocks,
+               e.transactions, e.uncles, e.fees, 0, t.dao_c - COALESCE(p.dao_c, s.dao_c),
+               s.compact_target
+          FROM (
+               SELECT h.epoch_number,
+                      MIN(h.number) AS start_number,
+                      MAX(h.number) AS end_number,
+                      COUNT(*) AS blocks,
+                      CAST(SUM(COALESCE(bt.transactions, 0)) AS BIGINT) AS transactions,
+                      CAST(SUM(COALESCE(bu.uncles, 0)) AS BIGINT) AS uncles,
+                      CAST(SUM(COALESCE(f.fees, 0)) AS BIGINT) AS fees
+                 FROM block_headers h
+                 LEFT JOIN (SELECT block_hash, COUNT(*) AS transactions
+                              FROM block_transactions
+                             GROUP BY block_hash) bt ON bt.block_hash = h.hash
+                 LEFT JOIN (SELECT block_hash, COUNT(*) AS uncles
+                              FROM block_uncles
+                             GROUP BY block_hash) bu ON bu.block_hash = h.hash
+                 LEFT JOIN {} f ON f.block_number = h.number
+                GROUP BY h.epoch_number
+               ) e
+          JOIN block_headers s ON s.number = e.start_number
+          JOIN block_headers t ON t.number = e.end_number
+          LEFT JOIN block_headers p ON p.number = e.start_number - 1
+    ;"#,
+        BLOCK_FEES
+    );
+    txn.execute(&sql, &[]).await?;
+    let sql = r#"
+        SELECT e.epoch_number, e.blocks, s.number, s.epoch_index, s.epoch_length
+          FROM epochs e
+          JOIN block_headers s ON s.number = e.start_number
+    ;"#;
+    for row in txn.query(sql, &[]).await? {
+        let epoch_number = row.try_get::<i64>(0)?;
+        let blocks = row.try_get::<i64>(1)? as u64;
+        let start_index = row.try_get::<i32>(3)? as u64;
+        let epoch_length = row.try_get::<i32>(4)? as u64;
+        // The genesis block has no primary issuance.
+        let skipped = if row.try_get::<i64>(2)? == 0 { 1 } else { 0 };
+        let primary_issuance = (start_index + skipped..start_index + blocks)
+            .map(|index| primary_reward(epoch_number as u64, index, epoch_length) as i64)
+            .sum::<i64>();
+        let sql = r#"
+            UPDATE epochs
+               SET primary_issuance = $2,
+                   secondary_issuance = secondary_issuance - $2
+             WHERE epoch_number = $1
+        ;"#;
+        txn.execute(sql, &[epoch_number.into(), primary_issuance.into()])
+            .await?;
+    }
+    Ok(())
+}
+
+// Adds the block to its epoch, the block should be inserted already.
+pub(in crate::storage) async fn insert_changes(txn: &dyn Transaction, number: u64) -> Result<()> {
+    log::trace!("add block {} to its epoch", number);
+    let changes = query_changes(txn, number).await?;
+    let sql = r#"
+        INSERT INTO epochs (
+            epoch_number, start_number, end_number, start_timestamp, end_timestamp, blocks,
+            transactions, uncles, fees, primary_issuance, secondary_issuance, compact_target
+        ) VALUES (
+            $1, $2, $2, $3, $3, 1, $4, $5, $6, $7, $8, $9
+        )
+        ON CONFLICT (epoch_number) DO UPDATE
+           SET end_number = excluded.end_number,
+               end_timestamp = excluded.end_timestamp,
+               blocks = epochs.blocks + 1,
+               transactions = epochs.transactions + excluded.transactions,
+               uncles = epochs.uncles + excluded.uncles,
+               fees = epochs.fees + excluded.fees,
+               primary_issuance = epochs.primary_issuance + excluded.primary_issuance,
+               secondary_issuance = epochs.secondary_issuance + excluded.secondary_issuance
+    ;"#;
+    let params: &[Value] = &[
+        changes.epoch_number.into(),
+        changes.number.into(),
+        changes.timestamp.into(),
+        changes.transactions.into(),
+        changes.uncles.into(),
+        changes.fees.into(),
+        changes.primary_issuance.into(),
+        changes.secondary_issuance.into(),
+        changes.compact_target.into(),
+    ];
+    txn.execute(sql, params).await?;
+    Ok(())
+}
+
+// Removes the block from its epoch, the block should be the last one and should not be removed
+// yet, the epoch is removed with its first block.
+pub(in crate::storage) async fn remove_changes(txn: &dyn Transaction, number: u64) -> Result<()> {
+    log::trace!("remove block {} from its epoch", number);
+    let changes = query_changes(txn, number).await?;
+    let sql = r#"
+        UPDATE epochs
+           SET end_number = $2 - 1,
+               end_timestamp = COALESCE(
+                   (SELECT timestamp FROM block_headers WHERE number = $2 - 1), end_timestamp),
+               blocks = blocks - 1,
+               transactions = transactions - $3,
+               uncles = uncles - $4,
+               fees = fees - $5,
+               primary_issuance = primary_issuance - $6,
+               secondary_issuance = secondary_issuance - $7
+         WHERE epoch_number = $1
+    ;"#;
+    let params: &[Value] = &[
+        changes.epoch_number.into(),
+        changes.number.into(),
+        changes.transactions.into(),
+        changes.uncles.into(),
+        changes.fees.into(),
+        changes.primary_issuance.into(),
+        changes.secondary_issuance.into(),
+    ];
+    txn.execute(sql, params).await?;
+    let sql = "DELETE FROM epochs WHERE epoch_number = $1 AND blocks = 0;";
+    txn.execute(sql, &[changes.epoch_number.into()]).await?;
+    Ok(())
+}
+
+// The issuance of a block is the increment of the total issuance in the `Dao` field, the
+// primary issuance is computed, the rest is the secondary issuance.
+//
+// The capacity of the genesis block is not issuance.
+async fn query_changes(txn: &dyn Transaction, number: u64) -> Result<Changes> {
+    let sql = r#"
+        SELECT h.epoch_number, h.epoch_index, h.epoch_length, h.timestamp, h.compact_target,
+               h.dao_c, p.dao_c,
+               (SELECT COUNT(*) FROM block_transactions bt WHERE bt.block_hash = h.hash),
+               (SELECT COUNT(*) FROM block_uncles bu WHERE bu.block_hash = h.hash)
+          FROM block_headers h
+          LEFT JOIN block_headers p ON p.number = h.number - 1
+         WHERE h.number = $1
+    ;"#;
+    let row = txn
+        .query_opt(sql, &[(number as i64).into()])
+        .await?
+        .ok_or_else(|| Error::Data(format!("no header of block {}", number)))?;
+    let epoch_number = row.try_get::<i32>(0)?;
+    let epoch_index = row.try_get::<i32>(1)? as u64;
+    let epoch_length = row.try_get::<i32>(2)? as u64;
+    let total_issuance = row.try_get::<i64>(5)?;
+    let (primary_issuance, issuance) = if let Some(parent) = row.try_get::<Option<i64>>(6)? {
+        let primary = primary_reward(epoch_number as u64, epoch_index, epoch_length) as i64;
+        (primary, total_issuance - parent)
+    } else {
+        (0, 0)
+    };
+    let fees = query_fees(txn, number, number)
+        .await?
+        .into_iter()
+        .map(|fee| fee.fee as i64)
+        .sum();
+    Ok(Changes {
+        epoch_number: i64::from(epoch_number),
+        number: number as i64,
+        timestamp: row.try_get::<i64>(3)?,
+        compact_target: row.try_get::<i64>(4)?,
+        transactions: row.try_get::<i64>(7)?,
+        uncles: row.try_get::<i64>(8)?,
+        fees,
+        primary_issuance,
+        secondary_issuance: issuance - primary_issuance,
+    })
+}
@@ -0,0 +1,321 @@
+// Copyright (C) 2019-2020 Boyu Yang
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+use super::{Row, Storage, Value};
+use crate::error::Result;
+
+/// Identifies a block by its number or its hash.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum BlockId {
+    Number(u64),
+    Hash(Vec<u8>),
+}
+
+/// The conditions to filter cells, all conditions which are set should be satisfied.
+#[derive(Debug, Clone, Default)]
+pub struct CellFilter {
+    pub lock_hash: Option<Vec<u8>>,
+    pub type_hash: Option<Vec<u8>>,
+    /// Only live cells if true, only consumed cells if false.
+    pub live: Option<bool>,
+    /// The cells as of the block at this height, the cells which are created after it are
+    /// excluded, and the cells which are consumed after it are live.
+    pub at: Option<u64>,
+}
+
+/// Looks up the stored chain for explorers.
+///
+/// Lists are paginated by cursors: a list returns the rows after the cursor, in the order of the
+/// cursor.
+pub trait Explorer {
+    /// Returns the headers of the blocks whose numbers are less than `before`, from the highest.
+    fn blocks(&self, before: Option<u64>, limit: u32) -> Result<Vec<Row>>;
+    fn block(&self, id: &BlockId) -> Result<Option<Row>>;
+    fn block_uncles(&self, block_hash: &[u8]) -> Result<Vec<Row>>;
+    fn block_proposals(&self, block_hash: &[u8]) -> Result<Vec<Row>>;
+    fn block_transactions(&self, block_hash: &[u8]) -> Result<Vec<Row>>;
+    fn transaction(&self, tx_hash: &[u8]) -> Result<Option<Row>>;
+    /// Returns the cells consumed by the transaction, a cellbase has no inputs.
+    fn transaction_inputs(&self, tx_hash: &[u8]) -> Result<Vec<Row>>;
+    fn transaction_outputs(&self, tx_hash: &[u8]) -> Result<Vec<Row>>;
+    fn transaction_cell_deps(&self, tx_hash: &[u8]) -> Result<Vec<Row>>;
+    fn transaction_header_deps(&self, tx_hash: &[u8]) -> Result<Vec<Row>>;
+    fn transaction_witnesses(&self, tx_hash: &[u8]) -> Result<Vec<Row>>;
+    /// Returns the cells after the out point `after`, ordered by the out points.
+    fn cells(
+        &self,
+        filter: &CellFilter,
+        after: Option<(&[u8], u32)>,
+        limit: u32,
+    ) -> Result<Vec<Row>>;
+    fn script(&self, script_hash: &[u8]) -> Result<Option<Row>>;
+}
+
+pub(super) const HEADER_COLUMNS: &str = r#"
+    number, hash, version, compact_target, timestamp,
+    epoch_number, epoch_index, epoch_length,
+    parent_hash, transactions_root, proposals_hash, uncles_hash,
+    dao_c, dao_ar, dao_s, dao_u, nonce
+"#;
+
+pub(super) const CELL_COLUMNS: &str = r#"
+    c.tx_hash, c."index", bh.number AS block_number, c.capacity,
+    c.lock_hash, c.type_hash, c.data_hash,
+    c.consumed_tx_hash, c.consumed_index, c.consumed_since, c.consumed_block_number
+"#;
+
+impl Storage {
+    fn query_by_hash(&self, sql: &str, hash: &[u8]) -> Result<Vec<Row>> {
+        let cli = self.backend();
+        self.block_on(cli.query(sql, &[hash.into()]))
+    }
+
+    fn query_opt_by_hash(&self, sql: &str, hash: &[u8]) -> Result<Option<Row>> {
+        let cli = self.backend();
+        self.block_on(cli.query_opt(sql, &[hash.into()]))
+    }
+}
+
+impl Explorer for Storage {
+    fn blocks(&self, before: Option<u64>, limit: u32) -> Result<Vec<Row>> {
+        log::trace!("query blocks before {:?}", before);
+        let sql = format!(
+            r#"
+            SELECT {}
+              FROM block_headers
+             WHERE number < $1
+             ORDER BY number DESC
+             LIMIT $2
+        ;"#,
+            HEADER_COLUMNS
+        );
+        let before = before.map(|number| number as i64).unwrap_or(i64::MAX);
+        let cli = self.backend();
+        let params = &[before.into(), i64::from(limit).into()];
+        self.block_on(cli.query(&sql, params))
+    }
+
+    fn block(&self, id: &BlockId) -> Result<Option<Row>> {
+        log::trace!("query block {:?}", id);
+        let (condition, param): (_, Value) = match id {
+            BlockId::Number(number) => ("number", (*number as i64).into()),
+            BlockId::Hash(hash) => ("hash", hash.as_slice().into()),
+        };
+        let sql = format!(
+            "SELECT {} FROM block_headers WHERE {} = $1;",
+            HEADER_COLUMNS, condition
+        );
+        let cli = self.backend();
+        self.block_on(cli.query_opt(&sql, &[param]))
+    }
+
+    fn block_uncles(&self, block_hash: &[u8]) -> Result<Vec<Row>> {
+        log::trace!("query uncles of a block");
+        let sql = r#"
+            SELECT bu.uncle_hash AS hash, uh.number, uh.timestamp, uh.compact_target
+              FROM block_uncles bu
+              JOIN uncle_headers uh ON uh.hash = bu.uncle_hash
+             WHERE bu.block_hash = $1
+             ORDER BY bu."index"
+        ;"#;
+        self.query_by_hash(sql, block_hash)
+    }
+
+    fn block_proposals(&self, block_hash: &[u8]) -> Result<Vec<Row>> {
+        log::trace!("query proposals of a block");
+        let sql = r#"
+            SELECT short_id
+              FROM block_proposals
+             WHERE block_hash = $1
+             ORDER BY "index"
+        ;"#;
+        self.query_by_hash(sql, block_hash)
+    }
+
+    fn block_transactions(&self, block_hash: &[u8]) -> Result<Vec<Row>> {
+        log::trace!("query transactions of a block");
+        let sql = r#"
+            SELECT tx_hash AS hash
+              FROM block_transactions
+             WHERE block_hash = $1
+             ORDER BY "index"
+        ;"#;
+        self.query_by_hash(sql, block_hash)
+    }
+
+    fn transaction(&self, tx_hash: &[u8]) -> Result<Option<Row>> {
+        log::trace!("query a transaction");
+        let sql = r#"
+            SELECT t.hash, t.version, bh.number AS block_number, bh.hash AS block_hash,
+                   bt."index", bh.timestamp
+              FROM transactions t
+              JOIN block_transactions bt ON bt.tx_hash = t.hash
+              JOIN block_headers bh ON bh.hash = bt.block_hash
+             WHERE t.hash = $1
+        ;"#;
+        self.query_opt_by_hash(sql, tx_hash)
+    }
+
+    fn transaction_inputs(&self, tx_hash: &[u8]) -> Result<Vec<Row>> {
+        log::trace!("query inputs of a transaction");
+        let sql = format!(
+            r#"
+            SELECT {}
+              FROM cells c
+              JOIN block_transactions bt ON bt.tx_hash = c.tx_hash
+              JOIN block_headers bh ON bh.hash = bt.block_hash
+             WHERE c.consumed_tx_hash = $1
+             ORDER BY c.consumed_index
+        ;"#,
+            CELL_COLUMNS
+        );
+        self.query_by_hash(&sql, tx_hash)
+    }
+
+    fn transaction_outputs(&self, tx_hash: &[u8]) -> Result<Vec<Row>> {
+        log::trace!("query outputs of a transaction");
+        let sql = format!(
+            r#"
+            SELECT {}
+              FROM cells c
+              JOIN block_transactions bt ON bt.tx_hash = c.tx_hash
+              JOIN block_headers bh ON bh.hash = bt.block_hash
+             WHERE c.tx_hash = $1
+             ORDER BY c."index"
+        ;"#,
+            CELL_COLUMNS
+        );
+        self.query_by_hash(&sql, tx_hash)
+    }
+
+    fn transaction_cell_deps(&self, tx_hash: &[u8]) -> Result<Vec<Row>> {
+        log::trace!("query cell deps of a transaction");
+        let sql = r#"
+            SELECT tx_hash, "index", dep_type
+              FROM tx_cell_deps
+             WHERE ref_tx_hash = $1
+             ORDER BY ref_dep_index
+        ;"#;
+        self.query_by_hash(sql, tx_hash)
+    }
+
+    fn transaction_header_deps(&self, tx_hash: &[u8]) -> Result<Vec<Row>> {
+        log::trace!("query header deps of a transaction");
+        let sql = r#"
+            SELECT block_hash
+              FROM tx_header_deps
+             WHERE ref_tx_hash = $1
+             ORDER BY ref_dep_index
+        ;"#;
+        self.query_by_hash(sql, tx_hash)
+    }
+
+    fn transaction_witnesses(&self, tx_hash: &[u8]) -> Result<Vec<Row>> {
+        log::trace!("query witnesses of a transaction");
+        let sql = r#"
+            SELECT witness
+              FROM tx_witnesses
+             WHERE ref_tx_hash = $1
+             ORDER BY ref_dep_index
+        ;"#;
+        self.query_by_hash(sql, tx_hash)
+    }
+
+    fn cells(
+        &self,
+        filter: &CellFilter,
+        after: Option<(&[u8], u32)>,
+        limit: u32,
+    ) -> Result<Vec<Row>> {
+        log::trace!("query cells by {:?} after {:?}", filter, after);
+        let mut conditions = Vec::new();
+        let mut params: Vec<Value> = Vec::new();
+        if let Some(ref lock_hash) = filter.lock_hash {
+            params.push(lock_hash.as_slice().into());
+            conditions.push(format!("c.lock_hash = ${}", params.len()));
+        }
+        if let Some(ref type_hash) = filter.type_hash {
+            params.push(type_hash.as_slice().into());
+            conditions.push(format!("c.type_hash = ${}", params.len()));
+        }
+        if let Some(at) = filter.at {
+            params.push((at as i64).into());
+            let n = params.len();
+            conditions.push(format!("c.created_block_number <= ${}", n));
+            match filter.live {
+                Some(true) => conditions.push(format!(
+                    "(c.consumed_block_number IS NULL OR c.consumed_block_number > ${})",
+                    n
+                )),
+                Some(false) => conditions.push(format!("c.consumed_block_number <= ${}", n)),
+                None => {}
+            }
+        } else {
+            match filter.live {
+                Some(true) => conditions.push("c.consumed_tx_hash IS NULL".to_owned()),
+                Some(false) => conditions.push("c.consumed_tx_hash IS NOT NULL".to_owned()),
+                None => {}
+            }
+        }
+        if let Some((tx_hash, index)) = after {
+            params.push(tx_hash.into());
+            params.push(i64::from(index).into());
+            conditions.push(format!(
+                r#"(c.tx_hash > ${0} OR (c.tx_hash = ${0} AND c."index" > ${1}))"#,
+                params.len() - 1,
+                params.len()
+            ));
+        }
+        params.push(i64::from(limit).into());
+        let sql = format!(
+            r#"
+            SELECT {}
+              FROM cells c
+              JOIN block_transactions bt ON bt.tx_hash = c.tx_hash
+              JOIN block_headers bh ON bh.hash = bt.block_hash
+             WHERE 1 = 1
+               {}
+             ORDER BY c.tx_hash, c."index"
+             LIMIT ${}
+        ;"#,
+            CELL_COLUMNS,
+            conditions
+                .iter()
+                .map(|condition| format!("AND {}", condition))
+                .collect::<Vec<_>>()
+                .join(" "),
+            params.len()
+        );
+        let cli = self.backend();
+        self.block_on(cli.query(&sql, &params))
+    }
+
+    fn script(&self, script_hash: &[u8]) -> Result<Option<Row>> {
+        log::trace!("query a script");
+        let sql = r#"
+            SELECT s.hash, s.code_hash, s.hash_type, s.args
+                 , (SELECT COUNT(*) FROM cells c WHERE c.lock_hash = s.hash) AS lock_cells
+                 , (SELECT COUNT(*)
+                      FROM cells c
+                     WHERE c.lock_hash = s.hash
+                       AND c.consumed_tx_hash IS NULL) AS live_lock_cells
+                 , (SELECT CAST(SUM(c.capacity) AS BIGINT)
+                      FROM cells c
+                     WHERE c.lock_hash = s.hash
+                       AND c.consumed_tx_hash IS NULL) AS live_lock_capacity
+                 , (SELECT COUNT(*) FROM cells c WHERE c.type_hash = s.hash) AS type_cells
+                 , (SELECT COUNT(*)
+                      FROM cells c
+                     WHERE c.type_hash = s.hash
+                       AND c.consumed_tx_hash IS NULL) AS live_type_cells
+              FROM scripts s
+             WHERE s.hash = $1
+        ;"#;
+        self.query_opt_by_hash(sql, script_hash)
+    }
+}
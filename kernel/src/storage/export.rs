@@ -0,0 +1,393 @@
+// Copyright (C) 2019-2020 Boyu Yang
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+use std::{fmt, str::FromStr};
+
+use super::{Row, Storage};
+use crate::error::{Error, Result};
+
+/// The datasets which could be exported by block ranges.
+///
+/// Each row is tagged with the number of the block which includes it, so a dataset could be
+/// partitioned by block ranges, and a partition never changes after the blocks are confirmed.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Dataset {
+    BlockHeaders,
+    BlockUncles,
+    BlockProposals,
+    Transactions,
+    TxCellDeps,
+    TxHeaderDeps,
+    TxWitnesses,
+    /// The cells created in the blocks, without the consumption states.
+    Cells,
+    /// The cells consumed in the blocks.
+    ConsumedCells,
+    /// The scripts used by the cells created in the blocks.
+    ///
+    /// A script could be exported in several partitions, it should be deduplicated by its hash.
+    Scripts,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ColumnType {
+    Integer,
+    Bytes,
+}
+
+/// The definition of a column of a dataset.
+#[derive(Debug, Clone, Copy)]
+pub struct Column {
+    pub name: &'static str,
+    pub type_: ColumnType,
+    pub nullable: bool,
+}
+
+pub trait Export {
+    /// Returns the number of the highest block which could be exported.
+    fn export_tip(&self) -> Result<Option<u64>>;
+    /// Returns the rows of a dataset in the blocks from `start` to `end` (both inclusive).
+    fn export(&self, dataset: Dataset, start: u64, end: u64) -> Result<Vec<Row>>;
+}
+
+const fn integer(name: &'static str) -> Column {
+    Column {
+        name,
+        type_: ColumnType::Integer,
+        nullable: false,
+    }
+}
+
+const fn bytes(name: &'static str) -> Column {
+    Column {
+        name,
+        type_: ColumnType::Bytes,
+        nullable: false,
+    }
+}
+
+const fn nullable(column: Column) -> Column {
+    Column {
+        name: column.name,
+        type_: column.type_,
+        nullable: true,
+    }
+}
+
+const HEADER_COLUMNS: &[Column] = &[
+    integer("number"),
+    bytes("hash"),
+    integer("version"),
+    integer("compact_target"),
+    integer("timestamp"),
+    integer("epoch_number"),
+    integer("epoch_index"),
+    integer("epoch_length"),
+    bytes("parent_hash"),
+    bytes("transactions_root"),
+    bytes("proposals_hash"),
+    bytes("uncles_hash"),
+    integer("dao_c"),
+    integer("dao_ar"),
+    integer("dao_s"),
+    integer("dao_u"),
+    bytes("nonce"),
+];
+
+const UNCLE_COLUMNS: &[Column] = &[
+    integer("block_number"),
+    bytes("block_hash"),
+    integer("index"),
+    integer("number"),
+    bytes("hash"),
+    integer("version"),
+    integer("compact_target"),
+    integer("timestamp"),
+    integer("epoch_number"),
+    integer("epoch_index"),
+    integer("epoch_length"),
+    bytes("parent_hash"),
+    bytes("transactions_root"),
+    bytes("proposals_hash"),
+    bytes("uncles_hash"),
+    integer("dao_c"),
+    integer("dao_ar"),
+    integer("dao_s"),
+    integer("dao_u"),
+    bytes("nonce"),
+];
+
+const PROPOSAL_COLUMNS: &[Column] = &[
+    integer("block_number"),
+    bytes("block_hash"),
+    integer("index"),
+    bytes("short_id"),
+];
+
+const TRANSACTION_COLUMNS: &[Column] = &[
+    integer("block_number"),
+    bytes("block_hash"),
+    integer("index"),
+    bytes("hash"),
+    integer("version"),
+];
+
+const CELL_DEP_COLUMNS: &[Column] = &[
+    integer("block_number"),
+    bytes("ref_tx_hash"),
+    integer("ref_index"),
+    integer("ref_dep_index"),
+    bytes("tx_hash"),
+    integer("index"),
+    integer("dep_type"),
+];
+
+const HEADER_DEP_COLUMNS: &[Column] = &[
+    integer("block_number"),
+    bytes("ref_tx_hash"),
+    integer("ref_index"),
+    integer("ref_dep_index"),
+    bytes("block_hash"),
+];
+
+const WITNESS_COLUMNS: &[Column] = &[
+    integer("block_number"),
+    bytes("ref_tx_hash"),
+    integer("ref_index"),
+    integer("ref_dep_index"),
+    bytes("witness"),
+];
+
+const CELL_COLUMNS: &[Column] = &[
+    integer("block_number"),
+    bytes("tx_hash"),
+    integer("index"),
+    integer("capacity"),
+    bytes("lock_hash"),
+    nullable(bytes("type_hash")),
+    bytes("data_hash"),
+];
+
+const CONSUMED_CELL_COLUMNS: &[Column] = &[
+    integer("block_number"),
+    bytes("consumed_tx_hash"),
+    integer("consumed_index"),
+    bytes("consumed_since"),
+    bytes("tx_hash"),
+    integer("index"),
+];
+
+const SCRIPT_COLUMNS: &[Column] = &[
+    bytes("hash"),
+    bytes("code_hash"),
+    integer("hash_type"),
+    bytes("args"),
+];
+
+impl Dataset {
+    pub const ALL: &'static [Self] = &[
+        Self::BlockHeaders,
+        Self::BlockUncles,
+        Self::BlockProposals,
+        Self::Transactions,
+        Self::TxCellDeps,
+        Self::TxHeaderDeps,
+        Self::TxWitnesses,
+        Self::Cells,
+        Self::ConsumedCells,
+        Self::Scripts,
+    ];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::BlockHeaders => "block_headers",
+            Self::BlockUncles => "block_uncles",
+            Self::BlockProposals => "block_proposals",
+            Self::Transactions => "transactions",
+            Self::TxCellDeps => "tx_cell_deps",
+            Self::TxHeaderDeps => "tx_header_deps",
+            Self::TxWitnesses => "tx_witnesses",
+            Self::Cells => "cells",
+            Self::ConsumedCells => "consumed_cells",
+            Self::Scripts => "scripts",
+        }
+    }
+
+    /// The columns of the rows, in order.
+    pub fn columns(self) -> &'static [Column] {
+        match self {
+            Self::BlockHeaders => HEADER_COLUMNS,
+            Self::BlockUncles => UNCLE_COLUMNS,
+            Self::BlockProposals => PROPOSAL_COLUMNS,
+            Self::Transactions => TRANSACTION_COLUMNS,
+            Self::TxCellDeps => CELL_DEP_COLUMNS,
+            Self::TxHeaderDeps => HEADER_DEP_COLUMNS,
+            Self::TxWitnesses => WITNESS_COLUMNS,
+            Self::Cells => CELL_COLUMNS,
+            Self::ConsumedCells => CONSUMED_CELL_COLUMNS,
+            Self::Scripts => SCRIPT_COLUMNS,
+        }
+    }
+
+    fn sql(self) -> &'static str {
+        match self {
+            Self::BlockHeaders => {
+                r#"
+                SELECT number, hash, version, compact_target, timestamp,
+                       epoch_number, epoch_index, epoch_length,
+                       parent_hash, transactions_root, proposals_hash, uncles_hash,
+                       dao_c, dao_ar, dao_s, dao_u, nonce
+                  FROM block_headers
+                 WHERE number BETWEEN $1 AND $2
+                 ORDER BY number
+            ;"#
+            }
+            Self::BlockUncles => {
+                r#"
+                SELECT bh.number, bu.block_hash, bu."index",
+                       uh.number, uh.hash, uh.version, uh.compact_target, uh.timestamp,
+                       uh.epoch_number, uh.epoch_index, uh.epoch_length,
+                       uh.parent_hash, uh.transactions_root, uh.proposals_hash, uh.uncles_hash,
+                       uh.dao_c, uh.dao_ar, uh.dao_s, uh.dao_u, uh.nonce
+                  FROM block_uncles bu
+                  JOIN block_headers bh ON bh.hash = bu.block_hash
+                  JOIN uncle_headers uh ON uh.hash = bu.uncle_hash
+                 WHERE bh.number BETWEEN $1 AND $2
+                 ORDER BY bh.number, bu."index"
+            ;"#
+            }
+            Self::BlockProposals => {
+                r#"
+                SELECT bh.number, bp.block_hash, bp."index", bp.short_id
+                  FROM block_proposals bp
+                  JOIN block_headers bh ON bh.hash = bp.block_hash
+                 WHERE bh.number BETWEEN $1 AND $2
+                 ORDER BY bh.number, bp."index"
+            ;"#
+            }
+            Self::Transactions => {
+                r#"
+                SELECT bh.number, bt.block_hash, bt."index", t.hash, t.version
+                  FROM block_transactions bt
+                  JOIN block_headers bh ON bh.hash = bt.block_hash
+                  JOIN transactions t ON t.hash = bt.tx_hash
+                 WHERE bh.number BETWEEN $1 AND $2
+                 ORDER BY bh.number, bt."index"
+            ;"#
+            }
+            Self::TxCellDeps => {
+                r#"
+                SELECT bh.number, d.ref_tx_hash, d.ref_index, d.ref_dep_index,
+                       d.tx_hash, d."index", d.dep_type
+                  FROM tx_cell_deps d
+                  JOIN block_transactions bt ON bt.tx_hash = d.ref_tx_hash
+                  JOIN block_headers bh ON bh.hash = bt.block_hash
+                 WHERE bh.number BETWEEN $1 AND $2
+                 ORDER BY bh.number, d.ref_index, d.ref_dep_index
+            ;"#
+            }
+            Self::TxHeaderDeps => {
+                r#"
+                SELECT bh.number, d.ref_tx_hash, d.ref_index, d.ref_dep_index, d.block_hash
+                  FROM tx_header_deps d
+                  JOIN block_transactions bt ON bt.tx_hash = d.ref_tx_hash
+                  JOIN block_headers bh ON bh.hash = bt.block_hash
+                 WHERE bh.number BETWEEN $1 AND $2
+                 ORDER BY bh.number, d.ref_index, d.ref_dep_index
+            ;"#
+            }
+            Self::TxWitnesses => {
+                r#"
+                SELECT bh.number, w.ref_tx_hash, w.ref_index, w.ref_dep_index, w.witness
+                  FROM tx_witnesses w
+                  JOIN block_transactions bt ON bt.tx_hash = w.ref_tx_hash
+                  JOIN block_headers bh ON bh.hash = bt.block_hash
+                 WHERE bh.number BETWEEN $1 AND $2
+                 ORDER BY bh.number, w.ref_index, w.ref_dep_index
+            ;"#
+            }
+            Self::Cells => {
+                r#"
+                SELECT bh.number, c.tx_hash, c."index", c.capacity,
+                       c.lock_hash, c.type_hash, c.data_hash
+                  FROM cells c
+                  JOIN block_transactions bt ON bt.tx_hash = c.tx_hash
+                  JOIN block_headers bh ON bh.hash = bt.block_hash
+                 WHERE bh.number BETWEEN $1 AND $2
+                 ORDER BY bh.number, bt."index", c."index"
+            ;"#
+            }
+            Self::ConsumedCells => {
+                r#"
+                SELECT bh.number, c.consumed_tx_hash, c.consumed_index, c.consumed_since,
+                       c.tx_hash, c."index"
+                  FROM cells c
+                  JOIN block_transactions bt ON bt.tx_hash = c.consumed_tx_hash
+                  JOIN block_headers bh ON bh.hash = bt.block_hash
+                 WHERE bh.number BETWEEN $1 AND $2
+                 ORDER BY bh.number, bt."index", c.consumed_index
+            ;"#
+            }
+            Self::Scripts => {
+                r#"
+                SELECT s.hash, s.code_hash, s.hash_type, s.args
+                  FROM scripts s
+                 WHERE EXISTS (
+                       SELECT 1
+                         FROM cells c
+                         JOIN block_transactions bt ON bt.tx_hash = c.tx_hash
+                         JOIN block_headers bh ON bh.hash = bt.block_hash
+                        WHERE (c.lock_hash = s.hash OR c.type_hash = s.hash)
+                          AND bh.number BETWEEN $1 AND $2)
+                 ORDER BY s.hash
+            ;"#
+            }
+        }
+    }
+}
+
+impl fmt::Display for Dataset {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.name())
+    }
+}
+
+impl FromStr for Dataset {
+    type Err = Error;
+    fn from_str(s: &str) -> Result<Self> {
+        Self::ALL
+            .iter()
+            .find(|dataset| dataset.name() == s)
+            .cloned()
+            .ok_or_else(|| Error::Data(format!("unknown dataset {}", s)))
+    }
+}
+
+impl Export for Storage {
+    fn export_tip(&self) -> Result<Option<u64>> {
+        log::trace!("query the tip to export");
+        let cli = self.backend();
+        let sql = "SELECT MAX(number) FROM block_headers;";
+        self.block_on(async {
+            if !cli.table_exists("block_headers").await? {
+                return Ok(None);
+            }
+            cli.query_one(sql, &[])
+                .await?
+                .try_get::<Option<i64>>(0)
+                .map(|num_opt| num_opt.map(|num| num as u64))
+        })
+    }
+
+    fn export(&self, dataset: Dataset, start: u64, end: u64) -> Result<Vec<Row>> {
+        log::trace!("export {} in blocks [{}, {}]", dataset, start, end);
+        let cli = self.backend();
+        let params = &[(start as i64).into(), (end as i64).into()];
+        self.block_on(cli.query(dataset.sql(), params))
+    }
+}
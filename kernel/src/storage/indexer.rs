@@ -0,0 +1,450 @@
+// Copyright (C) 2019-2020 Boyu Yang
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+use uckb_jsonrpc_core::types::{bytes::Bytes, core, packed, prelude::*};
+
+use super::{operations::hash_from_value, Row, Storage, Value};
+use crate::error::{Error, Result};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ScriptType {
+    Lock,
+    Type,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Order {
+    Asc,
+    Desc,
+}
+
+/// Searches cells by a script, the args of the script is matched as a prefix.
+///
+/// The ranges are half-open, the start is included and the end is excluded.
+#[derive(Debug, Clone)]
+pub struct SearchKey {
+    pub script: packed::Script,
+    pub script_type: ScriptType,
+    /// Filters by the other script of the cells, the args is matched as a prefix too.
+    pub filter_script: Option<packed::Script>,
+    pub output_data_len_range: Option<(u64, u64)>,
+    pub output_capacity_range: Option<(u64, u64)>,
+    pub block_range: Option<(u64, u64)>,
+}
+
+/// A live cell.
+#[derive(Debug, Clone)]
+pub struct IndexerCell {
+    pub output: packed::CellOutput,
+    pub output_data: Bytes,
+    pub out_point: packed::OutPoint,
+    pub block_number: core::BlockNumber,
+    pub tx_index: u32,
+}
+
+/// A transaction which creates or consumes a cell.
+#[derive(Debug, Clone)]
+pub struct IndexerTx {
+    pub tx_hash: packed::Byte32,
+    pub block_number: core::BlockNumber,
+    pub tx_index: u32,
+    pub io_index: u32,
+    /// The cell is an input of the transaction if true, otherwise an output.
+    pub is_input: bool,
+}
+
+/// A page of results, `last_cursor` is the position of the last object.
+#[derive(Debug, Clone)]
+pub struct IndexerPage<T> {
+    pub objects: Vec<T>,
+    pub last_cursor: Vec<u8>,
+}
+
+/// The queries of the CKB indexer, served by the stored cells and scripts.
+///
+/// The cursors are opaque bytes, the objects after the cursor in the order are returned.
+pub trait Indexer {
+    /// Returns the hash and the number of the stored tip block.
+    fn indexer_tip(&self) -> Result<Option<(packed::Byte32, core::BlockNumber)>>;
+    fn indexer_cells(
+        &self,
+        key: &SearchKey,
+        order: Order,
+        limit: u32,
+        after: Option<&[u8]>,
+    ) -> Result<IndexerPage<IndexerCell>>;
+    fn indexer_transactions(
+        &self,
+        key: &SearchKey,
+        order: Order,
+        limit: u32,
+        after: Option<&[u8]>,
+    ) -> Result<IndexerPage<IndexerTx>>;
+    /// Returns the total capacity of the live cells.
+    fn indexer_cells_capacity(&self, key: &SearchKey) -> Result<u64>;
+}
+
+impl Order {
+    fn sql(self) -> &'static str {
+        match self {
+            Self::Asc => "ASC",
+            Self::Desc => "DESC",
+        }
+    }
+
+    fn cmp_sql(self) -> &'static str {
+        match self {
+            Self::Asc => ">",
+            Self::Desc => "<",
+        }
+    }
+}
+
+// Collects the conditions of a query and their parameters.
+#[derive(Default)]
+struct Conditions {
+    conditions: Vec<String>,
+    params: Vec<Value>,
+}
+
+impl Conditions {
+    fn param(&mut self, value: Value) -> String {
+        self.params.push(value);
+        format!("${}", self.params.len())
+    }
+
+    fn push(&mut self, condition: String) {
+        self.conditions.push(condition);
+    }
+
+    fn script(&mut self, alias: &str, script: &packed::Script) {
+        let hash_type: u8 = script.hash_type().into();
+        let args = script.args().raw_data();
+        let code_hash = self.param(script.code_hash().raw_data().as_ref().into());
+        let hash_type = self.param(i64::from(hash_type).into());
+        self.push(format!("{}.code_hash = {}", alias, code_hash));
+        self.push(format!("{}.hash_type = {}", alias, hash_type));
+        if !args.is_empty() {
+            let len = self.param((args.len() as i64).into());
+            let args = self.param(args.as_ref().into());
+            self.push(format!("SUBSTR({}.args, 1, {}) = {}", alias, len, args));
+        }
+    }
+
+    fn range(&mut self, column: &str, range: Option<(u64, u64)>) {
+        if let Some((start, end)) = range {
+            let start = self.param((start as i64).into());
+            let end = self.param((end as i64).into());
+            self.push(format!("{} >= {} AND {} < {}", column, start, column, end));
+        }
+    }
+
+    // The position (a, b, c, ..) is after the cursor in the order.
+    fn after(&mut self, columns: &[&str], cursor: &[i64], order: Order) {
+        let values = cursor
+            .iter()
+            .map(|value| self.param((*value).into()))
+            .collect::<Vec<_>>();
+        let alternatives = (0..columns.len())
+            .map(|i| {
+                let mut parts = (0..i)
+                    .map(|j| format!("{} = {}", columns[j], values[j]))
+                    .collect::<Vec<_>>();
+                parts.push(format!("{} {} {}", columns[i], order.cmp_sql(), values[i]));
+                format!("({})", parts.join(" AND "))
+            })
+            .collect::<Vec<_>>();
+        self.push(format!("({})", alternatives.join(" OR ")));
+    }
+
+    fn where_sql(&self) -> String {
+        if self.conditions.is_empty() {
+            "1 = 1".to_owned()
+        } else {
+            self.conditions.join("\n               AND ")
+        }
+    }
+}
+
+fn script_columns(key: &SearchKey) -> (&'static str, &'static str) {
+    match key.script_type {
+        ScriptType::Lock => ("lock_hash", "type_hash"),
+        ScriptType::Type => ("type_hash", "lock_hash"),
+    }
+}
+
+// Appends the joins of the scripts and the conditions of a search key for a table of cells
+// aliased as `c`.
+fn search_key_sql(key: &SearchKey, conditions: &mut Conditions) -> String {
+    let (column, filter_column) = script_columns(key);
+    let mut joins = format!("JOIN scripts s ON s.hash = c.{}", column);
+    conditions.script("s", &key.script);
+    if let Some(ref filter_script) = key.filter_script {
+        joins.push_str(&format!(
+            "\n              JOIN scripts fs ON fs.hash = c.{}",
+            filter_column
+        ));
+        conditions.script("fs", filter_script);
+    }
+    joins
+}
+
+// Decodes a cursor which is a sequence of big-endian integers, in the sizes of the fields.
+fn decode_cursor(cursor: &[u8], sizes: &[usize]) -> Result<Vec<i64>> {
+    if cursor.len() != sizes.iter().sum::<usize>() {
+        return Err(Error::Data(format!(
+            "incorrect cursor (length: {})",
+            cursor.len()
+        )));
+    }
+    let mut start = 0;
+    let mut values = Vec::with_capacity(sizes.len());
+    for size in sizes {
+        let mut buf = [0u8; 8];
+        buf[(8 - size)..].copy_from_slice(&cursor[start..(start + size)]);
+        values.push(u64::from_be_bytes(buf) as i64);
+        start += size;
+    }
+    Ok(values)
+}
+
+fn build_script(code_hash: Vec<u8>, hash_type: i64, args: Vec<u8>) -> Result<packed::Script> {
+    Ok(packed::Script::new_builder()
+        .code_hash(hash_from_value(code_hash)?)
+        .hash_type(packed::Byte::new(hash_type as u8))
+        .args(Bytes::from(args).pack())
+        .build())
+}
+
+fn cell_from_row(row: &Row) -> Result<IndexerCell> {
+    let lock = build_script(row.try_get(5)?, row.try_get(6)?, row.try_get(7)?)?;
+    let type_opt = match (
+        row.try_get::<Option<Vec<u8>>>(8)?,
+        row.try_get::<Option<i64>>(9)?,
+        row.try_get::<Option<Vec<u8>>>(10)?,
+    ) {
+        (Some(code_hash), Some(hash_type), Some(args)) => {
+            Some(build_script(code_hash, hash_type, args)?)
+        }
+        _ => None,
+    };
+    let output = packed::CellOutput::new_builder()
+        .capacity((row.try_get::<i64>(4)? as u64).pack())
+        .lock(lock)
+        .type_(type_opt.pack())
+        .build();
+    let out_point = packed::OutPoint::new(
+        hash_from_value(row.try_get(0)?)?,
+        row.try_get::<i64>(1)? as u32,
+    );
+    Ok(IndexerCell {
+        output,
+        output_data: Bytes::from(row.try_get::<Vec<u8>>(11)?),
+        out_point,
+        block_number: row.try_get::<i64>(2)? as u64,
+        tx_index: row.try_get::<i64>(3)? as u32,
+    })
+}
+
+fn tx_from_row(row: &Row) -> Result<IndexerTx> {
+    Ok(IndexerTx {
+        tx_hash: hash_from_value(row.try_get(0)?)?,
+        block_number: row.try_get::<i64>(1)? as u64,
+        tx_index: row.try_get::<i64>(2)? as u32,
+        io_index: row.try_get::<i64>(3)? as u32,
+        is_input: row.try_get::<i64>(4)? == 0,
+    })
+}
+
+impl Indexer for Storage {
+    fn indexer_tip(&self) -> Result<Option<(packed::Byte32, core::BlockNumber)>> {
+        log::trace!("query the indexer tip");
+        let sql = r#"
+            SELECT hash, number
+              FROM block_headers
+             ORDER BY number DESC
+             LIMIT 1
+        ;"#;
+        let cli = self.backend();
+        self.block_on(cli.query_opt(sql, &[]))?
+            .map(|row| {
+                let hash = hash_from_value(row.try_get(0)?)?;
+                let number = row.try_get::<i64>(1)? as u64;
+                Ok((hash, number))
+            })
+            .transpose()
+    }
+
+    fn indexer_cells(
+        &self,
+        key: &SearchKey,
+        order: Order,
+        limit: u32,
+        after: Option<&[u8]>,
+    ) -> Result<IndexerPage<IndexerCell>> {
+        log::trace!("query indexer cells by {:?}", key);
+        let mut conditions = Conditions::default();
+        conditions.push("c.consumed_tx_hash IS NULL".to_owned());
+        let joins = search_key_sql(key, &mut conditions);
+        conditions.range("LENGTH(cd.data)", key.output_data_len_range);
+        conditions.range("c.capacity", key.output_capacity_range);
+        conditions.range("bh.number", key.block_range);
+        if let Some(cursor) = after {
+            // The block number, the transaction index and the output index.
+            let position = decode_cursor(cursor, &[8, 4, 4])?;
+            conditions.after(
+                &["bh.number", r#"bt."index""#, r#"c."index""#],
+                &position,
+                order,
+            );
+        }
+        let limit = conditions.param(i64::from(limit).into());
+        let sql = format!(
+            r#"
+            SELECT c.tx_hash, c."index", bh.number, bt."index", c.capacity,
+                   sl.code_hash, sl.hash_type, sl.args,
+                   st.code_hash, st.hash_type, st.args,
+                   cd.data
+              FROM cells c
+              JOIN block_transactions bt ON bt.tx_hash = c.tx_hash
+              JOIN block_headers bh ON bh.hash = bt.block_hash
+              JOIN cells_data cd ON cd.hash = c.data_hash
+              JOIN scripts sl ON sl.hash = c.lock_hash
+              LEFT JOIN scripts st ON st.hash = c.type_hash
+              {}
+             WHERE {}
+             ORDER BY bh.number {order}, bt."index" {order}, c."index" {order}
+             LIMIT {}
+        ;"#,
+            joins,
+            conditions.where_sql(),
+            limit,
+            order = order.sql(),
+        );
+        let cli = self.backend();
+        let rows = self.block_on(cli.query(&sql, &conditions.params))?;
+        let objects = rows.iter().map(cell_from_row).collect::<Result<Vec<_>>>()?;
+        let last_cursor = objects
+            .last()
+            .map(|cell| {
+                let index: u32 = cell.out_point.index().unpack();
+                let mut cursor = Vec::with_capacity(16);
+                cursor.extend_from_slice(&cell.block_number.to_be_bytes());
+                cursor.extend_from_slice(&cell.tx_index.to_be_bytes());
+                cursor.extend_from_slice(&index.to_be_bytes());
+                cursor
+            })
+            .unwrap_or_default();
+        Ok(IndexerPage {
+            objects,
+            last_cursor,
+        })
+    }
+
+    fn indexer_transactions(
+        &self,
+        key: &SearchKey,
+        order: Order,
+        limit: u32,
+        after: Option<&[u8]>,
+    ) -> Result<IndexerPage<IndexerTx>> {
+        log::trace!("query indexer transactions by {:?}", key);
+        let mut conditions = Conditions::default();
+        let joins = search_key_sql(key, &mut conditions);
+        let sql_where = conditions.where_sql();
+        let mut outer = Conditions {
+            conditions: Vec::new(),
+            params: conditions.params,
+        };
+        outer.range("t.block_number", key.block_range);
+        if let Some(cursor) = after {
+            // The block number, the transaction index, the index and the type of the cell.
+            let position = decode_cursor(cursor, &[8, 4, 4, 1])?;
+            let columns = ["t.block_number", "t.tx_index", "t.io_index", "t.io_type"];
+            outer.after(&columns, &position, order);
+        }
+        let limit = outer.param(i64::from(limit).into());
+        // The same parameters are used in both parts of the union.
+        let sql = format!(
+            r#"
+            SELECT t.tx_hash, t.block_number, t.tx_index, t.io_index, t.io_type
+              FROM (
+                   SELECT c.tx_hash AS tx_hash, bh.number AS block_number,
+                          bt."index" AS tx_index, c."index" AS io_index, 1 AS io_type
+                     FROM cells c
+                     JOIN block_transactions bt ON bt.tx_hash = c.tx_hash
+                     JOIN block_headers bh ON bh.hash = bt.block_hash
+                     {joins}
+                    WHERE {sql_where}
+                    UNION ALL
+                   SELECT c.consumed_tx_hash AS tx_hash, bh.number AS block_number,
+                          bt."index" AS tx_index, c.consumed_index AS io_index, 0 AS io_type
+                     FROM cells c
+                     JOIN block_transactions bt ON bt.tx_hash = c.consumed_tx_hash
+                     JOIN block_headers bh ON bh.hash = bt.block_hash
+                     {joins}
+                    WHERE {sql_where}
+                   ) AS t
+             WHERE {}
+             ORDER BY t.block_number {order}, t.tx_index {order},
+                      t.io_index {order}, t.io_type {order}
+             LIMIT {}
+        ;"#,
+            outer.where_sql(),
+            limit,
+            joins = joins,
+            sql_where = sql_where,
+            order = order.sql(),
+        );
+        let cli = self.backend();
+        let rows = self.block_on(cli.query(&sql, &outer.params))?;
+        let objects = rows.iter().map(tx_from_row).collect::<Result<Vec<_>>>()?;
+        let last_cursor = objects
+            .last()
+            .map(|tx| {
+                let mut cursor = Vec::with_capacity(17);
+                cursor.extend_from_slice(&tx.block_number.to_be_bytes());
+                cursor.extend_from_slice(&tx.tx_index.to_be_bytes());
+                cursor.extend_from_slice(&tx.io_index.to_be_bytes());
+                cursor.push(if tx.is_input { 0 } else { 1 });
+                cursor
+            })
+            .unwrap_or_default();
+        Ok(IndexerPage {
+            objects,
+            last_cursor,
+        })
+    }
+
+    fn indexer_cells_capacity(&self, key: &SearchKey) -> Result<u64> {
+        log::trace!("query indexer cells capacity by {:?}", key);
+        let mut conditions = Conditions::default();
+        conditions.push("c.consumed_tx_hash IS NULL".to_owned());
+        let joins = search_key_sql(key, &mut conditions);
+        conditions.range("LENGTH(cd.data)", key.output_data_len_range);
+        conditions.range("c.capacity", key.output_capacity_range);
+        conditions.range("bh.number", key.block_range);
+        let sql = format!(
+            r#"
+            SELECT CAST(SUM(c.capacity) AS BIGINT)
+              FROM cells c
+              JOIN block_transactions bt ON bt.tx_hash = c.tx_hash
+              JOIN block_headers bh ON bh.hash = bt.block_hash
+              JOIN cells_data cd ON cd.hash = c.data_hash
+              {}
+             WHERE {}
+        ;"#,
+            joins,
+            conditions.where_sql(),
+        );
+        let cli = self.backend();
+        self.block_on(cli.query_one(&sql, &conditions.params))?
+            .try_get::<Option<i64>>(0)
+            .map(|capacity| capacity.unwrap_or(0) as u64)
+    }
+}
@@ -0,0 +1,206 @@
+// Copyright (C) 2019-2020 Boyu Yang
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+use super::{Storage, Value};
+use crate::{
+    error::Result,
+    utilities::{utc_date, DAY_MILLIS},
+    Report,
+};
+
+pub(super) mod operations;
+
+/// The miners of blocks, which are the locks in the cellbase witnesses, and the rewards of
+/// blocks, which are maintained when blocks are inserted and removed.
+///
+/// The reward of a block is paid 11 blocks later, so the rewards of the latest blocks are null.
+/// The shares of the hashrate are estimated by the shares of the blocks.
+pub trait Mining {
+    /// The miners and the rewards of the latest blocks.
+    fn block_rewards(&self, limit: u32) -> Result<Report>;
+    /// The blocks and the rewards of each miner in each of the latest epochs.
+    fn miners_by_epoch(&self, epochs: u32) -> Result<Report>;
+    /// The blocks and the rewards of each miner in each of the latest days, in UTC.
+    fn miners_by_day(&self, days: u32) -> Result<Report>;
+    /// The uncles which are included in each of the latest epochs, the uncle rate, and the
+    /// distances between the uncles and the blocks which include them.
+    fn uncles_by_epoch(&self, epochs: u32) -> Result<Report>;
+    /// The uncles of each miner in each of the latest epochs.
+    ///
+    /// The uncles have no cellbases in the chain, so only the uncles which were synchronized
+    /// as blocks and were removed later are attributed to their miners.
+    fn uncle_miners_by_epoch(&self, epochs: u32) -> Result<Report>;
+}
+
+const REWARD_COLUMNS: &[&str] = &[
+    "total_reward",
+    "primary_reward",
+    "secondary_reward",
+    "commit_reward",
+    "proposal_reward",
+];
+
+const MINER_COLUMNS: &[&str] = &["lock_hash", "code_hash", "hash_type", "args"];
+
+const UNCLE_COLUMNS: &[&str] = &[
+    "epoch_number",
+    "blocks",
+    "uncles",
+    "uncle_rate",
+    "attributed_uncles",
+    "min_distance",
+    "avg_distance",
+    "max_distance",
+];
+
+// The epochs which are after the latest epochs.
+pub(super) const LATEST_EPOCHS: &str =
+    "h.epoch_number > (SELECT MAX(epoch_number) FROM block_headers) - $1";
+
+impl Mining for Storage {
+    fn block_rewards(&self, limit: u32) -> Result<Report> {
+        log::trace!("report the rewards of blocks");
+        let sql = format!(
+            r#"
+            SELECT number, {}, {}
+              FROM block_miners
+             ORDER BY number DESC
+             LIMIT $1
+        ;"#,
+            MINER_COLUMNS.join(", "),
+            REWARD_COLUMNS.join(", ")
+        );
+        let rows = self.query(&sql, &[i64::from(limit).into()])?;
+        let columns = ["block_number"]
+            .iter()
+            .chain(MINER_COLUMNS)
+            .chain(REWARD_COLUMNS)
+            .copied()
+            .collect::<Vec<_>>();
+        Ok(Report::with_rows(&columns, rows))
+    }
+
+    fn miners_by_epoch(&self, epochs: u32) -> Result<Report> {
+        log::trace!("report the miners by epoch");
+        let period = "h.epoch_number";
+        let rows = self.query(
+            &miners_sql(period, LATEST_EPOCHS),
+            &[i64::from(epochs).into()],
+        )?;
+        Ok(Report::with_rows(&share_columns("epoch_number"), rows))
+    }
+
+    fn miners_by_day(&self, days: u32) -> Result<Report> {
+        log::trace!("report the miners by day");
+        let period = "h.timestamp / $2";
+        let filter = "h.timestamp / $2 > (SELECT MAX(timestamp) FROM block_headers) / $2 - $1";
+        let params: &[Value] = &[i64::from(days).into(), (DAY_MILLIS as i64).into()];
+        let mut report = Report::new(&share_columns("day"));
+        for row in self.query(&miners_sql(period, filter), params)? {
+            let day = row.try_get::<i64>(0)? as u64;
+            let mut row = row.into_values();
+            row[0] = Value::Text(utc_date(day));
+            report.push(row);
+        }
+        Ok(report)
+    }
+
+    fn uncles_by_epoch(&self, epochs: u32) -> Result<Report> {
+        log::trace!("report the uncles by epoch");
+        // The uncles are counted in the epochs of the blocks which include them.
+        let sql = format!(
+            r#"
+            SELECT b.epoch_number, b.blocks, COALESCE(u.uncles, 0),
+                   CAST(COALESCE(u.uncles, 0) AS DOUBLE PRECISION)
+                       / CAST(b.blocks + COALESCE(u.uncles, 0) AS DOUBLE PRECISION),
+                   COALESCE(u.attributed, 0), u.min_distance, u.avg_distance, u.max_distance
+              FROM (
+                   SELECT h.epoch_number, COUNT(*) AS blocks
+                     FROM block_headers h
+                    WHERE {0}
+                    GROUP BY h.epoch_number
+                   ) b
+              LEFT JOIN (
+                   SELECT h.epoch_number, COUNT(*) AS uncles, COUNT(o.hash) AS attributed,
+                          MIN(h.number - u.number) AS min_distance,
+                          AVG(CAST(h.number - u.number AS DOUBLE PRECISION)) AS avg_distance,
+                          MAX(h.number - u.number) AS max_distance
+                     FROM block_uncles bu
+                     JOIN block_headers h ON h.hash = bu.block_hash
+                     JOIN uncle_headers u ON u.hash = bu.uncle_hash
+                     LEFT JOIN orphan_miners o ON o.hash = bu.uncle_hash
+                    WHERE {0}
+                    GROUP BY h.epoch_number
+                   ) u ON u.epoch_number = b.epoch_number
+             ORDER BY b.epoch_number
+        ;"#,
+            LATEST_EPOCHS
+        );
+        let rows = self.query(&sql, &[i64::from(epochs).into()])?;
+        Ok(Report::with_rows(UNCLE_COLUMNS, rows))
+    }
+
+    fn uncle_miners_by_epoch(&self, epochs: u32) -> Result<Report> {
+        log::trace!("report the miners of uncles by epoch");
+        let sql = format!(
+            r#"
+            SELECT h.epoch_number, o.lock_hash, o.code_hash, o.hash_type, o.args,
+                   COUNT(*) AS uncles
+              FROM block_uncles bu
+              JOIN block_headers h ON h.hash = bu.block_hash
+              JOIN orphan_miners o ON o.hash = bu.uncle_hash
+             WHERE {}
+             GROUP BY h.epoch_number, o.lock_hash, o.code_hash, o.hash_type, o.args
+             ORDER BY h.epoch_number DESC, uncles DESC, o.lock_hash
+        ;"#,
+            LATEST_EPOCHS
+        );
+        let rows = self.query(&sql, &[i64::from(epochs).into()])?;
+        let columns = ["epoch_number"]
+            .iter()
+            .chain(MINER_COLUMNS)
+            .chain(&["uncles"])
+            .copied()
+            .collect::<Vec<_>>();
+        Ok(Report::with_rows(&columns, rows))
+    }
+}
+
+fn share_columns(period: &'static str) -> Vec<&'static str> {
+    [period]
+        .iter()
+        .chain(MINER_COLUMNS)
+        .chain(&["blocks", "share"])
+        .chain(REWARD_COLUMNS)
+        .copied()
+        .collect()
+}
+
+// The blocks of each miner in each period, and their shares of the blocks in the period.
+fn miners_sql(period: &str, filter: &str) -> String {
+    let rewards = REWARD_COLUMNS
+        .iter()
+        .map(|column| format!("CAST(SUM(m.{0}) AS BIGINT) AS {0}", column))
+        .collect::<Vec<_>>()
+        .join(", ");
+    format!(
+        r#"
+        SELECT {0} AS period, m.lock_hash, m.code_hash, m.hash_type, m.args,
+               COUNT(*) AS blocks,
+               CAST(COUNT(*) AS DOUBLE PRECISION)
+                   / CAST(SUM(COUNT(*)) OVER (PARTITION BY {0}) AS DOUBLE PRECISION) AS share,
+               {1}
+          FROM block_miners m
+          JOIN block_headers h ON h.number = m.number
+         WHERE {2}
+         GROUP BY {0}, m.lock_hash, m.code_hash, m.hash_type, m.args
+         ORDER BY period DESC, blocks DESC, m.lock_hash
+    ;"#,
+        period, rewards, filter
+    )
+}
@@ -0,0 +1,458 @@
+// Copyright (C) 2019-2020 Boyu Yang
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+use std::collections::{HashMap, HashSet};
+
+use uckb_jsonrpc_core::types::{core, packed, prelude::*};
+
+use crate::{
+    error::{Error, Result},
+    storage::{
+        backend::{Backend, Transaction},
+        Value,
+    },
+};
+
+pub(in crate::storage) const TABLES: &[&str] = &["block_miners", "orphan_miners"];
+
+pub(in crate::storage) const INDEXES: &[&str] =
+    &[r#"CREATE INDEX IF NOT EXISTS block_miners_lock_hash_idx ON block_miners (lock_hash);"#];
+
+/// The reward of a block is paid by the cellbase of the block which is 11 blocks later.
+pub(in crate::storage) const FINALIZATION_DELAY: u64 = 11;
+
+/// The closest and the farthest distances between a proposal and the commitment.
+pub(in crate::storage) const PROPOSAL_WINDOW: (u64, u64) = (2, 10);
+// The primary issuance of an epoch before the first halving, in shannons.
+const INITIAL_PRIMARY_EPOCH_REWARD: u64 = 191_780_821_917_808;
+// The primary issuance halves every 4 years, an epoch is 4 hours.
+const PRIMARY_EPOCH_REWARD_HALVING_INTERVAL: u64 = 8_760;
+// The secondary issuance of an epoch, in shannons.
+const SECONDARY_EPOCH_REWARD: u64 = 61_369_863_013_698;
+// The proposer of a transaction gets 40% of its fee, the committer gets the rest.
+const PROPOSER_REWARD_RATIO: (u64, u64) = (4, 10);
+// The count of the stored blocks which are attributed to miners in a transaction.
+const FILL_BATCH_SIZE: u64 = 10_000;
+
+// The reward of a block and its components, in shannons.
+struct Rewards {
+    total: u64,
+    primary: u64,
+    secondary: u64,
+    commit: u64,
+    proposal: u64,
+}
+
+// Creates the tables if they don't exist, and attributes the stored blocks to miners by their
+// cellbases.
+//
+// The miners of the orphaned blocks are not filled, since the uncles have no cellbases, their
+// miners are known only if they were synchronized while they were in the chain.
+pub(in crate::storage) async fn create_tables(cli: &mut dyn Backend) -> Result<()> {
+    log::trace!("create tables of miners");
+    let sql = r#"
+        CREATE TABLE IF NOT EXISTS orphan_miners (
+            hash                BYTEA       NOT NULL PRIMARY KEY,
+            number              BIGINT      NOT NULL,
+            lock_hash           BYTEA       NOT NULL,
+            code_hash           BYTEA       NOT NULL,
+            hash_type           SMALLINT    NOT NULL,
+            args                BYTEA       NOT NULL
+        );"#;
+    cli.execute(sql, &[]).await?;
+    let sql = r#"
+        CREATE TABLE IF NOT EXISTS block_miners (
+            number              BIGINT      NOT NULL PRIMARY KEY,
+            lock_hash           BYTEA       NOT NULL,
+            code_hash           BYTEA       NOT NULL,
+            hash_type           SMALLINT    NOT NULL,
+            args                BYTEA       NOT NULL,
+            total_reward        BIGINT,
+            primary_reward      BIGINT,
+            secondary_reward    BIGINT,
+            commit_reward       BIGINT,
+            proposal_reward     BIGINT
+        );"#;
+    cli.execute(sql, &[]).await?;
+    fill_tables(cli).await
+}
+
+// Attributes the stored blocks after the last attributed one to miners, as `insert_changes`.
+//
+// The blocks are committed in batches, so an interrupted filling is resumed from the last batch.
+async fn fill_tables(cli: &mut dyn Backend) -> Result<()> {
+    let sql = r#"
+        SELECT (SELECT MAX(number) FROM block_miners),
+               (SELECT MAX(number) FROM block_headers)
+    ;"#;
+    let row = cli.query_one(sql, &[]).await?;
+    let start = row
+        .try_get::<Option<i64>>(0)?
+        .map(|number| number as u64 + 1)
+        .unwrap_or(0);
+    let tip = match row.try_get::<Option<i64>>(1)? {
+        Some(tip) if tip as u64 >= start => tip as u64,
+        _ => return Ok(()),
+    };
+    log::info!("attribute the stored blocks {} to {} to miners", start, tip);
+    let sql = r#"
+        SELECT h.number, w.witness
+          FROM block_headers h
+          JOIN block_transactions bt ON bt.block_hash = h.hash AND bt."index" = 0
+          JOIN tx_witnesses w ON w.ref_tx_hash = bt.tx_hash AND w.ref_dep_index = 0
+         WHERE h.number BETWEEN $1 AND $2
+         ORDER BY h.number
+    ;"#;
+    let mut from = start;
+    while from <= tip {
+        let to = tip.min(from + FILL_BATCH_SIZE - 1);
+        let txn = cli.transaction().await?;
+        for row in txn
+            .query(sql, &[(from as i64).into(), (to as i64).into()])
+            .await?
+        {
+            let number = row.try_get::<i64>(0)? as u64;
+            insert_miner(&*txn, number, &row.try_get::<Vec<u8>>(1)?).await?;
+            if number > FINALIZATION_DELAY {
+                update_rewards(&*txn, number - FINALIZATION_DELAY, number).await?;
+            }
+        }
+        txn.commit().await?;
+        log::info!("attributed the stored blocks up to {} of {}", to, tip);
+        from = to + 1;
+    }
+    Ok(())
+}
+
+// Attributes the block to the miner, and computes the reward of the block which is paid by it,
+// the transactions of the block should be inserted already.
+pub(in crate::storage) async fn insert_changes(
+    txn: &dyn Transaction,
+    block: &core::BlockView,
+) -> Result<()> {
+    log::trace!("insert the miner of block {}", block.number());
+    let witness = block
+        .transactions()
+        .first()
+        .and_then(|cellbase| cellbase.witnesses().get(0))
+        .map(|witness| witness.raw_data())
+        .unwrap_or_default();
+    insert_miner(txn, block.number(), &witness).await?;
+    if block.number() > FINALIZATION_DELAY {
+        let target = block.number() - FINALIZATION_DELAY;
+        update_rewards(txn, target, block.number()).await?;
+    }
+    Ok(())
+}
+
+// Removes the miner of the block, and the reward which is paid by it, the miner is kept as the
+// miner of an orphaned block, the header of the block should not be removed yet.
+pub(in crate::storage) async fn remove_changes(txn: &dyn Transaction, number: u64) -> Result<()> {
+    log::trace!("remove the miner of block {}", number);
+    let sql = r#"
+        INSERT INTO orphan_miners (
+            hash, number, lock_hash, code_hash, hash_type, args
+        )
+        SELECT h.hash, m.number, m.lock_hash, m.code_hash, m.hash_type, m.args
+          FROM block_miners m
+          JOIN block_headers h ON h.number = m.number
+         WHERE m.number = $1
+        ON CONFLICT (hash) DO NOTHING
+    ;"#;
+    txn.execute(sql, &[(number as i64).into()]).await?;
+    let sql = "DELETE FROM block_miners WHERE number = $1;";
+    txn.execute(sql, &[(number as i64).into()]).await?;
+    if number > FINALIZATION_DELAY {
+        let sql = r#"
+            UPDATE block_miners
+               SET total_reward = NULL,
+                   primary_reward = NULL,
+                   secondary_reward = NULL,
+                   commit_reward = NULL,
+                   proposal_reward = NULL
+             WHERE number = $1
+        ;"#;
+        let target = (number - FINALIZATION_DELAY) as i64;
+        txn.execute(sql, &[target.into()]).await?;
+    }
+    Ok(())
+}
+
+// The blocks whose cellbase witnesses are not `CellbaseWitness`, such as the genesis block, are
+// not attributed to any miner.
+async fn insert_miner(txn: &dyn Transaction, number: u64, witness: &[u8]) -> Result<u64> {
+    let lock = if let Ok(witness) = packed::CellbaseWitness::from_slice(witness) {
+        witness.lock()
+    } else {
+        log::debug!("no miner in the cellbase of block {}", number);
+        return Ok(0);
+    };
+    let sql = r#"
+        INSERT INTO block_miners (
+            number, lock_hash, code_hash, hash_type, args
+        ) VALUES (
+            $1, $2, $3, $4, $5
+        )
+    ;"#;
+    let hash_type: u8 = lock.hash_type().into();
+    txn.execute(
+        sql,
+        &[
+            (number as i64).into(),
+            lock.calc_script_hash().raw_data().as_ref().into(),
+            lock.code_hash().raw_data().as_ref().into(),
+            i16::from(hash_type).into(),
+            lock.args().raw_data().as_ref().into(),
+        ],
+    )
+    .await
+}
+
+// Computes the reward of the target block, which is paid by the cellbase of the block.
+async fn update_rewards(txn: &dyn Transaction, target: u64, number: u64) -> Result<u64> {
+    let rewards = compute_rewards(txn, target, number).await?;
+    let sql = r#"
+        UPDATE block_miners
+           SET total_reward = $2,
+               primary_reward = $3,
+               secondary_reward = $4,
+               commit_reward = $5,
+               proposal_reward = $6
+         WHERE number = $1
+    ;"#;
+    let params: &[Value] = &[
+        (target as i64).into(),
+        (rewards.total as i64).into(),
+        (rewards.primary as i64).into(),
+        (rewards.secondary as i64).into(),
+        (rewards.commit as i64).into(),
+        (rewards.proposal as i64).into(),
+    ];
+    txn.execute(sql, params).await
+}
+
+async fn compute_rewards(txn: &dyn Transaction, target: u64, number: u64) -> Result<Rewards> {
+    let sql = r#"
+        SELECT number, hash, epoch_number, epoch_index, epoch_length, dao_c, dao_u
+          FROM block_headers
+         WHERE number BETWEEN $1 AND $2
+         ORDER BY number
+    ;"#;
+    let rows = txn
+        .query(sql, &[(target as i64 - 1).into(), (target as i64).into()])
+        .await?;
+    let (parent, header) = match rows.as_slice() {
+        [parent, header] => (parent, header),
+        _ => {
+            let errmsg = format!("no header of block {} or its parent", target);
+            return Err(Error::Data(errmsg));
+        }
+    };
+    let target_hash = header.try_get::<Vec<u8>>(1)?;
+    let epoch_number = header.try_get::<i32>(2)? as u64;
+    let epoch_index = header.try_get::<i32>(3)? as u64;
+    let epoch_length = header.try_get::<i32>(4)? as u64;
+    let parent_c = parent.try_get::<i64>(5)? as u64;
+    let parent_u = parent.try_get::<i64>(6)? as u64;
+
+    let primary = primary_reward(epoch_number, epoch_index, epoch_length);
+    // The miners get the share of the secondary issuance as the occupied capacity.
+    let secondary = if parent_c > 0 {
+        let issuance = block_reward(SECONDARY_EPOCH_REWARD, epoch_index, epoch_length);
+        (u128::from(issuance) * u128::from(parent_u) / u128::from(parent_c)) as u64
+    } else {
+        0
+    };
+
+    let fees = query_fees(txn, target, target + PROPOSAL_WINDOW.1).await?;
+    let commit = fees
+        .iter()
+        .filter(|fee| fee.block_number == target)
+        .map(|fee| fee.fee - proposer_reward(fee.fee))
+        .sum();
+
+    // The proposals of the block and its uncles.
+    let sql = r#"
+        SELECT short_id
+          FROM block_proposals
+         WHERE block_hash = $1
+            OR block_hash IN (
+               SELECT uncle_hash
+                 FROM block_uncles
+                WHERE block_hash = $1)
+    ;"#;
+    let mut target_proposals = txn
+        .query(sql, &[target_hash.as_slice().into()])
+        .await?
+        .into_iter()
+        .map(|row| row.try_get::<Vec<u8>>(0))
+        .collect::<Result<HashSet<_>>>()?;
+    // The proposals before the block, which compete with it, and when they are proposed.
+    let sql = r#"
+        SELECT h.number, p.short_id
+          FROM block_headers h
+          JOIN block_proposals p ON p.block_hash = h.hash
+         WHERE h.number BETWEEN $1 AND $2
+        UNION ALL
+        SELECT h.number, p.short_id
+          FROM block_headers h
+          JOIN block_uncles u ON u.block_hash = h.hash
+          JOIN block_proposals p ON p.block_hash = u.uncle_hash
+         WHERE h.number BETWEEN $1 AND $2
+    ;"#;
+    let start = target
+        .saturating_sub(PROPOSAL_WINDOW.1 - PROPOSAL_WINDOW.0)
+        .max(1);
+    let mut competing = HashMap::<Vec<u8>, Vec<u64>>::new();
+    for row in txn
+        .query(sql, &[(start as i64).into(), (target as i64 - 1).into()])
+        .await?
+    {
+        let proposed_at = row.try_get::<i64>(0)? as u64;
+        competing
+            .entry(row.try_get::<Vec<u8>>(1)?)
+            .or_default()
+            .push(proposed_at);
+    }
+    // A transaction which is committed in the window is rewarded to its first proposer.
+    let mut proposal = 0;
+    let mut committed = fees
+        .iter()
+        .filter(|fee| fee.block_number >= target + PROPOSAL_WINDOW.0)
+        .collect::<Vec<_>>();
+    committed.sort_by_key(|fee| std::cmp::Reverse(fee.block_number));
+    for fee in committed {
+        let short_id = &fee.tx_hash[..10];
+        let earliest = fee.block_number.saturating_sub(PROPOSAL_WINDOW.1);
+        let proposed_before = competing
+            .get(short_id)
+            .map(|numbers| numbers.iter().any(|number| *number >= earliest))
+            .unwrap_or(false);
+        if target_proposals.remove(short_id) && !proposed_before {
+            proposal += proposer_reward(fee.fee);
+        }
+    }
+
+    let sql = r#"
+        SELECT CAST(COALESCE(SUM(c.capacity), 0) AS BIGINT)
+          FROM cells c
+          JOIN block_transactions bt ON bt.tx_hash = c.tx_hash AND bt."index" = 0
+          JOIN block_headers h ON h.hash = bt.block_hash
+         WHERE h.number = $1
+    ;"#;
+    let total = txn
+        .query_one(sql, &[(number as i64).into()])
+        .await?
+        .try_get::<i64>(0)? as u64;
+    Ok(Rewards {
+        total,
+        primary,
+        secondary,
+        commit,
+        proposal,
+    })
+}
+
+/// The primary issuance of the block at the index of the epoch.
+pub(in crate::storage) fn primary_reward(
+    epoch_number: u64,
+    epoch_index: u64,
+    epoch_length: u64,
+) -> u64 {
+    let halvings = epoch_number / PRIMARY_EPOCH_REWARD_HALVING_INTERVAL;
+    let epoch_reward = INITIAL_PRIMARY_EPOCH_REWARD
+        .checked_shr(halvings as u32)
+        .unwrap_or(0);
+    block_reward(epoch_reward, epoch_index, epoch_length)
+}
+
+// The issuance of an epoch is divided equally, the first blocks get the remainder.
+fn block_reward(epoch_reward: u64, epoch_index: u64, epoch_length: u64) -> u64 {
+    if epoch_length == 0 {
+        return 0;
+    }
+    let remainder = if epoch_index < epoch_reward % epoch_length {
+        1
+    } else {
+        0
+    };
+    epoch_reward / epoch_length + remainder
+}
+
+fn proposer_reward(fee: u64) -> u64 {
+    (u128::from(fee) * u128::from(PROPOSER_REWARD_RATIO.0) / u128::from(PROPOSER_REWARD_RATIO.1))
+        as u64
+}
+
+// The fees of the blocks as `query_fees`, in a statement which is grouped by block, to fill the
+// stored blocks at once.
+pub(in crate::storage) const BLOCK_FEES: &str = r#"(
+    SELECT i.block_number,
+           CAST(SUM(CASE WHEN i.capacity > COALESCE(o.capacity, 0)
+                         THEN i.capacity - COALESCE(o.capacity, 0)
+                         ELSE 0 END) AS BIGINT) AS fees
+      FROM (SELECT consumed_tx_hash AS tx_hash, consumed_block_number AS block_number,
+                   SUM(capacity) AS capacity
+              FROM cells
+             WHERE consumed_block_number IS NOT NULL
+             GROUP BY consumed_tx_hash, consumed_block_number) i
+      LEFT JOIN (SELECT tx_hash, SUM(capacity) AS capacity
+                   FROM cells
+                  GROUP BY tx_hash) o ON o.tx_hash = i.tx_hash
+     GROUP BY i.block_number
+    )"#;
+
+pub(in crate::storage) struct Fee {
+    tx_hash: Vec<u8>,
+    block_number: u64,
+    pub(in crate::storage) fee: u64,
+}
+
+// The fees of the transactions which are committed in the blocks, the cellbases are excluded.
+//
+// The interest of the withdrawals from NervosDAO is not known, so they are not counted.
+pub(in crate::storage) async fn query_fees(
+    txn: &dyn Transaction,
+    from: u64,
+    to: u64,
+) -> Result<Vec<Fee>> {
+    let params: &[Value] = &[(from as i64).into(), (to as i64).into()];
+    let sql = r#"
+        SELECT consumed_tx_hash, consumed_block_number, CAST(SUM(capacity) AS BIGINT)
+          FROM cells
+         WHERE consumed_block_number BETWEEN $1 AND $2
+         GROUP BY consumed_tx_hash, consumed_block_number
+    ;"#;
+    let inputs = txn.query(sql, params).await?;
+    let sql = r#"
+        SELECT tx_hash, CAST(SUM(capacity) AS BIGINT)
+          FROM cells
+         WHERE created_block_number BETWEEN $1 AND $2
+         GROUP BY tx_hash
+    ;"#;
+    let outputs = txn
+        .query(sql, params)
+        .await?
+        .into_iter()
+        .map(|row| Ok((row.try_get::<Vec<u8>>(0)?, row.try_get::<i64>(1)? as u64)))
+        .collect::<Result<HashMap<_, _>>>()?;
+    inputs
+        .into_iter()
+        .map(|row| {
+            let tx_hash = row.try_get::<Vec<u8>>(0)?;
+            let block_number = row.try_get::<i64>(1)? as u64;
+            let capacity = row.try_get::<i64>(2)? as u64;
+            let fee = capacity.saturating_sub(outputs.get(&tx_hash).copied().unwrap_or(0));
+            Ok(Fee {
+                tx_hash,
+                block_number,
+                fee,
+            })
+        })
+        .collect()
+}
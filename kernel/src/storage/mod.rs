@@ -10,34 +10,90 @@ use std::{future::Future, sync::Arc};
 
 use property::Property;
 
-use crate::{error::Result, postgres as pg, Runtime};
+use crate::{error::Result, Registry, Runtime};
 
+mod accounts;
+mod backend;
 mod base_data;
+mod epochs;
+mod explorer;
+mod export;
+mod indexer;
+mod mining;
 mod operations;
-pub mod traits;
+mod proposals;
+mod relations;
+mod rollups;
+mod script_usage;
+mod stats;
+mod udt;
+
+pub use self::{
+    accounts::Accounts,
+    backend::{FromValue, Row, Value},
+    base_data::BaseData,
+    epochs::Epochs,
+    explorer::{BlockId, CellFilter, Explorer},
+    export::{Column, ColumnType, Dataset, Export},
+    indexer::{Indexer, IndexerCell, IndexerPage, IndexerTx, Order, ScriptType, SearchKey},
+    mining::Mining,
+    proposals::Proposals,
+    relations::Relations,
+    rollups::Rollups,
+    script_usage::ScriptUsage,
+    stats::{Excluded, Stats},
+    udt::Udt,
+};
+
+use self::backend::{Backend, Postgres, Sqlite};
 
 #[derive(Property)]
-#[property(get(public), set(disable), mut(crate))]
+#[property(get(crate), set(disable), mut(crate))]
 pub struct Storage {
-    client: pg::Client,
+    backend: Box<dyn Backend>,
     #[property(get(disable))]
     runtime: Runtime,
+    // The known scripts to recognize the cells when blocks are inserted, such as the cells of
+    // sUDT, they are detected from the genesis block if they are not set.
+    #[property(get(disable), mut(disable))]
+    registry: Option<Registry>,
 }
 
 impl Storage {
+    /// Connects to a storage, the backend is chosen by the scheme of the URI.
+    ///
+    /// - `sqlite://path/to/file.db`, `sqlite:path/to/file.db` or `sqlite::memory:` for SQLite.
+    /// - `postgresql://...`, `postgres://...` or a key-value connection string for PostgreSQL.
     pub fn connect(rt: Runtime, uri: &str) -> Result<Self> {
-        let (client, connection) = rt.block_on(pg::connect(uri, pg::NoTls))?;
-        rt.spawn(async {
-            if let Err(err) = connection.await {
-                log::error!("connection error: {}", err);
-            }
-        });
+        let backend: Box<dyn Backend> = if let Some(path) = sqlite_path(uri) {
+            log::trace!("open sqlite database {}", path);
+            Box::new(Sqlite::open(path)?)
+        } else {
+            log::trace!("connect to postgresql database");
+            Box::new(Postgres::connect(&rt, uri)?)
+        };
         Ok(Self {
-            client,
+            backend,
             runtime: rt,
+            registry: None,
         })
     }
 
+    /// Sets the known scripts, otherwise the well-known scripts of the network are used, and
+    /// the network is detected from the genesis block.
+    pub fn set_registry(&mut self, registry: Registry) -> &mut Self {
+        self.registry = Some(registry);
+        self
+    }
+
+    /// Executes a query and returns all rows.
+    ///
+    /// The placeholders of parameters are `$1`, `$2`, etc, for all backends.
+    pub fn query(&self, sql: &str, params: &[Value]) -> Result<Vec<Row>> {
+        log::trace!("query: {}", sql);
+        self.block_on(self.backend.query(sql, params))
+    }
+
     pub fn block_on<F>(&self, future: F) -> F::Output
     where
         F: Future,
@@ -50,3 +106,11 @@ impl Storage {
         Arc::clone(&self.runtime)
     }
 }
+
+fn sqlite_path(uri: &str) -> Option<&str> {
+    if let Some(path) = uri.strip_prefix("sqlite://") {
+        Some(path)
+    } else {
+        uri.strip_prefix("sqlite:")
+    }
+}
@@ -8,10 +8,8 @@
 
 use uckb_jsonrpc_core::types::{packed, prelude::*};
 
-use crate::{
-    error::{Error, Result},
-    postgres as pg,
-};
+use super::backend::Backend;
+use crate::error::{Error, Result};
 
 pub(super) fn hash_from_value(hash_vec: Vec<u8>) -> Result<packed::Byte32> {
     if hash_vec.len() == 32 {
@@ -26,7 +24,7 @@ pub(super) fn hash_from_value(hash_vec: Vec<u8>) -> Result<packed::Byte32> {
     }
 }
 
-pub(super) async fn drop_table(cli: &pg::Client, table: &str) -> Result<u64> {
+pub(super) async fn drop_table(cli: &dyn Backend, table: &str) -> Result<u64> {
     let sql = format!("DROP TABLE IF EXISTS {};", table);
-    cli.execute(sql.as_str(), &[]).await.map_err(Into::into)
+    cli.execute(sql.as_str(), &[]).await
 }
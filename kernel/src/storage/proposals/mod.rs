@@ -0,0 +1,180 @@
+// Copyright (C) 2019-2020 Boyu Yang
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+use super::{
+    mining::{operations::PROPOSAL_WINDOW, LATEST_EPOCHS},
+    Storage,
+};
+use crate::{error::Result, Report};
+
+pub(super) mod operations;
+
+/// The proposals of the committed transactions, which are matched by the short IDs when blocks
+/// are inserted and removed.
+///
+/// A transaction is matched with the blocks and the uncles which propose it in the proposal
+/// window before the block which commits it, the proposals of an uncle are in the window as
+/// the block which includes the uncle.
+pub trait Proposals {
+    /// The distribution of the latencies between the first proposals and the commitments of
+    /// the transactions in each of the latest epochs.
+    ///
+    /// The latency is null if a transaction is not proposed in the window.
+    fn proposal_latencies(&self, epochs: u32) -> Result<Report>;
+    /// The proposals of the blocks and the uncles in each of the latest epochs, and how many of
+    /// them are committed, expired or pending.
+    fn proposals_by_epoch(&self, epochs: u32) -> Result<Report>;
+    /// The latest proposals which are never committed in the windows.
+    fn uncommitted_proposals(&self, limit: u32) -> Result<Report>;
+    /// The short IDs which are proposed more than once in the latest epochs, by the uncles and
+    /// other blocks or uncles.
+    fn duplicate_proposals(&self, epochs: u32) -> Result<Report>;
+}
+
+// The proposals of the blocks and the uncles in the filtered blocks, the number of a proposal
+// of an uncle is the number of the block which includes the uncle.
+fn proposals_sql(filter: &str) -> String {
+    format!(
+        r#"(
+        SELECT h.number, h.epoch_number, p.block_hash, p.short_id, 'block' AS proposer
+          FROM block_headers h
+          JOIN block_proposals p ON p.block_hash = h.hash
+         WHERE {0}
+        UNION ALL
+        SELECT h.number, h.epoch_number, p.block_hash, p.short_id, 'uncle' AS proposer
+          FROM block_headers h
+          JOIN block_uncles u ON u.block_hash = h.hash
+          JOIN block_proposals p ON p.block_hash = u.uncle_hash
+         WHERE {0}
+        )"#,
+        filter
+    )
+}
+
+// The proposals which are matched with the committed transactions.
+const COMMITTED: &str = r#"(
+    SELECT DISTINCT proposal_hash, short_id
+      FROM transaction_proposals
+    )"#;
+
+impl Proposals for Storage {
+    fn proposal_latencies(&self, epochs: u32) -> Result<Report> {
+        log::trace!("report the latencies of proposals");
+        let sql = format!(
+            r#"
+            SELECT h.epoch_number, tp.latency, COUNT(*) AS transactions,
+                   CAST(COUNT(*) AS DOUBLE PRECISION)
+                       / CAST(SUM(COUNT(*)) OVER (PARTITION BY h.epoch_number)
+                           AS DOUBLE PRECISION) AS share
+              FROM block_headers h
+              JOIN block_transactions bt ON bt.block_hash = h.hash AND bt."index" > 0
+              LEFT JOIN (
+                   SELECT tx_hash, block_number - MIN(proposal_number) AS latency
+                     FROM transaction_proposals
+                    GROUP BY tx_hash, block_number
+                   ) tp ON tp.tx_hash = bt.tx_hash
+             WHERE h.number > 0
+               AND {}
+             GROUP BY h.epoch_number, tp.latency
+             ORDER BY h.epoch_number DESC, tp.latency IS NULL, tp.latency
+        ;"#,
+            LATEST_EPOCHS
+        );
+        let rows = self.query(&sql, &[i64::from(epochs).into()])?;
+        let columns = ["epoch_number", "latency", "transactions", "share"];
+        Ok(Report::with_rows(&columns, rows))
+    }
+
+    fn proposals_by_epoch(&self, epochs: u32) -> Result<Report> {
+        log::trace!("report the proposals by epoch");
+        // The window of a proposal is passed if the last block which could commit it is stored.
+        let sql = format!(
+            r#"
+            SELECT p.epoch_number,
+                   SUM(CASE WHEN p.proposer = 'block' THEN 1 ELSE 0 END) AS proposals,
+                   SUM(CASE WHEN p.proposer = 'uncle' THEN 1 ELSE 0 END) AS uncle_proposals,
+                   COUNT(c.short_id) AS committed,
+                   SUM(CASE WHEN c.short_id IS NULL AND p.number + {0} <= t.number
+                            THEN 1 ELSE 0 END) AS expired,
+                   SUM(CASE WHEN c.short_id IS NULL AND p.number + {0} > t.number
+                            THEN 1 ELSE 0 END) AS pending
+              FROM {1} p
+              LEFT JOIN {2} c ON c.proposal_hash = p.block_hash AND c.short_id = p.short_id
+             CROSS JOIN (SELECT MAX(number) AS number FROM block_headers) t
+             GROUP BY p.epoch_number
+             ORDER BY p.epoch_number DESC
+        ;"#,
+            PROPOSAL_WINDOW.1,
+            proposals_sql(LATEST_EPOCHS),
+            COMMITTED
+        );
+        let rows = self.query(&sql, &[i64::from(epochs).into()])?;
+        let columns = [
+            "epoch_number",
+            "proposals",
+            "uncle_proposals",
+            "committed",
+            "expired",
+            "pending",
+        ];
+        Ok(Report::with_rows(&columns, rows))
+    }
+
+    fn uncommitted_proposals(&self, limit: u32) -> Result<Report> {
+        log::trace!("report the uncommitted proposals");
+        let filter = format!(
+            "h.number + {} <= (SELECT MAX(number) FROM block_headers)",
+            PROPOSAL_WINDOW.1
+        );
+        let sql = format!(
+            r#"
+            SELECT p.number, p.proposer, p.block_hash, p.short_id
+              FROM {} p
+              LEFT JOIN {} c ON c.proposal_hash = p.block_hash AND c.short_id = p.short_id
+             WHERE c.short_id IS NULL
+             ORDER BY p.number DESC, p.proposer, p.short_id
+             LIMIT $1
+        ;"#,
+            proposals_sql(&filter),
+            COMMITTED
+        );
+        let rows = self.query(&sql, &[i64::from(limit).into()])?;
+        let columns = ["block_number", "proposer", "proposer_hash", "short_id"];
+        Ok(Report::with_rows(&columns, rows))
+    }
+
+    fn duplicate_proposals(&self, epochs: u32) -> Result<Report> {
+        log::trace!("report the duplicate proposals");
+        let sql = format!(
+            r#"
+            SELECT p.short_id, COUNT(*) AS proposals,
+                   SUM(CASE WHEN p.proposer = 'uncle' THEN 1 ELSE 0 END) AS uncle_proposals,
+                   COUNT(c.short_id) AS committed,
+                   MIN(p.number) AS first_number, MAX(p.number) AS last_number
+              FROM {} p
+              LEFT JOIN {} c ON c.proposal_hash = p.block_hash AND c.short_id = p.short_id
+             GROUP BY p.short_id
+            HAVING COUNT(*) > 1
+               AND SUM(CASE WHEN p.proposer = 'uncle' THEN 1 ELSE 0 END) > 0
+             ORDER BY last_number DESC, p.short_id
+        ;"#,
+            proposals_sql(LATEST_EPOCHS),
+            COMMITTED
+        );
+        let rows = self.query(&sql, &[i64::from(epochs).into()])?;
+        let columns = [
+            "short_id",
+            "proposals",
+            "uncle_proposals",
+            "committed",
+            "first_number",
+            "last_number",
+        ];
+        Ok(Report::with_rows(&columns, rows))
+    }
+}
@@ -0,0 +1,100 @@
+// Copyright (C) 2019-2020 Boyu Yang
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+use crate::{
+    error::Result,
+    storage::{
+        backend::{Backend, Transaction},
+        mining::operations::PROPOSAL_WINDOW,
+        Value,
+    },
+};
+
+pub(in crate::storage) const TABLES: &[&str] = &["transaction_proposals"];
+
+pub(in crate::storage) const INDEXES: &[&str] = &[
+    r#"CREATE INDEX IF NOT EXISTS transaction_proposals_block_number_idx
+        ON transaction_proposals (block_number);"#,
+    r#"CREATE INDEX IF NOT EXISTS transaction_proposals_proposal_hash_idx
+        ON transaction_proposals (proposal_hash, short_id);"#,
+];
+
+// Creates the table if it doesn't exist, and matches all stored transactions with their
+// proposals in one statement.
+pub(in crate::storage) async fn create_tables(cli: &mut dyn Backend) -> Result<()> {
+    log::trace!("create tables of proposals");
+    if cli.table_exists("transaction_proposals").await? {
+        return Ok(());
+    }
+    let sql = r#"
+        CREATE TABLE IF NOT EXISTS transaction_proposals (
+            tx_hash             BYTEA       NOT NULL,
+            block_number        BIGINT      NOT NULL,
+            short_id            BYTEA       NOT NULL,
+            proposal_hash       BYTEA       NOT NULL,
+            proposal_number     BIGINT      NOT NULL,
+            PRIMARY KEY (tx_hash, proposal_hash)
+        );"#;
+    let txn = cli.transaction().await?;
+    txn.execute(sql, &[]).await?;
+    let matched = match_proposals(&*txn, 0, i64::MAX as u64).await?;
+    if matched > 0 {
+        log::info!("matched {} proposals of the stored transactions", matched);
+    }
+    txn.commit().await
+}
+
+// Matches the transactions of the block with the proposals in the window before it, the
+// proposals of the block and its uncles should be inserted already.
+pub(in crate::storage) async fn insert_changes(txn: &dyn Transaction, number: u64) -> Result<()> {
+    log::trace!("match the proposals of block {}", number);
+    match_proposals(txn, number, number).await?;
+    Ok(())
+}
+
+pub(in crate::storage) async fn remove_changes(txn: &dyn Transaction, number: u64) -> Result<()> {
+    log::trace!("remove the matched proposals of block {}", number);
+    let sql = "DELETE FROM transaction_proposals WHERE block_number = $1;";
+    txn.execute(sql, &[(number as i64).into()]).await?;
+    Ok(())
+}
+
+// Matches the transactions which are committed in the blocks with the proposals of the blocks
+// and the uncles in the proposal windows, the proposals of an uncle are in the window as the
+// block which includes the uncle.
+//
+// The short ID of a transaction is the first 10 bytes of its hash, the cellbases are not
+// proposed.
+async fn match_proposals(txn: &dyn Transaction, from: u64, to: u64) -> Result<u64> {
+    let sql = format!(
+        r#"
+        INSERT INTO transaction_proposals (
+            tx_hash, block_number, short_id, proposal_hash, proposal_number
+        )
+        SELECT bt.tx_hash, c.number, p.short_id, p.block_hash, h.number
+          FROM block_headers c
+          JOIN block_transactions bt ON bt.block_hash = c.hash AND bt."index" > 0
+          JOIN block_headers h ON h.number BETWEEN c.number - {1} AND c.number - {0}
+          JOIN block_proposals p
+            ON p.block_hash = h.hash AND p.short_id = SUBSTR(bt.tx_hash, 1, 10)
+         WHERE c.number BETWEEN $1 AND $2
+        UNION ALL
+        SELECT bt.tx_hash, c.number, p.short_id, p.block_hash, h.number
+          FROM block_headers c
+          JOIN block_transactions bt ON bt.block_hash = c.hash AND bt."index" > 0
+          JOIN block_headers h ON h.number BETWEEN c.number - {1} AND c.number - {0}
+          JOIN block_uncles u ON u.block_hash = h.hash
+          JOIN block_proposals p
+            ON p.block_hash = u.uncle_hash AND p.short_id = SUBSTR(bt.tx_hash, 1, 10)
+         WHERE c.number BETWEEN $1 AND $2
+    ;"#,
+        PROPOSAL_WINDOW.0, PROPOSAL_WINDOW.1
+    );
+    let params: &[Value] = &[(from as i64).into(), (to as i64).into()];
+    txn.execute(&sql, params).await
+}
@@ -0,0 +1,241 @@
+// Copyright (C) 2019-2020 Boyu Yang
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+use super::{
+    explorer::{CELL_COLUMNS, HEADER_COLUMNS},
+    Row, Storage, Value,
+};
+use crate::error::Result;
+
+// The count of keys in one statement, keeps the parameters under the limits of all backends.
+const BATCH_SIZE: usize = 500;
+
+/// Loads the rows related to many keys at once, so the resolvers of nested objects could batch
+/// their lookups instead of querying once for each object.
+///
+/// Each row includes the key which it relates to, the rows of the same key are in order.
+pub trait Relations {
+    fn headers_by_hashes(&self, hashes: &[Vec<u8>]) -> Result<Vec<Row>>;
+    /// Returns the uncle headers with the hashes of the blocks which include them.
+    fn uncles_by_block_hashes(&self, block_hashes: &[Vec<u8>]) -> Result<Vec<Row>>;
+    fn proposals_by_block_hashes(&self, block_hashes: &[Vec<u8>]) -> Result<Vec<Row>>;
+    fn transactions_by_block_hashes(&self, block_hashes: &[Vec<u8>]) -> Result<Vec<Row>>;
+    fn transactions_by_hashes(&self, tx_hashes: &[Vec<u8>]) -> Result<Vec<Row>>;
+    /// Returns the cells consumed by the transactions.
+    fn inputs_by_tx_hashes(&self, tx_hashes: &[Vec<u8>]) -> Result<Vec<Row>>;
+    fn outputs_by_tx_hashes(&self, tx_hashes: &[Vec<u8>]) -> Result<Vec<Row>>;
+    fn cell_deps_by_tx_hashes(&self, tx_hashes: &[Vec<u8>]) -> Result<Vec<Row>>;
+    fn header_deps_by_tx_hashes(&self, tx_hashes: &[Vec<u8>]) -> Result<Vec<Row>>;
+    fn witnesses_by_tx_hashes(&self, tx_hashes: &[Vec<u8>]) -> Result<Vec<Row>>;
+    fn cells_by_out_points(&self, out_points: &[(Vec<u8>, u32)]) -> Result<Vec<Row>>;
+    fn cells_data_by_hashes(&self, hashes: &[Vec<u8>]) -> Result<Vec<Row>>;
+    fn scripts_by_hashes(&self, hashes: &[Vec<u8>]) -> Result<Vec<Row>>;
+}
+
+impl Storage {
+    // Queries the keys in chunks, `{keys}` in the statement is replaced by the placeholders of
+    // the keys in a chunk.
+    fn query_by_keys(&self, sql: &str, keys: &[Vec<u8>]) -> Result<Vec<Row>> {
+        let cli = self.backend();
+        let mut rows = Vec::new();
+        for chunk in keys.chunks(BATCH_SIZE) {
+            let placeholders = (1..=chunk.len())
+                .map(|i| format!("${}", i))
+                .collect::<Vec<_>>()
+                .join(", ");
+            let sql = sql.replace("{keys}", &placeholders);
+            let params = chunk
+                .iter()
+                .map(|key| key.as_slice().into())
+                .collect::<Vec<Value>>();
+            rows.extend(self.block_on(cli.query(&sql, &params))?);
+        }
+        Ok(rows)
+    }
+}
+
+impl Relations for Storage {
+    fn headers_by_hashes(&self, hashes: &[Vec<u8>]) -> Result<Vec<Row>> {
+        log::trace!("query headers of {} blocks", hashes.len());
+        let sql = format!(
+            "SELECT {} FROM block_headers WHERE hash IN ({{keys}});",
+            HEADER_COLUMNS
+        );
+        self.query_by_keys(&sql, hashes)
+    }
+
+    fn uncles_by_block_hashes(&self, block_hashes: &[Vec<u8>]) -> Result<Vec<Row>> {
+        log::trace!("query uncles of {} blocks", block_hashes.len());
+        let sql = format!(
+            r#"
+            SELECT bu.block_hash, bu."index" AS uncle_index, {}
+              FROM block_uncles bu
+              JOIN uncle_headers uh ON uh.hash = bu.uncle_hash
+             WHERE bu.block_hash IN ({{keys}})
+             ORDER BY bu.block_hash, bu."index"
+        ;"#,
+            HEADER_COLUMNS
+        );
+        self.query_by_keys(&sql, block_hashes)
+    }
+
+    fn proposals_by_block_hashes(&self, block_hashes: &[Vec<u8>]) -> Result<Vec<Row>> {
+        log::trace!("query proposals of {} blocks", block_hashes.len());
+        let sql = r#"
+            SELECT block_hash, short_id
+              FROM block_proposals
+             WHERE block_hash IN ({keys})
+             ORDER BY block_hash, "index"
+        ;"#;
+        self.query_by_keys(sql, block_hashes)
+    }
+
+    fn transactions_by_block_hashes(&self, block_hashes: &[Vec<u8>]) -> Result<Vec<Row>> {
+        log::trace!("query transactions of {} blocks", block_hashes.len());
+        let sql = r#"
+            SELECT t.hash, t.version, bh.number AS block_number, bh.hash AS block_hash,
+                   bt."index"
+              FROM block_transactions bt
+              JOIN transactions t ON t.hash = bt.tx_hash
+              JOIN block_headers bh ON bh.hash = bt.block_hash
+             WHERE bt.block_hash IN ({keys})
+             ORDER BY bt.block_hash, bt."index"
+        ;"#;
+        self.query_by_keys(sql, block_hashes)
+    }
+
+    fn transactions_by_hashes(&self, tx_hashes: &[Vec<u8>]) -> Result<Vec<Row>> {
+        log::trace!("query {} transactions", tx_hashes.len());
+        let sql = r#"
+            SELECT t.hash, t.version, bh.number AS block_number, bh.hash AS block_hash,
+                   bt."index"
+              FROM transactions t
+              JOIN block_transactions bt ON bt.tx_hash = t.hash
+              JOIN block_headers bh ON bh.hash = bt.block_hash
+             WHERE t.hash IN ({keys})
+        ;"#;
+        self.query_by_keys(sql, tx_hashes)
+    }
+
+    fn inputs_by_tx_hashes(&self, tx_hashes: &[Vec<u8>]) -> Result<Vec<Row>> {
+        log::trace!("query inputs of {} transactions", tx_hashes.len());
+        let sql = format!(
+            r#"
+            SELECT {}
+              FROM cells c
+              JOIN block_transactions bt ON bt.tx_hash = c.tx_hash
+              JOIN block_headers bh ON bh.hash = bt.block_hash
+             WHERE c.consumed_tx_hash IN ({{keys}})
+             ORDER BY c.consumed_tx_hash, c.consumed_index
+        ;"#,
+            CELL_COLUMNS
+        );
+        self.query_by_keys(&sql, tx_hashes)
+    }
+
+    fn outputs_by_tx_hashes(&self, tx_hashes: &[Vec<u8>]) -> Result<Vec<Row>> {
+        log::trace!("query outputs of {} transactions", tx_hashes.len());
+        let sql = format!(
+            r#"
+            SELECT {}
+              FROM cells c
+              JOIN block_transactions bt ON bt.tx_hash = c.tx_hash
+              JOIN block_headers bh ON bh.hash = bt.block_hash
+             WHERE c.tx_hash IN ({{keys}})
+             ORDER BY c.tx_hash, c."index"
+        ;"#,
+            CELL_COLUMNS
+        );
+        self.query_by_keys(&sql, tx_hashes)
+    }
+
+    fn cell_deps_by_tx_hashes(&self, tx_hashes: &[Vec<u8>]) -> Result<Vec<Row>> {
+        log::trace!("query cell deps of {} transactions", tx_hashes.len());
+        let sql = r#"
+            SELECT ref_tx_hash, tx_hash, "index", dep_type
+              FROM tx_cell_deps
+             WHERE ref_tx_hash IN ({keys})
+             ORDER BY ref_tx_hash, ref_dep_index
+        ;"#;
+        self.query_by_keys(sql, tx_hashes)
+    }
+
+    fn header_deps_by_tx_hashes(&self, tx_hashes: &[Vec<u8>]) -> Result<Vec<Row>> {
+        log::trace!("query header deps of {} transactions", tx_hashes.len());
+        let sql = r#"
+            SELECT ref_tx_hash, block_hash
+              FROM tx_header_deps
+             WHERE ref_tx_hash IN ({keys})
+             ORDER BY ref_tx_hash, ref_dep_index
+        ;"#;
+        self.query_by_keys(sql, tx_hashes)
+    }
+
+    fn witnesses_by_tx_hashes(&self, tx_hashes: &[Vec<u8>]) -> Result<Vec<Row>> {
+        log::trace!("query witnesses of {} transactions", tx_hashes.len());
+        let sql = r#"
+            SELECT ref_tx_hash, witness
+              FROM tx_witnesses
+             WHERE ref_tx_hash IN ({keys})
+             ORDER BY ref_tx_hash, ref_dep_index
+        ;"#;
+        self.query_by_keys(sql, tx_hashes)
+    }
+
+    fn cells_by_out_points(&self, out_points: &[(Vec<u8>, u32)]) -> Result<Vec<Row>> {
+        log::trace!("query {} cells by out points", out_points.len());
+        let cli = self.backend();
+        let mut rows = Vec::new();
+        for chunk in out_points.chunks(BATCH_SIZE) {
+            let conditions = (0..chunk.len())
+                .map(|i| {
+                    format!(
+                        r#"(c.tx_hash = ${} AND c."index" = ${})"#,
+                        i * 2 + 1,
+                        i * 2 + 2
+                    )
+                })
+                .collect::<Vec<_>>()
+                .join(" OR ");
+            let sql = format!(
+                r#"
+                SELECT {}
+                  FROM cells c
+                  JOIN block_transactions bt ON bt.tx_hash = c.tx_hash
+                  JOIN block_headers bh ON bh.hash = bt.block_hash
+                 WHERE {}
+            ;"#,
+                CELL_COLUMNS, conditions
+            );
+            let params = chunk
+                .iter()
+                .flat_map(|(tx_hash, index)| {
+                    vec![tx_hash.as_slice().into(), i64::from(*index).into()]
+                })
+                .collect::<Vec<Value>>();
+            rows.extend(self.block_on(cli.query(&sql, &params))?);
+        }
+        Ok(rows)
+    }
+
+    fn cells_data_by_hashes(&self, hashes: &[Vec<u8>]) -> Result<Vec<Row>> {
+        log::trace!("query {} cells data", hashes.len());
+        let sql = "SELECT hash, data FROM cells_data WHERE hash IN ({keys});";
+        self.query_by_keys(sql, hashes)
+    }
+
+    fn scripts_by_hashes(&self, hashes: &[Vec<u8>]) -> Result<Vec<Row>> {
+        log::trace!("query {} scripts", hashes.len());
+        let sql = r#"
+            SELECT hash, code_hash, hash_type, args
+              FROM scripts
+             WHERE hash IN ({keys})
+        ;"#;
+        self.query_by_keys(sql, hashes)
+    }
+}
@@ -0,0 +1,79 @@
+// Copyright (C) 2019-2020 Boyu Yang
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+use super::{Storage, Value};
+use crate::{
+    error::Result,
+    utilities::{utc_date, utc_hour},
+    Report,
+};
+
+pub(super) mod operations;
+
+use self::operations::{self as ops, COUNTERS};
+
+/// The rollups of blocks by UTC day and by hour, which are maintained when blocks are inserted
+/// and removed.
+///
+/// The inputs and the outputs are the cells which are consumed and created in a period, the new
+/// cells and the consumed cells exclude the ones which are both created and consumed in it. The
+/// new addresses are the locks which are first seen, and the active addresses are the locks
+/// of the cells which are created or consumed. The moved capacity is the capacity of the inputs.
+pub trait Rollups {
+    /// Rolls up all stored blocks again.
+    fn rebuild_rollups(&mut self) -> Result<()>;
+    /// The rollups of the latest days.
+    fn rollups_by_day(&self, days: u32) -> Result<Report>;
+    /// The rollups of the latest hours.
+    fn rollups_by_hour(&self, hours: u32) -> Result<Report>;
+}
+
+// The rollups of the latest periods, which are labeled by the function.
+fn rollups<F>(storage: &Storage, name: &str, period: &str, periods: u32, label: F) -> Result<Report>
+where
+    F: Fn(u64) -> String,
+{
+    let sql = format!(
+        r#"
+        SELECT period, {1}
+          FROM {0}_rollups
+         WHERE period > (SELECT MAX(period) FROM {0}_rollups) - $1
+         ORDER BY period
+    ;"#,
+        name,
+        COUNTERS.join(", ")
+    );
+    let columns = [period].iter().chain(COUNTERS).copied().collect::<Vec<_>>();
+    let mut report = Report::new(&columns);
+    for row in storage.query(&sql, &[i64::from(periods).into()])? {
+        let period = row.try_get::<i64>(0)? as u64;
+        let mut row = row.into_values();
+        row[0] = Value::Text(label(period));
+        report.push(row);
+    }
+    Ok(report)
+}
+
+impl Rollups for Storage {
+    fn rebuild_rollups(&mut self) -> Result<()> {
+        log::trace!("rebuild the rollups");
+        let rt = self.runtime();
+        let cli = self.mut_backend();
+        rt.block_on(ops::rebuild_tables(cli.as_mut()))
+    }
+
+    fn rollups_by_day(&self, days: u32) -> Result<Report> {
+        log::trace!("report the rollups of {} days", days);
+        rollups(self, "daily", "day", days, utc_date)
+    }
+
+    fn rollups_by_hour(&self, hours: u32) -> Result<Report> {
+        log::trace!("report the rollups of {} hours", hours);
+        rollups(self, "hourly", "hour", hours, utc_hour)
+    }
+}
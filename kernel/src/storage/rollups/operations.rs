@@ -0,0 +1,416 @@
+// Copyright (C) 2019-2020 Boyu Yang
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+use crate::{
+    error::{Error, Result},
+    storage::{
+        backend::{Backend, Transaction},
+        mining::operations::{query_fees, BLOCK_FEES},
+        Value,
+    },
+    utilities::{DAY_MILLIS, HOUR_MILLIS},
+};
+
+pub(in crate::storage) const TABLES: &[&str] = &[
+    "daily_rollups",
+    "daily_rollup_locks",
+    "hourly_rollups",
+    "hourly_rollup_locks",
+];
+
+// The prefixes of the tables of the rollups, and the milliseconds of their periods.
+const ROLLUPS: &[(&str, u64)] = &[("daily", DAY_MILLIS), ("hourly", HOUR_MILLIS)];
+
+// The columns which are the sums of the changes of the blocks in a period.
+pub(in crate::storage) const COUNTERS: &[&str] = &[
+    "blocks",
+    "transactions",
+    "inputs",
+    "outputs",
+    "new_cells",
+    "consumed_cells",
+    "new_addresses",
+    "active_addresses",
+    "fees",
+    "capacity_moved",
+];
+
+// The locks of the cells which are created or consumed in the block.
+const TOUCHED_LOCKS: &str = r#"(
+    SELECT lock_hash FROM cells WHERE created_block_number = $1
+     UNION
+    SELECT lock_hash FROM cells WHERE consumed_block_number = $1
+    )"#;
+
+// What a block adds to the rollups, except the ones which depend on the period.
+struct Changes {
+    timestamp: i64,
+    transactions: i64,
+    inputs: i64,
+    outputs: i64,
+    new_addresses: i64,
+    fees: i64,
+    capacity_moved: i64,
+}
+
+// Creates the tables if they don't exist, and rolls up the stored blocks. All tables are
+// created and filled together, so only the first one is checked.
+pub(in crate::storage) async fn create_tables(cli: &mut dyn Backend) -> Result<()> {
+    log::trace!("create tables of rollups");
+    if cli.table_exists("daily_rollups").await? {
+        return Ok(());
+    }
+    let txn = cli.transaction().await?;
+    fill_tables(&*txn).await?;
+    txn.commit().await
+}
+
+// Drops the tables and rolls up the stored blocks again.
+pub(in crate::storage) async fn rebuild_tables(cli: &mut dyn Backend) -> Result<()> {
+    log::trace!("rebuild tables of rollups");
+    let txn = cli.transaction().await?;
+    for table in TABLES {
+        let sql = format!("DROP TABLE IF EXISTS {};", table);
+        txn.execute(&sql, &[]).await?;
+    }
+    fill_tables(&*txn).await?;
+    txn.commit().await
+}
+
+// Rolls up all stored blocks at once, as `insert_changes` block by block.
+async fn fill_tables(txn: &dyn Transaction) -> Result<()> {
+    let sql = "SELECT 1 FROM block_headers LIMIT 1;";
+    let filled = txn.query_opt(sql, &[]).await?.is_some();
+    if filled {
+        log::info!("roll up the stored blocks by day and by hour");
+    }
+    for (name, millis) in ROLLUPS {
+        let sql = format!(
+            r#"
+            CREATE TABLE IF NOT EXISTS {}_rollups (
+                period              BIGINT      NOT NULL PRIMARY KEY,
+                {}
+            );"#,
+            name,
+            COUNTERS
+                .iter()
+                .map(|column| format!("{:<20}BIGINT      NOT NULL", column))
+                .collect::<Vec<_>>()
+                .join(",\n                ")
+        );
+        txn.execute(&sql, &[]).await?;
+        // The blocks in the period which touch the lock, so the lock is active in the period
+        // until all of them are removed.
+        let sql = format!(
+            r#"
+            CREATE TABLE IF NOT EXISTS {}_rollup_locks (
+                period              BIGINT      NOT NULL,
+                lock_hash           BYTEA       NOT NULL,
+                blocks              BIGINT      NOT NULL,
+                PRIMARY KEY (period, lock_hash)
+            );"#,
+            name
+        );
+        txn.execute(&sql, &[]).await?;
+        if filled {
+            fill_rollups(txn, name, *millis).await?;
+        }
+    }
+    Ok(())
+}
+
+async fn fill_rollups(txn: &dyn Transaction, name: &str, millis: u64) -> Result<()> {
+    let sql = format!(
+        r#"
+        INSERT INTO {0}_rollup_locks (period, lock_hash, blocks)
+        SELECT period, lock_hash, COUNT(*)
+          FROM (
+               SELECT h.timestamp / {1} AS period, t.lock_hash
+                 FROM (
+                      SELECT created_block_number AS number, lock_hash
+                        FROM cells
+                       UNION
+                      SELECT consumed_block_number AS number, lock_hash
+                        FROM cells
+                       WHERE consumed_block_number IS NOT NULL
+                      ) t
+                 JOIN block_headers h ON h.number = t.number
+               ) l
+         GROUP BY period, lock_hash
+    ;"#,
+        name, millis
+    );
+    txn.execute(&sql, &[]).await?;
+    // The cells which are created and consumed in the same period are neither new cells nor
+    // consumed cells of the period.
+    let sql = format!(
+        r#"
+        INSERT INTO {0}_rollups (period, {2})
+        SELECT b.period, b.blocks, b.transactions, b.inputs, b.outputs,
+               b.outputs - COALESCE(s.cells, 0), b.inputs - COALESCE(s.cells, 0),
+               b.new_addresses, COALESCE(l.locks, 0), b.fees, b.capacity_moved
+          FROM (
+               SELECT period,
+                      COUNT(*) AS blocks,
+                      CAST(SUM(transactions) AS BIGINT) AS transactions,
+                      CAST(SUM(inputs) AS BIGINT) AS inputs,
+                      CAST(SUM(outputs) AS BIGINT) AS outputs,
+                      CAST(SUM(new_addresses) AS BIGINT) AS new_addresses,
+                      CAST(SUM(fees) AS BIGINT) AS fees,
+                      CAST(SUM(capacity_moved) AS BIGINT) AS capacity_moved
+                 FROM (
+                      SELECT h.timestamp / {1} AS period,
+                             COALESCE(bt.transactions, 0) AS transactions,
+                             COALESCE(i.cells, 0) AS inputs,
+                             COALESCE(o.cells, 0) AS outputs,
+                             COALESCE(n.locks, 0) AS new_addresses,
+                             COALESCE(f.fees, 0) AS fees,
+                             COALESCE(i.capacity, 0) AS capacity_moved
+                        FROM block_headers h
+                        LEFT JOIN (SELECT block_hash, COUNT(*) AS transactions
+                                     FROM block_transactions
+                                    GROUP BY block_hash) bt ON bt.block_hash = h.hash
+                        LEFT JOIN (SELECT consumed_block_number AS number, COUNT(*) AS cells,
+                                          SUM(capacity) AS capacity
+                                     FROM cells
+                                    WHERE consumed_block_number IS NOT NULL
+                                    GROUP BY consumed_block_number) i ON i.number = h.number
+                        LEFT JOIN (SELECT created_block_number AS number, COUNT(*) AS cells
+                                     FROM cells
+                                    GROUP BY created_block_number) o ON o.number = h.number
+                        LEFT JOIN (SELECT a.first_block_number AS number, COUNT(*) AS locks
+                                     FROM accounts a
+                                    WHERE EXISTS (
+                                          SELECT 1
+                                            FROM cells c
+                                           WHERE c.created_block_number = a.first_block_number
+                                             AND c.lock_hash = a.lock_hash
+                                          )
+                                    GROUP BY a.first_block_number) n ON n.number = h.number
+                        LEFT JOIN {3} f ON f.block_number = h.number
+                      ) p
+                GROUP BY period
+               ) b
+          LEFT JOIN (
+               SELECT period, COUNT(*) AS cells
+                 FROM (
+                      SELECT ch.timestamp / {1} AS period
+                        FROM cells c
+                        JOIN block_headers ch ON ch.number = c.created_block_number
+                        JOIN block_headers xh ON xh.number = c.consumed_block_number
+                       WHERE ch.timestamp / {1} = xh.timestamp / {1}
+                      ) c
+                GROUP BY period
+               ) s ON s.period = b.period
+          LEFT JOIN (
+               SELECT period, COUNT(*) AS locks
+                 FROM {0}_rollup_locks
+                GROUP BY period
+               ) l ON l.period = b.period
+    ;"#,
+        name,
+        millis,
+        COUNTERS.join(", "),
+        BLOCK_FEES
+    );
+    txn.execute(&sql, &[]).await?;
+    Ok(())
+}
+
+// Adds the block to the rollups of its periods, the block and the changes of the accounts
+// should be inserted already.
+pub(in crate::storage) async fn insert_changes(txn: &dyn Transaction, number: u64) -> Result<()> {
+    log::trace!("add block {} to the rollups", number);
+    let changes = query_changes(txn, number).await?;
+    for (name, millis) in ROLLUPS {
+        let period = changes.timestamp / *millis as i64;
+        let params: &[Value] = &[(number as i64).into(), period.into()];
+        let sql = format!(
+            r#"
+            SELECT COUNT(*)
+              FROM {} t
+             WHERE NOT EXISTS (
+                   SELECT 1
+                     FROM {}_rollup_locks l
+                    WHERE l.period = $2
+                      AND l.lock_hash = t.lock_hash
+                   )
+        ;"#,
+            TOUCHED_LOCKS, name
+        );
+        let active_addresses = txn.query_one(&sql, params).await?.try_get::<i64>(0)?;
+        let sql = format!(
+            r#"
+            INSERT INTO {1}_rollup_locks (period, lock_hash, blocks)
+            SELECT CAST($2 AS BIGINT), lock_hash, 1
+              FROM {0} t
+             WHERE 1 = 1
+            ON CONFLICT (period, lock_hash) DO UPDATE
+               SET blocks = {1}_rollup_locks.blocks + 1
+        ;"#,
+            TOUCHED_LOCKS, name
+        );
+        txn.execute(&sql, params).await?;
+        let same_period = query_same_period(txn, number, *millis, period).await?;
+        let sql = format!(
+            r#"
+            INSERT INTO {0}_rollups (period, {1})
+            VALUES ($1, 1, $2, $3, $4, $5, $6, $7, $8, $9, $10)
+            ON CONFLICT (period) DO UPDATE
+               SET {2}
+        ;"#,
+            name,
+            COUNTERS.join(", "),
+            COUNTERS
+                .iter()
+                .map(|column| format!("{1} = {0}_rollups.{1} + excluded.{1}", name, column))
+                .collect::<Vec<_>>()
+                .join(",\n                   ")
+        );
+        let params: &[Value] = &[
+            period.into(),
+            changes.transactions.into(),
+            changes.inputs.into(),
+            changes.outputs.into(),
+            (changes.outputs - same_period).into(),
+            (changes.inputs - same_period).into(),
+            changes.new_addresses.into(),
+            active_addresses.into(),
+            changes.fees.into(),
+            changes.capacity_moved.into(),
+        ];
+        txn.execute(&sql, params).await?;
+    }
+    Ok(())
+}
+
+// Removes the block from the rollups of its periods, the block should be the last one and
+// should not be removed yet, neither should the changes of the accounts. The period is removed
+// with its last block.
+pub(in crate::storage) async fn remove_changes(txn: &dyn Transaction, number: u64) -> Result<()> {
+    log::trace!("remove block {} from the rollups", number);
+    let changes = query_changes(txn, number).await?;
+    for (name, millis) in ROLLUPS {
+        let period = changes.timestamp / *millis as i64;
+        let sql = format!(
+            r#"
+            UPDATE {1}_rollup_locks
+               SET blocks = blocks - 1
+             WHERE period = $2
+               AND lock_hash IN {0}
+        ;"#,
+            TOUCHED_LOCKS, name
+        );
+        let params: &[Value] = &[(number as i64).into(), period.into()];
+        txn.execute(&sql, params).await?;
+        let sql = format!(
+            "DELETE FROM {}_rollup_locks WHERE period = $1 AND blocks = 0;",
+            name
+        );
+        let active_addresses = txn.execute(&sql, &[period.into()]).await? as i64;
+        let same_period = query_same_period(txn, number, *millis, period).await?;
+        let sql = format!(
+            r#"
+            UPDATE {}_rollups
+               SET {}
+             WHERE period = $1
+        ;"#,
+            name,
+            COUNTERS
+                .iter()
+                .enumerate()
+                .map(|(index, column)| format!("{0} = {0} - ${1}", column, index + 2))
+                .collect::<Vec<_>>()
+                .join(",\n                   ")
+        );
+        let params: &[Value] = &[
+            period.into(),
+            1.into(),
+            changes.transactions.into(),
+            changes.inputs.into(),
+            changes.outputs.into(),
+            (changes.outputs - same_period).into(),
+            (changes.inputs - same_period).into(),
+            changes.new_addresses.into(),
+            active_addresses.into(),
+            changes.fees.into(),
+            changes.capacity_moved.into(),
+        ];
+        txn.execute(&sql, params).await?;
+        let sql = format!(
+            "DELETE FROM {}_rollups WHERE period = $1 AND blocks = 0;",
+            name
+        );
+        txn.execute(&sql, &[period.into()]).await?;
+    }
+    Ok(())
+}
+
+// The inputs and the outputs are the cells which are consumed and created in the block, the
+// new addresses are the locks which are first seen in it, and the moved capacity is the
+// capacity of the consumed cells.
+async fn query_changes(txn: &dyn Transaction, number: u64) -> Result<Changes> {
+    let sql = r#"
+        SELECT h.timestamp,
+               (SELECT COUNT(*) FROM block_transactions bt WHERE bt.block_hash = h.hash),
+               (SELECT COUNT(*) FROM cells c WHERE c.consumed_block_number = h.number),
+               (SELECT COUNT(*) FROM cells c WHERE c.created_block_number = h.number),
+               (SELECT COUNT(*)
+                  FROM accounts a
+                 WHERE a.first_block_number = h.number
+                   AND a.lock_hash IN (
+                       SELECT c.lock_hash FROM cells c WHERE c.created_block_number = h.number
+                       )),
+               (SELECT CAST(COALESCE(SUM(c.capacity), 0) AS BIGINT)
+                  FROM cells c
+                 WHERE c.consumed_block_number = h.number)
+          FROM block_headers h
+         WHERE h.number = $1
+    ;"#;
+    let row = txn
+        .query_opt(sql, &[(number as i64).into()])
+        .await?
+        .ok_or_else(|| Error::Data(format!("no header of block {}", number)))?;
+    let fees = query_fees(txn, number, number)
+        .await?
+        .into_iter()
+        .map(|fee| fee.fee as i64)
+        .sum();
+    Ok(Changes {
+        timestamp: row.try_get::<i64>(0)?,
+        transactions: row.try_get::<i64>(1)?,
+        inputs: row.try_get::<i64>(2)?,
+        outputs: row.try_get::<i64>(3)?,
+        new_addresses: row.try_get::<i64>(4)?,
+        fees,
+        capacity_moved: row.try_get::<i64>(5)?,
+    })
+}
+
+// The cells which are consumed in the block and created in the same period, they are neither
+// new cells nor consumed cells of the period.
+async fn query_same_period(
+    txn: &dyn Transaction,
+    number: u64,
+    millis: u64,
+    period: i64,
+) -> Result<i64> {
+    let sql = r#"
+        SELECT COUNT(*)
+          FROM cells c
+          JOIN block_headers h ON h.number = c.created_block_number
+         WHERE c.consumed_block_number = $1
+           AND h.timestamp / $2 = $3
+    ;"#;
+    let params: &[Value] = &[
+        (number as i64).into(),
+        (millis as i64).into(),
+        period.into(),
+    ];
+    txn.query_one(sql, params).await?.try_get::<i64>(0)
+}
@@ -0,0 +1,198 @@
+// Copyright (C) 2019-2020 Boyu Yang
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+use super::{Storage, Value};
+use crate::{
+    error::{Error, Result},
+    Report,
+};
+
+pub(super) const TABLES: &[&str] = &["script_usage", "script_usage_epochs"];
+
+/// How the scripts are used, by their code hashes and hash types.
+///
+/// The usage is aggregated from the cells into tables when it is refreshed, the reports read
+/// the tables, so they show the usage as of the last refresh.
+pub trait ScriptUsage {
+    /// Aggregates the usage of scripts from the stored cells.
+    fn refresh_script_usage(&mut self) -> Result<()>;
+    /// The cells and their capacity, the count of distinct args, and the first and the last
+    /// blocks which create or consume the cells, of each script as a lock or as a type.
+    fn script_usage(&self) -> Result<Report>;
+    /// The cells of a script which are created and consumed in each epoch.
+    fn script_usage_by_epoch(&self, code_hash: &[u8], hash_type: u8) -> Result<Report>;
+}
+
+const USAGE_COLUMNS: &[&str] = &[
+    "code_hash",
+    "hash_type",
+    "script_type",
+    "cells",
+    "capacity",
+    "live_cells",
+    "live_capacity",
+    "distinct_args",
+    "first_block_number",
+    "last_block_number",
+];
+
+const EPOCH_COLUMNS: &[&str] = &[
+    "epoch_number",
+    "script_type",
+    "created_cells",
+    "created_capacity",
+    "consumed_cells",
+    "consumed_capacity",
+];
+
+impl ScriptUsage for Storage {
+    fn refresh_script_usage(&mut self) -> Result<()> {
+        log::trace!("refresh the usage of scripts");
+        let sqls = &[
+            r#"
+            CREATE TABLE IF NOT EXISTS script_usage (
+                code_hash           BYTEA       NOT NULL,
+                hash_type           SMALLINT    NOT NULL,
+                script_type         TEXT        NOT NULL,
+                cells               BIGINT      NOT NULL,
+                capacity            BIGINT      NOT NULL,
+                live_cells          BIGINT      NOT NULL,
+                live_capacity       BIGINT      NOT NULL,
+                distinct_args       BIGINT      NOT NULL,
+                first_block_number  BIGINT      NOT NULL,
+                last_block_number   BIGINT      NOT NULL,
+                PRIMARY KEY (code_hash, hash_type, script_type)
+            );"#,
+            r#"
+            CREATE TABLE IF NOT EXISTS script_usage_epochs (
+                code_hash           BYTEA       NOT NULL,
+                hash_type           SMALLINT    NOT NULL,
+                script_type         TEXT        NOT NULL,
+                epoch_number        INTEGER     NOT NULL,
+                created_cells       BIGINT      NOT NULL,
+                created_capacity    BIGINT      NOT NULL,
+                consumed_cells      BIGINT      NOT NULL,
+                consumed_capacity   BIGINT      NOT NULL,
+                PRIMARY KEY (code_hash, hash_type, script_type, epoch_number)
+            );"#,
+            "DELETE FROM script_usage;",
+            "DELETE FROM script_usage_epochs;",
+            r#"
+            INSERT INTO script_usage (
+                code_hash, hash_type, script_type, cells, capacity, live_cells, live_capacity,
+                distinct_args, first_block_number, last_block_number
+            )
+            SELECT s.code_hash, s.hash_type, c.script_type, COUNT(*),
+                   CAST(SUM(c.capacity) AS BIGINT),
+                   CAST(SUM(CASE WHEN c.consumed_tx_hash IS NULL THEN 1 ELSE 0 END) AS BIGINT),
+                   CAST(SUM(CASE WHEN c.consumed_tx_hash IS NULL THEN c.capacity ELSE 0 END)
+                        AS BIGINT),
+                   COUNT(DISTINCT s.args), MIN(c.created_block_number),
+                   MAX(COALESCE(c.consumed_block_number, c.created_block_number))
+              FROM (
+                   SELECT 'lock' AS script_type, lock_hash AS script_hash, capacity,
+                          consumed_tx_hash, created_block_number, consumed_block_number
+                     FROM cells
+                    UNION ALL
+                   SELECT 'type', type_hash, capacity,
+                          consumed_tx_hash, created_block_number, consumed_block_number
+                     FROM cells
+                    WHERE type_hash IS NOT NULL
+                   ) c
+              JOIN scripts s ON s.hash = c.script_hash
+             GROUP BY s.code_hash, s.hash_type, c.script_type
+            ;"#,
+            r#"
+            INSERT INTO script_usage_epochs (
+                code_hash, hash_type, script_type, epoch_number, created_cells,
+                created_capacity, consumed_cells, consumed_capacity
+            )
+            SELECT s.code_hash, s.hash_type, e.script_type, h.epoch_number,
+                   CAST(SUM(e.created) AS BIGINT),
+                   CAST(SUM(e.created * e.capacity) AS BIGINT),
+                   CAST(SUM(1 - e.created) AS BIGINT),
+                   CAST(SUM((1 - e.created) * e.capacity) AS BIGINT)
+              FROM (
+                   SELECT 'lock' AS script_type, lock_hash AS script_hash, capacity,
+                          created_block_number AS block_number, 1 AS created
+                     FROM cells
+                    UNION ALL
+                   SELECT 'lock', lock_hash, capacity, consumed_block_number, 0
+                     FROM cells
+                    WHERE consumed_block_number IS NOT NULL
+                    UNION ALL
+                   SELECT 'type', type_hash, capacity, created_block_number, 1
+                     FROM cells
+                    WHERE type_hash IS NOT NULL
+                    UNION ALL
+                   SELECT 'type', type_hash, capacity, consumed_block_number, 0
+                     FROM cells
+                    WHERE type_hash IS NOT NULL AND consumed_block_number IS NOT NULL
+                   ) e
+              JOIN scripts s ON s.hash = e.script_hash
+              JOIN block_headers h ON h.number = e.block_number
+             GROUP BY s.code_hash, s.hash_type, e.script_type, h.epoch_number
+            ;"#,
+        ];
+        let rt = self.runtime();
+        rt.block_on(async {
+            let txn = self.mut_backend().transaction().await?;
+            for sql in sqls {
+                txn.execute(sql, &[]).await?;
+            }
+            txn.commit().await
+        })
+    }
+
+    fn script_usage(&self) -> Result<Report> {
+        log::trace!("report the usage of scripts");
+        self.check_script_usage()?;
+        let sql = format!(
+            r#"
+            SELECT {}
+              FROM script_usage
+             ORDER BY cells DESC, code_hash, hash_type, script_type
+        ;"#,
+            USAGE_COLUMNS.join(", ")
+        );
+        self.query(&sql, &[])
+            .map(|rows| Report::with_rows(USAGE_COLUMNS, rows))
+    }
+
+    fn script_usage_by_epoch(&self, code_hash: &[u8], hash_type: u8) -> Result<Report> {
+        log::trace!("report the usage of a script by epoch");
+        self.check_script_usage()?;
+        let sql = format!(
+            r#"
+            SELECT {}
+              FROM script_usage_epochs
+             WHERE 1 = 1
+               AND code_hash = $1
+               AND hash_type = $2
+             ORDER BY epoch_number, script_type
+        ;"#,
+            EPOCH_COLUMNS.join(", ")
+        );
+        let params: &[Value] = &[code_hash.into(), i16::from(hash_type).into()];
+        self.query(&sql, params)
+            .map(|rows| Report::with_rows(EPOCH_COLUMNS, rows))
+    }
+}
+
+impl Storage {
+    fn check_script_usage(&self) -> Result<()> {
+        let cli = self.backend();
+        if self.block_on(cli.table_exists("script_usage"))? {
+            Ok(())
+        } else {
+            Err(Error::Data(
+                "the usage of scripts is not aggregated, please refresh it first".to_owned(),
+            ))
+        }
+    }
+}
@@ -0,0 +1,647 @@
+// Copyright (C) 2019-2020 Boyu Yang
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+use std::collections::{BTreeMap, HashMap};
+
+use super::{Storage, Value};
+use crate::{
+    error::Result,
+    utilities::{compact_to_difficulty, utc_date, DAY_MILLIS},
+    Report,
+};
+
+/// The capacity which is not counted as held by holders.
+#[derive(Debug, Clone, Default)]
+pub struct Excluded {
+    /// The locks which are not holders, such as the locks of exchanges.
+    pub lock_hashes: Vec<Vec<u8>>,
+    /// The scripts by code hash and hash type, the cells whose lock or type script is one of
+    /// them are not counted, such as the deposits of NervosDAO.
+    pub scripts: Vec<(Vec<u8>, u8)>,
+}
+
+/// The statistics reports on the stored chain.
+pub trait Stats {
+    /// The overview of the stored chain.
+    fn summary(&self) -> Result<Report>;
+    /// The live cells and their capacity as of the blocks at the heights.
+    fn supply(&self, numbers: &[u64]) -> Result<Report>;
+    /// The locks which hold the most capacity as of the block at the height `at`, or in the
+    /// current state if it is not set, with their ranks and their shares of the capacity of all
+    /// holders.
+    fn richlist(&self, at: Option<u64>, limit: u32, excluded: &Excluded) -> Result<Report>;
+    /// How the capacity is distributed among holders: the Gini coefficient, the shares of the
+    /// top holders, and the count of holders in each tenth of the capacity, from the richest.
+    fn distribution(&self, at: Option<u64>, excluded: &Excluded) -> Result<Report>;
+    /// The difficulty of each of the latest epochs, the adjustment from the previous epoch, and
+    /// the hashrate which is estimated from the work of the blocks and the uncles and the time
+    /// since the end of the previous epoch, in hashes per second.
+    fn hashrate(&self, epochs: u32) -> Result<Report>;
+    /// The distribution of the intervals between blocks in each of the latest epochs, in
+    /// milliseconds.
+    fn block_intervals_by_epoch(&self, epochs: u32) -> Result<Report>;
+    /// The distribution of the intervals between blocks in each of the latest days in UTC, in
+    /// milliseconds.
+    fn block_intervals_by_day(&self, days: u32) -> Result<Report>;
+    /// The latest blocks whose timestamps are before their parents, or after the time `now`
+    /// with the tolerance of the consensus, in milliseconds since the Unix epoch.
+    fn timestamp_anomalies(&self, now: u64, limit: u32) -> Result<Report>;
+    /// The duration of each of the latest epochs compared to the target, and the change of the
+    /// epoch length from the previous epoch, the abrupt changes are flagged.
+    fn epoch_durations(&self, epochs: u32) -> Result<Report>;
+}
+
+const TOP_HOLDERS: &[i64] = &[10, 100, 1000];
+
+// The percentiles of the intervals between blocks.
+const INTERVAL_PERCENTILES: &[usize] = &[50, 90, 99];
+
+// A block may be later than the current time by 15 seconds at most.
+const ALLOWED_FUTURE_BLOCKTIME: u64 = 15 * 1_000;
+
+// An epoch lasts 4 hours.
+const TARGET_EPOCH_DURATION: i64 = 4 * 60 * 60 * 1_000;
+
+// The epoch length changes abruptly if it is more than twice or less than half of the previous.
+const ABRUPT_LENGTH_CHANGE: f64 = 2.0;
+
+const EPOCH_DURATION_COLUMNS: &[&str] = &[
+    "epoch_number",
+    "start_number",
+    "blocks",
+    "epoch_length",
+    "length_change",
+    "abrupt_length_change",
+    "duration",
+    "target_duration",
+    "duration_ratio",
+];
+
+const HASHRATE_COLUMNS: &[&str] = &[
+    "epoch_number",
+    "start_number",
+    "blocks",
+    "epoch_length",
+    "uncles",
+    "compact_target",
+    "difficulty",
+    "difficulty_adjustment",
+    "duration",
+    "hashrate",
+];
+
+// The blocks of an epoch.
+struct Epoch {
+    number: i64,
+    start_number: i64,
+    blocks: i64,
+    length: i64,
+    compact_target: u32,
+    // The timestamp of the last block before the epoch, or of the first block in the epoch if
+    // the epoch is the first one.
+    start_timestamp: i64,
+    end_timestamp: i64,
+    // The count of blocks which are mined since the start timestamp.
+    mined: i64,
+}
+
+// The statement of the live cells and the balance of each lock which holds capacity, as of the
+// height `at` or in the current state, the parameters are appended.
+fn holders(at: Option<u64>, excluded: &Excluded, params: &mut Vec<Value>) -> String {
+    // The current balances are maintained in the accounts, the past ones and the ones without
+    // the cells of the excluded scripts are aggregated from the cells.
+    let grouped = at.is_some() || !excluded.scripts.is_empty();
+    let mut sql = if grouped {
+        let live = if let Some(at) = at {
+            params.push((at as i64).into());
+            format!(
+                "created_block_number <= ${0}
+               AND (consumed_block_number IS NULL OR consumed_block_number > ${0})",
+                params.len()
+            )
+        } else {
+            "consumed_tx_hash IS NULL".to_owned()
+        };
+        format!(
+            r#"
+            SELECT lock_hash, COUNT(*) AS live_cells, CAST(SUM(capacity) AS BIGINT) AS balance
+              FROM cells
+             WHERE 1 = 1
+               AND {}"#,
+            live
+        )
+    } else {
+        r#"
+            SELECT lock_hash, live_cells, balance
+              FROM accounts
+             WHERE live_cells > 0"#
+            .to_owned()
+    };
+    if !excluded.lock_hashes.is_empty() {
+        let placeholders = excluded
+            .lock_hashes
+            .iter()
+            .map(|lock_hash| {
+                params.push(lock_hash.as_slice().into());
+                format!("${}", params.len())
+            })
+            .collect::<Vec<_>>()
+            .join(", ");
+        sql.push_str(&format!(
+            "\n               AND lock_hash NOT IN ({})",
+            placeholders
+        ));
+    }
+    if !excluded.scripts.is_empty() {
+        let conditions = excluded
+            .scripts
+            .iter()
+            .map(|(code_hash, hash_type)| {
+                params.push(code_hash.as_slice().into());
+                params.push(i16::from(*hash_type).into());
+                format!(
+                    "(code_hash = ${} AND hash_type = ${})",
+                    params.len() - 1,
+                    params.len()
+                )
+            })
+            .collect::<Vec<_>>()
+            .join(" OR ");
+        sql.push_str(&format!(
+            r#"
+               AND lock_hash NOT IN (SELECT hash FROM scripts WHERE {0})
+               AND (type_hash IS NULL OR type_hash NOT IN (SELECT hash FROM scripts WHERE {0}))"#,
+            conditions
+        ));
+    }
+    if grouped {
+        sql.push_str("\n             GROUP BY lock_hash");
+    }
+    sql
+}
+
+// Loads the blocks of the latest epochs, and the epoch of the last block before them, which
+// only provides the start, returns the number of the first block of the latest epochs too.
+fn load_epochs(storage: &Storage, epochs: u32) -> Result<Option<(i64, Vec<Epoch>)>> {
+    let sql = r#"
+        SELECT MIN(number)
+          FROM block_headers
+         WHERE epoch_number > (SELECT MAX(epoch_number) FROM block_headers) - $1
+    ;"#;
+    let params: &[Value] = &[i64::from(epochs).into()];
+    let start_number = if let Some(number) = storage
+        .query(sql, params)?
+        .first()
+        .map(|row| row.try_get::<Option<i64>>(0))
+        .transpose()?
+        .flatten()
+    {
+        number
+    } else {
+        return Ok(None);
+    };
+    // The headers of the epochs, and the last header before them.
+    let sql = r#"
+        SELECT number, epoch_number, epoch_length, compact_target, timestamp
+          FROM block_headers
+         WHERE number >= $1
+         ORDER BY number
+    ;"#;
+    let mut previous: Option<Epoch> = None;
+    let mut list = Vec::new();
+    for row in storage.query(sql, &[(start_number - 1).into()])? {
+        let number = row.try_get::<i64>(0)?;
+        let epoch_number = row.try_get::<i64>(1)?;
+        let timestamp = row.try_get::<i64>(4)?;
+        if let Some(epoch) = previous
+            .as_mut()
+            .filter(|epoch| epoch.number == epoch_number)
+        {
+            epoch.blocks += 1;
+            epoch.mined += 1;
+            epoch.end_timestamp = timestamp;
+            continue;
+        }
+        let compact_target = row.try_get::<i64>(3)? as u32;
+        let (start_timestamp, mined) = if let Some(epoch) = previous.take() {
+            let end_timestamp = epoch.end_timestamp;
+            list.push(epoch);
+            (end_timestamp, 1)
+        } else {
+            (timestamp, 0)
+        };
+        previous = Some(Epoch {
+            number: epoch_number,
+            start_number: number,
+            blocks: 1,
+            length: row.try_get::<i64>(2)?,
+            compact_target,
+            start_timestamp,
+            end_timestamp: timestamp,
+            mined,
+        });
+    }
+    list.extend(previous);
+    Ok(Some((start_number, list)))
+}
+
+// The intervals between the blocks since the first block which matches the filter, grouped by
+// the periods of the blocks, only the latest periods are kept.
+fn block_intervals(
+    storage: &Storage,
+    filter: &str,
+    params: &[Value],
+    periods: u32,
+    period: impl Fn(i64, i64) -> i64,
+) -> Result<BTreeMap<i64, Vec<i64>>> {
+    let sql = format!(
+        r#"
+        SELECT number, epoch_number, timestamp
+          FROM block_headers
+         WHERE number >= (SELECT MIN(number) FROM block_headers WHERE {}) - 1
+         ORDER BY number
+    ;"#,
+        filter
+    );
+    let mut intervals = BTreeMap::<i64, Vec<i64>>::new();
+    let mut parent_timestamp = None;
+    for row in storage.query(&sql, params)? {
+        let epoch_number = row.try_get::<i64>(1)?;
+        let timestamp = row.try_get::<i64>(2)?;
+        if let Some(parent_timestamp) = parent_timestamp {
+            intervals
+                .entry(period(epoch_number, timestamp))
+                .or_default()
+                .push(timestamp - parent_timestamp);
+        }
+        parent_timestamp = Some(timestamp);
+    }
+    // The blocks whose timestamps are out of order may be in the periods before.
+    if let Some(last) = intervals.keys().last().copied() {
+        intervals = intervals.split_off(&(last - i64::from(periods) + 1));
+    }
+    Ok(intervals)
+}
+
+fn intervals_report(
+    period_column: &str,
+    intervals: BTreeMap<i64, Vec<i64>>,
+    period: impl Fn(i64) -> Value,
+) -> Report {
+    let percentile_columns = INTERVAL_PERCENTILES
+        .iter()
+        .map(|percentile| format!("p{}_interval", percentile))
+        .collect::<Vec<_>>();
+    let columns = [period_column, "intervals", "min_interval", "avg_interval"]
+        .iter()
+        .copied()
+        .chain(percentile_columns.iter().map(String::as_str))
+        .chain(Some("max_interval"))
+        .collect::<Vec<_>>();
+    let mut report = Report::new(&columns);
+    for (key, mut intervals) in intervals {
+        intervals.sort_unstable();
+        let count = intervals.len();
+        let sum = intervals
+            .iter()
+            .map(|interval| *interval as f64)
+            .sum::<f64>();
+        let mut row = vec![
+            period(key),
+            (count as i64).into(),
+            intervals[0].into(),
+            (sum / count as f64).into(),
+        ];
+        // The nearest-rank percentiles.
+        for percentile in INTERVAL_PERCENTILES {
+            // `usize::div_ceil` requires Rust 1.73.
+            #[allow(clippy::manual_div_ceil)]
+            let rank = (percentile * count + 99) / 100;
+            row.push(intervals[rank.max(1) - 1].into());
+        }
+        row.push(intervals[count - 1].into());
+        report.push(row);
+    }
+    report
+}
+
+impl Stats for Storage {
+    fn summary(&self) -> Result<Report> {
+        log::trace!("report the summary");
+        let sql = r#"
+            SELECT (SELECT MAX(number) FROM block_headers)
+                 , (SELECT hash FROM block_headers ORDER BY number DESC LIMIT 1)
+                 , (SELECT COUNT(*) FROM block_headers)
+                 , (SELECT COUNT(*) FROM uncle_headers)
+                 , (SELECT COUNT(*) FROM transactions)
+                 , (SELECT COUNT(*) FROM cells)
+                 , (SELECT COUNT(*) FROM cells WHERE consumed_tx_hash IS NULL)
+                 , (SELECT CAST(SUM(capacity) AS BIGINT)
+                      FROM cells
+                     WHERE consumed_tx_hash IS NULL)
+        ;"#;
+        let columns = &[
+            "tip_number",
+            "tip_hash",
+            "blocks",
+            "uncles",
+            "transactions",
+            "cells",
+            "live_cells",
+            "live_capacity",
+        ];
+        self.query(sql, &[])
+            .map(|rows| Report::with_rows(columns, rows))
+    }
+
+    fn supply(&self, numbers: &[u64]) -> Result<Report> {
+        log::trace!("report the supply at {} heights", numbers.len());
+        let sql = r#"
+            SELECT COUNT(*), CAST(COALESCE(SUM(capacity), 0) AS BIGINT)
+              FROM cells
+             WHERE 1 = 1
+               AND created_block_number <= $1
+               AND (consumed_block_number IS NULL OR consumed_block_number > $1)
+        ;"#;
+        let cli = self.backend();
+        let mut report = Report::new(&["block_number", "live_cells", "live_capacity"]);
+        for number in numbers {
+            let number = *number as i64;
+            let row = self.block_on(cli.query_one(sql, &[number.into()]))?;
+            let mut values = vec![number.into()];
+            values.extend(row.into_values());
+            report.push(values);
+        }
+        Ok(report)
+    }
+
+    fn richlist(&self, at: Option<u64>, limit: u32, excluded: &Excluded) -> Result<Report> {
+        log::trace!("report the rich list at {:?}", at);
+        let mut params: Vec<Value> = vec![i64::from(limit).into()];
+        let sql = format!(
+            r#"
+            SELECT r.rank, r.lock_hash, s.code_hash, s.hash_type, s.args, r.live_cells,
+                   r.balance, r.share
+              FROM (
+                   SELECT lock_hash, live_cells, balance,
+                          ROW_NUMBER() OVER (ORDER BY balance DESC, lock_hash) AS rank,
+                          CAST(balance AS DOUBLE PRECISION)
+                              / CAST(SUM(balance) OVER () AS DOUBLE PRECISION) AS share
+                     FROM ({}
+                          ) b
+                   ) r
+              JOIN scripts s ON s.hash = r.lock_hash
+             WHERE r.rank <= $1
+             ORDER BY r.rank
+        ;"#,
+            holders(at, excluded, &mut params)
+        );
+        let columns = &[
+            "rank",
+            "lock_hash",
+            "code_hash",
+            "hash_type",
+            "args",
+            "live_cells",
+            "balance",
+            "share",
+        ];
+        self.query(&sql, &params)
+            .map(|rows| Report::with_rows(columns, rows))
+    }
+
+    fn distribution(&self, at: Option<u64>, excluded: &Excluded) -> Result<Report> {
+        log::trace!("report the distribution at {:?}", at);
+        let mut params = Vec::new();
+        let top_columns = TOP_HOLDERS
+            .iter()
+            .map(|top| {
+                format!(
+                    "CAST(SUM(CASE WHEN r.rank <= {} THEN r.balance ELSE 0 END) AS BIGINT)",
+                    top
+                )
+            })
+            .collect::<Vec<_>>()
+            .join(", ");
+        // A holder is in the k-th tenth if the capacity of the richer holders is less than k
+        // tenths of the total capacity, the counts are cumulative.
+        let tenth_columns = (1..=10)
+            .map(|k| {
+                format!(
+                    "SUM(CASE WHEN r.richer * 10 < {} * t.total THEN 1 ELSE 0 END)",
+                    k
+                )
+            })
+            .collect::<Vec<_>>()
+            .join(", ");
+        let sql = format!(
+            r#"
+            SELECT COUNT(*), CAST(SUM(r.balance) AS BIGINT),
+                   SUM(CAST(r.rank AS DOUBLE PRECISION) * r.balance),
+                   {tops}, {tenths}
+              FROM (
+                   SELECT balance,
+                          ROW_NUMBER() OVER (ORDER BY balance DESC, lock_hash) AS rank,
+                          CAST(COALESCE(SUM(balance) OVER (
+                              ORDER BY balance DESC, lock_hash
+                              ROWS BETWEEN UNBOUNDED PRECEDING AND 1 PRECEDING
+                          ), 0) AS DOUBLE PRECISION) AS richer
+                     FROM ({holders}
+                          ) b
+                   ) r
+             CROSS JOIN (
+                   SELECT CAST(SUM(balance) AS DOUBLE PRECISION) AS total
+                     FROM ({holders}
+                          ) b
+                   ) t
+        ;"#,
+            tops = top_columns,
+            tenths = tenth_columns,
+            holders = holders(at, excluded, &mut params)
+        );
+        let row = self.block_on(self.backend().query_one(&sql, &params))?;
+        let count = row.try_get::<i64>(0)?;
+        let capacity = row.try_get::<Option<i64>>(1)?.unwrap_or(0);
+        let mut report = Report::new(&["metric", "value"]);
+        report.push(vec!["holders".into(), count.into()]);
+        report.push(vec!["capacity".into(), capacity.into()]);
+        // The Gini coefficient of balances ranked from the richest:
+        // G = (n + 1 - 2 * sum(rank * balance) / capacity) / n
+        let gini = if count > 0 && capacity > 0 {
+            let weighted = row.try_get::<f64>(2)?;
+            let n = count as f64;
+            (n + 1.0 - 2.0 * weighted / capacity as f64) / n
+        } else {
+            0.0
+        };
+        report.push(vec!["gini".into(), gini.into()]);
+        for (i, top) in TOP_HOLDERS.iter().enumerate() {
+            let held = row.try_get::<Option<i64>>(3 + i)?.unwrap_or(0);
+            let share = if capacity > 0 {
+                held as f64 / capacity as f64
+            } else {
+                0.0
+            };
+            report.push(vec![format!("top_{}_share", top).into(), share.into()]);
+        }
+        let mut counted = 0;
+        for k in 1..=10 {
+            let count = row
+                .try_get::<Option<i64>>(3 + TOP_HOLDERS.len() + k - 1)?
+                .unwrap_or(0);
+            let name = format!("tenth_{}_holders", k);
+            report.push(vec![name.into(), (count - counted).into()]);
+            counted = count;
+        }
+        Ok(report)
+    }
+
+    fn hashrate(&self, epochs: u32) -> Result<Report> {
+        log::trace!("report the hashrate of {} epochs", epochs);
+        let (start_number, list) = if let Some(loaded) = load_epochs(self, epochs)? {
+            loaded
+        } else {
+            return Ok(Report::new(HASHRATE_COLUMNS));
+        };
+        let sql = r#"
+            SELECT epoch_number, COUNT(*)
+              FROM uncle_headers
+             WHERE epoch_number >= $1
+             GROUP BY epoch_number
+        ;"#;
+        let first_epoch = list.first().map(|epoch| epoch.number).unwrap_or(0);
+        let uncles = self
+            .query(sql, &[first_epoch.into()])?
+            .into_iter()
+            .map(|row| Ok((row.try_get::<i64>(0)?, row.try_get::<i64>(1)?)))
+            .collect::<Result<HashMap<_, _>>>()?;
+        let mut report = Report::new(HASHRATE_COLUMNS);
+        let mut previous_difficulty = None;
+        for epoch in list {
+            let difficulty = compact_to_difficulty(epoch.compact_target);
+            let adjustment = match (previous_difficulty, difficulty) {
+                (Some(previous), Some(difficulty)) => Some(difficulty / previous),
+                _ => None,
+            };
+            previous_difficulty = difficulty;
+            // The epoch of the last header before the epochs only provides the start.
+            if epoch.start_number < start_number {
+                continue;
+            }
+            let uncles = uncles.get(&epoch.number).copied().unwrap_or(0);
+            let duration = epoch.end_timestamp - epoch.start_timestamp;
+            let hashrate = match difficulty {
+                Some(difficulty) if duration > 0 => {
+                    let work = difficulty * (epoch.mined + uncles) as f64;
+                    Some(work * 1_000.0 / duration as f64)
+                }
+                _ => None,
+            };
+            report.push(vec![
+                epoch.number.into(),
+                epoch.start_number.into(),
+                epoch.blocks.into(),
+                epoch.length.into(),
+                uncles.into(),
+                i64::from(epoch.compact_target).into(),
+                difficulty.into(),
+                adjustment.into(),
+                duration.into(),
+                hashrate.into(),
+            ]);
+        }
+        Ok(report)
+    }
+
+    fn block_intervals_by_epoch(&self, epochs: u32) -> Result<Report> {
+        log::trace!("report the block intervals of {} epochs", epochs);
+        let filter = "epoch_number > (SELECT MAX(epoch_number) FROM block_headers) - $1";
+        let params: &[Value] = &[i64::from(epochs).into()];
+        let intervals = block_intervals(self, filter, params, epochs, |epoch, _| epoch)?;
+        Ok(intervals_report("epoch_number", intervals, Value::from))
+    }
+
+    fn block_intervals_by_day(&self, days: u32) -> Result<Report> {
+        log::trace!("report the block intervals of {} days", days);
+        let filter = "timestamp / $2 > (SELECT MAX(timestamp) FROM block_headers) / $2 - $1";
+        let params: &[Value] = &[i64::from(days).into(), (DAY_MILLIS as i64).into()];
+        let intervals = block_intervals(self, filter, params, days, |_, timestamp| {
+            timestamp / DAY_MILLIS as i64
+        })?;
+        Ok(intervals_report("day", intervals, |day| {
+            Value::Text(utc_date(day as u64))
+        }))
+    }
+
+    fn timestamp_anomalies(&self, now: u64, limit: u32) -> Result<Report> {
+        log::trace!("report the anomalies of timestamps");
+        let sql = r#"
+            SELECT h.number, h.hash, h.timestamp, p.timestamp, h.timestamp - p.timestamp,
+                   CASE WHEN h.timestamp > $1 THEN 'future' ELSE 'out_of_order' END
+              FROM block_headers h
+              LEFT JOIN block_headers p ON p.number = h.number - 1
+             WHERE h.timestamp > $1
+                OR h.timestamp < p.timestamp
+             ORDER BY h.number DESC
+             LIMIT $2
+        ;"#;
+        let latest = (now + ALLOWED_FUTURE_BLOCKTIME) as i64;
+        let rows = self.query(sql, &[latest.into(), i64::from(limit).into()])?;
+        let columns = &[
+            "block_number",
+            "block_hash",
+            "timestamp",
+            "parent_timestamp",
+            "interval",
+            "anomaly",
+        ];
+        Ok(Report::with_rows(columns, rows))
+    }
+
+    fn epoch_durations(&self, epochs: u32) -> Result<Report> {
+        log::trace!("report the durations of {} epochs", epochs);
+        let mut report = Report::new(EPOCH_DURATION_COLUMNS);
+        let (start_number, list) = if let Some(loaded) = load_epochs(self, epochs)? {
+            loaded
+        } else {
+            return Ok(report);
+        };
+        let mut previous_length = None;
+        for epoch in list {
+            let change = previous_length
+                .filter(|length| *length > 0)
+                .map(|length| epoch.length as f64 / length as f64);
+            previous_length = Some(epoch.length);
+            if epoch.start_number < start_number {
+                continue;
+            }
+            let abrupt = change
+                .map(|change| change > ABRUPT_LENGTH_CHANGE || change * ABRUPT_LENGTH_CHANGE < 1.0)
+                .unwrap_or(false);
+            let duration = epoch.end_timestamp - epoch.start_timestamp;
+            // The duration of an epoch is compared when all its blocks are stored.
+            let ratio = if epoch.blocks >= epoch.length {
+                Some(duration as f64 / TARGET_EPOCH_DURATION as f64)
+            } else {
+                None
+            };
+            report.push(vec![
+                epoch.number.into(),
+                epoch.start_number.into(),
+                epoch.blocks.into(),
+                epoch.length.into(),
+                change.into(),
+                i64::from(abrupt).into(),
+                duration.into(),
+                TARGET_EPOCH_DURATION.into(),
+                ratio.into(),
+            ]);
+        }
+        Ok(report)
+    }
+}
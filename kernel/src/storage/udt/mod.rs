@@ -0,0 +1,168 @@
+// Copyright (C) 2019-2020 Boyu Yang
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+use uckb_jsonrpc_core::types::packed;
+
+use super::{BaseData as _, Storage, Value};
+use crate::{error::Result, Registry, Report};
+
+pub(super) mod operations;
+
+use self::operations as ops;
+
+const SUDT: &str = "sudt";
+
+/// The sUDT tokens, identified by the hashes of their type scripts, which are maintained when
+/// blocks are inserted and removed.
+///
+/// The amounts are printed as decimal texts in reports, since they are 128-bit integers.
+pub trait Udt {
+    /// The tokens with their supply, the count of holders, and the first and the last blocks
+    /// which mint, burn or transfer them.
+    fn udt_tokens(&self) -> Result<Report>;
+    /// The holders of a token which hold the most amount.
+    fn udt_holders(&self, type_hash: &[u8], limit: u32) -> Result<Report>;
+    /// The transactions which mint, burn or transfer a token, from the latest.
+    fn udt_transactions(&self, type_hash: &[u8], limit: u32) -> Result<Report>;
+}
+
+const TOKEN_COLUMNS: &[&str] = &[
+    "type_hash",
+    "owner_lock_hash",
+    "supply",
+    "holders",
+    "first_block_number",
+    "last_block_number",
+];
+
+const HOLDER_COLUMNS: &[&str] = &[
+    "lock_hash",
+    "code_hash",
+    "hash_type",
+    "args",
+    "balance",
+    "live_cells",
+];
+
+const TRANSACTION_COLUMNS: &[&str] = &[
+    "block_number",
+    "tx_index",
+    "tx_hash",
+    "kind",
+    "input_amount",
+    "output_amount",
+];
+
+impl Udt for Storage {
+    fn udt_tokens(&self) -> Result<Report> {
+        log::trace!("report the tokens");
+        let sql = r#"
+            SELECT t.type_hash, s.args, t.supply, t.holders,
+                   t.first_block_number, t.last_block_number
+              FROM udt_tokens t
+              JOIN scripts s ON s.hash = t.type_hash
+             ORDER BY t.holders DESC, t.type_hash
+        ;"#;
+        let rows = self.query(sql, &[])?;
+        with_amounts(Report::with_rows(TOKEN_COLUMNS, rows), &["supply"])
+    }
+
+    fn udt_holders(&self, type_hash: &[u8], limit: u32) -> Result<Report> {
+        log::trace!("report the holders of a token");
+        let sql = r#"
+            SELECT b.lock_hash, s.code_hash, s.hash_type, s.args, b.balance, b.live_cells
+              FROM udt_balances b
+              JOIN scripts s ON s.hash = b.lock_hash
+             WHERE b.type_hash = $1
+             ORDER BY b.balance DESC, b.lock_hash
+             LIMIT $2
+        ;"#;
+        let params: &[Value] = &[type_hash.into(), i64::from(limit).into()];
+        let rows = self.query(sql, params)?;
+        with_amounts(Report::with_rows(HOLDER_COLUMNS, rows), &["balance"])
+    }
+
+    fn udt_transactions(&self, type_hash: &[u8], limit: u32) -> Result<Report> {
+        log::trace!("report the transactions of a token");
+        let sql = r#"
+            SELECT block_number, tx_index, tx_hash, kind, input_amount, output_amount
+              FROM udt_transactions
+             WHERE type_hash = $1
+             ORDER BY block_number DESC, tx_index DESC
+             LIMIT $2
+        ;"#;
+        let params: &[Value] = &[type_hash.into(), i64::from(limit).into()];
+        let rows = self.query(sql, params)?;
+        let report = Report::with_rows(TRANSACTION_COLUMNS, rows);
+        with_amounts(report, &["input_amount", "output_amount"])
+    }
+}
+
+impl Storage {
+    // The code hashes and the hash types of sUDT.
+    //
+    // If the known scripts are not set, they are the well-known scripts of the network which
+    // is detected from the genesis block, the hash of it is queried if it is not provided.
+    // The chains which are not known have no well-known scripts.
+    pub(super) fn udt_scripts(
+        &mut self,
+        genesis_hash: Option<packed::Byte32>,
+    ) -> Result<Vec<(Vec<u8>, u8)>> {
+        if self.registry.is_none() {
+            let genesis_hash = if genesis_hash.is_some() {
+                genesis_hash
+            } else {
+                self.block_hash(0)?
+            };
+            if let Some(genesis_hash) = genesis_hash {
+                let registry = Registry::detect_network(&genesis_hash.raw_data())
+                    .map(Registry::builtin)
+                    .unwrap_or_default();
+                self.registry = Some(registry);
+            }
+        }
+        let scripts = self
+            .registry
+            .as_ref()
+            .map(|registry| {
+                registry
+                    .code_hashes(SUDT)
+                    .into_iter()
+                    .map(|(code_hash, hash_type)| (code_hash.to_vec(), hash_type))
+                    .collect()
+            })
+            .unwrap_or_default();
+        Ok(scripts)
+    }
+}
+
+// Replaces the amounts in the columns with decimal texts.
+fn with_amounts(report: Report, columns: &[&str]) -> Result<Report> {
+    let positions = report
+        .columns()
+        .iter()
+        .enumerate()
+        .filter(|(_, column)| columns.contains(&column.as_str()))
+        .map(|(position, _)| position)
+        .collect::<Vec<_>>();
+    let names = report
+        .columns()
+        .iter()
+        .map(String::as_str)
+        .collect::<Vec<_>>();
+    let mut with_amounts = Report::new(&names);
+    for row in report.rows() {
+        let mut row = row.clone();
+        for position in &positions {
+            let amount = ops::amount_from_value(&row[*position])?;
+            row[*position] = Value::Text(amount.to_string());
+        }
+        with_amounts.push(row);
+    }
+    Ok(with_amounts)
+}
@@ -0,0 +1,582 @@
+// Copyright (C) 2019-2020 Boyu Yang
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+use std::collections::{BTreeMap, BTreeSet};
+
+use uckb_jsonrpc_core::types::core;
+
+use crate::{
+    error::{Error, Result},
+    storage::{
+        backend::{Backend, Transaction},
+        Row, Value,
+    },
+};
+
+pub(in crate::storage) const TABLES: &[&str] = &[
+    "udt_cells",
+    "udt_balances",
+    "udt_tokens",
+    "udt_transactions",
+    "udt_scripts",
+];
+
+pub(in crate::storage) const INDEXES: &[&str] = &[
+    r#"CREATE INDEX IF NOT EXISTS udt_cells_created_block_number_idx
+           ON udt_cells (created_block_number);"#,
+    r#"CREATE INDEX IF NOT EXISTS udt_balances_balance_idx ON udt_balances (type_hash, balance);"#,
+    r#"CREATE INDEX IF NOT EXISTS udt_transactions_block_number_idx
+           ON udt_transactions (block_number);"#,
+];
+
+// The hash of the type script of a token and the hash of the lock script of a holder.
+type Holder = (Vec<u8>, Vec<u8>);
+
+// The amounts of tokens flow in and out, in a transaction or in a block.
+#[derive(Default)]
+struct Changes {
+    // The input and the output amounts of each token.
+    tokens: BTreeMap<Vec<u8>, (u128, u128)>,
+    // The input and the output amounts, and the change of live cells, of each token and holder.
+    holders: BTreeMap<Holder, (u128, u128, i64)>,
+}
+
+impl Changes {
+    fn input(&mut self, type_hash: Vec<u8>, lock_hash: Vec<u8>, amount: u128) {
+        let token = self.tokens.entry(type_hash.clone()).or_default();
+        token.0 = token.0.wrapping_add(amount);
+        let holder = self.holders.entry((type_hash, lock_hash)).or_default();
+        holder.0 = holder.0.wrapping_add(amount);
+        holder.2 -= 1;
+    }
+
+    fn output(&mut self, type_hash: Vec<u8>, lock_hash: Vec<u8>, amount: u128) {
+        let token = self.tokens.entry(type_hash.clone()).or_default();
+        token.1 = token.1.wrapping_add(amount);
+        let holder = self.holders.entry((type_hash, lock_hash)).or_default();
+        holder.1 = holder.1.wrapping_add(amount);
+        holder.2 += 1;
+    }
+
+    // Swaps the inputs and the outputs, to revert the changes.
+    fn reverse(self) -> Self {
+        let tokens = self
+            .tokens
+            .into_iter()
+            .map(|(key, (input, output))| (key, (output, input)))
+            .collect();
+        let holders = self
+            .holders
+            .into_iter()
+            .map(|(key, (input, output, cells))| (key, (output, input, -cells)))
+            .collect();
+        Self { tokens, holders }
+    }
+}
+
+// Decodes the amount of a sUDT cell, which is the first 16 bytes of the data in little endian.
+fn decode_amount(data: &[u8]) -> Option<u128> {
+    if data.len() < 16 {
+        return None;
+    }
+    let mut tmp = [0u8; 16];
+    tmp.copy_from_slice(&data[..16]);
+    Some(u128::from_le_bytes(tmp))
+}
+
+// The amounts are stored in 16 bytes in big endian, so they are sorted by values.
+fn amount_to_value(amount: u128) -> Value {
+    Value::Bytes(amount.to_be_bytes().to_vec())
+}
+
+pub(in crate::storage) fn amount_from_value(value: &Value) -> Result<u128> {
+    match value {
+        Value::Bytes(bytes) if bytes.len() == 16 => {
+            let mut tmp = [0u8; 16];
+            tmp.copy_from_slice(bytes);
+            Ok(u128::from_be_bytes(tmp))
+        }
+        _ => Err(Error::Data(format!("incorrect amount {}", value))),
+    }
+}
+
+fn amount_from_row(row: &Row, index: usize) -> Result<u128> {
+    amount_from_value(&row.try_get::<Value>(index)?)
+}
+
+// Creates the tables if they don't exist, and fills them from the stored cells of the scripts.
+//
+// The scripts which the tables are filled with are stored in `udt_scripts`, the tables are
+// dropped and filled again when the known scripts are changed, e.g. a registry is provided.
+pub(in crate::storage) async fn create_tables(
+    cli: &mut dyn Backend,
+    scripts: &[(Vec<u8>, u8)],
+) -> Result<()> {
+    log::trace!("create tables of user-defined tokens");
+    let refill = if cli.table_exists("udt_tokens").await? {
+        if cli.table_exists("udt_scripts").await? && stored_scripts(cli).await? == sorted(scripts) {
+            return Ok(());
+        }
+        log::info!("the known sUDT scripts are changed, fill the tables of tokens again");
+        true
+    } else {
+        false
+    };
+    let txn = cli.transaction().await?;
+    if refill {
+        for table in TABLES {
+            let sql = format!("DROP TABLE IF EXISTS {};", table);
+            txn.execute(&sql, &[]).await?;
+        }
+    }
+    let sqls = &[
+        r#"
+        CREATE TABLE IF NOT EXISTS udt_cells (
+            tx_hash             BYTEA       NOT NULL,
+            "index"             INTEGER     NOT NULL,
+            type_hash           BYTEA       NOT NULL,
+            lock_hash           BYTEA       NOT NULL,
+            amount              BYTEA       NOT NULL,
+            created_block_number    BIGINT  NOT NULL,
+            PRIMARY KEY (tx_hash, "index")
+        );"#,
+        r#"
+        CREATE TABLE IF NOT EXISTS udt_balances (
+            type_hash           BYTEA       NOT NULL,
+            lock_hash           BYTEA       NOT NULL,
+            balance             BYTEA       NOT NULL,
+            live_cells          BIGINT      NOT NULL,
+            PRIMARY KEY (type_hash, lock_hash)
+        );"#,
+        r#"
+        CREATE TABLE IF NOT EXISTS udt_tokens (
+            type_hash           BYTEA       NOT NULL PRIMARY KEY,
+            supply              BYTEA       NOT NULL,
+            holders             BIGINT      NOT NULL,
+            first_block_number  BIGINT      NOT NULL,
+            last_block_number   BIGINT      NOT NULL
+        );"#,
+        r#"
+        CREATE TABLE IF NOT EXISTS udt_transactions (
+            type_hash           BYTEA       NOT NULL,
+            block_number        BIGINT      NOT NULL,
+            tx_index            INTEGER     NOT NULL,
+            tx_hash             BYTEA       NOT NULL,
+            kind                TEXT        NOT NULL,
+            input_amount        BYTEA       NOT NULL,
+            output_amount       BYTEA       NOT NULL,
+            PRIMARY KEY (type_hash, block_number, tx_index)
+        );"#,
+        r#"
+        CREATE TABLE IF NOT EXISTS udt_scripts (
+            code_hash           BYTEA       NOT NULL,
+            hash_type           SMALLINT    NOT NULL,
+            PRIMARY KEY (code_hash, hash_type)
+        );"#,
+    ];
+    for sql in sqls {
+        txn.execute(sql, &[]).await?;
+    }
+    record_scripts(&*txn, scripts).await?;
+    let sql = r#"
+        SELECT c.tx_hash, c."index", c.type_hash, c.lock_hash, d.data,
+               c.created_block_number, ct."index", c.consumed_tx_hash, c.consumed_block_number,
+               cc."index"
+          FROM cells c
+          JOIN scripts s ON s.hash = c.type_hash
+          JOIN cells_data d ON d.hash = c.data_hash
+          JOIN block_transactions ct ON ct.tx_hash = c.tx_hash
+          LEFT JOIN block_transactions cc ON cc.tx_hash = c.consumed_tx_hash
+         WHERE 1 = 1
+           AND s.code_hash = $1
+           AND s.hash_type = $2
+    ;"#;
+    // The changes of each transaction, by `(block number, tx index, tx hash)`.
+    let mut txs = BTreeMap::<(i64, i32, Vec<u8>), Changes>::new();
+    for (code_hash, hash_type) in scripts {
+        let params = &[code_hash.as_slice().into(), i16::from(*hash_type).into()];
+        for row in txn.query(sql, params).await? {
+            let data = row.try_get::<Vec<u8>>(4)?;
+            let amount = if let Some(amount) = decode_amount(&data) {
+                amount
+            } else {
+                continue;
+            };
+            let tx_hash = row.try_get::<Vec<u8>>(0)?;
+            let index = row.try_get::<i32>(1)?;
+            let type_hash = row.try_get::<Vec<u8>>(2)?;
+            let lock_hash = row.try_get::<Vec<u8>>(3)?;
+            let block_number = row.try_get::<i64>(5)?;
+            insert_cell(
+                &*txn,
+                (&tx_hash, index),
+                &type_hash,
+                &lock_hash,
+                amount,
+                block_number,
+            )
+            .await?;
+            let tx_index = row.try_get::<i32>(6)?;
+            txs.entry((block_number, tx_index, tx_hash))
+                .or_default()
+                .output(type_hash.clone(), lock_hash.clone(), amount);
+            if let Some(consumed_tx_hash) = row.try_get::<Option<Vec<u8>>>(7)? {
+                let block_number = row.try_get::<i64>(8)?;
+                let tx_index = row.try_get::<i32>(9)?;
+                txs.entry((block_number, tx_index, consumed_tx_hash))
+                    .or_default()
+                    .input(type_hash, lock_hash, amount);
+            }
+        }
+    }
+    for ((block_number, tx_index, tx_hash), changes) in txs {
+        record_changes(&*txn, &changes, block_number, tx_index, &tx_hash).await?;
+    }
+    txn.commit().await
+}
+
+fn sorted(scripts: &[(Vec<u8>, u8)]) -> BTreeSet<(Vec<u8>, u8)> {
+    scripts.iter().cloned().collect()
+}
+
+async fn stored_scripts(cli: &dyn Backend) -> Result<BTreeSet<(Vec<u8>, u8)>> {
+    let sql = "SELECT code_hash, hash_type FROM udt_scripts;";
+    let mut scripts = BTreeSet::new();
+    for row in cli.query(sql, &[]).await? {
+        let code_hash = row.try_get::<Vec<u8>>(0)?;
+        let hash_type = row.try_get::<u8>(1)?;
+        scripts.insert((code_hash, hash_type));
+    }
+    Ok(scripts)
+}
+
+// Replaces the scripts which the tables are filled with.
+pub(in crate::storage) async fn record_scripts(
+    txn: &dyn Transaction,
+    scripts: &[(Vec<u8>, u8)],
+) -> Result<()> {
+    txn.execute("DELETE FROM udt_scripts;", &[]).await?;
+    let sql = "INSERT INTO udt_scripts (code_hash, hash_type) VALUES ($1, $2);";
+    for (code_hash, hash_type) in sorted(scripts) {
+        let params = &[code_hash.as_slice().into(), i16::from(hash_type).into()];
+        txn.execute(sql, params).await?;
+    }
+    Ok(())
+}
+
+async fn insert_cell(
+    txn: &dyn Transaction,
+    (tx_hash, index): (&[u8], i32),
+    type_hash: &[u8],
+    lock_hash: &[u8],
+    amount: u128,
+    block_number: i64,
+) -> Result<u64> {
+    let sql = r#"
+        INSERT INTO udt_cells (
+            tx_hash, "index", type_hash, lock_hash, amount, created_block_number
+        ) VALUES (
+            $1, $2, $3, $4, $5, $6
+        )
+    ;"#;
+    txn.execute(
+        sql,
+        &[
+            tx_hash.into(),
+            index.into(),
+            type_hash.into(),
+            lock_hash.into(),
+            amount_to_value(amount),
+            block_number.into(),
+        ],
+    )
+    .await
+}
+
+// Records the mints, burns and transfers of the tokens which the transaction touches, the
+// cells of the transaction should be inserted and consumed already.
+pub(in crate::storage) async fn insert_changes(
+    txn: &dyn Transaction,
+    tx: &core::TransactionView,
+    block_number: u64,
+    tx_index: usize,
+    scripts: &[(Vec<u8>, u8)],
+) -> Result<()> {
+    if scripts.is_empty() {
+        return Ok(());
+    }
+    log::trace!("insert token changes for transaction {:#}", tx.hash());
+    let mut changes = Changes::default();
+    let outputs = tx.data().raw().outputs().into_iter();
+    let outputs_data = tx.data().raw().outputs_data().into_iter();
+    for (index, (output, data)) in outputs.zip(outputs_data).enumerate() {
+        let type_script = if let Some(type_script) = output.type_().to_opt() {
+            type_script
+        } else {
+            continue;
+        };
+        let code_hash = type_script.code_hash().raw_data();
+        let hash_type: u8 = type_script.hash_type().into();
+        if !scripts
+            .iter()
+            .any(|script| script.0.as_slice() == code_hash.as_ref() && script.1 == hash_type)
+        {
+            continue;
+        }
+        let amount = if let Some(amount) = decode_amount(data.raw_data().as_ref()) {
+            amount
+        } else {
+            log::warn!("no amount in the token cell {:#}:{}", tx.hash(), index);
+            continue;
+        };
+        let type_hash = type_script.calc_script_hash().raw_data().to_vec();
+        let lock_hash = output.lock().calc_script_hash().raw_data().to_vec();
+        insert_cell(
+            txn,
+            (tx.hash().raw_data().as_ref(), index as i32),
+            &type_hash,
+            &lock_hash,
+            amount,
+            block_number as i64,
+        )
+        .await?;
+        changes.output(type_hash, lock_hash, amount);
+    }
+    if tx_index != 0 {
+        let sql = r#"
+            SELECT u.type_hash, u.lock_hash, u.amount
+              FROM udt_cells u
+              JOIN cells c ON c.tx_hash = u.tx_hash AND c."index" = u."index"
+             WHERE c.consumed_tx_hash = $1
+        ;"#;
+        for row in txn
+            .query(sql, &[tx.hash().raw_data().as_ref().into()])
+            .await?
+        {
+            let amount = amount_from_row(&row, 2)?;
+            changes.input(row.try_get(0)?, row.try_get(1)?, amount);
+        }
+    }
+    let tx_hash = tx.hash().raw_data();
+    record_changes(
+        txn,
+        &changes,
+        block_number as i64,
+        tx_index as i32,
+        tx_hash.as_ref(),
+    )
+    .await
+}
+
+async fn record_changes(
+    txn: &dyn Transaction,
+    changes: &Changes,
+    block_number: i64,
+    tx_index: i32,
+    tx_hash: &[u8],
+) -> Result<()> {
+    let insert_sql = r#"
+        INSERT INTO udt_transactions (
+            type_hash, block_number, tx_index, tx_hash, kind, input_amount, output_amount
+        ) VALUES (
+            $1, $2, $3, $4, $5, $6, $7
+        )
+    ;"#;
+    let token_sql = r#"
+        INSERT INTO udt_tokens (
+            type_hash, supply, holders, first_block_number, last_block_number
+        ) VALUES (
+            $1, $2, 0, $3, $3
+        )
+        ON CONFLICT (type_hash) DO UPDATE
+           SET
+               supply = excluded.supply,
+               last_block_number = excluded.last_block_number
+    ;"#;
+    update_balances(txn, changes).await?;
+    for (type_hash, (input, output)) in &changes.tokens {
+        let kind = if output > input {
+            "mint"
+        } else if output < input {
+            "burn"
+        } else {
+            "transfer"
+        };
+        txn.execute(
+            insert_sql,
+            &[
+                type_hash.as_slice().into(),
+                block_number.into(),
+                tx_index.into(),
+                tx_hash.into(),
+                kind.into(),
+                amount_to_value(*input),
+                amount_to_value(*output),
+            ],
+        )
+        .await?;
+        let supply = query_supply(txn, type_hash)
+            .await?
+            .unwrap_or(0)
+            .wrapping_add(*output)
+            .wrapping_sub(*input);
+        let params = &[
+            type_hash.as_slice().into(),
+            amount_to_value(supply),
+            block_number.into(),
+        ];
+        txn.execute(token_sql, params).await?;
+        update_holders(txn, type_hash).await?;
+    }
+    Ok(())
+}
+
+// Reverts the changes in the block, the tokens which are first seen in it are removed.
+pub(in crate::storage) async fn remove_changes(
+    txn: &dyn Transaction,
+    block_number: u64,
+) -> Result<()> {
+    log::trace!("remove token changes for block {}", block_number);
+    let sqls = &[
+        r#"
+        SELECT type_hash, lock_hash, amount
+          FROM udt_cells
+         WHERE created_block_number = $1
+        ;"#,
+        r#"
+        SELECT u.type_hash, u.lock_hash, u.amount
+          FROM udt_cells u
+          JOIN cells c ON c.tx_hash = u.tx_hash AND c."index" = u."index"
+         WHERE c.consumed_block_number = $1
+        ;"#,
+    ];
+    let mut changes = Changes::default();
+    for (i, sql) in sqls.iter().enumerate() {
+        for row in txn.query(sql, &[(block_number as i64).into()]).await? {
+            let amount = amount_from_row(&row, 2)?;
+            if i == 0 {
+                changes.output(row.try_get(0)?, row.try_get(1)?, amount);
+            } else {
+                changes.input(row.try_get(0)?, row.try_get(1)?, amount);
+            }
+        }
+    }
+    let changes = changes.reverse();
+    let sqls = &[
+        "DELETE FROM udt_transactions WHERE block_number = $1;",
+        "DELETE FROM udt_cells WHERE created_block_number = $1;",
+    ];
+    for sql in sqls {
+        txn.execute(sql, &[(block_number as i64).into()]).await?;
+    }
+    update_balances(txn, &changes).await?;
+    let remove_sql = r#"
+        DELETE FROM udt_tokens
+         WHERE 1 = 1
+           AND type_hash = $1
+           AND first_block_number = $2
+    ;"#;
+    let update_sql = r#"
+        UPDATE udt_tokens
+           SET
+               supply = $2,
+               last_block_number = (
+                   SELECT MAX(block_number)
+                     FROM udt_transactions
+                    WHERE type_hash = $1
+               )
+         WHERE type_hash = $1
+    ;"#;
+    for (type_hash, (input, output)) in &changes.tokens {
+        let params = &[type_hash.as_slice().into(), (block_number as i64).into()];
+        if txn.execute(remove_sql, params).await? == 0 {
+            let supply = query_supply(txn, type_hash)
+                .await?
+                .unwrap_or(0)
+                .wrapping_add(*output)
+                .wrapping_sub(*input);
+            let params = &[type_hash.as_slice().into(), amount_to_value(supply)];
+            txn.execute(update_sql, params).await?;
+            update_holders(txn, type_hash).await?;
+        }
+    }
+    Ok(())
+}
+
+// The amounts wrap around as the changes are applied, so reverting them always restores the
+// previous balances.
+async fn update_balances(txn: &dyn Transaction, changes: &Changes) -> Result<()> {
+    let query_sql = r#"
+        SELECT balance, live_cells
+          FROM udt_balances
+         WHERE 1 = 1
+           AND type_hash = $1
+           AND lock_hash = $2
+    ;"#;
+    let remove_sql = r#"
+        DELETE FROM udt_balances
+         WHERE 1 = 1
+           AND type_hash = $1
+           AND lock_hash = $2
+    ;"#;
+    let upsert_sql = r#"
+        INSERT INTO udt_balances (
+            type_hash, lock_hash, balance, live_cells
+        ) VALUES (
+            $1, $2, $3, $4
+        )
+        ON CONFLICT (type_hash, lock_hash) DO UPDATE
+           SET
+               balance = excluded.balance,
+               live_cells = excluded.live_cells
+    ;"#;
+    for ((type_hash, lock_hash), (input, output, cells)) in &changes.holders {
+        let key = &[type_hash.as_slice().into(), lock_hash.as_slice().into()];
+        let (balance, live_cells) = if let Some(row) = txn.query_opt(query_sql, key).await? {
+            (amount_from_row(&row, 0)?, row.try_get::<i64>(1)?)
+        } else {
+            (0, 0)
+        };
+        let balance = balance.wrapping_add(*output).wrapping_sub(*input);
+        let live_cells = live_cells + cells;
+        if live_cells == 0 {
+            txn.execute(remove_sql, key).await?;
+        } else {
+            let params = &[
+                type_hash.as_slice().into(),
+                lock_hash.as_slice().into(),
+                amount_to_value(balance),
+                live_cells.into(),
+            ];
+            txn.execute(upsert_sql, params).await?;
+        }
+    }
+    Ok(())
+}
+
+async fn update_holders(txn: &dyn Transaction, type_hash: &[u8]) -> Result<u64> {
+    let sql = r#"
+        UPDATE udt_tokens
+           SET
+               holders = (
+                   SELECT COUNT(*)
+                     FROM udt_balances
+                    WHERE type_hash = $1
+               )
+         WHERE type_hash = $1
+    ;"#;
+    txn.execute(sql, &[type_hash.into()]).await
+}
+
+async fn query_supply(txn: &dyn Transaction, type_hash: &[u8]) -> Result<Option<u128>> {
+    let sql = "SELECT supply FROM udt_tokens WHERE type_hash = $1;";
+    txn.query_opt(sql, &[type_hash.into()])
+        .await?
+        .map(|row| amount_from_row(&row, 0))
+        .transpose()
+}
@@ -0,0 +1,29 @@
+// Copyright (C) 2019-2020 Boyu Yang
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+use std::time::Duration;
+
+use uckb_jsonrpc_core::types::core;
+
+/// Callbacks for the events of a syncer.
+///
+/// All methods do nothing by default.
+pub trait SyncListener: Send {
+    /// The tip of the block source was fetched, `stored` is the number of the stored tip.
+    fn on_tip_fetched(&mut self, _tip: core::BlockNumber, _stored: Option<core::BlockNumber>) {}
+    /// Failed to fetch data from the block source, `failed_cnt` is the count of the continuous
+    /// failures.
+    fn on_source_failed(&mut self, _failed_cnt: u64) {}
+    /// A block was inserted into the storage, `elapsed` is the duration of the insertion.
+    fn on_block_inserted(&mut self, _block: &core::BlockView, _elapsed: Duration) {}
+    /// The block at the height was removed from the storage since a reorganization,
+    /// `elapsed` is the duration of the removal.
+    fn on_block_rolled_back(&mut self, _number: core::BlockNumber, _elapsed: Duration) {}
+    /// The storage caught up with the tip of the block source.
+    fn on_caught_up(&mut self, _tip: core::BlockNumber) {}
+}
@@ -0,0 +1,275 @@
+// Copyright (C) 2019-2020 Boyu Yang
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+use std::{
+    sync::mpsc::RecvTimeoutError,
+    thread,
+    time::{Duration, Instant},
+};
+
+use uckb_jsonrpc_core::types::core;
+
+use crate::{
+    error::{Error, Result},
+    source::{BlockSource, Subscription},
+    storage::{BaseData as _, Storage},
+};
+
+mod listener;
+mod policy;
+
+pub use listener::SyncListener;
+pub use policy::SyncPolicy;
+
+/// Synchronizes the blocks from a block source into a storage.
+pub struct Syncer<S: BlockSource> {
+    source: S,
+    storage: Storage,
+    policy: SyncPolicy,
+    listeners: Vec<Box<dyn SyncListener>>,
+    next: Option<core::BlockNumber>,
+    retry_cnt: u64,
+    failed_cnt: u64,
+    caught_up: Option<core::BlockNumber>,
+    // The block which is not served by the block source though it is not above the tip.
+    missing: Option<core::BlockNumber>,
+}
+
+impl<S: BlockSource> Syncer<S> {
+    pub fn new(source: S, storage: Storage) -> Self {
+        Self {
+            source,
+            storage,
+            policy: Default::default(),
+            listeners: Vec::new(),
+            next: None,
+            retry_cnt: 0,
+            failed_cnt: 0,
+            caught_up: None,
+            missing: None,
+        }
+    }
+
+    pub fn with_policy(mut self, policy: SyncPolicy) -> Self {
+        self.policy = policy;
+        self
+    }
+
+    pub fn add_listener<L: SyncListener + 'static>(&mut self, listener: L) -> &mut Self {
+        self.listeners.push(Box::new(listener));
+        self
+    }
+
+    pub fn source(&self) -> &S {
+        &self.source
+    }
+
+    pub fn storage(&self) -> &Storage {
+        &self.storage
+    }
+
+    pub fn policy(&self) -> &SyncPolicy {
+        &self.policy
+    }
+
+    /// Returns the number of the next block to synchronize.
+    pub fn next_number(&mut self) -> Result<core::BlockNumber> {
+        if let Some(next) = self.next {
+            Ok(next)
+        } else {
+            let next = self.storage.initialize()?.map(|n| n + 1).unwrap_or(0);
+            log::info!("current storage has base data before height {}", next);
+            self.next = Some(next);
+            Ok(next)
+        }
+    }
+
+    /// Synchronizes blocks forever.
+    ///
+    /// Errors from the block source are retried, errors from the storage are returned.
+    pub fn run(&mut self) -> Result<()> {
+        let mut subscription = self.subscribe();
+        loop {
+            let wait = self.poll()?;
+            self.wait(&mut subscription, wait);
+        }
+    }
+
+    /// Synchronizes blocks until the storage caught up with the tip of the block source,
+    /// and returns the number of the tip.
+    ///
+    /// The block source should serve all blocks up to its tip, such as a dump file, a missing
+    /// block is an error rather than retried.
+    pub fn run_to_tip(&mut self) -> Result<core::BlockNumber> {
+        let mut subscription = None;
+        loop {
+            let wait = self.poll()?;
+            if let Some(tip) = self.caught_up {
+                return Ok(tip);
+            }
+            if let Some(number) = self.missing {
+                return Err(Error::Data(format!(
+                    "no block {} in the block source whose tip is {}",
+                    number,
+                    self.source.tip_number()?
+                )));
+            }
+            self.wait(&mut subscription, wait);
+        }
+    }
+
+    /// Makes one turn of synchronization, and returns the duration to wait before the next turn.
+    pub fn poll(&mut self) -> Result<Duration> {
+        let next = self.next_number()?;
+        let tip = match self.source.tip_number() {
+            Ok(tip) => {
+                self.failed_cnt = 0;
+                tip
+            }
+            Err(err) => {
+                log::error!("failed to get tip block number since {}", err);
+                return Ok(self.source_failed());
+            }
+        };
+        for listener in self.listeners.iter_mut() {
+            listener.on_tip_fetched(tip, next.checked_sub(1));
+        }
+        log::info!("current tip number is {}", tip);
+        if tip < next {
+            if next > 0 && !self.verify_stored_tip(next - 1)? {
+                return Ok(Duration::from_secs(0));
+            }
+            self.caught_up(tip);
+            self.retry_cnt += 1;
+            log::trace!("no new block");
+            return Ok(self.policy.idle_wait(self.retry_cnt));
+        } else {
+            self.retry_cnt = 0;
+            self.caught_up = None;
+        }
+        self.missing = None;
+        let mut i = next;
+        while i <= tip {
+            log::info!("synchronize block {} ...", i);
+            match self.source.block_by_number(i) {
+                Ok(Some(block)) => {
+                    let start = Instant::now();
+                    let result = self.storage.insert_block(&block);
+                    if let Err(Error::UnknownParentBlock { number, hash }) = result {
+                        log::warn!("rollback unknown parent block ({}, {:#x})", number, hash);
+                        self.rollback(number)?;
+                        return Ok(Duration::from_secs(0));
+                    } else {
+                        self.failed_cnt = 0;
+                        result?;
+                        i += 1;
+                        self.next = Some(i);
+                        let elapsed = start.elapsed();
+                        for listener in self.listeners.iter_mut() {
+                            listener.on_block_inserted(&block, elapsed);
+                        }
+                    }
+                }
+                Ok(None) => {
+                    log::warn!("block {} is missing", i);
+                    self.failed_cnt = 0;
+                    self.missing = Some(i);
+                    return Ok(Duration::from_secs(0));
+                }
+                Err(err) => {
+                    log::error!("failed to get block number {} since {}", i, err);
+                    return Ok(self.source_failed());
+                }
+            }
+        }
+        self.caught_up(tip);
+        Ok(Duration::from_secs(0))
+    }
+
+    // Checks if the stored block at the height is still in the chain of the block source,
+    // otherwise removes it.
+    // Since the tip of the block source is not higher than the stored tip, the reorganization
+    // can not be found by the parent hash of the next block.
+    fn verify_stored_tip(&mut self, number: core::BlockNumber) -> Result<bool> {
+        let expected = match self.source.block_hash_by_number(number) {
+            Ok(hash_opt) => hash_opt,
+            Err(err) => {
+                log::error!("failed to get block hash {} since {}", number, err);
+                return Ok(true);
+            }
+        };
+        let stored = self.storage.block_hash(number)?;
+        if stored.is_some() && stored != expected {
+            log::warn!("rollback the stored tip block {}", number);
+            self.rollback(number)?;
+            Ok(false)
+        } else {
+            Ok(true)
+        }
+    }
+
+    fn rollback(&mut self, number: core::BlockNumber) -> Result<()> {
+        let start = Instant::now();
+        self.storage.remove_block(number)?;
+        let elapsed = start.elapsed();
+        self.next = Some(number);
+        self.caught_up = None;
+        for listener in self.listeners.iter_mut() {
+            listener.on_block_rolled_back(number, elapsed);
+        }
+        Ok(())
+    }
+
+    // Returns the duration to wait before retrying.
+    fn source_failed(&mut self) -> Duration {
+        self.failed_cnt += 1;
+        for listener in self.listeners.iter_mut() {
+            listener.on_source_failed(self.failed_cnt);
+        }
+        self.policy.retry_wait(self.failed_cnt)
+    }
+
+    fn subscribe(&self) -> Option<Subscription> {
+        self.source.subscribe().unwrap_or_else(|err| {
+            log::warn!("failed to subscribe new tip blocks since {}", err);
+            None
+        })
+    }
+
+    fn wait(&self, subscription: &mut Option<Subscription>, wait: Duration) {
+        if wait == Duration::from_secs(0) {
+            return;
+        }
+        log::trace!("retry after {} secs", wait.as_secs());
+        if let Some(receiver) = subscription {
+            match receiver.recv_timeout(wait) {
+                Ok(number) => {
+                    log::trace!("new tip block {}", number);
+                    while receiver.try_recv().is_ok() {}
+                }
+                Err(RecvTimeoutError::Timeout) => {}
+                Err(RecvTimeoutError::Disconnected) => {
+                    log::warn!("the subscription of new tip blocks is closed");
+                    *subscription = None;
+                }
+            }
+        } else {
+            thread::sleep(wait);
+        }
+    }
+
+    fn caught_up(&mut self, tip: core::BlockNumber) {
+        if self.caught_up.is_none() {
+            log::info!("caught up with the tip {}", tip);
+            self.caught_up = Some(tip);
+            for listener in self.listeners.iter_mut() {
+                listener.on_caught_up(tip);
+            }
+        }
+    }
+}
@@ -0,0 +1,41 @@
+// Copyright (C) 2019-2020 Boyu Yang
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+use std::{cmp, time::Duration};
+
+use property::Property;
+
+/// The policies which control how a syncer waits and retries.
+#[derive(Property, Clone, Debug)]
+#[property(get(public), set(public), mut(disable))]
+pub struct SyncPolicy {
+    /// The max seconds to wait before polling the tip again when there is no new block.
+    max_idle_secs: u64,
+    /// The max seconds to wait before retrying when the block source failed.
+    max_retry_secs: u64,
+}
+
+impl Default for SyncPolicy {
+    fn default() -> Self {
+        Self {
+            max_idle_secs: 10,
+            max_retry_secs: 90,
+        }
+    }
+}
+
+impl SyncPolicy {
+    pub(super) fn idle_wait(&self, retry_cnt: u64) -> Duration {
+        Duration::from_secs(cmp::min(retry_cnt, self.max_idle_secs))
+    }
+
+    pub(super) fn retry_wait(&self, failed_cnt: u64) -> Duration {
+        let secs = failed_cnt.saturating_mul(failed_cnt);
+        Duration::from_secs(cmp::min(secs, self.max_retry_secs))
+    }
+}
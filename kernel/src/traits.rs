@@ -6,4 +6,11 @@
 // option. This file may not be copied, modified, or distributed
 // except according to those terms.
 
-pub use super::base_data::BaseData;
+pub use crate::{
+    source::BlockSource,
+    storage::{
+        Accounts, BaseData, Epochs, Explorer, Export, Indexer, Mining, Proposals, Relations,
+        Rollups, ScriptUsage, Stats, Udt,
+    },
+    syncer::SyncListener,
+};
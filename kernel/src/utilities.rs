@@ -8,6 +8,8 @@
 
 use property::Property;
 
+use crate::error::{Error, Result};
+
 #[derive(Property)]
 #[property(get(public), set(disable), mut(disable))]
 pub(crate) struct Dao {
@@ -31,3 +33,66 @@ impl Dao {
         Self { c, ar, s, u }
     }
 }
+
+/// The milliseconds of a day.
+pub(crate) const DAY_MILLIS: u64 = 24 * 60 * 60 * 1_000;
+
+/// The milliseconds of an hour.
+pub(crate) const HOUR_MILLIS: u64 = 60 * 60 * 1_000;
+
+/// Formats the day since the Unix epoch as a date in UTC, such as "2019-11-16".
+pub(crate) fn utc_date(day: u64) -> String {
+    // The civil calendar from days, with the eras of 400 years which start at March 1st.
+    let days = day as i64 + 719_468;
+    let era = days.div_euclid(146_097);
+    let day_of_era = days.rem_euclid(146_097);
+    let year_of_era =
+        (day_of_era - day_of_era / 1_460 + day_of_era / 36_524 - day_of_era / 146_096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let month_index = (5 * day_of_year + 2) / 153;
+    let day_of_month = day_of_year - (153 * month_index + 2) / 5 + 1;
+    let month = if month_index < 10 {
+        month_index + 3
+    } else {
+        month_index - 9
+    };
+    let year = year_of_era + era * 400 + if month <= 2 { 1 } else { 0 };
+    format!("{:04}-{:02}-{:02}", year, month, day_of_month)
+}
+
+/// Formats the hour since the Unix epoch as a time in UTC, such as "2019-11-16 08:00".
+pub(crate) fn utc_hour(hour: u64) -> String {
+    format!("{} {:02}:00", utc_date(hour / 24), hour % 24)
+}
+
+/// Decodes a compact target of a header into the difficulty, which is the expected count of
+/// hashes to find a block.
+///
+/// The compact target is the exponent in the highest byte and the mantissa in the lower 3 bytes,
+/// the target is `mantissa * 256 ^ (exponent - 3)`, and the difficulty is `2 ^ 256 / target`.
+/// Returns `None` if the target is zero or overflows, as the exponent is greater than 32.
+pub fn compact_to_difficulty(compact: u32) -> Option<f64> {
+    let exponent = compact >> 24;
+    let mut mantissa = compact & 0x00ff_ffff;
+    let shift = if exponent <= 3 {
+        mantissa >>= 8 * (3 - exponent);
+        0
+    } else {
+        8 * (exponent - 3)
+    };
+    if mantissa == 0 || exponent > 32 {
+        return None;
+    }
+    Some(2f64.powi(256 - shift as i32) / f64::from(mantissa))
+}
+
+pub(crate) fn hex_to_bytes(input: &str) -> Result<Vec<u8>> {
+    let hex = input.trim_start_matches("0x").as_bytes();
+    if hex.is_empty() {
+        return Ok(Vec::new());
+    }
+    let mut bytes = vec![0u8; hex.len() / 2];
+    faster_hex::hex_decode(hex, &mut bytes)
+        .map_err(|err| Error::Data(format!("incorrect hex string ({})", err)))?;
+    Ok(bytes)
+}
@@ -0,0 +1,105 @@
+// Copyright (C) 2019-2020 Boyu Yang
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+mod common;
+
+use common::{ChainBuilder, TestStorage};
+use uckb_scanner::{
+    traits::{Accounts as _, BaseData as _, Explorer as _},
+    CellFilter,
+};
+
+#[test]
+fn maintain_accounts_through_reorganizations() {
+    for storage in TestStorage::all("maintain_accounts_through_reorganizations") {
+        let mut chain_a = ChainBuilder::new();
+        chain_a.extend(6, &[]);
+        let mut chain_b = chain_a.fork(3, 1);
+        chain_b.extend(5, &[]);
+        let source = chain_a.source();
+        storage.sync(&source);
+        chain_b.apply_to(&source);
+        storage.sync(&source);
+        let storage = storage.connect();
+
+        let lock_hashes = storage
+            .query("SELECT DISTINCT lock_hash FROM cells;", &[])
+            .unwrap()
+            .into_iter()
+            .map(|row| row.try_get::<Vec<u8>>(0).unwrap())
+            .collect::<Vec<_>>();
+        assert!(lock_hashes.len() > 2);
+        for lock_hash in lock_hashes {
+            let account = storage.account(&lock_hash).unwrap().unwrap();
+            let balance = account.try_get::<i64>(4).unwrap();
+            let live_cells = account.try_get::<i64>(5).unwrap();
+            let first = account.try_get::<i64>(6).unwrap();
+            let last = account.try_get::<i64>(7).unwrap();
+
+            let filter = CellFilter {
+                lock_hash: Some(lock_hash.clone()),
+                live: Some(true),
+                ..Default::default()
+            };
+            let cells = storage.cells(&filter, None, 1000).unwrap();
+            let capacity = cells
+                .iter()
+                .map(|row| row.try_get::<i64>(3).unwrap())
+                .sum::<i64>();
+            assert_eq!(balance, capacity);
+            assert_eq!(live_cells, cells.len() as i64);
+
+            // Pages of changes, from the latest.
+            let mut changes = Vec::new();
+            let mut before = None;
+            loop {
+                let rows = storage.account_changes(&lock_hash, before, 2).unwrap();
+                if let Some(row) = rows.last() {
+                    let block_number = row.try_get::<i64>(0).unwrap() as u64;
+                    let tx_index = row.try_get::<i64>(1).unwrap() as u32;
+                    before = Some((block_number, tx_index));
+                } else {
+                    break;
+                }
+                changes.extend(rows);
+            }
+            assert_eq!(changes[0].try_get::<i64>(5).unwrap(), balance);
+            assert_eq!(changes[0].try_get::<i64>(0).unwrap(), last);
+            assert_eq!(changes.last().unwrap().try_get::<i64>(0).unwrap(), first);
+            let mut total = 0;
+            for change in changes.iter().rev() {
+                total += change.try_get::<i64>(3).unwrap();
+                assert_eq!(change.try_get::<i64>(5).unwrap(), total);
+            }
+            let cells_total = changes
+                .iter()
+                .map(|change| change.try_get::<i64>(4).unwrap())
+                .sum::<i64>();
+            assert_eq!(cells_total, live_cells);
+        }
+    }
+}
+
+#[test]
+fn fill_accounts_of_existing_storages() {
+    for storage in TestStorage::all("fill_accounts_of_existing_storages") {
+        let mut chain_a = ChainBuilder::new();
+        chain_a.extend(5, &[]);
+        let mut chain = chain_a.fork(2, 1);
+        chain.extend(4, &[chain_a.block(3)]);
+        storage.sync(&chain.source());
+        let expected = storage.snapshot();
+
+        let mut conn = storage.connect();
+        for sql in &["DROP TABLE account_changes;", "DROP TABLE accounts;"] {
+            conn.query(sql, &[]).unwrap();
+        }
+        assert_eq!(conn.initialize().unwrap(), Some(6));
+        assert_eq!(storage.snapshot(), expected);
+    }
+}
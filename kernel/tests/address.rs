@@ -0,0 +1,86 @@
+// Copyright (C) 2019-2020 Boyu Yang
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+mod common;
+
+use common::ChainBuilder;
+use uckb_jsonrpc_core::types::prelude::*;
+use uckb_scanner::{Address, Network};
+
+fn hex(input: &str) -> Vec<u8> {
+    let mut bytes = vec![0u8; input.len() / 2];
+    faster_hex::hex_decode(input.as_bytes(), &mut bytes).unwrap();
+    bytes
+}
+
+#[test]
+fn encode_and_decode_addresses() {
+    let blake160 = hex("9bd7e06f3ecf4be0f2fcd2188b23f1b9fcc88e5d4b65a8637b17723bbda3cce8");
+    let multisig = hex("5c5069eb0857efc65e1bca0c07df34c31663b3622fd3876c876320fc9634e2a8");
+    let cases = vec![
+        (
+            Address::new(
+                Network::Mainnet,
+                &blake160,
+                1,
+                &hex("b39bbc0b3673c7d36450bc14cfcdad2d559c6c64"),
+            ),
+            Some("ckb1qyqt8xaupvm8837nv3gtc9x0ekkj64vud3jqfwyw5v"),
+            "ckb1qjda0cr08m85hc8jlnfp3zer7xulejywt49kt2rr0vthywaa50xw3vumhs9nvu786dj9p0q5elx66t24n3kxgj53qks",
+        ),
+        (
+            Address::new(
+                Network::Mainnet,
+                &multisig,
+                1,
+                &hex("4fb2be2e5d0c1a3b8694f832350a33c1685d477a"),
+            ),
+            Some("ckb1qyq5lv479ewscx3ms620sv34pgeuz6zagaaqklhtgg"),
+            "ckb1q3w9q60tppt7l3j7r09qcp7lxnp3vcanvgha8pmvsa3jplykxn32snajhch96rq68wrff7pjx59r8stgt4rh5g96ahs",
+        ),
+    ];
+    for (address, short, full) in cases {
+        let address = address.unwrap();
+        assert_eq!(address.to_short().as_deref(), short);
+        assert_eq!(address.to_full(), full);
+        assert_eq!(full.parse::<Address>().unwrap(), address);
+        if let Some(short) = short {
+            assert_eq!(short.parse::<Address>().unwrap(), address);
+        }
+    }
+
+    let chain = ChainBuilder::new();
+    let lock = chain.block(0).transactions()[0]
+        .outputs()
+        .get(0)
+        .unwrap()
+        .lock();
+    let address = Address::new(
+        Network::Testnet,
+        lock.code_hash().as_slice(),
+        lock.hash_type().into(),
+        &lock.args().raw_data(),
+    )
+    .unwrap();
+    assert!(address.to_short().is_none());
+    let full = address.to_full();
+    assert!(full.starts_with("ckt1"));
+    let decoded = full.parse::<Address>().unwrap();
+    assert_eq!(decoded.network(), &Network::Testnet);
+    assert_eq!(
+        decoded.script_hash(),
+        lock.calc_script_hash().raw_data().to_vec()
+    );
+
+    assert!("ckb1qyqt8xaupvm8837nv3gtc9x0ekkj64vud3jqfwyw5w"
+        .parse::<Address>()
+        .is_err());
+    assert!("bc1qw508d6qejxtdg4y5r3zarvary0c5xw7kv8f3t4"
+        .parse::<Address>()
+        .is_err());
+}
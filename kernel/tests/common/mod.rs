@@ -0,0 +1,421 @@
+// Copyright (C) 2019-2020 Boyu Yang
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+#![allow(dead_code)]
+
+use std::{
+    collections::BTreeMap,
+    env, fs,
+    path::PathBuf,
+    process,
+    sync::{Arc, Mutex, MutexGuard},
+};
+
+use tokio::runtime;
+use uckb_jsonrpc_core::types::{bytes::Bytes, core, packed, prelude::*};
+use uckb_scanner::{traits::BaseData as _, MemorySource, Registry, Storage, Syncer};
+
+/// The environment variable which provides a connection URI to a PostgreSQL database.
+///
+/// All tables in that database will be dropped by the tests.
+pub const TEST_STORAGE_URI: &str = "UCKB_SCANNER_TEST_STORAGE_URI";
+
+static STORAGE_LOCK: Mutex<()> = Mutex::new(());
+
+pub struct TestStorage {
+    runtime: Arc<runtime::Runtime>,
+    uri: String,
+    sqlite_file: Option<PathBuf>,
+    _guard: Option<MutexGuard<'static, ()>>,
+}
+
+impl TestStorage {
+    /// Returns a temporary SQLite storage, and a PostgreSQL storage if a database is provided
+    /// for tests.
+    pub fn all(name: &str) -> Vec<Self> {
+        let mut storages = vec![Self::sqlite(name)];
+        if let Ok(uri) = env::var(TEST_STORAGE_URI) {
+            storages.push(Self::postgres(uri));
+        } else {
+            eprintln!("skip PostgreSQL since {} is not set", TEST_STORAGE_URI);
+        }
+        storages
+    }
+
+    fn sqlite(name: &str) -> Self {
+        let file = env::temp_dir().join(format!("uckb-scanner-{}-{}.db", process::id(), name));
+        let _ = fs::remove_file(&file);
+        let uri = format!("sqlite://{}", file.display());
+        Self::open(uri, Some(file), None)
+    }
+
+    fn postgres(uri: String) -> Self {
+        let guard = STORAGE_LOCK.lock().unwrap_or_else(|err| err.into_inner());
+        Self::open(uri, None, Some(guard))
+    }
+
+    fn open(
+        uri: String,
+        sqlite_file: Option<PathBuf>,
+        guard: Option<MutexGuard<'static, ()>>,
+    ) -> Self {
+        let runtime = runtime::Builder::new_multi_thread()
+            .worker_threads(2)
+            .enable_all()
+            .build()
+            .map(Arc::new)
+            .unwrap();
+        let storage = Self {
+            runtime,
+            uri,
+            sqlite_file,
+            _guard: guard,
+        };
+        storage.reset();
+        storage
+    }
+
+    pub fn connect(&self) -> Storage {
+        Storage::connect(Arc::clone(&self.runtime), &self.uri).unwrap()
+    }
+
+    /// Synchronizes all blocks of the source, and returns the tip number.
+    pub fn sync(&self, source: &MemorySource) -> core::BlockNumber {
+        Syncer::new(source.clone(), self.connect())
+            .run_to_tip()
+            .unwrap()
+    }
+
+    /// Synchronizes all blocks of the source with the known scripts, and returns the tip number.
+    pub fn sync_with_registry(
+        &self,
+        source: &MemorySource,
+        registry: &Registry,
+    ) -> core::BlockNumber {
+        let mut storage = self.connect();
+        storage.set_registry(registry.clone());
+        Syncer::new(source.clone(), storage).run_to_tip().unwrap()
+    }
+
+    /// Drops all tables.
+    pub fn reset(&self) {
+        self.connect().destory().unwrap();
+    }
+
+    /// Dumps all rows of all tables, each row is formatted as a string and rows are sorted.
+    ///
+    /// The miners of the orphaned blocks are not dumped, since they depend on the history of the
+    /// synchronization rather than the chain.
+    pub fn snapshot(&self) -> BTreeMap<String, Vec<String>> {
+        let storage = self.connect();
+        let sql = if self.sqlite_file.is_some() {
+            "SELECT name FROM sqlite_master WHERE type = 'table';"
+        } else {
+            "SELECT table_name::TEXT FROM information_schema.tables WHERE table_schema = 'public';"
+        };
+        let mut tables = BTreeMap::new();
+        for row in storage.query(sql, &[]).unwrap() {
+            let table: String = row.try_get(0).unwrap();
+            if table == "orphan_miners" {
+                continue;
+            }
+            let sql = format!("SELECT * FROM {};", table);
+            let mut rows = storage
+                .query(&sql, &[])
+                .unwrap()
+                .iter()
+                .map(|row| {
+                    row.columns()
+                        .iter()
+                        .zip(row.values())
+                        .map(|(column, value)| format!("{}={}", column, value))
+                        .collect::<Vec<_>>()
+                        .join(",")
+                })
+                .collect::<Vec<_>>();
+            rows.sort();
+            tables.insert(table, rows);
+        }
+        tables
+    }
+}
+
+impl Drop for TestStorage {
+    fn drop(&mut self) {
+        if let Some(ref file) = self.sqlite_file {
+            for suffix in &["", "-wal", "-shm"] {
+                let _ = fs::remove_file(format!("{}{}", file.display(), suffix));
+            }
+        }
+    }
+}
+
+/// Builds a synthetic chain.
+///
+/// Each block has a cellbase, whose witness names the lock of the seed as the miner, a
+/// transaction which spends the two oldest live cells and a transaction which spends an output
+/// of the previous transaction in the same block.
+/// The second output of the spending transaction is a token cell, whose type script has the
+/// code hash `[2u8; 32]` with the hash type "data".
+/// Different forks spend the same cells in different ways.
+/// Each block proposes its own transactions, the transactions don't depend on the hashes of
+/// blocks, so a fork with the same seed builds the same transactions.
+#[derive(Clone)]
+pub struct ChainBuilder {
+    seed: u8,
+    blocks: Vec<core::BlockView>,
+    live_cells: Vec<packed::OutPoint>,
+    // The extra proposals of the next block.
+    proposals: Vec<packed::ProposalShortId>,
+}
+
+impl ChainBuilder {
+    pub fn new() -> Self {
+        let genesis = {
+            let cellbase = core::TransactionBuilder::default()
+                .input(packed::CellInput::new_cellbase_input(0))
+                .outputs((0..4).map(|i| cell_output(i, None)))
+                .outputs_data((0..4).map(|_| Bytes::new().pack()))
+                .witness(Bytes::from(vec![0]).pack())
+                .build();
+            let system = core::TransactionBuilder::default()
+                .output(cell_output(0xff, Some(type_script(0))))
+                .output_data(Bytes::from(vec![0xff; 8]).pack())
+                .build();
+            header(core::BlockBuilder::default(), 0, 0, 0)
+                .transaction(cellbase)
+                .transaction(system)
+                .build()
+        };
+        let mut chain = Self {
+            seed: 0,
+            blocks: Vec::new(),
+            live_cells: Vec::new(),
+            proposals: Vec::new(),
+        };
+        chain.push(genesis);
+        chain
+    }
+
+    /// Forks the chain after the block at the height, new blocks will be built with the seed.
+    pub fn fork(&self, number: core::BlockNumber, seed: u8) -> Self {
+        let mut chain = Self {
+            seed,
+            blocks: Vec::new(),
+            live_cells: Vec::new(),
+            proposals: Vec::new(),
+        };
+        for block in &self.blocks[..=(number as usize)] {
+            chain.push(block.clone());
+        }
+        chain
+    }
+
+    /// Appends blocks, the first block includes the uncles.
+    pub fn extend(&mut self, count: usize, uncles: &[core::BlockView]) -> &mut Self {
+        for i in 0..count {
+            let uncles = if i == 0 { uncles } else { &[] };
+            let block = self.build_block(uncles);
+            self.proposals.clear();
+            self.push(block);
+        }
+        self
+    }
+
+    /// The next block proposes the transactions too.
+    pub fn propose(&mut self, txs: &[core::TransactionView]) -> &mut Self {
+        self.proposals
+            .extend(txs.iter().map(core::TransactionView::proposal_short_id));
+        self
+    }
+
+    /// Changes the header of the block at the height, the blocks after it are linked to it
+    /// again, their transactions are not changed.
+    pub fn modify<F>(&mut self, number: core::BlockNumber, change: F) -> &mut Self
+    where
+        F: FnOnce(core::BlockBuilder) -> core::BlockBuilder,
+    {
+        let number = number as usize;
+        self.blocks[number] = change(self.blocks[number].as_advanced_builder()).build();
+        for i in (number + 1)..self.blocks.len() {
+            self.blocks[i] = self.blocks[i]
+                .as_advanced_builder()
+                .parent_hash(self.blocks[i - 1].hash())
+                .build();
+        }
+        self
+    }
+
+    pub fn block(&self, number: core::BlockNumber) -> core::BlockView {
+        self.blocks[number as usize].clone()
+    }
+
+    /// Creates a source which serves this chain.
+    pub fn source(&self) -> MemorySource {
+        let source = MemorySource::new();
+        self.apply_to(&source);
+        source
+    }
+
+    /// Replaces the blocks in the source which are different with this chain.
+    pub fn apply_to(&self, source: &MemorySource) {
+        let fork_point = source
+            .blocks()
+            .iter()
+            .zip(self.blocks.iter())
+            .take_while(|(a, b)| a.hash() == b.hash())
+            .count();
+        source.truncate(fork_point as core::BlockNumber);
+        for block in &self.blocks[fork_point..] {
+            source.push(block.clone()).unwrap();
+        }
+    }
+
+    fn push(&mut self, block: core::BlockView) {
+        for (tx_index, tx) in block.transactions().into_iter().enumerate() {
+            if tx_index != 0 {
+                for input in tx.inputs().into_iter() {
+                    let out_point = input.previous_output();
+                    self.live_cells.retain(|live| live != &out_point);
+                }
+            }
+            for index in 0..tx.outputs().len() {
+                self.live_cells
+                    .push(packed::OutPoint::new(tx.hash(), index as u32));
+            }
+        }
+        self.blocks.push(block);
+    }
+
+    fn build_block(&self, uncles: &[core::BlockView]) -> core::BlockView {
+        let parent = self.blocks.last().unwrap().header();
+        let number = parent.number() + 1;
+        let seed = self.seed;
+        let cellbase = core::TransactionBuilder::default()
+            .input(packed::CellInput::new_cellbase_input(number))
+            .output(cell_output(seed, None))
+            .output_data(Bytes::new().pack())
+            .witness(cellbase_witness(seed).as_bytes().pack())
+            .build();
+        let genesis_system = packed::OutPoint::new(self.blocks[0].transactions()[1].hash(), 0);
+        let spend = core::TransactionBuilder::default()
+            .cell_dep(
+                packed::CellDep::new_builder()
+                    .out_point(genesis_system)
+                    .dep_type(core::DepType::Code.into())
+                    .build(),
+            )
+            .header_dep(self.blocks[0].hash())
+            .inputs(
+                self.live_cells
+                    .iter()
+                    .take(2)
+                    .map(|out_point| packed::CellInput::new(out_point.clone(), number)),
+            )
+            .output(cell_output(seed, None))
+            .output(cell_output(0, Some(type_script(seed % 2))))
+            .output_data(Bytes::new().pack())
+            .output_data(Bytes::from(udt_data(number, seed)).pack())
+            .witness(Bytes::from(vec![seed, number as u8]).pack())
+            .build();
+        let chained = core::TransactionBuilder::default()
+            .input(packed::CellInput::new(
+                packed::OutPoint::new(spend.hash(), 0),
+                0,
+            ))
+            .output(cell_output(seed.wrapping_add(number as u8), None))
+            .output_data(Bytes::from(vec![number as u8]).pack())
+            .build();
+        let proposals = vec![spend.proposal_short_id(), chained.proposal_short_id()]
+            .into_iter()
+            .chain(self.proposals.iter().cloned())
+            .collect::<Vec<_>>();
+        header(
+            core::BlockBuilder::default(),
+            number,
+            seed,
+            parent.timestamp(),
+        )
+        .parent_hash(parent.hash())
+        .transaction(cellbase)
+        .transaction(spend)
+        .transaction(chained)
+        .uncles(uncles.iter().map(|uncle| uncle.as_uncle()))
+        .proposals(proposals)
+        .build()
+    }
+}
+
+fn header(
+    builder: core::BlockBuilder,
+    number: core::BlockNumber,
+    seed: u8,
+    parent_timestamp: u64,
+) -> core::BlockBuilder {
+    let epoch = core::EpochNumberWithFraction::new(number / 10, number % 10, 10);
+    let dao = {
+        let mut dao = [0u8; 32];
+        dao[0..8].copy_from_slice(&(number * 1_000).to_le_bytes());
+        dao[8..16].copy_from_slice(&(10_000_000_000_000_000u64 + number).to_le_bytes());
+        dao[16..24].copy_from_slice(&(number * 10).to_le_bytes());
+        dao[24..32].copy_from_slice(&(number * 100).to_le_bytes());
+        dao
+    };
+    builder
+        .number(number.pack())
+        .timestamp((parent_timestamp + 8_000 + u64::from(seed)).pack())
+        .epoch(epoch.pack())
+        .compact_target(compact_target(number / 10).pack())
+        .dao(dao.pack())
+        .nonce((u128::from(seed) << 64 | u128::from(number)).pack())
+}
+
+/// The compact target of the blocks in the epoch, the difficulty is adjusted in each epoch.
+pub fn compact_target(epoch: core::EpochNumber) -> u32 {
+    0x1e08_3126 + epoch as u32 * 0x100
+}
+
+fn cell_output(seed: u8, type_opt: Option<packed::Script>) -> packed::CellOutput {
+    packed::CellOutput::new_builder()
+        .capacity(core::Capacity::shannons(10_000_000_000 + u64::from(seed)).pack())
+        .lock(lock_script(seed))
+        .type_(type_opt.pack())
+        .build()
+}
+
+// The data of a token cell, the amount is the first 16 bytes.
+fn udt_data(number: core::BlockNumber, seed: u8) -> Vec<u8> {
+    let amount = u128::from(number) * 1_000 + u128::from(seed);
+    let mut data = amount.to_le_bytes().to_vec();
+    data.push(seed);
+    data
+}
+
+// The miner of a block is the lock in the witness of the cellbase.
+fn cellbase_witness(seed: u8) -> packed::CellbaseWitness {
+    packed::CellbaseWitness::new_builder()
+        .lock(lock_script(seed))
+        .message(Bytes::from(vec![seed]).pack())
+        .build()
+}
+
+fn lock_script(seed: u8) -> packed::Script {
+    packed::Script::new_builder()
+        .code_hash([1u8; 32].pack())
+        .hash_type(core::ScriptHashType::Type.into())
+        .args(Bytes::from(vec![seed]).pack())
+        .build()
+}
+
+fn type_script(seed: u8) -> packed::Script {
+    packed::Script::new_builder()
+        .code_hash([2u8; 32].pack())
+        .hash_type(core::ScriptHashType::Data.into())
+        .args(Bytes::from(vec![seed]).pack())
+        .build()
+}
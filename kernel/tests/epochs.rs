@@ -0,0 +1,124 @@
+// Copyright (C) 2019-2020 Boyu Yang
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+mod common;
+
+use common::{compact_target, ChainBuilder, TestStorage};
+use uckb_scanner::{
+    traits::{BaseData as _, Epochs as _},
+    utilities::compact_to_difficulty,
+    Storage, Value,
+};
+
+const PRIMARY_EPOCH_REWARD: i64 = 191_780_821_917_808;
+
+// The fees of the transactions in the blocks, computed from the cells directly.
+fn fees(storage: &Storage, from: u64, to: u64) -> i64 {
+    let sql = r#"
+        SELECT CAST(i.capacity - COALESCE(o.capacity, 0) AS BIGINT)
+          FROM (SELECT consumed_tx_hash AS tx_hash, SUM(capacity) AS capacity
+                  FROM cells
+                 WHERE consumed_block_number BETWEEN $1 AND $2
+                 GROUP BY consumed_tx_hash) i
+          LEFT JOIN (SELECT tx_hash, SUM(capacity) AS capacity
+                       FROM cells
+                      GROUP BY tx_hash) o ON o.tx_hash = i.tx_hash
+    ;"#;
+    storage
+        .query(sql, &[(from as i64).into(), (to as i64).into()])
+        .unwrap()
+        .iter()
+        .map(|row| row.try_get::<i64>(0).unwrap().max(0))
+        .sum()
+}
+
+fn assert_epochs(storage: &Storage, chain: &ChainBuilder, tip: u64) {
+    let report = storage.epoch_summaries(100).unwrap();
+    assert_eq!(report.rows().len() as u64, tip / 10 + 1);
+    for (epoch, row) in report.rows().iter().enumerate() {
+        let epoch = epoch as u64;
+        let numbers = (epoch * 10..=tip.min(epoch * 10 + 9)).collect::<Vec<_>>();
+        let (start, end) = (numbers[0], *numbers.last().unwrap());
+        let blocks = numbers.iter().map(|number| chain.block(*number));
+        let transactions = blocks
+            .clone()
+            .map(|block| block.transactions().len() as i64)
+            .sum::<i64>();
+        let uncles = blocks
+            .map(|block| block.uncles().data().len() as i64)
+            .sum::<i64>();
+        // The synthetic chain has 10 blocks in each epoch, the total issuance increases by 1000
+        // in each block.
+        let extra = |number: &u64| if number % 10 < 8 { 1 } else { 0 };
+        let primary = numbers
+            .iter()
+            .filter(|number| **number > 0)
+            .map(|number| PRIMARY_EPOCH_REWARD / 10 + extra(number))
+            .sum::<i64>();
+        let issuance = (end - start.max(1) + 1) as i64 * 1_000;
+        let expected: Vec<Value> = vec![
+            (epoch as i64).into(),
+            (start as i64).into(),
+            (end as i64).into(),
+            (chain.block(start).timestamp() as i64).into(),
+            (chain.block(end).timestamp() as i64).into(),
+            (numbers.len() as i64).into(),
+            transactions.into(),
+            uncles.into(),
+            fees(storage, start, end).into(),
+            primary.into(),
+            (issuance - primary).into(),
+            issuance.into(),
+            i64::from(compact_target(epoch)).into(),
+            compact_to_difficulty(compact_target(epoch)).into(),
+        ];
+        assert_eq!(row, &expected);
+    }
+    let report = storage.epoch_summaries(1).unwrap();
+    assert_eq!(report.rows().len(), 1);
+    assert_eq!(report.rows()[0][0], Value::Integer((tip / 10) as i64));
+}
+
+#[test]
+fn summarize_epochs_through_reorganizations() {
+    for storage in TestStorage::all("summarize_epochs_through_reorganizations") {
+        let mut chain_a = ChainBuilder::new();
+        chain_a.extend(25, &[]);
+        let mut chain_b = chain_a.fork(19, 1);
+        chain_b.extend(3, &[chain_a.block(20), chain_a.block(21)]);
+        let source = chain_a.source();
+        let tip = storage.sync(&source);
+        assert_epochs(&storage.connect(), &chain_a, tip);
+        chain_b.apply_to(&source);
+        let tip = storage.sync(&source);
+        assert_eq!(tip, 22);
+        assert_epochs(&storage.connect(), &chain_b, tip);
+
+        let reorganized = storage.snapshot();
+        storage.reset();
+        storage.sync(&chain_b.source());
+        assert_eq!(storage.snapshot(), reorganized);
+    }
+}
+
+#[test]
+fn fill_epochs_of_existing_storages() {
+    for storage in TestStorage::all("fill_epochs_of_existing_storages") {
+        let mut chain_a = ChainBuilder::new();
+        chain_a.extend(12, &[]);
+        let mut chain = chain_a.fork(8, 1);
+        chain.extend(16, &[chain_a.block(9)]);
+        storage.sync(&chain.source());
+        let expected = storage.snapshot();
+
+        let mut conn = storage.connect();
+        conn.query("DROP TABLE epochs;", &[]).unwrap();
+        assert_eq!(conn.initialize().unwrap(), Some(24));
+        assert_eq!(storage.snapshot(), expected);
+    }
+}
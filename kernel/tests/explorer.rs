@@ -0,0 +1,102 @@
+// Copyright (C) 2019-2020 Boyu Yang
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+mod common;
+
+use common::{ChainBuilder, TestStorage};
+use uckb_scanner::{traits::Explorer as _, BlockId, CellFilter};
+
+#[test]
+fn explore_blocks_transactions_and_cells() {
+    for storage in TestStorage::all("explore_blocks_transactions_and_cells") {
+        let mut chain = ChainBuilder::new();
+        chain.extend(12, &[]);
+        storage.sync(&chain.source());
+        let storage = storage.connect();
+
+        let block = chain.block(5);
+        let hash = block.hash().raw_data().to_vec();
+        let by_number = storage.block(&BlockId::Number(5)).unwrap().unwrap();
+        let by_hash = storage
+            .block(&BlockId::Hash(hash.clone()))
+            .unwrap()
+            .unwrap();
+        assert_eq!(by_number.values(), by_hash.values());
+        assert!(storage.block(&BlockId::Number(13)).unwrap().is_none());
+        assert_eq!(storage.block_transactions(&hash).unwrap().len(), 3);
+        assert_eq!(storage.block_proposals(&hash).unwrap().len(), 2);
+
+        let numbers = |before, limit| {
+            storage
+                .blocks(before, limit)
+                .unwrap()
+                .iter()
+                .map(|row| row.try_get::<i64>(0).unwrap())
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(numbers(None, 3), vec![12, 11, 10]);
+        assert_eq!(numbers(Some(2), 3), vec![1, 0]);
+
+        let tx = block.transactions()[1].clone();
+        let tx_hash = tx.hash().raw_data().to_vec();
+        let row = storage.transaction(&tx_hash).unwrap().unwrap();
+        assert_eq!(row.try_get::<i64>(2).unwrap(), 5);
+        assert_eq!(storage.transaction_inputs(&tx_hash).unwrap().len(), 2);
+        assert_eq!(storage.transaction_outputs(&tx_hash).unwrap().len(), 2);
+        assert_eq!(storage.transaction_cell_deps(&tx_hash).unwrap().len(), 1);
+        assert_eq!(storage.transaction_header_deps(&tx_hash).unwrap().len(), 1);
+        assert_eq!(storage.transaction_witnesses(&tx_hash).unwrap().len(), 1);
+
+        // The cellbases of a chain without forks are locked by the same script.
+        let lock_hash = block.transactions()[0]
+            .outputs()
+            .get(0)
+            .unwrap()
+            .lock()
+            .calc_script_hash()
+            .raw_data()
+            .to_vec();
+        let mut filter = CellFilter {
+            lock_hash: Some(lock_hash.clone()),
+            ..Default::default()
+        };
+        let all = storage.cells(&filter, None, 1000).unwrap();
+        let mut paged = Vec::new();
+        let mut cursor: Option<(Vec<u8>, u32)> = None;
+        loop {
+            let after = cursor
+                .as_ref()
+                .map(|(tx_hash, index)| (tx_hash.as_slice(), *index));
+            let rows = storage.cells(&filter, after, 4).unwrap();
+            if let Some(last) = rows.last() {
+                let tx_hash = last.try_get::<Vec<u8>>(0).unwrap();
+                let index = last.try_get::<i64>(1).unwrap() as u32;
+                cursor = Some((tx_hash, index));
+            } else {
+                break;
+            }
+            paged.extend(rows.into_iter().map(|row| row.into_values()));
+        }
+        assert!(all.len() > 4);
+        assert_eq!(
+            all.into_iter()
+                .map(|row| row.into_values())
+                .collect::<Vec<_>>(),
+            paged
+        );
+        filter.live = Some(true);
+        let live = storage.cells(&filter, None, 1000).unwrap().len();
+        filter.live = Some(false);
+        let dead = storage.cells(&filter, None, 1000).unwrap().len();
+        assert_eq!(live + dead, paged.len());
+
+        let script = storage.script(&lock_hash).unwrap().unwrap();
+        assert_eq!(script.try_get::<i64>(4).unwrap(), paged.len() as i64);
+        assert_eq!(script.try_get::<i64>(5).unwrap(), live as i64);
+    }
+}
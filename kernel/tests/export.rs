@@ -0,0 +1,49 @@
+// Copyright (C) 2019-2020 Boyu Yang
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+mod common;
+
+use common::{ChainBuilder, TestStorage};
+use uckb_scanner::{traits::Export as _, Dataset};
+
+#[test]
+fn export_all_datasets() {
+    for storage in TestStorage::all("export_all_datasets") {
+        let mut chain = ChainBuilder::new();
+        chain.extend(6, &[]);
+        let mut fork = chain.fork(2, 1);
+        fork.extend(5, &[chain.block(3)]);
+        let source = chain.source();
+        storage.sync(&source);
+        fork.apply_to(&source);
+        storage.sync(&source);
+
+        let storage = storage.connect();
+        assert_eq!(storage.export_tip().unwrap(), Some(7));
+        for dataset in Dataset::ALL {
+            let rows = storage.export(*dataset, 0, 7).unwrap();
+            let (head, tail) = (
+                storage.export(*dataset, 0, 3).unwrap(),
+                storage.export(*dataset, 4, 7).unwrap(),
+            );
+            for row in &rows {
+                assert_eq!(row.values().len(), dataset.columns().len());
+            }
+            if *dataset != Dataset::Scripts {
+                assert_eq!(rows.len(), head.len() + tail.len(), "{}", dataset);
+            }
+        }
+        let count = |dataset, start, end| storage.export(dataset, start, end).unwrap().len();
+        assert_eq!(count(Dataset::BlockHeaders, 0, 7), 8);
+        assert_eq!(count(Dataset::BlockUncles, 3, 3), 1);
+        assert_eq!(count(Dataset::Transactions, 1, 7), 7 * 3);
+        assert_eq!(count(Dataset::Cells, 0, 0), 5);
+        assert_eq!(count(Dataset::ConsumedCells, 1, 7), 7 * 3);
+        assert_eq!(count(Dataset::TxWitnesses, 0, 0), 1);
+    }
+}
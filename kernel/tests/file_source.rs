@@ -0,0 +1,91 @@
+// Copyright (C) 2019-2020 Boyu Yang
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+mod common;
+
+use common::{ChainBuilder, TestStorage};
+use uckb_jsonrpc_core::types::{core, prelude::*};
+use uckb_scanner::{error::Error, traits::BlockSource as _, FileSource, Syncer};
+
+// Dumps the blocks in molecule-encoded hex, one block in each line.
+fn dump(blocks: &[core::BlockView]) -> String {
+    blocks
+        .iter()
+        .map(|block| {
+            let hex = block
+                .data()
+                .as_slice()
+                .iter()
+                .map(|byte| format!("{:02x}", byte))
+                .collect::<String>();
+            format!("0x{}\n", hex)
+        })
+        .collect()
+}
+
+fn load(blocks: &[core::BlockView]) -> Result<FileSource, Error> {
+    FileSource::load(dump(blocks).as_bytes())
+}
+
+#[test]
+fn load_chained_dumps_only() {
+    let mut chain = ChainBuilder::new();
+    chain.extend(6, &[]);
+    let blocks = (0..=6)
+        .map(|number| chain.block(number))
+        .collect::<Vec<_>>();
+
+    let source = load(&blocks[2..]).unwrap();
+    assert_eq!(source.tip_number().unwrap(), 6);
+    assert!(source.block_by_number(1).unwrap().is_none());
+
+    let gapped = [&blocks[..3], &blocks[4..]].concat();
+    assert!(matches!(load(&gapped), Err(Error::Data(_))));
+
+    let mut fork = chain.fork(3, 1);
+    fork.extend(3, &[]);
+    let forked = [&blocks[..4], &[fork.block(4), fork.block(5)][..]].concat();
+    assert_eq!(load(&forked).unwrap().tip_number().unwrap(), 5);
+    let unchained = [&blocks[..5], &[fork.block(5)][..]].concat();
+    assert!(matches!(load(&unchained), Err(Error::Data(_))));
+}
+
+#[test]
+fn stop_syncing_when_blocks_are_missing() {
+    for storage in TestStorage::all("stop_syncing_when_blocks_are_missing") {
+        let mut chain = ChainBuilder::new();
+        chain.extend(10, &[]);
+        let blocks = (0..=10)
+            .map(|number| chain.block(number))
+            .collect::<Vec<_>>();
+        let source = load(&blocks[..=4]).unwrap();
+        assert_eq!(
+            Syncer::new(source, storage.connect()).run_to_tip().unwrap(),
+            4
+        );
+
+        // The dump starts above the next height of the storage.
+        let source = load(&blocks[7..]).unwrap();
+        let result = Syncer::new(source, storage.connect()).run_to_tip();
+        assert!(matches!(result, Err(Error::Data(_))));
+
+        // The dump forks before the stored tip, the stored tip is rolled back, but the dump
+        // doesn't have its replacement.
+        let mut fork = chain.fork(3, 1);
+        fork.extend(2, &[]);
+        let source = load(&[fork.block(5)]).unwrap();
+        let result = Syncer::new(source, storage.connect()).run_to_tip();
+        assert!(matches!(result, Err(Error::Data(_))));
+
+        let source = load(&blocks).unwrap();
+        assert_eq!(
+            Syncer::new(source, storage.connect()).run_to_tip().unwrap(),
+            10
+        );
+    }
+}
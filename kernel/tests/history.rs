@@ -0,0 +1,109 @@
+// Copyright (C) 2019-2020 Boyu Yang
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+mod common;
+
+use common::{ChainBuilder, TestStorage};
+use uckb_scanner::{
+    traits::{BaseData as _, Explorer as _, Stats as _},
+    CellFilter, MemorySource, Row, Value,
+};
+
+const HEIGHTS: &[u64] = &[0, 3, 6, 9];
+
+// The out points and the capacities of the cells.
+fn out_points(rows: Vec<Row>) -> Vec<Vec<Value>> {
+    rows.into_iter()
+        .map(|row| {
+            let mut values = row.into_values();
+            values.truncate(4);
+            values
+        })
+        .collect()
+}
+
+#[test]
+fn query_the_state_at_past_heights() {
+    for storage in TestStorage::all("query_the_state_at_past_heights") {
+        let mut chain = ChainBuilder::new();
+        chain.extend(12, &[]);
+        let lock_hash = chain.block(0).transactions()[0]
+            .outputs()
+            .get(0)
+            .unwrap()
+            .lock()
+            .calc_script_hash()
+            .raw_data()
+            .to_vec();
+        let filter = CellFilter {
+            lock_hash: Some(lock_hash),
+            live: Some(true),
+            ..Default::default()
+        };
+
+        // The current states when the chain is synchronized to each height.
+        let source = MemorySource::new();
+        let mut expected = Vec::new();
+        for number in HEIGHTS {
+            chain.fork(*number, 0).apply_to(&source);
+            storage.sync(&source);
+            let storage = storage.connect();
+            let summary = storage.summary().unwrap().rows()[0].clone();
+            let balances = storage
+                .richlist(None, 100, &Default::default())
+                .unwrap()
+                .rows()
+                .to_owned();
+            let cells = out_points(storage.cells(&filter, None, 1000).unwrap());
+            expected.push((summary[6..].to_vec(), balances, cells));
+        }
+
+        chain.apply_to(&source);
+        storage.sync(&source);
+        let storage = storage.connect();
+        let supply = storage.supply(HEIGHTS).unwrap();
+        for (i, number) in HEIGHTS.iter().enumerate() {
+            let (ref live, ref balances, ref cells) = expected[i];
+            let row = &supply.rows()[i];
+            assert_eq!(row[0], Value::Integer(*number as i64));
+            assert_eq!(&row[1..], &live[..], "supply at {}", number);
+            let report = storage
+                .richlist(Some(*number), 100, &Default::default())
+                .unwrap();
+            assert_eq!(report.rows(), &balances[..], "balances at {}", number);
+            let filter = CellFilter {
+                at: Some(*number),
+                ..filter.clone()
+            };
+            let rows = storage.cells(&filter, None, 1000).unwrap();
+            assert_eq!(&out_points(rows), cells, "cells at {}", number);
+        }
+    }
+}
+
+#[test]
+fn upgrade_cells_without_block_numbers() {
+    for storage in TestStorage::all("upgrade_cells_without_block_numbers") {
+        let mut chain = ChainBuilder::new();
+        chain.extend(8, &[]);
+        storage.sync(&chain.source());
+        let expected = storage.snapshot();
+
+        let mut conn = storage.connect();
+        for sql in &[
+            "DROP INDEX cells_created_block_number_idx;",
+            "DROP INDEX cells_consumed_block_number_idx;",
+            "ALTER TABLE cells DROP COLUMN created_block_number;",
+            "ALTER TABLE cells DROP COLUMN consumed_block_number;",
+        ] {
+            conn.query(sql, &[]).unwrap();
+        }
+        assert_eq!(conn.initialize().unwrap(), Some(8));
+        assert_eq!(storage.snapshot(), expected);
+    }
+}
@@ -0,0 +1,131 @@
+// Copyright (C) 2019-2020 Boyu Yang
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+mod common;
+
+use common::{ChainBuilder, TestStorage};
+use uckb_jsonrpc_core::types::{packed, prelude::*};
+use uckb_scanner::{
+    traits::Indexer as _, IndexerCell, IndexerTx, Order, ScriptType, SearchKey, Storage,
+};
+
+fn search_key(script: packed::Script) -> SearchKey {
+    SearchKey {
+        script,
+        script_type: ScriptType::Lock,
+        filter_script: None,
+        output_data_len_range: None,
+        output_capacity_range: None,
+        block_range: None,
+    }
+}
+
+fn all_cells(storage: &Storage, key: &SearchKey, order: Order) -> Vec<IndexerCell> {
+    let mut cells = Vec::new();
+    let mut cursor: Option<Vec<u8>> = None;
+    loop {
+        let page = storage
+            .indexer_cells(key, order, 4, cursor.as_deref())
+            .unwrap();
+        if page.objects.is_empty() {
+            break;
+        }
+        cells.extend(page.objects);
+        cursor = Some(page.last_cursor);
+    }
+    cells
+}
+
+fn all_transactions(storage: &Storage, key: &SearchKey) -> Vec<IndexerTx> {
+    let mut txs = Vec::new();
+    let mut cursor: Option<Vec<u8>> = None;
+    loop {
+        let page = storage
+            .indexer_transactions(key, Order::Asc, 3, cursor.as_deref())
+            .unwrap();
+        if page.objects.is_empty() {
+            break;
+        }
+        txs.extend(page.objects);
+        cursor = Some(page.last_cursor);
+    }
+    txs
+}
+
+fn out_points(cells: &[IndexerCell]) -> Vec<packed::OutPoint> {
+    cells.iter().map(|cell| cell.out_point.clone()).collect()
+}
+
+#[test]
+fn search_cells_and_transactions_by_scripts() {
+    for storage in TestStorage::all("search_cells_and_transactions_by_scripts") {
+        let mut chain = ChainBuilder::new();
+        chain.extend(12, &[]);
+        storage.sync(&chain.source());
+        let storage = storage.connect();
+
+        let tip = chain.block(12);
+        assert_eq!(
+            storage.indexer_tip().unwrap(),
+            Some((tip.hash(), tip.number()))
+        );
+
+        // The cellbases of a chain without forks are locked by the same script.
+        let lock = tip.transactions()[0].outputs().get(0).unwrap().lock();
+        let key = search_key(lock.clone());
+        let asc = all_cells(&storage, &key, Order::Asc);
+        let mut desc = all_cells(&storage, &key, Order::Desc);
+        assert!(asc.len() > 4);
+        desc.reverse();
+        assert_eq!(out_points(&asc), out_points(&desc));
+        let positions = asc
+            .iter()
+            .map(|cell| (cell.block_number, cell.tx_index, cell.out_point.index()))
+            .collect::<Vec<_>>();
+        assert!(positions
+            .windows(2)
+            .all(|pair| (pair[0].0, pair[0].1) <= (pair[1].0, pair[1].1)));
+        assert!(asc.iter().all(|cell| cell.output.lock() == lock));
+
+        let capacity = asc
+            .iter()
+            .map(|cell| Unpack::<u64>::unpack(&cell.output.capacity()))
+            .sum::<u64>();
+        assert_eq!(storage.indexer_cells_capacity(&key).unwrap(), capacity);
+
+        // The args is matched as a prefix.
+        let prefix_key = search_key(lock.as_builder().args(packed::Bytes::default()).build());
+        let prefix = all_cells(&storage, &prefix_key, Order::Asc);
+        assert!(prefix.len() > asc.len());
+
+        let mut ranged_key = key.clone();
+        ranged_key.block_range = Some((8, 11));
+        let ranged = all_cells(&storage, &ranged_key, Order::Asc);
+        let expected = asc
+            .iter()
+            .filter(|cell| cell.block_number >= 8 && cell.block_number < 11)
+            .cloned()
+            .collect::<Vec<_>>();
+        assert!(!ranged.is_empty());
+        assert_eq!(out_points(&ranged), out_points(&expected));
+
+        let txs = all_transactions(&storage, &key);
+        let inputs = txs.iter().filter(|tx| tx.is_input).count();
+        assert!(inputs > 0);
+        assert!(txs.len() > inputs);
+        let positions = txs
+            .iter()
+            .map(|tx| (tx.block_number, tx.tx_index, tx.io_index, tx.is_input))
+            .collect::<Vec<_>>();
+        let mut sorted = positions.clone();
+        sorted.sort_by_key(|&(number, tx_index, io_index, is_input)| {
+            (number, tx_index, io_index, !is_input)
+        });
+        assert_eq!(positions, sorted);
+    }
+}
@@ -0,0 +1,274 @@
+// Copyright (C) 2019-2020 Boyu Yang
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+mod common;
+
+use std::collections::BTreeMap;
+
+use common::{ChainBuilder, TestStorage};
+use uckb_jsonrpc_core::types::prelude::*;
+use uckb_scanner::{
+    traits::{BaseData as _, Mining as _},
+    Storage, Value,
+};
+
+const PRIMARY_EPOCH_REWARD: u64 = 191_780_821_917_808;
+const SECONDARY_EPOCH_REWARD: u64 = 61_369_863_013_698;
+
+// The fees of the transactions in the block, computed from the cells directly.
+fn fees(storage: &Storage, number: i64) -> Vec<u64> {
+    let sql = r#"
+        SELECT CAST(i.capacity - COALESCE(o.capacity, 0) AS BIGINT)
+          FROM (SELECT consumed_tx_hash AS tx_hash, SUM(capacity) AS capacity
+                  FROM cells
+                 WHERE consumed_block_number = $1
+                 GROUP BY consumed_tx_hash) i
+          LEFT JOIN (SELECT tx_hash, SUM(capacity) AS capacity
+                       FROM cells
+                      GROUP BY tx_hash) o ON o.tx_hash = i.tx_hash
+    ;"#;
+    storage
+        .query(sql, &[number.into()])
+        .unwrap()
+        .iter()
+        .map(|row| row.try_get::<i64>(0).unwrap().max(0) as u64)
+        .collect()
+}
+
+// The fee of the transaction, computed from the cells directly.
+fn fee(storage: &Storage, tx_hash: &[u8]) -> u64 {
+    let sql = r#"
+        SELECT CAST((SELECT SUM(capacity) FROM cells WHERE consumed_tx_hash = $1)
+                    - (SELECT SUM(capacity) FROM cells WHERE tx_hash = $1) AS BIGINT)
+    ;"#;
+    let row = storage.query(sql, &[tx_hash.into()]).unwrap().remove(0);
+    row.try_get::<i64>(0).unwrap() as u64
+}
+
+fn integer(value: &Value) -> Option<i64> {
+    match value {
+        Value::Integer(value) => Some(*value),
+        Value::Null => None,
+        _ => panic!("unexpected value {:?}", value),
+    }
+}
+
+fn assert_miners(storage: &Storage, chain: &ChainBuilder, tip: u64) {
+    let report = storage.block_rewards(500).unwrap();
+    let mut total_commit = 0;
+    // The genesis block has no miner.
+    assert_eq!(report.rows().len() as u64, tip);
+    for row in report.rows() {
+        let number = integer(&row[0]).unwrap() as u64;
+        let block = chain.block(number);
+        let seed = block.transactions()[0]
+            .outputs()
+            .get(0)
+            .unwrap()
+            .lock()
+            .args();
+        assert_eq!(row[4], Value::Bytes(seed.raw_data().to_vec()));
+        let rewards = row[5..].iter().map(integer).collect::<Vec<_>>();
+        if number + 11 > tip {
+            assert!(rewards.iter().all(Option::is_none));
+            continue;
+        }
+        let rewards = rewards.into_iter().map(Option::unwrap).collect::<Vec<_>>();
+        let cellbase = chain.block(number + 11).transactions()[0].outputs_capacity();
+        assert_eq!(rewards[0] as u64, cellbase.unwrap().as_u64());
+        // The synthetic chain has 10 blocks in each epoch.
+        let index = number % 10;
+        let extra = |reward: u64| if index < reward % 10 { 1 } else { 0 };
+        let primary = PRIMARY_EPOCH_REWARD / 10 + extra(PRIMARY_EPOCH_REWARD);
+        assert_eq!(rewards[1] as u64, primary);
+        // The occupied capacity is a tenth in the synthetic chain.
+        let secondary = if number > 1 {
+            (SECONDARY_EPOCH_REWARD / 10 + extra(SECONDARY_EPOCH_REWARD)) / 10
+        } else {
+            0
+        };
+        assert_eq!(rewards[2] as u64, secondary);
+        let commit = fees(storage, number as i64)
+            .into_iter()
+            .map(|fee| fee - fee * 4 / 10)
+            .sum::<u64>();
+        assert_eq!(rewards[3] as u64, commit);
+        total_commit += commit;
+        // The transactions are proposed and committed in the same block.
+        assert_eq!(rewards[4], 0);
+    }
+    assert!(total_commit > 0);
+
+    let mut blocks = BTreeMap::<i64, i64>::new();
+    let mut shares = BTreeMap::<i64, f64>::new();
+    for row in storage.miners_by_epoch(100).unwrap().rows() {
+        let epoch = integer(&row[0]).unwrap();
+        *blocks.entry(epoch).or_default() += integer(&row[5]).unwrap();
+        *shares.entry(epoch).or_default() += match row[6] {
+            Value::Float(share) => share,
+            _ => panic!("unexpected row {:?}", row),
+        };
+    }
+    assert_eq!(blocks.values().sum::<i64>() as u64, tip);
+    for (epoch, count) in blocks {
+        let expected = (1..=tip).filter(|n| (n / 10) as i64 == epoch).count();
+        assert_eq!(count as usize, expected);
+        assert!((shares[&epoch] - 1.0).abs() < 1e-9);
+    }
+    let report = storage.miners_by_epoch(1).unwrap();
+    assert!(report
+        .rows()
+        .iter()
+        .all(|row| integer(&row[0]) == Some((tip / 10) as i64)));
+
+    // The timestamps of the synthetic chain are in the first day of the Unix epoch.
+    let report = storage.miners_by_day(7).unwrap();
+    let mut count = 0;
+    for row in report.rows() {
+        assert_eq!(row[0], Value::Text("1970-01-01".to_owned()));
+        count += integer(&row[5]).unwrap();
+    }
+    assert_eq!(count as u64, tip);
+}
+
+#[test]
+fn attribute_blocks_to_miners_through_reorganizations() {
+    for storage in TestStorage::all("attribute_blocks_to_miners_through_reorganizations") {
+        let mut chain_a = ChainBuilder::new();
+        chain_a.extend(16, &[]);
+        let mut chain_b = chain_a.fork(9, 1);
+        chain_b.extend(14, &[chain_a.block(10)]);
+        let source = chain_a.source();
+        let tip = storage.sync(&source);
+        assert_miners(&storage.connect(), &chain_a, tip);
+        chain_b.apply_to(&source);
+        let tip = storage.sync(&source);
+        assert_miners(&storage.connect(), &chain_b, tip);
+
+        let reorganized = storage.snapshot();
+        storage.reset();
+        storage.sync(&chain_b.source());
+        assert_eq!(storage.snapshot(), reorganized);
+    }
+}
+
+#[test]
+fn fill_miners_of_existing_storages() {
+    for storage in TestStorage::all("fill_miners_of_existing_storages") {
+        let mut chain_a = ChainBuilder::new();
+        chain_a.extend(8, &[]);
+        let mut chain = chain_a.fork(4, 1);
+        chain.extend(12, &[chain_a.block(5)]);
+        storage.sync(&chain.source());
+        let expected = storage.snapshot();
+
+        let mut conn = storage.connect();
+        conn.query("DROP TABLE block_miners;", &[]).unwrap();
+        assert_eq!(conn.initialize().unwrap(), Some(16));
+        assert_eq!(storage.snapshot(), expected);
+
+        // The filling is interrupted after the block 4, whose rewards are not paid yet.
+        let sqls = &[
+            "DELETE FROM block_miners WHERE number > 4;",
+            r#"
+            UPDATE block_miners
+               SET total_reward = NULL,
+                   primary_reward = NULL,
+                   secondary_reward = NULL,
+                   commit_reward = NULL,
+                   proposal_reward = NULL
+            ;"#,
+        ];
+        for sql in sqls {
+            conn.query(sql, &[]).unwrap();
+        }
+        assert_eq!(conn.initialize().unwrap(), Some(16));
+        assert_eq!(storage.snapshot(), expected);
+    }
+}
+
+#[test]
+fn report_uncles_and_their_miners() {
+    for storage in TestStorage::all("report_uncles_and_their_miners") {
+        let mut chain_a = ChainBuilder::new();
+        chain_a.extend(25, &[]);
+        let mut chain_c = chain_a.fork(15, 2);
+        chain_c.extend(3, &[]);
+        let mut chain_b = chain_a.fork(18, 1);
+        chain_b
+            .extend(2, &[])
+            .extend(2, &[chain_a.block(19), chain_a.block(20)])
+            .extend(8, &[chain_c.block(17)]);
+        let source = chain_a.source();
+        storage.sync(&source);
+        chain_b.apply_to(&source);
+        assert_eq!(storage.sync(&source), 30);
+        let storage = storage.connect();
+
+        let report = storage.uncles_by_epoch(10).unwrap();
+        assert_eq!(report.rows().len(), 4);
+        for row in report.rows() {
+            if row[0] == Value::Integer(2) {
+                // The blocks 19 and 20 are included by the block 21, the block 17 of another
+                // fork is included by the block 23.
+                assert_eq!(row[1..3], [Value::Integer(10), Value::Integer(3)]);
+                assert_eq!(row[3], Value::Float(3.0 / 13.0));
+                assert_eq!(row[4], Value::Integer(2));
+                assert_eq!(row[5], Value::Integer(1));
+                assert_eq!(row[6], Value::Float(3.0));
+                assert_eq!(row[7], Value::Integer(6));
+            } else {
+                assert_eq!(row[2..6], [0.into(), 0.0.into(), 0.into(), Value::Null]);
+            }
+        }
+        assert_eq!(storage.uncles_by_epoch(1).unwrap().rows().len(), 1);
+
+        // Only the orphaned blocks which were synchronized have known miners.
+        let report = storage.uncle_miners_by_epoch(10).unwrap();
+        assert_eq!(report.rows().len(), 1);
+        let row = &report.rows()[0];
+        assert_eq!(row[0], Value::Integer(2));
+        assert_eq!(row[4], Value::Bytes(vec![0]));
+        assert_eq!(row[5], Value::Integer(2));
+    }
+}
+
+// The first transaction of the block 15 is proposed by the block 4, which is too far, by the
+// block 8, which is the first proposer in the window, and by the block 12, which competes with
+// the block 8.
+#[test]
+fn reward_the_first_proposers_in_the_window() {
+    for storage in TestStorage::all("reward_the_first_proposers_in_the_window") {
+        let mut chain_a = ChainBuilder::new();
+        chain_a.extend(30, &[]);
+        let txs = [chain_a.block(15).transactions()[1].clone()];
+        let mut chain = chain_a.fork(3, 0);
+        chain
+            .propose(&txs)
+            .extend(4, &[])
+            .propose(&txs)
+            .extend(4, &[])
+            .propose(&txs)
+            .extend(19, &[]);
+        assert_eq!(chain.block(15).tx_hashes(), chain_a.block(15).tx_hashes());
+        assert_eq!(storage.sync(&chain.source()), 30);
+        let storage = storage.connect();
+
+        let fee = fee(&storage, txs[0].hash().as_slice());
+        assert!(fee > 0);
+        let proposals = storage
+            .block_rewards(500)
+            .unwrap()
+            .rows()
+            .iter()
+            .filter_map(|row| Some((integer(&row[0])?, integer(&row[9])?)))
+            .filter(|(_, proposal)| *proposal > 0)
+            .collect::<Vec<_>>();
+        assert_eq!(proposals, vec![(8, (fee * 4 / 10) as i64)]);
+    }
+}
@@ -0,0 +1,131 @@
+// Copyright (C) 2019-2020 Boyu Yang
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+mod common;
+
+use common::{ChainBuilder, TestStorage};
+use uckb_scanner::{
+    traits::{BaseData as _, Proposals as _},
+    Value,
+};
+
+// The blocks propose their own transactions, which are not in the proposal windows, the chain
+// proposes some transactions in advance:
+// - The block 9 proposes the first transaction of the block 12, and the first transaction of
+//   the block 20, which is too far.
+// - The block 10 proposes both transactions of the block 12.
+// - The block 11 and an uncle which is included by the block 12 propose the first transaction
+//   of the block 14.
+fn build_chains() -> (ChainBuilder, ChainBuilder) {
+    let mut chain_a = ChainBuilder::new();
+    chain_a.extend(20, &[]);
+    let tx = |number: u64, index: usize| chain_a.block(number).transactions()[index].clone();
+    let mut chain = chain_a.fork(8, 0);
+    chain
+        .propose(&[tx(12, 1), tx(20, 1)])
+        .extend(1, &[])
+        .propose(&[tx(12, 1), tx(12, 2)])
+        .extend(1, &[]);
+    let mut uncle = chain.fork(10, 1);
+    uncle.propose(&[tx(14, 1)]).extend(1, &[]);
+    chain
+        .propose(&[tx(14, 1)])
+        .extend(1, &[])
+        .extend(9, &[uncle.block(11)]);
+    for number in 1..=20 {
+        assert_eq!(
+            chain.block(number).tx_hashes(),
+            chain_a.block(number).tx_hashes()
+        );
+    }
+    (chain_a, chain)
+}
+
+#[test]
+fn match_proposals_through_reorganizations() {
+    for storage in TestStorage::all("match_proposals_through_reorganizations") {
+        let (chain_a, chain) = build_chains();
+        let source = chain_a.source();
+        storage.sync(&source);
+        chain.apply_to(&source);
+        assert_eq!(storage.sync(&source), 20);
+
+        let conn = storage.connect();
+        let latencies = conn
+            .proposal_latencies(10)
+            .unwrap()
+            .rows()
+            .iter()
+            .map(|row| row[..3].to_vec())
+            .collect::<Vec<_>>();
+        let expected = vec![
+            vec![2.into(), Value::Null, 2.into()],
+            vec![1.into(), 2.into(), 1.into()],
+            vec![1.into(), 3.into(), 2.into()],
+            vec![1.into(), Value::Null, 17.into()],
+            vec![0.into(), Value::Null, 18.into()],
+        ];
+        assert_eq!(latencies, expected);
+
+        let report = conn.proposals_by_epoch(10).unwrap();
+        let expected: Vec<Vec<Value>> = vec![
+            vec![2.into(), 2.into(), 0.into(), 0.into(), 0.into(), 2.into()],
+            vec![1.into(), 23.into(), 3.into(), 4.into(), 2.into(), 20.into()],
+            vec![0.into(), 20.into(), 0.into(), 1.into(), 19.into(), 0.into()],
+        ];
+        assert_eq!(report.rows(), &expected[..]);
+        assert_eq!(conn.proposals_by_epoch(1).unwrap().rows().len(), 1);
+
+        let report = conn.uncommitted_proposals(100).unwrap();
+        assert_eq!(report.rows().len(), 21);
+        let numbers = report.rows()[..3]
+            .iter()
+            .map(|row| row[0].clone())
+            .collect::<Vec<_>>();
+        assert_eq!(numbers, vec![10.into(), 10.into(), 9.into()]);
+        let too_far = chain.block(20).transactions()[1].proposal_short_id();
+        assert!(report
+            .rows()
+            .iter()
+            .any(|row| row[3] == Value::Bytes(too_far.raw_data().to_vec())));
+
+        // The block 14 proposes its own transaction too.
+        let report = conn.duplicate_proposals(10).unwrap();
+        assert_eq!(report.rows().len(), 1);
+        let short_id = chain.block(14).transactions()[1].proposal_short_id();
+        let expected: Vec<Value> = vec![
+            short_id.raw_data().to_vec().into(),
+            3.into(),
+            1.into(),
+            2.into(),
+            11.into(),
+            14.into(),
+        ];
+        assert_eq!(report.rows()[0], expected);
+
+        let reorganized = storage.snapshot();
+        storage.reset();
+        storage.sync(&chain.source());
+        assert_eq!(storage.snapshot(), reorganized);
+    }
+}
+
+#[test]
+fn fill_proposals_of_existing_storages() {
+    for storage in TestStorage::all("fill_proposals_of_existing_storages") {
+        let (_, chain) = build_chains();
+        storage.sync(&chain.source());
+        let expected = storage.snapshot();
+
+        let mut conn = storage.connect();
+        conn.query("DROP TABLE transaction_proposals;", &[])
+            .unwrap();
+        assert_eq!(conn.initialize().unwrap(), Some(20));
+        assert_eq!(storage.snapshot(), expected);
+    }
+}
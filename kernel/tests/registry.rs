@@ -0,0 +1,120 @@
+// Copyright (C) 2019-2020 Boyu Yang
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+use std::fs;
+
+use uckb_scanner::{Network, Registry};
+
+fn hash(hex: &str) -> Vec<u8> {
+    let mut bytes = vec![0u8; 32];
+    faster_hex::hex_decode(hex.trim_start_matches("0x").as_bytes(), &mut bytes).unwrap();
+    bytes
+}
+
+#[test]
+fn presets_of_networks() {
+    let names = [
+        "secp256k1_blake160",
+        "secp256k1_multisig",
+        "dao",
+        "type_id",
+        "anyone_can_pay",
+        "sudt",
+        "cheque",
+    ];
+    for network in &[Network::Mainnet, Network::Testnet] {
+        let registry = Registry::builtin(*network);
+        for name in &names {
+            assert!(!registry.code_hashes(name).is_empty(), "{}", name);
+        }
+        for script in registry.scripts() {
+            assert_eq!(script.code_hash().len(), 32, "{}", script.name());
+        }
+        for cell_dep in registry.cell_deps() {
+            assert_eq!(cell_dep.tx_hash().len(), 32, "{}", cell_dep.name());
+        }
+    }
+
+    let mainnet = Registry::builtin(Network::Mainnet);
+    let testnet = Registry::builtin(Network::Testnet);
+    // The system scripts could be referred by the type hash or by the data hash.
+    let dao_type = hash("0x82d76d1b75fe2fd9a27dfbaa65a039221a380d76c926f378d3f81cf3e7e13f2e");
+    let dao_data = hash("0x32064a14ce10d95d4b7343054cc19d73b25b16ae61a6c681011ca781a60c7923");
+    for registry in &[&mainnet, &testnet] {
+        assert_eq!(registry.name(&dao_type, 1), Some("dao"));
+        assert_eq!(registry.name(&dao_data, 0), Some("dao"));
+        assert_eq!(registry.name(&dao_data, 1), None);
+    }
+    assert_ne!(mainnet.code_hashes("cheque"), testnet.code_hashes("cheque"));
+
+    // The lock of secp256k1 is provided by a dep group or by the cell of the code.
+    let dep_group = hash("0x71a7ba8fc96349fea0ed3a5c47992e3b4084b031a42264a018e0072e8172e46c");
+    let code = hash("0xe2fb199810d49a4d8beec56718ba2593b665db9d52299a0f9e6e75416d73ff5c");
+    assert_eq!(
+        mainnet.cell_dep_name(&dep_group, 0),
+        Some("secp256k1_blake160")
+    );
+    assert_eq!(
+        mainnet.cell_dep_name(&dep_group, 1),
+        Some("secp256k1_multisig")
+    );
+    assert_eq!(mainnet.cell_dep_name(&code, 1), Some("secp256k1_blake160"));
+    assert_eq!(mainnet.cell_dep_name(&code, 2), Some("dao"));
+    assert_eq!(testnet.cell_dep_name(&dep_group, 0), None);
+}
+
+#[test]
+fn detect_networks_by_genesis() {
+    let mainnet = hash("0x92b197aa1fba0f63633922c61c92375c9c074a93e85963554f5499fe1450d0e5");
+    let testnet = hash("0x10639e0895502b5688a6be8cf69460d76541bfa4821629d86d62ba0aae3f9606");
+    assert_eq!(Registry::detect_network(&mainnet), Some(Network::Mainnet));
+    assert_eq!(Registry::detect_network(&testnet), Some(Network::Testnet));
+    assert_eq!(Registry::detect_network(&[0u8; 32]), None);
+}
+
+#[test]
+fn load_cell_deps_from_file() {
+    let path = std::env::temp_dir().join("uckb-scanner-test-registry-cell-deps.json");
+    let content = format!(
+        r#"[{{
+            "name": "my_type",
+            "code_hash": "0x{}",
+            "hash_type": "type",
+            "cell_deps": [
+                {{"tx_hash": "0x{}", "index": 3, "dep_type": "code"}},
+                {{"tx_hash": "0x{}", "index": 0, "dep_type": "dep_group"}}
+            ]
+        }}]"#,
+        "01".repeat(32),
+        "02".repeat(32),
+        "03".repeat(32),
+    );
+    fs::write(&path, content).unwrap();
+    let mut registry = Registry::builtin(Network::Testnet);
+    let count = registry.cell_deps().len();
+    registry.load(&path).unwrap();
+    assert_eq!(registry.name(&[1u8; 32], 1), Some("my_type"));
+    assert_eq!(registry.cell_dep_name(&[2u8; 32], 3), Some("my_type"));
+    assert_eq!(registry.cell_dep_name(&[3u8; 32], 0), Some("my_type"));
+    assert_eq!(registry.cell_dep_name(&[3u8; 32], 1), None);
+    assert_eq!(registry.cell_deps().len(), count + 2);
+
+    let content = format!(
+        r#"[{{
+            "name": "bad",
+            "code_hash": "0x{}",
+            "hash_type": "type",
+            "cell_deps": [{{"tx_hash": "0x{}", "index": 0, "dep_type": "unknown"}}]
+        }}]"#,
+        "01".repeat(32),
+        "02".repeat(32),
+    );
+    fs::write(&path, content).unwrap();
+    assert!(registry.load(&path).is_err());
+    fs::remove_file(&path).unwrap();
+}
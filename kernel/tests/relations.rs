@@ -0,0 +1,111 @@
+// Copyright (C) 2019-2020 Boyu Yang
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+mod common;
+
+use common::{ChainBuilder, TestStorage};
+use uckb_scanner::traits::{Explorer as _, Relations as _};
+
+#[test]
+fn load_relations_of_many_keys() {
+    for storage in TestStorage::all("load_relations_of_many_keys") {
+        let mut chain_a = ChainBuilder::new();
+        chain_a.extend(5, &[]);
+        let mut chain = chain_a.fork(2, 1);
+        chain.extend(4, &[chain_a.block(3), chain_a.block(4)]);
+        storage.sync(&chain.source());
+        let storage = storage.connect();
+
+        let blocks = (0..=6)
+            .map(|number| chain.block(number))
+            .collect::<Vec<_>>();
+        // Many unknown keys, so the keys are queried in more than one chunk.
+        let mut block_hashes = (0..600u32)
+            .map(|i| i.to_le_bytes().repeat(8))
+            .collect::<Vec<_>>();
+        block_hashes.extend(blocks.iter().map(|block| block.hash().raw_data().to_vec()));
+        assert_eq!(
+            storage.headers_by_hashes(&block_hashes).unwrap().len(),
+            blocks.len()
+        );
+
+        let uncles = storage.uncles_by_block_hashes(&block_hashes).unwrap();
+        let uncle_hashes = uncles
+            .iter()
+            .map(|row| row.try_get::<Vec<u8>>(3).unwrap())
+            .collect::<Vec<_>>();
+        let expected = vec![
+            chain_a.block(3).hash().raw_data().to_vec(),
+            chain_a.block(4).hash().raw_data().to_vec(),
+        ];
+        assert_eq!(uncle_hashes, expected);
+        let including = chain.block(3).hash().raw_data().to_vec();
+        assert!(uncles
+            .iter()
+            .all(|row| row.try_get::<Vec<u8>>(0).unwrap() == including));
+
+        let txs = storage.transactions_by_block_hashes(&block_hashes).unwrap();
+        for block in &blocks {
+            let hash = block.hash().raw_data().to_vec();
+            let batched = txs
+                .iter()
+                .filter(|row| row.try_get::<Vec<u8>>(3).unwrap() == hash)
+                .map(|row| row.try_get::<Vec<u8>>(0).unwrap())
+                .collect::<Vec<_>>();
+            let single = storage
+                .block_transactions(&hash)
+                .unwrap()
+                .iter()
+                .map(|row| row.try_get::<Vec<u8>>(0).unwrap())
+                .collect::<Vec<_>>();
+            assert_eq!(batched, single);
+        }
+
+        let tx_hashes = txs
+            .iter()
+            .map(|row| row.try_get::<Vec<u8>>(0).unwrap())
+            .collect::<Vec<_>>();
+        assert_eq!(
+            storage.transactions_by_hashes(&tx_hashes).unwrap().len(),
+            tx_hashes.len()
+        );
+        let all_txs = blocks
+            .iter()
+            .flat_map(|block| block.transactions())
+            .collect::<Vec<_>>();
+        let outputs = storage.outputs_by_tx_hashes(&tx_hashes).unwrap();
+        assert_eq!(
+            outputs.len(),
+            all_txs.iter().map(|tx| tx.outputs().len()).sum::<usize>()
+        );
+        let inputs = storage.inputs_by_tx_hashes(&tx_hashes).unwrap();
+        assert_eq!(
+            inputs.len(),
+            all_txs
+                .iter()
+                .filter(|tx| !tx.is_cellbase())
+                .map(|tx| tx.inputs().len())
+                .sum::<usize>()
+        );
+        assert_eq!(
+            storage.witnesses_by_tx_hashes(&tx_hashes).unwrap().len(),
+            all_txs.iter().map(|tx| tx.witnesses().len()).sum::<usize>()
+        );
+
+        let out_points = outputs
+            .iter()
+            .map(|row| {
+                let tx_hash = row.try_get::<Vec<u8>>(0).unwrap();
+                let index = row.try_get::<i64>(1).unwrap() as u32;
+                (tx_hash, index)
+            })
+            .collect::<Vec<_>>();
+        let cells = storage.cells_by_out_points(&out_points).unwrap();
+        assert_eq!(cells.len(), outputs.len());
+    }
+}
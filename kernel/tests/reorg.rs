@@ -0,0 +1,170 @@
+// Copyright (C) 2019-2020 Boyu Yang
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+//! Synchronizes synthetic chains with forks into storages, and checks that the storage after
+//! reorganizations is the same as a fresh synchronization of the winning chain.
+//!
+//! All tests run against a temporary SQLite database. Set `UCKB_SCANNER_TEST_STORAGE_URI` to
+//! run them against a PostgreSQL database too, all tables in that database will be dropped.
+
+mod common;
+
+use common::{ChainBuilder, TestStorage};
+
+fn assert_same_as_fresh_sync(storage: &TestStorage, winner: &ChainBuilder) {
+    let reorganized = storage.snapshot();
+    storage.reset();
+    storage.sync(&winner.source());
+    let fresh = storage.snapshot();
+    assert_eq!(
+        reorganized.keys().collect::<Vec<_>>(),
+        fresh.keys().collect::<Vec<_>>()
+    );
+    for (table, rows) in fresh.iter() {
+        assert_eq!(&reorganized[table], rows, "table {} is different", table);
+    }
+}
+
+#[test]
+fn sync_a_chain() {
+    for storage in TestStorage::all("sync_a_chain") {
+        let mut chain = ChainBuilder::new();
+        chain.extend(8, &[]);
+        assert_eq!(storage.sync(&chain.source()), 8);
+        let snapshot = storage.snapshot();
+        assert_eq!(snapshot["block_headers"].len(), 9);
+        assert_eq!(snapshot["block_transactions"].len(), 2 + 8 * 3);
+        assert_eq!(snapshot["block_proposals"].len(), 8 * 2);
+        let consumed = snapshot["cells"]
+            .iter()
+            .filter(|row| !row.contains("consumed_tx_hash=null"))
+            .count();
+        assert_eq!(consumed, 8 * 3);
+    }
+}
+
+#[test]
+fn reorg_to_a_longer_fork() {
+    for storage in TestStorage::all("reorg_to_a_longer_fork") {
+        let mut chain_a = ChainBuilder::new();
+        chain_a.extend(6, &[]);
+        let mut chain_b = chain_a.fork(3, 1);
+        chain_b.extend(5, &[]);
+
+        let source = chain_a.source();
+        storage.sync(&source);
+        chain_b.apply_to(&source);
+        assert_eq!(storage.sync(&source), 8);
+        assert_same_as_fresh_sync(&storage, &chain_b);
+    }
+}
+
+#[test]
+fn reorg_with_orphaned_blocks_as_uncles() {
+    for storage in TestStorage::all("reorg_with_orphaned_blocks_as_uncles") {
+        let mut chain_a = ChainBuilder::new();
+        chain_a.extend(5, &[]);
+        let mut chain_b = chain_a.fork(2, 1);
+        chain_b.extend(4, &[chain_a.block(3), chain_a.block(4)]);
+
+        let source = chain_a.source();
+        storage.sync(&source);
+        chain_b.apply_to(&source);
+        storage.sync(&source);
+        assert_same_as_fresh_sync(&storage, &chain_b);
+    }
+}
+
+#[test]
+fn reorg_from_genesis() {
+    for storage in TestStorage::all("reorg_from_genesis") {
+        let mut chain_a = ChainBuilder::new();
+        chain_a.extend(4, &[]);
+        let mut chain_b = chain_a.fork(0, 1);
+        chain_b.extend(6, &[chain_a.block(1)]);
+
+        let source = chain_a.source();
+        storage.sync(&source);
+        chain_b.apply_to(&source);
+        storage.sync(&source);
+        assert_same_as_fresh_sync(&storage, &chain_b);
+    }
+}
+
+#[test]
+fn reorg_back_and_forth() {
+    for storage in TestStorage::all("reorg_back_and_forth") {
+        let mut chain_a = ChainBuilder::new();
+        chain_a.extend(5, &[]);
+        let mut chain_b = chain_a.fork(2, 1);
+        chain_b.extend(4, &[chain_a.block(3)]);
+        let mut chain_c = chain_a.clone();
+        chain_c.extend(3, &[chain_b.block(3), chain_b.block(4)]);
+
+        let source = chain_a.source();
+        storage.sync(&source);
+        chain_b.apply_to(&source);
+        storage.sync(&source);
+        chain_c.apply_to(&source);
+        assert_eq!(storage.sync(&source), 8);
+        assert_same_as_fresh_sync(&storage, &chain_c);
+    }
+}
+
+#[test]
+fn reorg_when_uncles_are_shared() {
+    for storage in TestStorage::all("reorg_when_uncles_are_shared") {
+        let mut chain_a = ChainBuilder::new();
+        chain_a.extend(4, &[]);
+        let mut chain_b = chain_a.fork(2, 1);
+        chain_b.extend(3, &[chain_a.block(3)]);
+        let mut chain_c = chain_a.fork(2, 2);
+        chain_c.extend(1, &[chain_a.block(3)]);
+        chain_c.extend(3, &[chain_b.block(3)]);
+
+        let source = chain_a.source();
+        storage.sync(&source);
+        chain_b.apply_to(&source);
+        storage.sync(&source);
+        chain_c.apply_to(&source);
+        storage.sync(&source);
+        assert_same_as_fresh_sync(&storage, &chain_c);
+    }
+}
+
+#[test]
+fn reorg_to_a_fork_at_the_same_height() {
+    for storage in TestStorage::all("reorg_to_a_fork_at_the_same_height") {
+        let mut chain_a = ChainBuilder::new();
+        chain_a.extend(6, &[]);
+        let mut chain_b = chain_a.fork(4, 1);
+        chain_b.extend(2, &[]);
+
+        let source = chain_a.source();
+        storage.sync(&source);
+        chain_b.apply_to(&source);
+        assert_eq!(storage.sync(&source), 6);
+        assert_same_as_fresh_sync(&storage, &chain_b);
+    }
+}
+
+#[test]
+fn reorg_to_a_shorter_fork() {
+    for storage in TestStorage::all("reorg_to_a_shorter_fork") {
+        let mut chain_a = ChainBuilder::new();
+        chain_a.extend(7, &[]);
+        let mut chain_b = chain_a.fork(3, 1);
+        chain_b.extend(2, &[chain_a.block(4)]);
+
+        let source = chain_a.source();
+        storage.sync(&source);
+        chain_b.apply_to(&source);
+        assert_eq!(storage.sync(&source), 5);
+        assert_same_as_fresh_sync(&storage, &chain_b);
+    }
+}
@@ -0,0 +1,186 @@
+// Copyright (C) 2019-2020 Boyu Yang
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+mod common;
+
+use std::collections::{BTreeMap, HashMap, HashSet};
+
+use common::{ChainBuilder, TestStorage};
+use uckb_jsonrpc_core::types::{core, packed, prelude::*};
+use uckb_scanner::{
+    traits::{BaseData as _, Rollups as _},
+    Report, Value,
+};
+
+const HOUR_MILLIS: u64 = 60 * 60 * 1_000;
+const DAY_MILLIS: u64 = 24 * HOUR_MILLIS;
+
+// A block is mined every 50 minutes, so an hour has one or two blocks, and the block 29 is the
+// first one of the second day.
+fn spread(chain: &mut ChainBuilder, from: u64, to: u64) {
+    for number in from..=to {
+        let timestamp = number * 50 * 60 * 1_000;
+        chain.modify(number, |builder| builder.timestamp(timestamp.pack()));
+    }
+}
+
+// The rollups of the chain, computed from the blocks directly.
+fn rollups(chain: &ChainBuilder, tip: u64, millis: u64) -> Vec<Vec<i64>> {
+    let mut cells: HashMap<Vec<u8>, (u64, u64, Vec<u8>)> = HashMap::new();
+    let mut seen_locks = HashSet::new();
+    let mut active_locks: HashMap<u64, HashSet<Vec<u8>>> = HashMap::new();
+    let mut rollups: BTreeMap<u64, Vec<i64>> = BTreeMap::new();
+    for number in 0..=tip {
+        let block = chain.block(number);
+        let period = block.timestamp() / millis;
+        let mut changes = vec![0i64; 10];
+        changes[0] = 1;
+        changes[1] = block.transactions().len() as i64;
+        for (tx_index, tx) in block.transactions().into_iter().enumerate() {
+            let mut inputs_capacity = 0;
+            if tx_index != 0 {
+                for input in tx.inputs().into_iter() {
+                    let (created, capacity, lock_hash) =
+                        cells[input.previous_output().as_slice()].clone();
+                    let created_period = chain.block(created).timestamp() / millis;
+                    changes[2] += 1;
+                    if created_period != period {
+                        changes[5] += 1;
+                    } else {
+                        changes[4] -= 1;
+                    }
+                    changes[9] += capacity as i64;
+                    inputs_capacity += capacity;
+                    active_locks.entry(period).or_default().insert(lock_hash);
+                }
+            }
+            for (index, output) in tx.outputs().into_iter().enumerate() {
+                let capacity: u64 = output.capacity().unpack();
+                let lock_hash = output.lock().calc_script_hash().as_slice().to_vec();
+                if seen_locks.insert(lock_hash.clone()) {
+                    changes[6] += 1;
+                }
+                active_locks
+                    .entry(period)
+                    .or_default()
+                    .insert(lock_hash.clone());
+                let out_point = packed::OutPoint::new(tx.hash(), index as u32);
+                cells.insert(out_point.as_slice().to_vec(), (number, capacity, lock_hash));
+                changes[3] += 1;
+                changes[4] += 1;
+            }
+            if tx_index != 0 {
+                let outputs_capacity = tx.outputs_capacity().map(core::Capacity::as_u64).unwrap();
+                changes[8] += inputs_capacity.saturating_sub(outputs_capacity) as i64;
+            }
+        }
+        let rollup = rollups.entry(period).or_insert_with(|| vec![0; 10]);
+        for (total, change) in rollup.iter_mut().zip(changes) {
+            *total += change;
+        }
+    }
+    rollups
+        .into_iter()
+        .map(|(period, mut rollup)| {
+            rollup[7] = active_locks[&period].len() as i64;
+            rollup
+        })
+        .collect()
+}
+
+fn assert_rollups(storage: &TestStorage, chain: &ChainBuilder, tip: u64) {
+    let conn = storage.connect();
+    let values = |report: &Report| {
+        report
+            .rows()
+            .iter()
+            .map(|row| row[1..].to_vec())
+            .collect::<Vec<_>>()
+    };
+    let expected = |millis| {
+        rollups(chain, tip, millis)
+            .into_iter()
+            .map(|rollup| rollup.into_iter().map(Value::from).collect::<Vec<_>>())
+            .collect::<Vec<_>>()
+    };
+    let report = conn.rollups_by_day(10).unwrap();
+    assert_eq!(values(&report), expected(DAY_MILLIS));
+    assert_eq!(report.rows()[0][0], Value::Text("1970-01-01".to_owned()));
+    let report = conn.rollups_by_hour(100).unwrap();
+    assert_eq!(values(&report), expected(HOUR_MILLIS));
+    assert_eq!(
+        report.rows()[0][0],
+        Value::Text("1970-01-01 00:00".to_owned())
+    );
+
+    let report = conn.rollups_by_day(1).unwrap();
+    assert_eq!(report.rows().len(), 1);
+    assert_eq!(report.rows()[0][0], Value::Text("1970-01-02".to_owned()));
+    let last = chain.block(tip).timestamp() / HOUR_MILLIS;
+    let report = conn.rollups_by_hour(3).unwrap();
+    assert_eq!(report.rows().len(), 3);
+    let label = format!("1970-01-02 {:02}:00", last - 24);
+    assert_eq!(report.rows()[2][0], Value::Text(label));
+}
+
+#[test]
+fn roll_up_blocks_through_reorganizations() {
+    for storage in TestStorage::all("roll_up_blocks_through_reorganizations") {
+        let mut chain_a = ChainBuilder::new();
+        chain_a.extend(34, &[]);
+        spread(&mut chain_a, 1, 34);
+        let mut chain_b = chain_a.fork(27, 1);
+        chain_b.extend(3, &[chain_a.block(28)]);
+        spread(&mut chain_b, 28, 30);
+        let source = chain_a.source();
+        let tip = storage.sync(&source);
+        assert_rollups(&storage, &chain_a, tip);
+        chain_b.apply_to(&source);
+        let tip = storage.sync(&source);
+        assert_eq!(tip, 30);
+        assert_rollups(&storage, &chain_b, tip);
+
+        let reorganized = storage.snapshot();
+        storage.reset();
+        storage.sync(&chain_b.source());
+        assert_eq!(storage.snapshot(), reorganized);
+    }
+}
+
+#[test]
+fn rebuild_rollups_of_existing_storages() {
+    for storage in TestStorage::all("rebuild_rollups_of_existing_storages") {
+        let mut chain_a = ChainBuilder::new();
+        chain_a.extend(32, &[]);
+        spread(&mut chain_a, 1, 32);
+        let mut chain = chain_a.fork(27, 1);
+        chain.extend(5, &[chain_a.block(28)]);
+        spread(&mut chain, 28, 32);
+        let source = chain_a.source();
+        storage.sync(&source);
+        chain.apply_to(&source);
+        assert_eq!(storage.sync(&source), 32);
+        let expected = storage.snapshot();
+
+        let mut conn = storage.connect();
+        for table in &[
+            "daily_rollups",
+            "daily_rollup_locks",
+            "hourly_rollups",
+            "hourly_rollup_locks",
+        ] {
+            conn.query(&format!("DROP TABLE {};", table), &[]).unwrap();
+        }
+        assert_eq!(conn.initialize().unwrap(), Some(32));
+        assert_eq!(storage.snapshot(), expected);
+
+        conn.query("DELETE FROM hourly_rollups;", &[]).unwrap();
+        conn.rebuild_rollups().unwrap();
+        assert_eq!(storage.snapshot(), expected);
+    }
+}
@@ -0,0 +1,183 @@
+// Copyright (C) 2019-2020 Boyu Yang
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+mod common;
+
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    fs,
+};
+
+use common::{ChainBuilder, TestStorage};
+use uckb_scanner::{traits::ScriptUsage as _, KnownScript, Network, Registry, Storage, Value};
+
+#[derive(Default)]
+struct Usage {
+    cells: i64,
+    capacity: i64,
+    live_cells: i64,
+    live_capacity: i64,
+    args: BTreeSet<Vec<u8>>,
+    first: i64,
+    last: i64,
+    // The epoch number and the count of created and consumed cells.
+    epochs: BTreeMap<i64, (i64, i64)>,
+}
+
+type Key = (Vec<u8>, i64, String);
+
+// The usage of scripts, computed from the cells directly.
+fn usage(storage: &Storage) -> BTreeMap<Key, Usage> {
+    let sql = r#"
+        SELECT 'lock', s.code_hash, s.hash_type, s.args, c.capacity,
+               c.created_block_number, c.consumed_block_number
+          FROM cells c
+          JOIN scripts s ON s.hash = c.lock_hash
+         UNION ALL
+        SELECT 'type', s.code_hash, s.hash_type, s.args, c.capacity,
+               c.created_block_number, c.consumed_block_number
+          FROM cells c
+          JOIN scripts s ON s.hash = c.type_hash
+    ;"#;
+    let mut usage = BTreeMap::<Key, Usage>::new();
+    for row in storage.query(sql, &[]).unwrap() {
+        let key = (
+            row.try_get::<Vec<u8>>(1).unwrap(),
+            row.try_get::<i64>(2).unwrap(),
+            row.try_get::<String>(0).unwrap(),
+        );
+        let capacity = row.try_get::<i64>(4).unwrap();
+        let created = row.try_get::<i64>(5).unwrap();
+        let consumed = row.try_get::<Option<i64>>(6).unwrap();
+        let item = usage.entry(key).or_insert_with(|| Usage {
+            first: i64::MAX,
+            ..Default::default()
+        });
+        item.cells += 1;
+        item.capacity += capacity;
+        if consumed.is_none() {
+            item.live_cells += 1;
+            item.live_capacity += capacity;
+        }
+        item.args.insert(row.try_get::<Vec<u8>>(3).unwrap());
+        item.first = item.first.min(created);
+        item.last = item.last.max(consumed.unwrap_or(created));
+        // There are 10 blocks in each epoch.
+        item.epochs.entry(created / 10).or_default().0 += 1;
+        if let Some(consumed) = consumed {
+            item.epochs.entry(consumed / 10).or_default().1 += 1;
+        }
+    }
+    usage
+}
+
+#[test]
+fn aggregate_usage_of_scripts() {
+    for storage in TestStorage::all("aggregate_usage_of_scripts") {
+        let mut chain_a = ChainBuilder::new();
+        chain_a.extend(24, &[]);
+        let mut chain_b = chain_a.fork(15, 1);
+        chain_b.extend(12, &[]);
+        let source = chain_a.source();
+        storage.sync(&source);
+        let mut conn = storage.connect();
+        assert!(conn.script_usage().is_err());
+        conn.refresh_script_usage().unwrap();
+        chain_b.apply_to(&source);
+        storage.sync(&source);
+        conn.refresh_script_usage().unwrap();
+
+        let expected = usage(&conn);
+        assert!(expected.len() >= 2);
+        let report = conn.script_usage().unwrap();
+        assert_eq!(report.rows().len(), expected.len());
+        for row in report.rows() {
+            let key = match (&row[0], &row[1], &row[2]) {
+                (Value::Bytes(code_hash), Value::Integer(hash_type), Value::Text(script_type)) => {
+                    (code_hash.clone(), *hash_type, script_type.clone())
+                }
+                _ => panic!("unexpected row {:?}", row),
+            };
+            let item = &expected[&key];
+            let values = [
+                item.cells,
+                item.capacity,
+                item.live_cells,
+                item.live_capacity,
+                item.args.len() as i64,
+                item.first,
+                item.last,
+            ];
+            for (value, expected) in row[3..].iter().zip(&values) {
+                assert_eq!(value, &Value::Integer(*expected));
+            }
+
+            let report = conn.script_usage_by_epoch(&key.0, key.1 as u8).unwrap();
+            let epochs = report
+                .rows()
+                .iter()
+                .filter(|row| row[1] == Value::Text(key.2.clone()))
+                .map(|row| match (&row[0], &row[2], &row[4]) {
+                    (Value::Integer(epoch), Value::Integer(created), Value::Integer(consumed)) => {
+                        (*epoch, (*created, *consumed))
+                    }
+                    _ => panic!("unexpected row {:?}", row),
+                })
+                .collect::<BTreeMap<_, _>>();
+            assert_eq!(epochs, item.epochs);
+        }
+    }
+}
+
+#[test]
+fn name_scripts_by_registry() {
+    let sudt = |registry: &Registry| {
+        registry
+            .scripts()
+            .iter()
+            .find(|known| known.name() == "sudt")
+            .cloned()
+            .unwrap()
+    };
+    let mainnet = Registry::builtin(Network::Mainnet);
+    let testnet = Registry::builtin(Network::Testnet);
+    assert_ne!(sudt(&mainnet).code_hash(), sudt(&testnet).code_hash());
+    let blake160 = [
+        0x9b, 0xd7, 0xe0, 0x6f, 0x3e, 0xcf, 0x4b, 0xe0, 0xf2, 0xfc, 0xd2, 0x18, 0x8b, 0x23, 0xf1,
+        0xb9, 0xfc, 0xc8, 0x8e, 0x5d, 0x4b, 0x65, 0xa8, 0x63, 0x7b, 0x17, 0x72, 0x3b, 0xbd, 0xa3,
+        0xcc, 0xe8,
+    ];
+    assert_eq!(testnet.name(&blake160, 1), Some("secp256k1_blake160"));
+    assert_eq!(testnet.name(&blake160, 0), None);
+
+    let path = std::env::temp_dir().join("uckb-scanner-test-registry.json");
+    let content = format!(
+        r#"[
+            {{"name": "my_lock", "code_hash": "0x{}", "hash_type": "data"}},
+            {{"name": "blake160", "code_hash": "0x{}", "hash_type": "type"}}
+        ]"#,
+        "01".repeat(32),
+        faster_hex::hex_string(&blake160).unwrap()
+    );
+    fs::write(&path, content).unwrap();
+    let mut registry = testnet.clone();
+    registry.load(&path).unwrap();
+    assert_eq!(registry.name(&[1u8; 32], 0), Some("my_lock"));
+    assert_eq!(registry.name(&blake160, 1), Some("blake160"));
+    assert_eq!(registry.scripts().len(), testnet.scripts().len() + 1);
+    registry.insert(KnownScript::new("other", &[1u8; 32], 1));
+    assert_eq!(registry.name(&[1u8; 32], 1), Some("other"));
+
+    fs::write(
+        &path,
+        r#"[{"name": "bad", "code_hash": "0x01", "hash_type": "type"}]"#,
+    )
+    .unwrap();
+    assert!(registry.load(&path).is_err());
+    fs::remove_file(&path).unwrap();
+}
@@ -0,0 +1,300 @@
+// Copyright (C) 2019-2020 Boyu Yang
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+mod common;
+
+use std::collections::BTreeMap;
+
+use common::{compact_target, ChainBuilder, TestStorage};
+use uckb_jsonrpc_core::types::{core, prelude::*};
+use uckb_scanner::{
+    traits::Stats as _, utilities::compact_to_difficulty, Excluded, Report, Storage, Value,
+};
+
+// The balances of holders from the richest, computed from the cells directly. All type scripts
+// of the synthetic chain share a code hash, so the typed cells are excluded if it is excluded.
+fn holders(storage: &Storage, at: u64, excluded: &[Vec<u8>], typed: bool) -> Vec<(Vec<u8>, i64)> {
+    let sql = r#"
+        SELECT lock_hash, capacity, type_hash
+          FROM cells
+         WHERE 1 = 1
+           AND created_block_number <= $1
+           AND (consumed_block_number IS NULL OR consumed_block_number > $1)
+    ;"#;
+    let mut balances = BTreeMap::new();
+    for row in storage.query(sql, &[(at as i64).into()]).unwrap() {
+        let lock_hash = row.try_get::<Vec<u8>>(0).unwrap();
+        let excluded_type = typed && row.try_get::<Option<Vec<u8>>>(2).unwrap().is_some();
+        if !excluded.contains(&lock_hash) && !excluded_type {
+            *balances.entry(lock_hash).or_insert(0) += row.try_get::<i64>(1).unwrap();
+        }
+    }
+    let mut holders = balances.into_iter().collect::<Vec<_>>();
+    holders.sort_by(|a, b| b.1.cmp(&a.1).then_with(|| a.0.cmp(&b.0)));
+    holders
+}
+
+fn metric(report: &Report, name: &str) -> Value {
+    report
+        .rows()
+        .iter()
+        .find(|row| row[0] == Value::Text(name.to_owned()))
+        .map(|row| row[1].clone())
+        .unwrap()
+}
+
+fn float(value: Value) -> f64 {
+    if let Value::Float(value) = value {
+        value
+    } else {
+        panic!("{:?} is not a float", value);
+    }
+}
+
+#[test]
+fn report_rich_list_and_distribution() {
+    for storage in TestStorage::all("report_rich_list_and_distribution") {
+        let mut chain = ChainBuilder::new();
+        chain.extend(30, &[]);
+        storage.sync(&chain.source());
+        let storage = storage.connect();
+
+        let cases = [
+            (Some(12), false, false),
+            (None, false, false),
+            (None, true, false),
+            (None, false, true),
+            (Some(12), true, true),
+        ];
+        for (at, richest_excluded, typed_excluded) in &cases {
+            let mut expected = holders(&storage, at.unwrap_or(30), &[], *typed_excluded);
+            let mut excluded = Excluded::default();
+            if *richest_excluded {
+                excluded.lock_hashes.push(expected.remove(0).0);
+            }
+            if *typed_excluded {
+                let data = core::ScriptHashType::Data as u8;
+                excluded.scripts.push((vec![2u8; 32], data));
+            }
+            let capacity = expected.iter().map(|(_, balance)| balance).sum::<i64>();
+
+            let richlist = storage.richlist(*at, 5, &excluded).unwrap();
+            assert_eq!(richlist.rows().len(), 5);
+            for (i, row) in richlist.rows().iter().enumerate() {
+                assert_eq!(row[0], Value::Integer(i as i64 + 1));
+                assert_eq!(row[1], Value::Bytes(expected[i].0.clone()));
+                assert_eq!(row[6], Value::Integer(expected[i].1));
+                let share = expected[i].1 as f64 / capacity as f64;
+                assert!((float(row[7].clone()) - share).abs() < 1e-9);
+            }
+
+            let report = storage.distribution(*at, &excluded).unwrap();
+            let count = expected.len();
+            assert_eq!(metric(&report, "holders"), Value::Integer(count as i64));
+            assert_eq!(metric(&report, "capacity"), Value::Integer(capacity));
+            let mut differences = 0.0;
+            for (_, a) in &expected {
+                for (_, b) in &expected {
+                    differences += (a - b).abs() as f64;
+                }
+            }
+            let gini =
+                differences / (2.0 * (count * count) as f64 * (capacity as f64 / count as f64));
+            assert!((float(metric(&report, "gini")) - gini).abs() < 1e-9);
+            let top_10 = expected.iter().take(10).map(|(_, b)| b).sum::<i64>();
+            let top_10_share = top_10 as f64 / capacity as f64;
+            assert!((float(metric(&report, "top_10_share")) - top_10_share).abs() < 1e-9);
+            assert!((float(metric(&report, "top_1000_share")) - 1.0).abs() < 1e-9);
+            let mut tenths = vec![0; 10];
+            let mut richer = 0;
+            for (_, balance) in &expected {
+                tenths[(richer * 10 / capacity) as usize] += 1;
+                richer += balance;
+            }
+            for (k, holders) in tenths.into_iter().enumerate() {
+                let name = format!("tenth_{}_holders", k + 1);
+                assert_eq!(metric(&report, &name), Value::Integer(holders));
+            }
+        }
+    }
+}
+
+#[test]
+fn decode_compact_targets() {
+    assert_eq!(compact_to_difficulty(0x2001_0000), Some(256.0));
+    let difficulty = 2f64.powi(48) / f64::from(0xffff);
+    assert_eq!(compact_to_difficulty(0x1d00_ffff), Some(difficulty));
+    // The mantissa is shifted right if the exponent is less than 3.
+    assert_eq!(compact_to_difficulty(0x0300_0001), Some(2f64.powi(256)));
+    assert_eq!(compact_to_difficulty(0x0200_0100), Some(2f64.powi(256)));
+    assert_eq!(compact_to_difficulty(0x0200_00ff), None);
+    assert_eq!(compact_to_difficulty(0x2100_0001), None);
+    assert_eq!(compact_to_difficulty(0), None);
+}
+
+#[test]
+fn report_hashrate_by_epoch() {
+    for storage in TestStorage::all("report_hashrate_by_epoch") {
+        let mut chain_a = ChainBuilder::new();
+        chain_a.extend(35, &[]);
+        let mut chain = chain_a.fork(20, 1);
+        chain.extend(15, &[chain_a.block(21), chain_a.block(22)]);
+        let tip = storage.sync(&chain.source());
+        let storage = storage.connect();
+
+        let report = storage.hashrate(100).unwrap();
+        assert_eq!(report.rows().len() as u64, tip / 10 + 1);
+        for row in report.rows() {
+            let epoch = match row[0] {
+                Value::Integer(epoch) => epoch as u64,
+                _ => panic!("unexpected row {:?}", row),
+            };
+            let numbers = (epoch * 10..=tip.min(epoch * 10 + 9)).collect::<Vec<_>>();
+            let uncles = if epoch == 2 { 2 } else { 0 };
+            assert_eq!(row[1], Value::Integer(numbers[0] as i64));
+            assert_eq!(row[2], Value::Integer(numbers.len() as i64));
+            assert_eq!(row[4], Value::Integer(uncles));
+            assert_eq!(row[5], Value::Integer(i64::from(compact_target(epoch))));
+            let difficulty = compact_to_difficulty(compact_target(epoch)).unwrap();
+            assert_eq!(row[6], Value::Float(difficulty));
+            if epoch > 0 {
+                let previous = compact_to_difficulty(compact_target(epoch - 1)).unwrap();
+                assert!((float(row[7].clone()) - difficulty / previous).abs() < 1e-12);
+            } else {
+                assert_eq!(row[7], Value::Null);
+            }
+            let timestamp = |number: u64| chain.block(number).timestamp() as i64;
+            let end = timestamp(*numbers.last().unwrap());
+            let (start, mined) = if epoch > 0 {
+                (timestamp(numbers[0] - 1), numbers.len())
+            } else {
+                (timestamp(0), numbers.len() - 1)
+            };
+            assert_eq!(row[8], Value::Integer(end - start));
+            let hashrate =
+                difficulty * (mined as i64 + uncles) as f64 * 1_000.0 / (end - start) as f64;
+            assert!((float(row[9].clone()) / hashrate - 1.0).abs() < 1e-9);
+        }
+
+        // The adjustment of the first epoch is from the epoch before it.
+        let report = storage.hashrate(2).unwrap();
+        assert_eq!(report.rows().len(), 2);
+        assert_eq!(report.rows()[0][0], Value::Integer((tip / 10 - 1) as i64));
+        assert_ne!(report.rows()[0][7], Value::Null);
+    }
+}
+
+#[test]
+fn report_block_intervals_and_anomalies() {
+    for storage in TestStorage::all("report_block_intervals_and_anomalies") {
+        let mut chain = ChainBuilder::new();
+        chain.extend(35, &[]);
+        let timestamp = |chain: &ChainBuilder, number: u64| chain.block(number).timestamp();
+        // The block 15 is before its parent, the block 35 is in the future.
+        let before = timestamp(&chain, 13);
+        let now = timestamp(&chain, 34);
+        chain.modify(15, |builder| builder.timestamp(before.pack()));
+        chain.modify(35, |builder| builder.timestamp((now + 20_000).pack()));
+        // The epoch 2 is much shorter than the epochs around it.
+        for number in 20..30 {
+            let epoch = core::EpochNumberWithFraction::new(2, number - 20, 4);
+            chain.modify(number, |builder| builder.epoch(epoch.pack()));
+        }
+        storage.sync(&chain.source());
+        let storage = storage.connect();
+
+        let report = storage.block_intervals_by_epoch(10).unwrap();
+        let expected: Vec<Vec<Value>> = vec![
+            vec![0.into(), 9.into(), 8_000.into(), 8_000.0.into()],
+            vec![1.into(), 10.into(), (-8_000).into(), 8_000.0.into()],
+            vec![2.into(), 10.into(), 8_000.into(), 8_000.0.into()],
+            vec![3.into(), 6.into(), 8_000.into(), 10_000.0.into()],
+        ];
+        let percentiles: Vec<Vec<Value>> = vec![
+            vec![8_000.into(), 8_000.into(), 8_000.into(), 8_000.into()],
+            vec![8_000.into(), 8_000.into(), 24_000.into(), 24_000.into()],
+            vec![8_000.into(), 8_000.into(), 8_000.into(), 8_000.into()],
+            vec![8_000.into(), 20_000.into(), 20_000.into(), 20_000.into()],
+        ];
+        assert_eq!(report.rows().len(), expected.len());
+        for (row, (expected, percentiles)) in
+            report.rows().iter().zip(expected.iter().zip(percentiles))
+        {
+            assert_eq!(row[..4], expected[..]);
+            assert_eq!(row[4..], percentiles[..]);
+        }
+        assert_eq!(storage.block_intervals_by_epoch(1).unwrap().rows().len(), 1);
+
+        // The timestamps of the synthetic chain are in the first day of the Unix epoch.
+        let report = storage.block_intervals_by_day(7).unwrap();
+        assert_eq!(report.rows().len(), 1);
+        let row = &report.rows()[0];
+        assert_eq!(
+            row[..3],
+            [
+                Value::Text("1970-01-01".to_owned()),
+                35.into(),
+                (-8_000).into()
+            ]
+        );
+        assert_eq!(row[7], Value::Integer(24_000));
+
+        let report = storage.timestamp_anomalies(now, 10).unwrap();
+        let anomalies = report
+            .rows()
+            .iter()
+            .map(|row| (row[0].clone(), row[4].clone(), row[5].clone()))
+            .collect::<Vec<_>>();
+        let expected = vec![
+            (35.into(), 20_000.into(), Value::Text("future".to_owned())),
+            (
+                15.into(),
+                (-8_000).into(),
+                Value::Text("out_of_order".to_owned()),
+            ),
+        ];
+        assert_eq!(anomalies, expected);
+        assert_eq!(
+            storage
+                .timestamp_anomalies(now + 5_000, 10)
+                .unwrap()
+                .rows()
+                .len(),
+            1
+        );
+
+        let report = storage.epoch_durations(10).unwrap();
+        assert_eq!(report.rows().len(), 4);
+        let expected: Vec<Vec<Value>> = vec![
+            vec![Value::Null, 0.into()],
+            vec![1.0.into(), 0.into()],
+            vec![0.4.into(), 1.into()],
+            vec![2.5.into(), 1.into()],
+        ];
+        for (epoch, (row, expected)) in report.rows().iter().zip(expected).enumerate() {
+            assert_eq!(row[0], Value::Integer(epoch as i64));
+            assert_eq!(row[4..6], expected[..]);
+            let start = if epoch > 0 { epoch as u64 * 10 - 1 } else { 0 };
+            let end = (epoch as u64 * 10 + 9).min(35);
+            let duration = timestamp(&chain, end) as i64 - timestamp(&chain, start) as i64;
+            assert_eq!(row[6], Value::Integer(duration));
+            assert_eq!(row[7], Value::Integer(4 * 60 * 60 * 1_000));
+            if epoch < 3 {
+                assert_eq!(
+                    row[8],
+                    Value::Float(duration as f64 / (4 * 60 * 60 * 1_000) as f64)
+                );
+            } else {
+                assert_eq!(row[8], Value::Null);
+            }
+        }
+        // The change of the length of the first epoch is from the epoch before it.
+        let report = storage.epoch_durations(2).unwrap();
+        assert_eq!(report.rows()[0][4..6], [0.4.into(), 1.into()]);
+    }
+}
@@ -0,0 +1,235 @@
+// Copyright (C) 2019-2020 Boyu Yang
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+mod common;
+
+use std::collections::{BTreeMap, BTreeSet};
+
+use common::{ChainBuilder, TestStorage};
+use uckb_scanner::{
+    traits::{BaseData as _, Udt as _},
+    KnownScript, Registry, Storage, Value,
+};
+
+#[derive(Default)]
+struct Expected {
+    // The supply of each token.
+    supply: BTreeMap<Vec<u8>, u128>,
+    // The balance and the live cells of each token and holder.
+    balances: BTreeMap<(Vec<u8>, Vec<u8>), (u128, i64)>,
+    // The input and the output amounts of each token and transaction.
+    transactions: BTreeMap<(Vec<u8>, Vec<u8>), (u128, u128)>,
+}
+
+// The token cells use the type scripts with the code hash `[2u8; 32]` in the synthetic chains.
+fn registry() -> Registry {
+    let mut registry = Registry::default();
+    registry.insert(KnownScript::new("sudt", &[2u8; 32], 0));
+    registry
+}
+
+// The tokens, computed from the cells directly.
+fn expected(storage: &Storage) -> Expected {
+    let sql = r#"
+        SELECT c.type_hash, c.lock_hash, d.data, c.tx_hash, c.consumed_tx_hash
+          FROM cells c
+          JOIN scripts s ON s.hash = c.type_hash
+          JOIN cells_data d ON d.hash = c.data_hash
+         WHERE s.code_hash = $1
+    ;"#;
+    let mut expected = Expected::default();
+    for row in storage.query(sql, &[vec![2u8; 32].into()]).unwrap() {
+        let data = row.try_get::<Vec<u8>>(2).unwrap();
+        if data.len() < 16 {
+            continue;
+        }
+        let mut tmp = [0u8; 16];
+        tmp.copy_from_slice(&data[..16]);
+        let amount = u128::from_le_bytes(tmp);
+        let type_hash = row.try_get::<Vec<u8>>(0).unwrap();
+        let lock_hash = row.try_get::<Vec<u8>>(1).unwrap();
+        let tx_hash = row.try_get::<Vec<u8>>(3).unwrap();
+        expected
+            .transactions
+            .entry((type_hash.clone(), tx_hash))
+            .or_default()
+            .1 += amount;
+        if let Some(consumed_tx_hash) = row.try_get::<Option<Vec<u8>>>(4).unwrap() {
+            expected
+                .transactions
+                .entry((type_hash.clone(), consumed_tx_hash))
+                .or_default()
+                .0 += amount;
+        } else {
+            *expected.supply.entry(type_hash.clone()).or_default() += amount;
+            let balance = expected.balances.entry((type_hash, lock_hash)).or_default();
+            balance.0 += amount;
+            balance.1 += 1;
+        }
+    }
+    expected
+}
+
+fn amount(value: &Value) -> u128 {
+    match value {
+        Value::Text(text) => text.parse().unwrap(),
+        _ => panic!("unexpected amount {:?}", value),
+    }
+}
+
+fn assert_tokens(storage: &Storage) {
+    let expected = expected(storage);
+    assert!(!expected.supply.is_empty());
+    let tokens = storage.udt_tokens().unwrap();
+    // The tokens which are burned totally are still listed.
+    let count = expected
+        .transactions
+        .keys()
+        .map(|(token, _)| token)
+        .collect::<BTreeSet<_>>()
+        .len();
+    assert_eq!(tokens.rows().len(), count);
+    for row in tokens.rows() {
+        let type_hash = match &row[0] {
+            Value::Bytes(type_hash) => type_hash.clone(),
+            _ => panic!("unexpected row {:?}", row),
+        };
+        let supply = expected.supply.get(&type_hash).copied().unwrap_or(0);
+        assert_eq!(amount(&row[2]), supply);
+        let holders = expected
+            .balances
+            .keys()
+            .filter(|(token, _)| token == &type_hash)
+            .count();
+        assert_eq!(row[3], Value::Integer(holders as i64));
+
+        let report = storage.udt_holders(&type_hash, 500).unwrap();
+        assert_eq!(report.rows().len(), holders);
+        let mut last = u128::MAX;
+        for row in report.rows() {
+            let lock_hash = match &row[0] {
+                Value::Bytes(lock_hash) => lock_hash.clone(),
+                _ => panic!("unexpected row {:?}", row),
+            };
+            let (balance, live_cells) = expected.balances[&(type_hash.clone(), lock_hash)];
+            assert_eq!(amount(&row[4]), balance);
+            assert_eq!(row[5], Value::Integer(live_cells));
+            assert!(balance <= last);
+            last = balance;
+        }
+
+        let report = storage.udt_transactions(&type_hash, 500).unwrap();
+        let count = expected
+            .transactions
+            .keys()
+            .filter(|(token, _)| token == &type_hash)
+            .count();
+        assert_eq!(report.rows().len(), count);
+        for row in report.rows() {
+            let tx_hash = match &row[2] {
+                Value::Bytes(tx_hash) => tx_hash.clone(),
+                _ => panic!("unexpected row {:?}", row),
+            };
+            let (input, output) = expected.transactions[&(type_hash.clone(), tx_hash)];
+            assert_eq!(amount(&row[4]), input);
+            assert_eq!(amount(&row[5]), output);
+            let kind = if output > input {
+                "mint"
+            } else if output < input {
+                "burn"
+            } else {
+                "transfer"
+            };
+            assert_eq!(row[3], Value::Text(kind.to_owned()));
+        }
+    }
+}
+
+#[test]
+fn track_tokens_through_reorganizations() {
+    for storage in TestStorage::all("track_tokens_through_reorganizations") {
+        let registry = registry();
+        let mut chain_a = ChainBuilder::new();
+        chain_a.extend(12, &[]);
+        let mut chain_b = chain_a.fork(7, 1);
+        chain_b.extend(8, &[chain_a.block(8)]);
+        let source = chain_a.source();
+        storage.sync_with_registry(&source, &registry);
+        assert_tokens(&storage.connect());
+        chain_b.apply_to(&source);
+        storage.sync_with_registry(&source, &registry);
+        assert_tokens(&storage.connect());
+        assert_eq!(storage.connect().udt_tokens().unwrap().rows().len(), 2);
+
+        let reorganized = storage.snapshot();
+        storage.reset();
+        storage.sync_with_registry(&chain_b.source(), &registry);
+        assert_eq!(storage.snapshot(), reorganized);
+    }
+}
+
+#[test]
+fn no_tokens_without_known_scripts() {
+    for storage in TestStorage::all("no_tokens_without_known_scripts") {
+        let mut chain = ChainBuilder::new();
+        chain.extend(4, &[]);
+        storage.sync(&chain.source());
+        assert!(storage.connect().udt_tokens().unwrap().rows().is_empty());
+    }
+}
+
+#[test]
+fn fill_tokens_of_existing_storages() {
+    for storage in TestStorage::all("fill_tokens_of_existing_storages") {
+        let registry = registry();
+        let mut chain_a = ChainBuilder::new();
+        chain_a.extend(5, &[]);
+        let mut chain = chain_a.fork(2, 1);
+        chain.extend(4, &[chain_a.block(3)]);
+        storage.sync_with_registry(&chain.source(), &registry);
+        let expected = storage.snapshot();
+
+        let mut conn = storage.connect();
+        for sql in &[
+            "DROP TABLE udt_cells;",
+            "DROP TABLE udt_balances;",
+            "DROP TABLE udt_tokens;",
+            "DROP TABLE udt_transactions;",
+        ] {
+            conn.query(sql, &[]).unwrap();
+        }
+        conn.set_registry(registry);
+        assert_eq!(conn.initialize().unwrap(), Some(6));
+        assert_eq!(storage.snapshot(), expected);
+    }
+}
+
+#[test]
+fn fill_tokens_again_when_scripts_are_changed() {
+    for storage in TestStorage::all("fill_tokens_again_when_scripts_are_changed") {
+        let registry = registry();
+        let mut chain_a = ChainBuilder::new();
+        chain_a.extend(6, &[]);
+        let mut chain = chain_a.fork(3, 1);
+        chain.extend(4, &[chain_a.block(4)]);
+        let source = chain.source();
+        storage.sync_with_registry(&source, &registry);
+        let expected = storage.snapshot();
+
+        storage.reset();
+        storage.sync_with_registry(&source, &Registry::default());
+        assert!(storage.connect().udt_tokens().unwrap().rows().is_empty());
+        storage.sync_with_registry(&source, &registry);
+        assert_tokens(&storage.connect());
+        assert_eq!(storage.snapshot(), expected);
+
+        // The tables are not filled again when the scripts are not changed.
+        storage.sync_with_registry(&source, &registry);
+        assert_eq!(storage.snapshot(), expected);
+    }
+}
@@ -6,22 +6,386 @@
 // option. This file may not be copied, modified, or distributed
 // except according to those terms.
 
-use std::{convert::TryFrom, net::SocketAddr};
+use std::{convert::TryFrom, net::SocketAddr, path::PathBuf};
 
+use kernel::{Dataset, Network};
 use property::Property;
 
 use uckb_jsonrpc_client::url;
 
-use crate::error::{Error, Result};
+use crate::{
+    error::{Error, Result},
+    output::OutputFormat,
+};
+
+const DEFAULT_BALANCES_LIMIT: u64 = 20;
+const MAX_BALANCES_LIMIT: u64 = 500;
+const DEFAULT_EPOCHS: u64 = 10;
+const DEFAULT_DAYS: u64 = 7;
+const DEFAULT_HOURS: u64 = 24;
 
 pub(crate) enum AppConfig {
     Sync(SyncArgs),
+    Export(ExportArgs),
+    Query(QueryArgs),
+    Stats(StatsArgs),
+    Serve(ServeArgs),
+    KnownScripts(KnownScriptsArgs),
+    RebuildRollups(RebuildRollupsArgs),
+}
+
+pub(crate) enum SyncSource {
+    JsonRpc {
+        jsonrpc_url: url::Url,
+        subscribe_socket: SocketAddr,
+    },
+    File(PathBuf),
 }
 
+/// The tokens are tracked with the well-known scripts of the network, which is detected from the
+/// genesis block if neither the network nor the file of known scripts is set.
 #[derive(Property)]
 pub(crate) struct SyncArgs {
-    jsonrpc_url: url::Url,
-    subscribe_socket: SocketAddr,
+    source: SyncSource,
+    storage_uri: String,
+    http_listen: Option<SocketAddr>,
+    ready_max_lag: u64,
+    network: Option<Network>,
+    registry: Option<PathBuf>,
+}
+
+#[derive(Property)]
+pub(crate) struct ExportArgs {
+    storage_uri: String,
+    output_dir: PathBuf,
+    datasets: Vec<Dataset>,
+    partition_size: u64,
+    confirmations: u64,
+}
+
+#[derive(Property)]
+pub(crate) struct QueryArgs {
+    storage_uri: String,
+    format: OutputFormat,
+    sql: String,
+}
+
+#[derive(Clone)]
+pub(crate) enum StatsReport {
+    Summary,
+    /// The heights are the multiples of `step` up to `at`, and `at` itself; the tip is used if
+    /// `at` is not set.
+    Supply {
+        at: Option<u64>,
+        step: Option<u64>,
+    },
+    /// The excluded are lock hashes, addresses or names of known scripts, the deposits of
+    /// NervosDAO are excluded too if `exclude_dao` is set.
+    Richlist {
+        at: Option<u64>,
+        limit: u32,
+        excluded: Vec<String>,
+        exclude_dao: bool,
+    },
+    Distribution {
+        at: Option<u64>,
+        excluded: Vec<String>,
+        exclude_dao: bool,
+    },
+    Scripts,
+    /// The hash type is "data" or "type".
+    ScriptEpochs {
+        code_hash: String,
+        hash_type: String,
+    },
+    Tokens,
+    TokenHolders {
+        type_hash: String,
+        limit: u32,
+    },
+    TokenTransactions {
+        type_hash: String,
+        limit: u32,
+    },
+    BlockRewards {
+        limit: u32,
+    },
+    /// The shares of miners in each of the latest `epochs` epochs.
+    MinerEpochs {
+        epochs: u32,
+    },
+    /// The shares of miners in each of the latest `days` days.
+    MinerDays {
+        days: u32,
+    },
+    Hashrate {
+        epochs: u32,
+    },
+    Uncles {
+        epochs: u32,
+    },
+    UncleMiners {
+        epochs: u32,
+    },
+    ProposalLatencies {
+        epochs: u32,
+    },
+    Proposals {
+        epochs: u32,
+    },
+    UncommittedProposals {
+        limit: u32,
+    },
+    DuplicateProposals {
+        epochs: u32,
+    },
+    /// The intervals between blocks in each of the latest `epochs` epochs.
+    IntervalEpochs {
+        epochs: u32,
+    },
+    /// The intervals between blocks in each of the latest `days` days.
+    IntervalDays {
+        days: u32,
+    },
+    TimestampAnomalies {
+        limit: u32,
+    },
+    EpochDurations {
+        epochs: u32,
+    },
+    Epochs {
+        epochs: u32,
+    },
+    /// The rollups of the latest `days` days.
+    RollupDays {
+        days: u32,
+    },
+    /// The rollups of the latest `hours` hours.
+    RollupHours {
+        hours: u32,
+    },
+}
+
+impl StatsReport {
+    /// Parses a report by its name, `arg` returns the value of a parameter of the report.
+    pub(crate) fn parse<'a, F>(name: &str, arg: F) -> Result<Self>
+    where
+        F: Fn(&str) -> Option<&'a str>,
+    {
+        let number = |key: &str| {
+            arg(key)
+                .map(|value| {
+                    value.parse::<u64>().map_err(|err| {
+                        Error::Argument(format!("failed to parse '{}' since {}", key, err))
+                    })
+                })
+                .transpose()
+        };
+        let limit = || {
+            let limit = number("limit")?.unwrap_or(DEFAULT_BALANCES_LIMIT);
+            if limit == 0 || limit > MAX_BALANCES_LIMIT {
+                return Err(Error::Argument(format!(
+                    "'limit' should be in [1, {}]",
+                    MAX_BALANCES_LIMIT
+                )));
+            }
+            Ok(limit as u32)
+        };
+        // A comma-separated list.
+        let excluded = || {
+            arg("exclude")
+                .map(|value| {
+                    value
+                        .split(',')
+                        .map(str::trim)
+                        .filter(|item| !item.is_empty())
+                        .map(ToOwned::to_owned)
+                        .collect()
+                })
+                .unwrap_or_default()
+        };
+        let flag = |key: &str| match arg(key) {
+            None | Some("false") => Ok(false),
+            Some("true") => Ok(true),
+            Some(value) => Err(Error::Argument(format!(
+                "'{}' should be true or false, but got {}",
+                key, value
+            ))),
+        };
+        // A count of periods, such as epochs and days.
+        let periods = |key: &str, default: u64| {
+            let periods = number(key)?.unwrap_or(default);
+            if periods == 0 || periods > u64::from(u32::MAX) {
+                return Err(Error::Argument(format!("'{}' should be positive", key)));
+            }
+            Ok(periods as u32)
+        };
+        let type_hash = || {
+            arg("type_hash")
+                .map(ToOwned::to_owned)
+                .ok_or_else(|| Error::Argument("'type_hash' is required".to_owned()))
+        };
+        match name {
+            "summary" => Ok(Self::Summary),
+            "supply" => {
+                let at = number("at")?;
+                let step = number("step")?;
+                if step == Some(0) {
+                    return Err(Error::Argument("'step' should be positive".to_owned()));
+                }
+                Ok(Self::Supply { at, step })
+            }
+            "richlist" | "balances" => {
+                let at = number("at")?;
+                let limit = limit()?;
+                let excluded = excluded();
+                let exclude_dao = flag("exclude_dao")?;
+                Ok(Self::Richlist {
+                    at,
+                    limit,
+                    excluded,
+                    exclude_dao,
+                })
+            }
+            "distribution" => {
+                let at = number("at")?;
+                let excluded = excluded();
+                let exclude_dao = flag("exclude_dao")?;
+                Ok(Self::Distribution {
+                    at,
+                    excluded,
+                    exclude_dao,
+                })
+            }
+            "scripts" => Ok(Self::Scripts),
+            "script-epochs" => {
+                let code_hash = arg("code_hash")
+                    .map(ToOwned::to_owned)
+                    .ok_or_else(|| Error::Argument("'code_hash' is required".to_owned()))?;
+                let hash_type = arg("hash_type").unwrap_or("type").to_owned();
+                Ok(Self::ScriptEpochs {
+                    code_hash,
+                    hash_type,
+                })
+            }
+            "tokens" => Ok(Self::Tokens),
+            "token-holders" => {
+                let type_hash = type_hash()?;
+                let limit = limit()?;
+                Ok(Self::TokenHolders { type_hash, limit })
+            }
+            "token-transactions" => {
+                let type_hash = type_hash()?;
+                let limit = limit()?;
+                Ok(Self::TokenTransactions { type_hash, limit })
+            }
+            "block-rewards" => {
+                let limit = limit()?;
+                Ok(Self::BlockRewards { limit })
+            }
+            "miner-epochs" => {
+                let epochs = periods("epochs", DEFAULT_EPOCHS)?;
+                Ok(Self::MinerEpochs { epochs })
+            }
+            "miner-days" => {
+                let days = periods("days", DEFAULT_DAYS)?;
+                Ok(Self::MinerDays { days })
+            }
+            "hashrate" => {
+                let epochs = periods("epochs", DEFAULT_EPOCHS)?;
+                Ok(Self::Hashrate { epochs })
+            }
+            "uncles" => {
+                let epochs = periods("epochs", DEFAULT_EPOCHS)?;
+                Ok(Self::Uncles { epochs })
+            }
+            "uncle-miners" => {
+                let epochs = periods("epochs", DEFAULT_EPOCHS)?;
+                Ok(Self::UncleMiners { epochs })
+            }
+            "proposal-latencies" => {
+                let epochs = periods("epochs", DEFAULT_EPOCHS)?;
+                Ok(Self::ProposalLatencies { epochs })
+            }
+            "proposals" => {
+                let epochs = periods("epochs", DEFAULT_EPOCHS)?;
+                Ok(Self::Proposals { epochs })
+            }
+            "uncommitted-proposals" => {
+                let limit = limit()?;
+                Ok(Self::UncommittedProposals { limit })
+            }
+            "duplicate-proposals" => {
+                let epochs = periods("epochs", DEFAULT_EPOCHS)?;
+                Ok(Self::DuplicateProposals { epochs })
+            }
+            "interval-epochs" => {
+                let epochs = periods("epochs", DEFAULT_EPOCHS)?;
+                Ok(Self::IntervalEpochs { epochs })
+            }
+            "interval-days" => {
+                let days = periods("days", DEFAULT_DAYS)?;
+                Ok(Self::IntervalDays { days })
+            }
+            "timestamp-anomalies" => {
+                let limit = limit()?;
+                Ok(Self::TimestampAnomalies { limit })
+            }
+            "epoch-durations" => {
+                let epochs = periods("epochs", DEFAULT_EPOCHS)?;
+                Ok(Self::EpochDurations { epochs })
+            }
+            "epochs" => {
+                let epochs = periods("epochs", DEFAULT_EPOCHS)?;
+                Ok(Self::Epochs { epochs })
+            }
+            "rollup-days" => {
+                let days = periods("days", DEFAULT_DAYS)?;
+                Ok(Self::RollupDays { days })
+            }
+            "rollup-hours" => {
+                let hours = periods("hours", DEFAULT_HOURS)?;
+                Ok(Self::RollupHours { hours })
+            }
+            _ => Err(Error::Argument(format!("unknown report {}", name))),
+        }
+    }
+}
+
+/// The network is detected from the stored genesis block if it is not set.
+#[derive(Property)]
+pub(crate) struct StatsArgs {
+    storage_uri: String,
+    format: OutputFormat,
+    network: Option<Network>,
+    registry: Option<PathBuf>,
+    report: StatsReport,
+    refresh: bool,
+}
+
+#[derive(Property)]
+pub(crate) struct ServeArgs {
+    storage_uri: String,
+    listen: SocketAddr,
+    indexer_listen: Option<SocketAddr>,
+    graphql_listen: Option<SocketAddr>,
+    workers: u64,
+    network: Option<Network>,
+    registry: Option<PathBuf>,
+}
+
+/// The storage is only used to detect the network, so it is optional.
+#[derive(Property)]
+pub(crate) struct KnownScriptsArgs {
+    storage_uri: Option<String>,
+    format: OutputFormat,
+    network: Option<Network>,
+    registry: Option<PathBuf>,
+    search: Option<String>,
+}
+
+#[derive(Property)]
+pub(crate) struct RebuildRollupsArgs {
     storage_uri: String,
 }
 
@@ -39,6 +403,16 @@ impl<'a> TryFrom<&'a clap::ArgMatches<'a>> for AppConfig {
     fn try_from(matches: &'a clap::ArgMatches) -> Result<Self> {
         match matches.subcommand() {
             ("sync", Some(matches)) => SyncArgs::try_from(matches).map(AppConfig::Sync),
+            ("export", Some(matches)) => ExportArgs::try_from(matches).map(AppConfig::Export),
+            ("query", Some(matches)) => QueryArgs::try_from(matches).map(AppConfig::Query),
+            ("stats", Some(matches)) => StatsArgs::try_from(matches).map(AppConfig::Stats),
+            ("serve", Some(matches)) => ServeArgs::try_from(matches).map(AppConfig::Serve),
+            ("known-scripts", Some(matches)) => {
+                KnownScriptsArgs::try_from(matches).map(AppConfig::KnownScripts)
+            }
+            ("rebuild-rollups", Some(matches)) => {
+                RebuildRollupsArgs::try_from(matches).map(AppConfig::RebuildRollups)
+            }
             _ => unreachable!(),
         }
     }
@@ -47,23 +421,234 @@ impl<'a> TryFrom<&'a clap::ArgMatches<'a>> for AppConfig {
 impl<'a> TryFrom<&'a clap::ArgMatches<'a>> for SyncArgs {
     type Error = Error;
     fn try_from(matches: &'a clap::ArgMatches) -> Result<Self> {
-        let jsonrpc_url = matches
-            .value_of("jsonrpc-url")
-            .map(|url_str| url::Url::parse(url_str))
-            .transpose()?
-            .ok_or_else(|| Error::Unreachable("no argument 'jsonrpc-url'".to_owned()))?;
-        let subscribe_socket = matches
-            .value_of("subscribe-socket")
-            .map(|addr_str| addr_str.parse().unwrap())
-            .ok_or_else(|| Error::Unreachable("no argument 'subscribe-socket'".to_owned()))?;
+        let source = if let Some(path) = matches.value_of("blocks-file") {
+            SyncSource::File(PathBuf::from(path))
+        } else {
+            let jsonrpc_url = matches
+                .value_of("jsonrpc-url")
+                .map(url::Url::parse)
+                .transpose()?
+                .ok_or_else(|| Error::Unreachable("no argument 'jsonrpc-url'".to_owned()))?;
+            let subscribe_socket = matches
+                .value_of("subscribe-socket")
+                .map(|addr_str| addr_str.parse().unwrap())
+                .ok_or_else(|| Error::Unreachable("no argument 'subscribe-socket'".to_owned()))?;
+            SyncSource::JsonRpc {
+                jsonrpc_url,
+                subscribe_socket,
+            }
+        };
+        let storage_uri = matches
+            .value_of("storage-uri")
+            .map(ToOwned::to_owned)
+            .ok_or_else(|| Error::Unreachable("no argument 'storage-uri'".to_owned()))?;
+        let http_listen = parse_socket_addr(matches, "http-listen")?;
+        let ready_max_lag = parse_number(matches, "ready-max-lag")?;
+        let network = parse_network(matches)?;
+        let registry = matches.value_of("registry").map(PathBuf::from);
+        Ok(Self {
+            source,
+            storage_uri,
+            http_listen,
+            ready_max_lag,
+            network,
+            registry,
+        })
+    }
+}
+
+impl<'a> TryFrom<&'a clap::ArgMatches<'a>> for ExportArgs {
+    type Error = Error;
+    fn try_from(matches: &'a clap::ArgMatches) -> Result<Self> {
+        let storage_uri = matches
+            .value_of("storage-uri")
+            .map(ToOwned::to_owned)
+            .ok_or_else(|| Error::Unreachable("no argument 'storage-uri'".to_owned()))?;
+        let output_dir = matches
+            .value_of("output-dir")
+            .map(PathBuf::from)
+            .ok_or_else(|| Error::Unreachable("no argument 'output-dir'".to_owned()))?;
+        let datasets = if let Some(values) = matches.values_of("datasets") {
+            values
+                .map(str::parse)
+                .collect::<kernel::error::Result<Vec<Dataset>>>()?
+        } else {
+            Dataset::ALL.to_owned()
+        };
+        let partition_size = parse_number(matches, "partition-size")?;
+        if partition_size == 0 {
+            return Err(Error::Argument(
+                "'partition-size' should be positive".to_owned(),
+            ));
+        }
+        let confirmations = parse_number(matches, "confirmations")?;
+        Ok(Self {
+            storage_uri,
+            output_dir,
+            datasets,
+            partition_size,
+            confirmations,
+        })
+    }
+}
+
+impl<'a> TryFrom<&'a clap::ArgMatches<'a>> for QueryArgs {
+    type Error = Error;
+    fn try_from(matches: &'a clap::ArgMatches) -> Result<Self> {
+        let storage_uri = matches
+            .value_of("storage-uri")
+            .map(ToOwned::to_owned)
+            .ok_or_else(|| Error::Unreachable("no argument 'storage-uri'".to_owned()))?;
+        let format = parse_format(matches)?;
+        let sql = matches
+            .value_of("sql")
+            .map(ToOwned::to_owned)
+            .ok_or_else(|| Error::Unreachable("no argument 'sql'".to_owned()))?;
+        Ok(Self {
+            storage_uri,
+            format,
+            sql,
+        })
+    }
+}
+
+impl<'a> TryFrom<&'a clap::ArgMatches<'a>> for StatsArgs {
+    type Error = Error;
+    fn try_from(matches: &'a clap::ArgMatches) -> Result<Self> {
+        let (report, sub_matches) = match matches.subcommand() {
+            (name, Some(sub_matches)) => (
+                StatsReport::parse(name, |key| {
+                    // The flags have no values.
+                    sub_matches.value_of(key).or_else(|| {
+                        if sub_matches.is_present(key) {
+                            Some("true")
+                        } else {
+                            None
+                        }
+                    })
+                })?,
+                sub_matches,
+            ),
+            _ => unreachable!(),
+        };
+        // The global arguments could be provided before or after the name of the report.
+        let matches = |name| {
+            if sub_matches.occurrences_of(name) > 0 {
+                sub_matches
+            } else {
+                matches
+            }
+        };
+        let storage_uri = matches("storage-uri")
+            .value_of("storage-uri")
+            .map(ToOwned::to_owned)
+            .ok_or_else(|| Error::Argument("'storage-uri' is required".to_owned()))?;
+        let format = parse_format(matches("format"))?;
+        let network = parse_network(matches("network"))?;
+        let registry = matches("registry").value_of("registry").map(PathBuf::from);
+        let refresh = sub_matches.is_present("refresh");
+        Ok(Self {
+            storage_uri,
+            format,
+            network,
+            registry,
+            report,
+            refresh,
+        })
+    }
+}
+
+impl<'a> TryFrom<&'a clap::ArgMatches<'a>> for ServeArgs {
+    type Error = Error;
+    fn try_from(matches: &'a clap::ArgMatches) -> Result<Self> {
         let storage_uri = matches
             .value_of("storage-uri")
             .map(ToOwned::to_owned)
             .ok_or_else(|| Error::Unreachable("no argument 'storage-uri'".to_owned()))?;
+        let listen = matches
+            .value_of("listen")
+            .ok_or_else(|| Error::Unreachable("no argument 'listen'".to_owned()))?
+            .parse()
+            .map_err(|err| Error::Argument(format!("failed to parse 'listen' since {}", err)))?;
+        let indexer_listen = parse_socket_addr(matches, "indexer-listen")?;
+        let graphql_listen = parse_socket_addr(matches, "graphql-listen")?;
+        let workers = parse_number(matches, "workers")?;
+        if workers == 0 {
+            return Err(Error::Argument("'workers' should be positive".to_owned()));
+        }
+        let network = parse_network(matches)?;
+        let registry = matches.value_of("registry").map(PathBuf::from);
+        Ok(Self {
+            storage_uri,
+            listen,
+            indexer_listen,
+            graphql_listen,
+            workers,
+            network,
+            registry,
+        })
+    }
+}
+
+impl<'a> TryFrom<&'a clap::ArgMatches<'a>> for KnownScriptsArgs {
+    type Error = Error;
+    fn try_from(matches: &'a clap::ArgMatches) -> Result<Self> {
+        let storage_uri = matches.value_of("storage-uri").map(ToOwned::to_owned);
+        let format = parse_format(matches)?;
+        let network = parse_network(matches)?;
+        let registry = matches.value_of("registry").map(PathBuf::from);
+        let search = matches.value_of("search").map(ToOwned::to_owned);
         Ok(Self {
-            jsonrpc_url,
-            subscribe_socket,
             storage_uri,
+            format,
+            network,
+            registry,
+            search,
         })
     }
 }
+
+impl<'a> TryFrom<&'a clap::ArgMatches<'a>> for RebuildRollupsArgs {
+    type Error = Error;
+    fn try_from(matches: &'a clap::ArgMatches) -> Result<Self> {
+        let storage_uri = matches
+            .value_of("storage-uri")
+            .map(ToOwned::to_owned)
+            .ok_or_else(|| Error::Unreachable("no argument 'storage-uri'".to_owned()))?;
+        Ok(Self { storage_uri })
+    }
+}
+
+fn parse_format(matches: &clap::ArgMatches) -> Result<OutputFormat> {
+    matches
+        .value_of("format")
+        .ok_or_else(|| Error::Unreachable("no argument 'format'".to_owned()))?
+        .parse()
+}
+
+fn parse_network(matches: &clap::ArgMatches) -> Result<Option<Network>> {
+    matches
+        .value_of("network")
+        .map(str::parse)
+        .transpose()
+        .map_err(Into::into)
+}
+
+fn parse_number(matches: &clap::ArgMatches, name: &str) -> Result<u64> {
+    matches
+        .value_of(name)
+        .ok_or_else(|| Error::Unreachable(format!("no argument '{}'", name)))?
+        .parse()
+        .map_err(|err| Error::Argument(format!("failed to parse '{}' since {}", name, err)))
+}
+
+fn parse_socket_addr(matches: &clap::ArgMatches, name: &str) -> Result<Option<SocketAddr>> {
+    matches
+        .value_of(name)
+        .map(|addr_str| {
+            addr_str
+                .parse()
+                .map_err(|err| Error::Argument(format!("failed to parse '{}' since {}", name, err)))
+        })
+        .transpose()
+}
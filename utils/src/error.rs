@@ -16,6 +16,8 @@ use uckb_jsonrpc_client::{error::Error as RpcError, url};
 pub(crate) enum Error {
     #[error("internal error: should be unreachable, {0}")]
     Unreachable(String),
+    #[error("argument error: {0}")]
+    Argument(String),
 
     #[error("io error: {0}")]
     IO(#[from] io::Error),
@@ -26,6 +28,12 @@ pub(crate) enum Error {
 
     #[error("kernel error: {0}")]
     Kernel(#[from] kernel::error::Error),
+    #[error("http error: {0}")]
+    Http(String),
+    #[error("metrics error: {0}")]
+    Metrics(#[from] prometheus::Error),
+    #[error("parquet error: {0}")]
+    Parquet(#[from] parquet::errors::ParquetError),
 }
 
 pub(crate) type Result<T> = ::std::result::Result<T, Error>;
@@ -8,6 +8,8 @@
 
 mod config;
 mod error;
+mod output;
+mod service;
 mod subcmd;
 
 fn main() -> anyhow::Result<()> {
@@ -18,6 +20,12 @@ fn main() -> anyhow::Result<()> {
     let config = config::build_commandline()?;
     match config {
         config::AppConfig::Sync(args) => subcmd::sync::execute(args),
+        config::AppConfig::Export(args) => subcmd::export::execute(args),
+        config::AppConfig::Query(args) => subcmd::query::execute(args),
+        config::AppConfig::Stats(args) => subcmd::stats::execute(args),
+        config::AppConfig::Serve(args) => subcmd::serve::execute(args),
+        config::AppConfig::KnownScripts(args) => subcmd::known_scripts::execute(args),
+        config::AppConfig::RebuildRollups(args) => subcmd::rebuild_rollups::execute(args),
     }?;
 
     log::info!("done.");
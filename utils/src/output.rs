@@ -0,0 +1,160 @@
+// Copyright (C) 2019-2020 Boyu Yang
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+use std::{io, str::FromStr};
+
+use kernel::{Report, Value};
+
+use crate::error::{Error, Result};
+
+/// The format to print reports, bytes are printed as `0x`-prefixed hex in all formats.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum OutputFormat {
+    /// Aligned columns for humans.
+    Table,
+    /// Comma-separated values with a header line, nulls are empty fields.
+    Csv,
+    /// A JSON object per line.
+    JsonLines,
+}
+
+impl FromStr for OutputFormat {
+    type Err = Error;
+    fn from_str(s: &str) -> Result<Self> {
+        match s {
+            "table" => Ok(Self::Table),
+            "csv" => Ok(Self::Csv),
+            "jsonl" => Ok(Self::JsonLines),
+            _ => Err(Error::Argument(format!("unknown output format {}", s))),
+        }
+    }
+}
+
+impl OutputFormat {
+    pub(crate) fn write<W: io::Write>(self, w: &mut W, report: &Report) -> Result<()> {
+        match self {
+            Self::Table => write_table(w, report),
+            Self::Csv => write_csv(w, report),
+            Self::JsonLines => write_json_lines(w, report),
+        }
+        .map_err(Into::into)
+    }
+
+    /// Prints the report to the standard output.
+    pub(crate) fn print(self, report: &Report) -> Result<()> {
+        let stdout = io::stdout();
+        let mut lock = stdout.lock();
+        self.write(&mut lock, report)
+    }
+}
+
+fn write_table<W: io::Write>(w: &mut W, report: &Report) -> io::Result<()> {
+    let cells = report
+        .rows()
+        .iter()
+        .map(|row| row.iter().map(ToString::to_string).collect::<Vec<_>>())
+        .collect::<Vec<_>>();
+    let widths = report
+        .columns()
+        .iter()
+        .enumerate()
+        .map(|(index, column)| {
+            cells
+                .iter()
+                .filter_map(|row| row.get(index))
+                .map(|cell| cell.chars().count())
+                .chain(Some(column.chars().count()))
+                .max()
+                .unwrap_or(0)
+        })
+        .collect::<Vec<_>>();
+    let header = report
+        .columns()
+        .iter()
+        .zip(widths.iter())
+        .map(|(column, width)| format!("{:<width$}", column, width = width))
+        .collect::<Vec<_>>();
+    writeln!(w, "{}", header.join(" | ").trim_end())?;
+    let separator = widths
+        .iter()
+        .map(|width| "-".repeat(*width))
+        .collect::<Vec<_>>();
+    writeln!(w, "{}", separator.join("-+-"))?;
+    for (row, texts) in report.rows().iter().zip(cells.iter()) {
+        let line = row
+            .iter()
+            .zip(texts.iter())
+            .zip(widths.iter())
+            .map(|((value, text), width)| match value {
+                Value::Integer(_) | Value::Float(_) => {
+                    format!("{:>width$}", text, width = width)
+                }
+                _ => format!("{:<width$}", text, width = width),
+            })
+            .collect::<Vec<_>>();
+        writeln!(w, "{}", line.join(" | ").trim_end())?;
+    }
+    Ok(())
+}
+
+fn csv_field(text: &str) -> String {
+    if text.contains(&[',', '"', '\n', '\r'][..]) {
+        format!("\"{}\"", text.replace('"', "\"\""))
+    } else {
+        text.to_owned()
+    }
+}
+
+fn write_csv<W: io::Write>(w: &mut W, report: &Report) -> io::Result<()> {
+    let header = report
+        .columns()
+        .iter()
+        .map(|column| csv_field(column))
+        .collect::<Vec<_>>();
+    writeln!(w, "{}", header.join(","))?;
+    for row in report.rows() {
+        let line = row
+            .iter()
+            .map(|value| match value {
+                Value::Null => String::new(),
+                _ => csv_field(&value.to_string()),
+            })
+            .collect::<Vec<_>>();
+        writeln!(w, "{}", line.join(","))?;
+    }
+    Ok(())
+}
+
+pub(crate) fn json_value(value: &Value) -> serde_json::Value {
+    match value {
+        Value::Null => serde_json::Value::Null,
+        Value::Integer(value) => (*value).into(),
+        Value::Float(value) => serde_json::Number::from_f64(*value)
+            .map(serde_json::Value::Number)
+            .unwrap_or(serde_json::Value::Null),
+        Value::Text(value) => value.clone().into(),
+        Value::Bytes(_) => value.to_string().into(),
+    }
+}
+
+// The keys are written in the order of the columns.
+fn write_json_lines<W: io::Write>(w: &mut W, report: &Report) -> io::Result<()> {
+    for row in report.rows() {
+        let fields = report
+            .columns()
+            .iter()
+            .zip(row.iter())
+            .map(|(column, value)| {
+                let key = serde_json::Value::from(column.as_str());
+                format!("{}:{}", key, json_value(value))
+            })
+            .collect::<Vec<_>>();
+        writeln!(w, "{{{}}}", fields.join(","))?;
+    }
+    Ok(())
+}
@@ -0,0 +1,553 @@
+// Copyright (C) 2019-2020 Boyu Yang
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+use std::{cmp, collections::HashMap};
+
+use kernel::{
+    traits::{Accounts as _, Explorer as _},
+    Address, BlockId, CellFilter, Network, Registry, Report, Row, Storage,
+};
+use serde_json::{json, Map, Value as JsonValue};
+use uckb_jsonrpc_client::url::form_urlencoded;
+
+use super::{json_response, HttpResponse};
+use crate::{
+    config::StatsReport,
+    error::{Error, Result},
+    output::json_value,
+    subcmd::stats,
+};
+
+const DEFAULT_LIMIT: u32 = 50;
+const MAX_LIMIT: u32 = 500;
+
+/// The read-only REST API over the stored chain.
+///
+/// Bytes are encoded as `0x`-prefixed hex. Lists are returned as `{"data": [..], "next_cursor":
+/// ..}`, pass `next_cursor` as the parameter `cursor` to fetch the next page, it is `null` on the
+/// last page. A lock hash could also be provided as an address.
+pub(crate) struct Api {
+    storage: Storage,
+    network: Network,
+    registry: Registry,
+}
+
+type Query = HashMap<String, String>;
+
+impl Api {
+    pub(crate) fn new(storage: Storage, network: Network, registry: Registry) -> Self {
+        Self {
+            storage,
+            network,
+            registry,
+        }
+    }
+
+    pub(crate) fn handle(&self, url: &str) -> HttpResponse {
+        let (path, query_str) = if let Some(pos) = url.find('?') {
+            (&url[..pos], &url[pos + 1..])
+        } else {
+            (url, "")
+        };
+        let query = form_urlencoded::parse(query_str.as_bytes())
+            .into_owned()
+            .collect::<Query>();
+        let segments = path
+            .trim_matches('/')
+            .split('/')
+            .filter(|segment| !segment.is_empty())
+            .collect::<Vec<_>>();
+        let result = match segments.as_slice() {
+            ["blocks"] => self.blocks(&query),
+            ["blocks", id] => self.block(id),
+            ["transactions", hash] => self.transaction(hash),
+            ["cells"] => self.cells(&query),
+            ["scripts", hash] => self.script(hash),
+            ["accounts", hash] => self.account(hash),
+            ["accounts", hash, "changes"] => self.account_changes(hash, &query),
+            ["stats", report] => self.stats(report, &query),
+            _ => Ok(None),
+        };
+        match result {
+            Ok(Some(body)) => json_response(200, &body),
+            Ok(None) => json_response(404, &json!({ "error": "not found" })),
+            Err(Error::Argument(message)) => json_response(400, &json!({ "error": message })),
+            Err(err) => {
+                log::error!("failed to handle {} since {}", path, err);
+                json_response(500, &json!({ "error": err.to_string() }))
+            }
+        }
+    }
+
+    fn blocks(&self, query: &Query) -> Result<Option<JsonValue>> {
+        let limit = parse_limit(query)?;
+        let before = query
+            .get("cursor")
+            .map(|cursor| parse_cursor_number(cursor))
+            .transpose()?;
+        let mut rows = self.storage.blocks(before, limit + 1)?;
+        let next_cursor = next_page(&mut rows, limit)
+            .map(|row| row.try_get::<i64>(0))
+            .transpose()?
+            .map(|number| number.to_string());
+        Ok(Some(page(&rows, next_cursor)))
+    }
+
+    fn block(&self, id: &str) -> Result<Option<JsonValue>> {
+        let id = if id.starts_with("0x") {
+            BlockId::Hash(parse_hash(id)?)
+        } else {
+            BlockId::Number(parse_cursor_number(id)?)
+        };
+        let row = if let Some(row) = self.storage.block(&id)? {
+            row
+        } else {
+            return Ok(None);
+        };
+        let hash = row.try_get::<Vec<u8>>(1)?;
+        let mut block = object(&row);
+        block.insert(
+            "uncles".to_owned(),
+            objects(&self.storage.block_uncles(&hash)?),
+        );
+        block.insert(
+            "proposals".to_owned(),
+            values(&self.storage.block_proposals(&hash)?),
+        );
+        block.insert(
+            "transactions".to_owned(),
+            values(&self.storage.block_transactions(&hash)?),
+        );
+        Ok(Some(block.into()))
+    }
+
+    fn transaction(&self, hash: &str) -> Result<Option<JsonValue>> {
+        let hash = parse_hash(hash)?;
+        let row = if let Some(row) = self.storage.transaction(&hash)? {
+            row
+        } else {
+            return Ok(None);
+        };
+        let mut tx = object(&row);
+        tx.insert(
+            "cell_deps".to_owned(),
+            objects(&self.storage.transaction_cell_deps(&hash)?),
+        );
+        tx.insert(
+            "header_deps".to_owned(),
+            values(&self.storage.transaction_header_deps(&hash)?),
+        );
+        tx.insert(
+            "inputs".to_owned(),
+            objects(&self.storage.transaction_inputs(&hash)?),
+        );
+        tx.insert(
+            "outputs".to_owned(),
+            objects(&self.storage.transaction_outputs(&hash)?),
+        );
+        tx.insert(
+            "witnesses".to_owned(),
+            values(&self.storage.transaction_witnesses(&hash)?),
+        );
+        Ok(Some(tx.into()))
+    }
+
+    fn cells(&self, query: &Query) -> Result<Option<JsonValue>> {
+        let limit = parse_limit(query)?;
+        let filter = CellFilter {
+            lock_hash: query
+                .get("lock_hash")
+                .map(|s| parse_lock_hash(s, self.network))
+                .transpose()?,
+            type_hash: query.get("type_hash").map(|s| parse_hash(s)).transpose()?,
+            live: query
+                .get("live")
+                .map(|live| match live.as_str() {
+                    "true" => Ok(true),
+                    "false" => Ok(false),
+                    _ => Err(Error::Argument(format!("incorrect live {}", live))),
+                })
+                .transpose()?,
+            at: query
+                .get("at")
+                .map(|at| parse_cursor_number(at))
+                .transpose()?,
+        };
+        if filter.lock_hash.is_none() && filter.type_hash.is_none() {
+            return Err(Error::Argument(
+                "require the parameter lock_hash or type_hash".to_owned(),
+            ));
+        }
+        let after = query
+            .get("cursor")
+            .map(|cursor| parse_cursor_out_point(cursor))
+            .transpose()?;
+        let after_ref = after
+            .as_ref()
+            .map(|(tx_hash, index)| (tx_hash.as_slice(), *index));
+        let mut rows = self.storage.cells(&filter, after_ref, limit + 1)?;
+        let next_cursor = next_page(&mut rows, limit)
+            .map(cursor_out_point)
+            .transpose()?;
+        Ok(Some(page(&rows, next_cursor)))
+    }
+
+    // The addresses are only for the scripts which are used as locks.
+    fn script(&self, hash: &str) -> Result<Option<JsonValue>> {
+        let hash = parse_hash(hash)?;
+        self.storage
+            .script(&hash)?
+            .map(|row| {
+                let mut script = object(&row);
+                if row.try_get::<i64>(4)? > 0 {
+                    let address = script_address(&row, self.network)?;
+                    script.insert("address".to_owned(), address.to_full().into());
+                    script.insert("short_address".to_owned(), address.to_short().into());
+                }
+                Ok(script.into())
+            })
+            .transpose()
+    }
+
+    fn account(&self, hash: &str) -> Result<Option<JsonValue>> {
+        let hash = parse_lock_hash(hash, self.network)?;
+        self.storage
+            .account(&hash)?
+            .map(|row| {
+                let mut account = object(&row);
+                let address = script_address(&row, self.network)?;
+                account.insert("address".to_owned(), address.to_full().into());
+                account.insert("short_address".to_owned(), address.to_short().into());
+                Ok(account.into())
+            })
+            .transpose()
+    }
+
+    fn account_changes(&self, hash: &str, query: &Query) -> Result<Option<JsonValue>> {
+        let hash = parse_lock_hash(hash, self.network)?;
+        let limit = parse_limit(query)?;
+        let before = query
+            .get("cursor")
+            .map(|cursor| parse_cursor_position(cursor))
+            .transpose()?;
+        let mut rows = self.storage.account_changes(&hash, before, limit + 1)?;
+        let next_cursor = next_page(&mut rows, limit)
+            .map(cursor_position)
+            .transpose()?;
+        Ok(Some(page(&rows, next_cursor)))
+    }
+
+    fn stats(&self, report: &str, query: &Query) -> Result<Option<JsonValue>> {
+        let report = StatsReport::parse(report, |key| query.get(key).map(String::as_str))?;
+        let report = stats::generate(&self.storage, report, self.network, &self.registry)?;
+        Ok(Some(report_objects(&report)))
+    }
+}
+
+fn object(row: &Row) -> Map<String, JsonValue> {
+    row.columns()
+        .iter()
+        .zip(row.values())
+        .map(|(column, value)| (column.clone(), json_value(value)))
+        .collect()
+}
+
+fn objects(rows: &[Row]) -> JsonValue {
+    rows.iter().map(object).map(JsonValue::Object).collect()
+}
+
+// The values of the first column.
+fn values(rows: &[Row]) -> JsonValue {
+    rows.iter()
+        .filter_map(|row| row.values().first())
+        .map(json_value)
+        .collect()
+}
+
+fn report_objects(report: &Report) -> JsonValue {
+    report
+        .rows()
+        .iter()
+        .map(|row| {
+            report
+                .columns()
+                .iter()
+                .zip(row.iter())
+                .map(|(column, value)| (column.clone(), json_value(value)))
+                .collect::<Map<_, _>>()
+        })
+        .map(JsonValue::Object)
+        .collect()
+}
+
+fn page(rows: &[Row], next_cursor: Option<String>) -> JsonValue {
+    json!({
+        "data": objects(rows),
+        "next_cursor": next_cursor,
+    })
+}
+
+// The rows are queried with one more row than the limit to know whether there is a next page,
+// returns the last row of this page if there is.
+fn next_page(rows: &mut Vec<Row>, limit: u32) -> Option<&Row> {
+    if rows.len() > limit as usize {
+        rows.truncate(limit as usize);
+        rows.last()
+    } else {
+        None
+    }
+}
+
+// The cursor of a cell, the row starts with the hash of the transaction and the index.
+fn cursor_out_point(row: &Row) -> Result<String> {
+    let tx_hash = row.try_get::<Vec<u8>>(0)?;
+    let index = row.try_get::<i64>(1)?;
+    Ok(format!("{}-{}", encode_hex(&tx_hash), index))
+}
+
+// The cursor of a transaction, the row starts with the block number and the index in the block.
+fn cursor_position(row: &Row) -> Result<String> {
+    let block_number = row.try_get::<i64>(0)?;
+    let tx_index = row.try_get::<i64>(1)?;
+    Ok(format!("{}-{}", block_number, tx_index))
+}
+
+fn parse_limit(query: &Query) -> Result<u32> {
+    query
+        .get("limit")
+        .map(|limit| {
+            limit
+                .parse::<u32>()
+                .map_err(|err| Error::Argument(format!("incorrect limit {} ({})", limit, err)))
+        })
+        .transpose()
+        .map(|limit| cmp::min(limit.unwrap_or(DEFAULT_LIMIT), MAX_LIMIT))
+}
+
+fn parse_cursor_number(input: &str) -> Result<u64> {
+    input
+        .parse()
+        .map_err(|err| Error::Argument(format!("incorrect number {} ({})", input, err)))
+}
+
+fn parse_cursor_out_point(input: &str) -> Result<(Vec<u8>, u32)> {
+    let mut parts = input.splitn(2, '-');
+    let tx_hash = parts.next().map(parse_hash).transpose()?;
+    let index = parts.next().map(|index| index.parse::<u32>().ok());
+    if let (Some(tx_hash), Some(Some(index))) = (tx_hash, index) {
+        Ok((tx_hash, index))
+    } else {
+        Err(Error::Argument(format!("incorrect cursor {}", input)))
+    }
+}
+
+// The position of a transaction, as `{block_number}-{tx_index}`.
+fn parse_cursor_position(input: &str) -> Result<(u64, u32)> {
+    let mut parts = input.splitn(2, '-');
+    let block_number = parts.next().and_then(|number| number.parse::<u64>().ok());
+    let tx_index = parts.next().and_then(|index| index.parse::<u32>().ok());
+    if let (Some(block_number), Some(tx_index)) = (block_number, tx_index) {
+        Ok((block_number, tx_index))
+    } else {
+        Err(Error::Argument(format!("incorrect cursor {}", input)))
+    }
+}
+
+pub(crate) fn parse_hash(input: &str) -> Result<Vec<u8>> {
+    let hex = input
+        .strip_prefix("0x")
+        .ok_or_else(|| Error::Argument(format!("hash {} should be 0x-prefixed", input)))?;
+    let mut hash = [0u8; 32];
+    if hex.len() != hash.len() * 2 {
+        return Err(Error::Argument(format!(
+            "incorrect length of hash {}",
+            input
+        )));
+    }
+    faster_hex::hex_decode(hex.as_bytes(), &mut hash)
+        .map_err(|err| Error::Argument(format!("incorrect hash {} ({})", input, err)))?;
+    Ok(hash.to_vec())
+}
+
+/// Parses a lock hash, or an address of the network into the hash of its lock script.
+pub(crate) fn parse_lock_hash(input: &str, network: Network) -> Result<Vec<u8>> {
+    if input.starts_with("0x") {
+        return parse_hash(input);
+    }
+    let address = input.parse::<Address>().map_err(|err| {
+        Error::Argument(format!(
+            "{} is neither a lock hash nor an address ({})",
+            input, err
+        ))
+    })?;
+    if *address.network() != network {
+        return Err(Error::Argument(format!(
+            "address {} is not for {}",
+            input, network
+        )));
+    }
+    Ok(address.script_hash())
+}
+
+/// The address of a script, the row starts with the hash, the code hash, the hash type and the
+/// args of the script.
+pub(crate) fn script_address(row: &Row, network: Network) -> Result<Address> {
+    let code_hash = row.try_get::<Vec<u8>>(1)?;
+    let hash_type = row.try_get::<i64>(2)?;
+    let args = row.try_get::<Vec<u8>>(3)?;
+    Address::new(network, &code_hash, hash_type as u8, &args).map_err(Into::into)
+}
+
+pub(super) fn encode_hex(bytes: &[u8]) -> String {
+    format!("0x{}", faster_hex::hex_string(bytes).expect("a hex string"))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Read as _;
+
+    use kernel::{traits::BaseData as _, Network, Registry, Row, Storage};
+    use serde_json::Value as JsonValue;
+
+    use super::{
+        cursor_out_point, cursor_position, encode_hex, next_page, parse_cursor_out_point,
+        parse_cursor_position, parse_limit, Api, Query, DEFAULT_LIMIT, MAX_LIMIT,
+    };
+    use crate::service::memory_storage;
+
+    fn rows(storage: &Storage, sql: &str) -> Vec<Row> {
+        storage.query(sql, &[]).unwrap()
+    }
+
+    fn get(api: &Api, url: &str) -> (u16, JsonValue) {
+        let response = api.handle(url);
+        let status_code = response.status_code().0;
+        let mut text = String::new();
+        response.into_reader().read_to_string(&mut text).unwrap();
+        (status_code, serde_json::from_str(&text).unwrap())
+    }
+
+    #[test]
+    fn parse_limits() {
+        let limit = |limit: &str| {
+            let mut query = Query::new();
+            query.insert("limit".to_owned(), limit.to_owned());
+            parse_limit(&query)
+        };
+        assert_eq!(parse_limit(&Query::new()).unwrap(), DEFAULT_LIMIT);
+        assert_eq!(limit("10").unwrap(), 10);
+        assert_eq!(limit("500").unwrap(), MAX_LIMIT);
+        assert_eq!(limit("501").unwrap(), MAX_LIMIT);
+        assert_eq!(limit("4294967295").unwrap(), MAX_LIMIT);
+        assert!(limit("-1").is_err());
+        assert!(limit("4294967296").is_err());
+        assert!(limit("ten").is_err());
+    }
+
+    #[test]
+    fn parse_cursors() {
+        let tx_hash = encode_hex(&[1u8; 32]);
+        let cursor = format!("{}-3", tx_hash);
+        assert_eq!(parse_cursor_out_point(&cursor).unwrap(), (vec![1u8; 32], 3));
+        assert!(parse_cursor_out_point(&tx_hash).is_err());
+        assert!(parse_cursor_out_point(&format!("{}-", tx_hash)).is_err());
+        assert!(parse_cursor_out_point(&format!("{}--1", tx_hash)).is_err());
+        assert!(parse_cursor_out_point(&format!("{}-3", &tx_hash[2..])).is_err());
+        assert!(parse_cursor_out_point(&format!("{}-3", &tx_hash[..64])).is_err());
+        assert!(parse_cursor_out_point("12-3").is_err());
+
+        assert_eq!(parse_cursor_position("12-3").unwrap(), (12, 3));
+        assert_eq!(parse_cursor_position("0-0").unwrap(), (0, 0));
+        assert!(parse_cursor_position("12").is_err());
+        assert!(parse_cursor_position("12-").is_err());
+        assert!(parse_cursor_position("-3").is_err());
+        assert!(parse_cursor_position("12-3-4").is_err());
+        assert!(parse_cursor_position("12-4294967296").is_err());
+        assert!(parse_cursor_position(&cursor).is_err());
+    }
+
+    #[test]
+    fn next_pages() {
+        let storage = memory_storage();
+        let sql = r#"
+            SELECT 7 AS block_number, 2 AS tx_index
+             UNION ALL SELECT 7, 1
+             UNION ALL SELECT 6, 5
+        ;"#;
+        let mut page = rows(&storage, sql);
+        assert!(next_page(&mut page, 3).is_none());
+        assert_eq!(page.len(), 3);
+        let cursor = next_page(&mut page, 2)
+            .map(cursor_position)
+            .unwrap()
+            .unwrap();
+        assert_eq!(page.len(), 2);
+        assert_eq!(cursor, "7-1");
+        assert_eq!(parse_cursor_position(&cursor).unwrap(), (7, 1));
+
+        let tx_hash = encode_hex(&[1u8; 32]);
+        let sql = format!(
+            r#"
+            SELECT x'{0}' AS tx_hash, 0 AS "index"
+             UNION ALL SELECT x'{0}', 1
+        ;"#,
+            &tx_hash[2..]
+        );
+        let mut page = rows(&storage, &sql);
+        let cursor = next_page(&mut page, 1)
+            .map(cursor_out_point)
+            .unwrap()
+            .unwrap();
+        assert_eq!(page.len(), 1);
+        assert_eq!(cursor, format!("{}-0", tx_hash));
+        assert_eq!(parse_cursor_out_point(&cursor).unwrap(), (vec![1u8; 32], 0));
+    }
+
+    #[test]
+    fn addresses_of_lock_scripts_only() {
+        let mut storage = memory_storage();
+        storage.initialize().unwrap();
+        let lock_hash = encode_hex(&[1u8; 32]);
+        let type_hash = encode_hex(&[2u8; 32]);
+        let sql = format!(
+            r#"
+            INSERT INTO scripts (hash, code_hash, hash_type, args)
+            VALUES (x'{0}', x'{2}', 1, x'{3}'), (x'{1}', x'{2}', 0, x'')
+        ;"#,
+            &lock_hash[2..],
+            &type_hash[2..],
+            "03".repeat(32),
+            "04".repeat(20),
+        );
+        rows(&storage, &sql);
+        let sql = format!(
+            r#"
+            INSERT INTO cells (
+                tx_hash, "index", capacity, lock_hash, type_hash, data_hash, created_block_number
+            ) VALUES (x'{0}', 0, 100, x'{1}', x'{2}', x'{0}', 0)
+        ;"#,
+            "05".repeat(32),
+            &lock_hash[2..],
+            &type_hash[2..],
+        );
+        rows(&storage, &sql);
+        let api = Api::new(storage, Network::Mainnet, Registry::default());
+
+        let (status_code, script) = get(&api, &format!("/scripts/{}", lock_hash));
+        assert_eq!(status_code, 200);
+        assert_eq!(script["lock_cells"], 1);
+        assert!(script["address"].is_string());
+
+        let (status_code, script) = get(&api, &format!("/scripts/{}", type_hash));
+        assert_eq!(status_code, 200);
+        assert_eq!(script["lock_cells"], 0);
+        assert_eq!(script["type_cells"], 1);
+        assert!(script.get("address").is_none());
+        assert!(script.get("short_address").is_none());
+    }
+}
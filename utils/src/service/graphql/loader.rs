@@ -0,0 +1,142 @@
+// Copyright (C) 2019-2020 Boyu Yang
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+use std::{
+    collections::{HashMap, HashSet},
+    hash::Hash,
+    sync::Arc,
+};
+
+use kernel::{
+    error::{Error, Result},
+    FromValue, Row, Storage,
+};
+use parking_lot::Mutex;
+
+/// A row, with the rows which are loaded in the same query as its siblings.
+#[derive(Clone)]
+pub(super) struct Node {
+    rows: Arc<Vec<Row>>,
+    index: usize,
+}
+
+type Fetch<K> = fn(&Storage, &[K]) -> Result<Vec<Row>>;
+type KeyOf<K> = Box<dyn Fn(&Row) -> Result<Option<K>> + Send + Sync>;
+
+/// Loads the rows related to the keys of nodes, and caches them during a request.
+///
+/// When the rows of a node are missed, the rows of all its siblings which are not cached are
+/// loaded together, so a list of nodes queries once for each relation instead of once for each
+/// node.
+pub(super) struct Loader<K> {
+    fetch: Fetch<K>,
+    // Extracts the key from a fetched row.
+    key_of: KeyOf<K>,
+    cache: Mutex<HashMap<K, Vec<Node>>>,
+}
+
+impl Node {
+    pub(super) fn all(rows: Vec<Row>) -> Vec<Self> {
+        let rows = Arc::new(rows);
+        (0..rows.len())
+            .map(|index| Self {
+                rows: Arc::clone(&rows),
+                index,
+            })
+            .collect()
+    }
+
+    pub(super) fn get<T: FromValue>(&self, name: &str) -> Result<T> {
+        column(&self.rows[self.index], name)
+    }
+}
+
+/// Gets the value of a column by its name.
+pub(super) fn column<T: FromValue>(row: &Row, name: &str) -> Result<T> {
+    row.columns()
+        .iter()
+        .position(|column| column == name)
+        .ok_or_else(|| Error::Data(format!("no column named {}", name)))
+        .and_then(|index| row.try_get(index))
+}
+
+/// Uses the bytes in a column as the key, a null column has no key.
+pub(super) fn bytes_key(name: &'static str) -> impl Fn(&Row) -> Result<Option<Vec<u8>>> {
+    move |row| column(row, name)
+}
+
+/// Uses an out point as the key.
+pub(super) fn out_point_key(
+    tx_hash: &'static str,
+    index: &'static str,
+) -> impl Fn(&Row) -> Result<Option<(Vec<u8>, u32)>> {
+    move |row| {
+        let tx_hash = column::<Vec<u8>>(row, tx_hash)?;
+        let index = column::<i64>(row, index)?;
+        Ok(Some((tx_hash, index as u32)))
+    }
+}
+
+impl<K: Clone + Eq + Hash> Loader<K> {
+    pub(super) fn new<F>(fetch: Fetch<K>, key_of: F) -> Self
+    where
+        F: Fn(&Row) -> Result<Option<K>> + Send + Sync + 'static,
+    {
+        Self {
+            fetch,
+            key_of: Box::new(key_of),
+            cache: Mutex::new(HashMap::new()),
+        }
+    }
+
+    pub(super) fn clear(&self) {
+        self.cache.lock().clear();
+    }
+
+    /// Returns the nodes related to the key of the node, `key_in` extracts the key from the node
+    /// and its siblings.
+    pub(super) fn load<F>(&self, storage: &Storage, node: &Node, key_in: F) -> Result<Vec<Node>>
+    where
+        F: Fn(&Row) -> Result<Option<K>>,
+    {
+        let key = if let Some(key) = key_in(&node.rows[node.index])? {
+            key
+        } else {
+            return Ok(Vec::new());
+        };
+        let keys = {
+            let cache = self.cache.lock();
+            if let Some(nodes) = cache.get(&key) {
+                return Ok(nodes.clone());
+            }
+            let mut seen = HashSet::new();
+            let mut keys = Vec::new();
+            for row in node.rows.iter() {
+                if let Some(key) = key_in(row)? {
+                    if !cache.contains_key(&key) && seen.insert(key.clone()) {
+                        keys.push(key);
+                    }
+                }
+            }
+            keys
+        };
+        let rows = (self.fetch)(storage, &keys)?;
+        let mut grouped = keys
+            .into_iter()
+            .map(|key| (key, Vec::new()))
+            .collect::<HashMap<_, _>>();
+        for node in Node::all(rows) {
+            if let Some(key) = (self.key_of)(&node.rows[node.index])? {
+                grouped.entry(key).or_insert_with(Vec::new).push(node);
+            }
+        }
+        let nodes = grouped.get(&key).cloned().unwrap_or_default();
+        self.cache.lock().extend(grouped);
+        Ok(nodes)
+    }
+}
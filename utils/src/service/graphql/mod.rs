@@ -0,0 +1,254 @@
+// Copyright (C) 2019-2020 Boyu Yang
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+use std::collections::HashMap;
+
+use juniper::{
+    http::{graphiql, GraphQLBatchRequest},
+    parser::parse_document_source,
+    DefaultScalarValue, Definition, EmptyMutation, EmptySubscription, RootNode, Selection,
+};
+use kernel::{Network, Storage};
+use serde::Deserialize;
+use serde_json::json;
+
+use super::{html_response, json_response, json_text_response, HttpResponse};
+
+mod loader;
+mod schema;
+
+use self::schema::{Context, Query};
+
+type Schema = RootNode<'static, Query, EmptyMutation<Context>, EmptySubscription<Context>>;
+
+type Selections<'a> = [Selection<'a, DefaultScalarValue>];
+
+// The limit of the nesting of the fields in a query.
+const MAX_QUERY_DEPTH: usize = 10;
+
+// The query text of a request or of each request in a batch.
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum QueryTexts {
+    Single(QueryText),
+    Batch(Vec<QueryText>),
+}
+
+#[derive(Deserialize)]
+struct QueryText {
+    query: String,
+}
+
+/// The GraphQL API over the stored chain.
+///
+/// The relations of a list of objects are loaded in batches, so the count of queries depends on
+/// the depth of a GraphQL query instead of the count of objects.
+pub(crate) struct Graphql {
+    schema: Schema,
+    context: Context,
+}
+
+impl Graphql {
+    pub(crate) fn new(storage: Storage, network: Network) -> Self {
+        let schema = Schema::new(Query, EmptyMutation::new(), EmptySubscription::new());
+        let context = Context::new(storage, network);
+        Self { schema, context }
+    }
+
+    /// Executes a GraphQL request or a batch of requests.
+    pub(crate) fn handle(&self, body: &str) -> HttpResponse {
+        let request: GraphQLBatchRequest = match serde_json::from_str(body) {
+            Ok(request) => request,
+            Err(err) => return json_response(400, &json!({ "error": err.to_string() })),
+        };
+        let queries = match serde_json::from_str(body) {
+            Ok(QueryTexts::Single(query)) => vec![query],
+            Ok(QueryTexts::Batch(queries)) => queries,
+            Err(err) => return json_response(400, &json!({ "error": err.to_string() })),
+        };
+        for QueryText { query } in &queries {
+            if self.query_depth(query) > MAX_QUERY_DEPTH {
+                let errmsg = format!("the query is nested deeper than {}", MAX_QUERY_DEPTH);
+                return json_response(400, &json!({ "error": errmsg }));
+            }
+        }
+        self.context.clear();
+        let response = request.execute_sync(&self.schema, &self.context);
+        let status_code = if response.is_ok() { 200 } else { 400 };
+        match serde_json::to_string(&response) {
+            Ok(text) => json_text_response(status_code, text),
+            Err(err) => json_response(500, &json!({ "error": err.to_string() })),
+        }
+    }
+
+    // The deepest nesting of the fields of the operations, a query which fails to be parsed is
+    // left to the execution to report.
+    fn query_depth(&self, query: &str) -> usize {
+        let document = match parse_document_source(query, &self.schema.schema) {
+            Ok(document) => document,
+            Err(_) => return 0,
+        };
+        let fragments = document
+            .iter()
+            .filter_map(|definition| match definition {
+                Definition::Fragment(fragment) => {
+                    Some((fragment.item.name.item, &fragment.item.selection_set[..]))
+                }
+                Definition::Operation(_) => None,
+            })
+            .collect::<HashMap<_, _>>();
+        let mut depths = HashMap::new();
+        document
+            .iter()
+            .filter_map(|definition| match definition {
+                Definition::Operation(operation) => Some(selections_depth(
+                    &operation.item.selection_set,
+                    &fragments,
+                    &mut depths,
+                )),
+                Definition::Fragment(_) => None,
+            })
+            .max()
+            .unwrap_or(0)
+    }
+
+    /// The GraphiQL page to explore the API in a browser.
+    pub(crate) fn graphiql(endpoint: &str) -> HttpResponse {
+        html_response(200, graphiql::graphiql_source(endpoint, None))
+    }
+}
+
+// The deepest nesting of the fields in the selections, the depths of the fragments are
+// remembered, so a fragment which is spread many times is walked once.
+fn selections_depth<'a>(
+    selections: &'a Selections<'a>,
+    fragments: &HashMap<&'a str, &'a Selections<'a>>,
+    depths: &mut HashMap<&'a str, usize>,
+) -> usize {
+    selections
+        .iter()
+        .map(|selection| match selection {
+            Selection::Field(field) => match field.item.selection_set {
+                // The introspection doesn't query the storage.
+                Some(_) if field.item.name.item.starts_with("__") => 0,
+                Some(ref selections) => 1 + selections_depth(selections, fragments, depths),
+                None => 0,
+            },
+            Selection::InlineFragment(fragment) => {
+                selections_depth(&fragment.item.selection_set, fragments, depths)
+            }
+            Selection::FragmentSpread(spread) => {
+                let name = spread.item.name.item;
+                if let Some(depth) = depths.get(name) {
+                    return *depth;
+                }
+                // The cycles of fragments are rejected by the validation, they are not followed.
+                depths.insert(name, 0);
+                let depth = fragments
+                    .get(name)
+                    .map(|selections| selections_depth(selections, fragments, depths))
+                    .unwrap_or(0);
+                depths.insert(name, depth);
+                depth
+            }
+        })
+        .max()
+        .unwrap_or(0)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Read as _;
+
+    use kernel::Network;
+
+    use super::{Graphql, MAX_QUERY_DEPTH};
+    use crate::service::memory_storage;
+
+    fn graphql() -> Graphql {
+        Graphql::new(memory_storage(), Network::Mainnet)
+    }
+
+    // Nests `parent` in the block at the tip, the depth of the query is `depth`.
+    fn nested_query(depth: usize) -> String {
+        let mut query = "number".to_owned();
+        for _ in 1..depth {
+            query = format!("parent {{ {} }}", query);
+        }
+        format!("{{ tip {{ {} }} }}", query)
+    }
+
+    // Each fragment spreads the previous fragment twice, the depth of the query is `count + 1`.
+    fn fragments_query(count: usize) -> String {
+        let mut query = "fragment F0 on Block { number }".to_owned();
+        for i in 1..=count {
+            query.push_str(&format!(
+                " fragment F{} on Block {{ hash parent {{ ...F{} }} ...F{} }}",
+                i,
+                i - 1,
+                i - 1
+            ));
+        }
+        query.push_str(&format!(" {{ tip {{ ...F{} ...F{} }} }}", count, count));
+        query
+    }
+
+    #[test]
+    fn depth_of_nested_queries() {
+        let graphql = graphql();
+        assert_eq!(graphql.query_depth("{ tip { number } }"), 1);
+        assert_eq!(graphql.query_depth(&nested_query(4)), 4);
+        let query = "{ tip { number transactions { hash } parent { uncles { hash } } } }";
+        assert_eq!(graphql.query_depth(query), 3);
+        let query = "{ tip { ... on Block { parent { number } } } }";
+        assert_eq!(graphql.query_depth(query), 2);
+        let query = "query A { tip { number } } query B { tip { parent { number } } }";
+        assert_eq!(graphql.query_depth(query), 2);
+        // The query which fails to be parsed is left to the execution.
+        assert_eq!(graphql.query_depth("{ tip { number }"), 0);
+    }
+
+    #[test]
+    fn reject_queries_over_the_limit() {
+        let graphql = graphql();
+        let body = serde_json::json!({ "query": nested_query(MAX_QUERY_DEPTH + 1) }).to_string();
+        let response = graphql.handle(&body);
+        assert_eq!(response.status_code().0, 400);
+        let mut text = String::new();
+        response.into_reader().read_to_string(&mut text).unwrap();
+        assert!(text.contains("the query is nested deeper than 10"));
+
+        // Any query in a batch could be rejected.
+        let body = serde_json::json!([
+            { "query": nested_query(1) },
+            { "query": nested_query(MAX_QUERY_DEPTH + 1) },
+        ])
+        .to_string();
+        assert_eq!(graphql.handle(&body).status_code().0, 400);
+    }
+
+    #[test]
+    fn walk_fragments_once() {
+        let graphql = graphql();
+        assert_eq!(graphql.query_depth(&fragments_query(3)), 4);
+        // The fragments are spread 2^40 times in total.
+        assert_eq!(graphql.query_depth(&fragments_query(40)), 41);
+    }
+
+    #[test]
+    fn skip_introspection() {
+        let graphql = graphql();
+        let types = "types { fields { type { ofType { ofType { ofType { name } } } } } }";
+        let query = format!("{{ __schema {{ {} }} }}", types);
+        assert_eq!(graphql.query_depth(&query), 0);
+        let query = format!("{{ __schema {{ {} }} tip {{ number }} }}", types);
+        assert_eq!(graphql.query_depth(&query), 1);
+        let query = "{ tip { __typename number } }";
+        assert_eq!(graphql.query_depth(query), 1);
+    }
+}
@@ -0,0 +1,637 @@
+// Copyright (C) 2019-2020 Boyu Yang
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+use std::{cmp, convert::TryFrom};
+
+use juniper::{
+    graphql_object, graphql_scalar, FieldError, FieldResult, GraphQLEnum, InputValue,
+    ParseScalarResult, ParseScalarValue, Value,
+};
+use kernel::{
+    error::Result,
+    traits::{Explorer as _, Relations},
+    Address, BlockId, Network, Storage,
+};
+use parking_lot::Mutex;
+
+use super::loader::{bytes_key, out_point_key, Loader, Node};
+use crate::service::api::{encode_hex, parse_hash, parse_lock_hash};
+
+const DEFAULT_LIMIT: i32 = 20;
+const MAX_LIMIT: i32 = 500;
+
+/// The storage and the loaders of the relations, the loaders cache rows during a request.
+pub(super) struct Context {
+    storage: Mutex<Storage>,
+    network: Network,
+    headers: Loader<Vec<u8>>,
+    uncles: Loader<Vec<u8>>,
+    proposals: Loader<Vec<u8>>,
+    block_transactions: Loader<Vec<u8>>,
+    transactions: Loader<Vec<u8>>,
+    inputs: Loader<Vec<u8>>,
+    outputs: Loader<Vec<u8>>,
+    cell_deps: Loader<Vec<u8>>,
+    header_deps: Loader<Vec<u8>>,
+    witnesses: Loader<Vec<u8>>,
+    cells: Loader<(Vec<u8>, u32)>,
+    cells_data: Loader<Vec<u8>>,
+    scripts: Loader<Vec<u8>>,
+}
+
+impl juniper::Context for Context {}
+
+impl Context {
+    pub(super) fn new(storage: Storage, network: Network) -> Self {
+        Self {
+            storage: Mutex::new(storage),
+            network,
+            headers: Loader::new(Storage::headers_by_hashes, bytes_key("hash")),
+            uncles: Loader::new(Storage::uncles_by_block_hashes, bytes_key("block_hash")),
+            proposals: Loader::new(Storage::proposals_by_block_hashes, bytes_key("block_hash")),
+            block_transactions: Loader::new(
+                Storage::transactions_by_block_hashes,
+                bytes_key("block_hash"),
+            ),
+            transactions: Loader::new(Storage::transactions_by_hashes, bytes_key("hash")),
+            inputs: Loader::new(Storage::inputs_by_tx_hashes, bytes_key("consumed_tx_hash")),
+            outputs: Loader::new(Storage::outputs_by_tx_hashes, bytes_key("tx_hash")),
+            cell_deps: Loader::new(Storage::cell_deps_by_tx_hashes, bytes_key("ref_tx_hash")),
+            header_deps: Loader::new(Storage::header_deps_by_tx_hashes, bytes_key("ref_tx_hash")),
+            witnesses: Loader::new(Storage::witnesses_by_tx_hashes, bytes_key("ref_tx_hash")),
+            cells: Loader::new(
+                Storage::cells_by_out_points,
+                out_point_key("tx_hash", "index"),
+            ),
+            cells_data: Loader::new(Storage::cells_data_by_hashes, bytes_key("hash")),
+            scripts: Loader::new(Storage::scripts_by_hashes, bytes_key("hash")),
+        }
+    }
+
+    /// Drops the cached rows, the stored chain may be changed between requests.
+    pub(super) fn clear(&self) {
+        for loader in &[
+            &self.headers,
+            &self.uncles,
+            &self.proposals,
+            &self.block_transactions,
+            &self.transactions,
+            &self.inputs,
+            &self.outputs,
+            &self.cell_deps,
+            &self.header_deps,
+            &self.witnesses,
+            &self.cells_data,
+            &self.scripts,
+        ] {
+            loader.clear();
+        }
+        self.cells.clear();
+    }
+}
+
+/// An unsigned 64-bit integer, it is serialized as a decimal string since the integers in
+/// GraphQL are 32-bit.
+#[derive(Debug, Clone, Copy)]
+pub(super) struct Uint64(u64);
+
+#[graphql_scalar(
+    name = "Uint64",
+    description = "An unsigned 64-bit integer as a string"
+)]
+impl<S> GraphQLScalar for Uint64
+where
+    S: ScalarValue,
+{
+    fn resolve(&self) -> Value {
+        Value::scalar(self.0.to_string())
+    }
+
+    fn from_input_value(value: &InputValue) -> Option<Uint64> {
+        match value {
+            InputValue::Scalar(scalar) => scalar
+                .as_string()
+                .and_then(|s| s.parse().ok())
+                .or_else(|| scalar.as_int().and_then(|i| u64::try_from(i).ok()))
+                .map(Uint64),
+            _ => None,
+        }
+    }
+
+    fn from_str<'a>(value: ScalarToken<'a>) -> ParseScalarResult<'a, S> {
+        <String as ParseScalarValue<S>>::from_str(value)
+    }
+}
+
+#[derive(GraphQLEnum)]
+pub(super) enum ScriptHashType {
+    Data,
+    Type,
+}
+
+#[derive(GraphQLEnum)]
+pub(super) enum DepType {
+    Code,
+    DepGroup,
+}
+
+pub(super) struct Query;
+pub(super) struct Block(Node);
+pub(super) struct Uncle(Node);
+pub(super) struct Transaction(Node);
+pub(super) struct CellDep(Node);
+pub(super) struct Cell(Node);
+pub(super) struct Script(Node);
+
+impl Node {
+    fn hex(&self, name: &str) -> Result<String> {
+        self.get::<Vec<u8>>(name).map(|bytes| encode_hex(&bytes))
+    }
+
+    fn opt_hex(&self, name: &str) -> Result<Option<String>> {
+        self.get::<Option<Vec<u8>>>(name)
+            .map(|bytes| bytes.map(|bytes| encode_hex(&bytes)))
+    }
+
+    fn uint64(&self, name: &str) -> Result<Uint64> {
+        self.get::<i64>(name).map(|value| Uint64(value as u64))
+    }
+
+    fn opt_uint64(&self, name: &str) -> Result<Option<Uint64>> {
+        self.get::<Option<i64>>(name)
+            .map(|value| value.map(|value| Uint64(value as u64)))
+    }
+
+    fn int(&self, name: &str) -> Result<i32> {
+        self.get::<i32>(name)
+    }
+}
+
+fn wrap<T>(nodes: Vec<Node>, f: fn(Node) -> T) -> Vec<T> {
+    nodes.into_iter().map(f).collect()
+}
+
+fn first<T>(nodes: Vec<Node>, f: fn(Node) -> T) -> Option<T> {
+    nodes.into_iter().next().map(f)
+}
+
+fn parse_limit(limit: i32) -> FieldResult<u32> {
+    if limit <= 0 {
+        Err(FieldError::from("limit should be greater than 0"))
+    } else {
+        Ok(cmp::min(limit, MAX_LIMIT) as u32)
+    }
+}
+
+#[graphql_object(context = Context)]
+impl Query {
+    /// The highest stored block.
+    fn tip(context: &Context) -> FieldResult<Option<Block>> {
+        let rows = context.storage.lock().blocks(None, 1)?;
+        Ok(first(Node::all(rows), Block))
+    }
+
+    /// A block by its number or its hash.
+    fn block(
+        context: &Context,
+        number: Option<Uint64>,
+        hash: Option<String>,
+    ) -> FieldResult<Option<Block>> {
+        let id = match (number, hash) {
+            (Some(number), None) => BlockId::Number(number.0),
+            (None, Some(hash)) => BlockId::Hash(parse_hash(&hash)?),
+            _ => return Err(FieldError::from("require exactly one of number and hash")),
+        };
+        let row = context.storage.lock().block(&id)?;
+        Ok(first(Node::all(row.into_iter().collect()), Block))
+    }
+
+    /// The blocks whose numbers are less than `before`, from the highest.
+    #[graphql(arguments(limit(default = DEFAULT_LIMIT)))]
+    fn blocks(context: &Context, before: Option<Uint64>, limit: i32) -> FieldResult<Vec<Block>> {
+        let limit = parse_limit(limit)?;
+        let rows = context.storage.lock().blocks(before.map(|n| n.0), limit)?;
+        Ok(wrap(Node::all(rows), Block))
+    }
+
+    fn transaction(context: &Context, hash: String) -> FieldResult<Option<Transaction>> {
+        let rows = context
+            .storage
+            .lock()
+            .transactions_by_hashes(&[parse_hash(&hash)?])?;
+        Ok(first(Node::all(rows), Transaction))
+    }
+
+    /// A cell by its out point.
+    fn cell(context: &Context, tx_hash: String, index: i32) -> FieldResult<Option<Cell>> {
+        let out_point = (parse_hash(&tx_hash)?, index as u32);
+        let rows = context.storage.lock().cells_by_out_points(&[out_point])?;
+        Ok(first(Node::all(rows), Cell))
+    }
+
+    /// Finds a script by its hash, or a lock script by its address.
+    fn script(context: &Context, hash: String) -> FieldResult<Option<Script>> {
+        let rows = context
+            .storage
+            .lock()
+            .scripts_by_hashes(&[parse_lock_hash(&hash, context.network)?])?;
+        Ok(first(Node::all(rows), Script))
+    }
+}
+
+#[graphql_object(context = Context)]
+impl Block {
+    fn number(&self) -> FieldResult<Uint64> {
+        Ok(self.0.uint64("number")?)
+    }
+
+    fn hash(&self) -> FieldResult<String> {
+        Ok(self.0.hex("hash")?)
+    }
+
+    fn version(&self) -> FieldResult<i32> {
+        Ok(self.0.int("version")?)
+    }
+
+    fn compact_target(&self) -> FieldResult<Uint64> {
+        Ok(self.0.uint64("compact_target")?)
+    }
+
+    fn timestamp(&self) -> FieldResult<Uint64> {
+        Ok(self.0.uint64("timestamp")?)
+    }
+
+    fn epoch_number(&self) -> FieldResult<Uint64> {
+        Ok(self.0.uint64("epoch_number")?)
+    }
+
+    fn epoch_index(&self) -> FieldResult<i32> {
+        Ok(self.0.int("epoch_index")?)
+    }
+
+    fn epoch_length(&self) -> FieldResult<i32> {
+        Ok(self.0.int("epoch_length")?)
+    }
+
+    fn parent_hash(&self) -> FieldResult<String> {
+        Ok(self.0.hex("parent_hash")?)
+    }
+
+    fn transactions_root(&self) -> FieldResult<String> {
+        Ok(self.0.hex("transactions_root")?)
+    }
+
+    fn proposals_hash(&self) -> FieldResult<String> {
+        Ok(self.0.hex("proposals_hash")?)
+    }
+
+    fn uncles_hash(&self) -> FieldResult<String> {
+        Ok(self.0.hex("uncles_hash")?)
+    }
+
+    fn dao_c(&self) -> FieldResult<Uint64> {
+        Ok(self.0.uint64("dao_c")?)
+    }
+
+    fn dao_ar(&self) -> FieldResult<Uint64> {
+        Ok(self.0.uint64("dao_ar")?)
+    }
+
+    fn dao_s(&self) -> FieldResult<Uint64> {
+        Ok(self.0.uint64("dao_s")?)
+    }
+
+    fn dao_u(&self) -> FieldResult<Uint64> {
+        Ok(self.0.uint64("dao_u")?)
+    }
+
+    fn nonce(&self) -> FieldResult<String> {
+        Ok(self.0.hex("nonce")?)
+    }
+
+    /// The parent block, it is null for the genesis block.
+    fn parent(&self, context: &Context) -> FieldResult<Option<Block>> {
+        let nodes =
+            context
+                .headers
+                .load(&context.storage.lock(), &self.0, bytes_key("parent_hash"))?;
+        Ok(first(nodes, Block))
+    }
+
+    fn transactions(&self, context: &Context) -> FieldResult<Vec<Transaction>> {
+        let nodes =
+            context
+                .block_transactions
+                .load(&context.storage.lock(), &self.0, bytes_key("hash"))?;
+        Ok(wrap(nodes, Transaction))
+    }
+
+    fn uncles(&self, context: &Context) -> FieldResult<Vec<Uncle>> {
+        let nodes = context
+            .uncles
+            .load(&context.storage.lock(), &self.0, bytes_key("hash"))?;
+        Ok(wrap(nodes, Uncle))
+    }
+
+    /// The short ids of the proposed transactions.
+    fn proposals(&self, context: &Context) -> FieldResult<Vec<String>> {
+        let nodes = context
+            .proposals
+            .load(&context.storage.lock(), &self.0, bytes_key("hash"))?;
+        Ok(nodes
+            .iter()
+            .map(|node| node.hex("short_id"))
+            .collect::<Result<_>>()?)
+    }
+}
+
+#[graphql_object(context = Context)]
+impl Uncle {
+    fn number(&self) -> FieldResult<Uint64> {
+        Ok(self.0.uint64("number")?)
+    }
+
+    fn hash(&self) -> FieldResult<String> {
+        Ok(self.0.hex("hash")?)
+    }
+
+    /// The position in the uncles of the block which includes it.
+    fn index(&self) -> FieldResult<i32> {
+        Ok(self.0.int("uncle_index")?)
+    }
+
+    fn compact_target(&self) -> FieldResult<Uint64> {
+        Ok(self.0.uint64("compact_target")?)
+    }
+
+    fn timestamp(&self) -> FieldResult<Uint64> {
+        Ok(self.0.uint64("timestamp")?)
+    }
+
+    fn epoch_number(&self) -> FieldResult<Uint64> {
+        Ok(self.0.uint64("epoch_number")?)
+    }
+
+    fn epoch_index(&self) -> FieldResult<i32> {
+        Ok(self.0.int("epoch_index")?)
+    }
+
+    fn epoch_length(&self) -> FieldResult<i32> {
+        Ok(self.0.int("epoch_length")?)
+    }
+
+    fn parent_hash(&self) -> FieldResult<String> {
+        Ok(self.0.hex("parent_hash")?)
+    }
+
+    fn nonce(&self) -> FieldResult<String> {
+        Ok(self.0.hex("nonce")?)
+    }
+
+    /// The block which includes this uncle.
+    fn block(&self, context: &Context) -> FieldResult<Option<Block>> {
+        let nodes =
+            context
+                .headers
+                .load(&context.storage.lock(), &self.0, bytes_key("block_hash"))?;
+        Ok(first(nodes, Block))
+    }
+}
+
+#[graphql_object(context = Context)]
+impl Transaction {
+    fn hash(&self) -> FieldResult<String> {
+        Ok(self.0.hex("hash")?)
+    }
+
+    fn version(&self) -> FieldResult<i32> {
+        Ok(self.0.int("version")?)
+    }
+
+    /// The position in the block, the cellbase is 0.
+    fn index(&self) -> FieldResult<i32> {
+        Ok(self.0.int("index")?)
+    }
+
+    fn block_number(&self) -> FieldResult<Uint64> {
+        Ok(self.0.uint64("block_number")?)
+    }
+
+    fn block(&self, context: &Context) -> FieldResult<Option<Block>> {
+        let nodes =
+            context
+                .headers
+                .load(&context.storage.lock(), &self.0, bytes_key("block_hash"))?;
+        Ok(first(nodes, Block))
+    }
+
+    /// The cells consumed by this transaction, a cellbase has no inputs.
+    fn inputs(&self, context: &Context) -> FieldResult<Vec<Cell>> {
+        let nodes = context
+            .inputs
+            .load(&context.storage.lock(), &self.0, bytes_key("hash"))?;
+        Ok(wrap(nodes, Cell))
+    }
+
+    fn outputs(&self, context: &Context) -> FieldResult<Vec<Cell>> {
+        let nodes = context
+            .outputs
+            .load(&context.storage.lock(), &self.0, bytes_key("hash"))?;
+        Ok(wrap(nodes, Cell))
+    }
+
+    fn cell_deps(&self, context: &Context) -> FieldResult<Vec<CellDep>> {
+        let nodes = context
+            .cell_deps
+            .load(&context.storage.lock(), &self.0, bytes_key("hash"))?;
+        Ok(wrap(nodes, CellDep))
+    }
+
+    fn header_deps(&self, context: &Context) -> FieldResult<Vec<Block>> {
+        let deps = context
+            .header_deps
+            .load(&context.storage.lock(), &self.0, bytes_key("hash"))?;
+        let mut blocks = Vec::with_capacity(deps.len());
+        for dep in &deps {
+            let nodes =
+                context
+                    .headers
+                    .load(&context.storage.lock(), dep, bytes_key("block_hash"))?;
+            blocks.extend(first(nodes, Block));
+        }
+        Ok(blocks)
+    }
+
+    fn witnesses(&self, context: &Context) -> FieldResult<Vec<String>> {
+        let nodes = context
+            .witnesses
+            .load(&context.storage.lock(), &self.0, bytes_key("hash"))?;
+        Ok(nodes
+            .iter()
+            .map(|node| node.hex("witness"))
+            .collect::<Result<_>>()?)
+    }
+}
+
+#[graphql_object(context = Context)]
+impl CellDep {
+    fn tx_hash(&self) -> FieldResult<String> {
+        Ok(self.0.hex("tx_hash")?)
+    }
+
+    fn index(&self) -> FieldResult<i32> {
+        Ok(self.0.int("index")?)
+    }
+
+    fn dep_type(&self) -> FieldResult<DepType> {
+        match self.0.int("dep_type")? {
+            0 => Ok(DepType::Code),
+            1 => Ok(DepType::DepGroup),
+            value => Err(FieldError::from(format!("unknown dep type {}", value))),
+        }
+    }
+
+    /// The cell which the out point refers to.
+    fn cell(&self, context: &Context) -> FieldResult<Option<Cell>> {
+        let nodes = context.cells.load(
+            &context.storage.lock(),
+            &self.0,
+            out_point_key("tx_hash", "index"),
+        )?;
+        Ok(first(nodes, Cell))
+    }
+}
+
+#[graphql_object(context = Context)]
+impl Cell {
+    fn tx_hash(&self) -> FieldResult<String> {
+        Ok(self.0.hex("tx_hash")?)
+    }
+
+    fn index(&self) -> FieldResult<i32> {
+        Ok(self.0.int("index")?)
+    }
+
+    fn block_number(&self) -> FieldResult<Uint64> {
+        Ok(self.0.uint64("block_number")?)
+    }
+
+    fn capacity(&self) -> FieldResult<Uint64> {
+        Ok(self.0.uint64("capacity")?)
+    }
+
+    fn lock_hash(&self) -> FieldResult<String> {
+        Ok(self.0.hex("lock_hash")?)
+    }
+
+    fn type_hash(&self) -> FieldResult<Option<String>> {
+        Ok(self.0.opt_hex("type_hash")?)
+    }
+
+    fn data_hash(&self) -> FieldResult<String> {
+        Ok(self.0.hex("data_hash")?)
+    }
+
+    fn live(&self) -> FieldResult<bool> {
+        Ok(self.0.get::<Option<Vec<u8>>>("consumed_tx_hash")?.is_none())
+    }
+
+    fn consumed_since(&self) -> FieldResult<Option<String>> {
+        Ok(self.0.opt_hex("consumed_since")?)
+    }
+
+    fn consumed_block_number(&self) -> FieldResult<Option<Uint64>> {
+        Ok(self.0.opt_uint64("consumed_block_number")?)
+    }
+
+    fn lock(&self, context: &Context) -> FieldResult<Option<Script>> {
+        let nodes =
+            context
+                .scripts
+                .load(&context.storage.lock(), &self.0, bytes_key("lock_hash"))?;
+        Ok(first(nodes, Script))
+    }
+
+    #[graphql(name = "type")]
+    fn type_(&self, context: &Context) -> FieldResult<Option<Script>> {
+        let nodes =
+            context
+                .scripts
+                .load(&context.storage.lock(), &self.0, bytes_key("type_hash"))?;
+        Ok(first(nodes, Script))
+    }
+
+    fn data(&self, context: &Context) -> FieldResult<Option<String>> {
+        let nodes =
+            context
+                .cells_data
+                .load(&context.storage.lock(), &self.0, bytes_key("data_hash"))?;
+        Ok(nodes.first().map(|node| node.hex("data")).transpose()?)
+    }
+
+    /// The transaction which creates this cell.
+    fn created_by(&self, context: &Context) -> FieldResult<Option<Transaction>> {
+        let nodes =
+            context
+                .transactions
+                .load(&context.storage.lock(), &self.0, bytes_key("tx_hash"))?;
+        Ok(first(nodes, Transaction))
+    }
+
+    /// The transaction which consumes this cell, it is null for a live cell.
+    fn consumed_by(&self, context: &Context) -> FieldResult<Option<Transaction>> {
+        let nodes = context.transactions.load(
+            &context.storage.lock(),
+            &self.0,
+            bytes_key("consumed_tx_hash"),
+        )?;
+        Ok(first(nodes, Transaction))
+    }
+}
+
+#[graphql_object(context = Context)]
+impl Script {
+    fn hash(&self) -> FieldResult<String> {
+        Ok(self.0.hex("hash")?)
+    }
+
+    fn code_hash(&self) -> FieldResult<String> {
+        Ok(self.0.hex("code_hash")?)
+    }
+
+    fn hash_type(&self) -> FieldResult<ScriptHashType> {
+        match self.0.int("hash_type")? {
+            0 => Ok(ScriptHashType::Data),
+            1 => Ok(ScriptHashType::Type),
+            value => Err(FieldError::from(format!("unknown hash type {}", value))),
+        }
+    }
+
+    fn args(&self) -> FieldResult<String> {
+        Ok(self.0.hex("args")?)
+    }
+
+    /// The address in the full format.
+    fn address(&self, context: &Context) -> FieldResult<String> {
+        Ok(self.to_address(context.network)?.to_full())
+    }
+
+    /// The address in the short format, only for the well-known locks.
+    fn short_address(&self, context: &Context) -> FieldResult<Option<String>> {
+        Ok(self.to_address(context.network)?.to_short())
+    }
+}
+
+impl Script {
+    fn to_address(&self, network: Network) -> Result<Address> {
+        let code_hash = self.0.get::<Vec<u8>>("code_hash")?;
+        let hash_type = self.0.int("hash_type")?;
+        let args = self.0.get::<Vec<u8>>("args")?;
+        Address::new(network, &code_hash, hash_type as u8, &args)
+    }
+}
@@ -0,0 +1,128 @@
+// Copyright (C) 2019-2020 Boyu Yang
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+use std::{sync::Arc, time::Duration};
+
+use kernel::{traits::SyncListener, Storage};
+use parking_lot::RwLock;
+use uckb_jsonrpc_core::types::core;
+
+use super::{text_response, HttpResponse};
+
+/// The status of the sync loop, shared with the HTTP service.
+#[derive(Clone, Default)]
+pub(crate) struct SyncState {
+    inner: Arc<RwLock<SyncStatus>>,
+}
+
+#[derive(Default)]
+struct SyncStatus {
+    tip: Option<core::BlockNumber>,
+    stored: Option<core::BlockNumber>,
+    // The count of the continuous failures to fetch data from the block source.
+    source_failed_cnt: u64,
+}
+
+/// Updates the sync state by the events of the syncer.
+pub(crate) struct SyncStateListener {
+    state: SyncState,
+}
+
+/// Checks the liveness and the readiness of the sync process.
+pub(crate) struct Health {
+    state: SyncState,
+    // A dedicated connection, so the checks never block the sync loop.
+    storage: Storage,
+    max_lag: u64,
+}
+
+impl SyncState {
+    pub(crate) fn listener(&self) -> SyncStateListener {
+        SyncStateListener {
+            state: self.clone(),
+        }
+    }
+}
+
+impl SyncListener for SyncStateListener {
+    fn on_tip_fetched(&mut self, tip: core::BlockNumber, stored: Option<core::BlockNumber>) {
+        let mut status = self.state.inner.write();
+        status.tip = Some(tip);
+        status.stored = stored;
+        status.source_failed_cnt = 0;
+    }
+
+    fn on_source_failed(&mut self, failed_cnt: u64) {
+        self.state.inner.write().source_failed_cnt = failed_cnt;
+    }
+
+    fn on_block_inserted(&mut self, block: &core::BlockView, _elapsed: Duration) {
+        self.state.inner.write().stored = Some(block.number());
+    }
+
+    fn on_block_rolled_back(&mut self, number: core::BlockNumber, _elapsed: Duration) {
+        self.state.inner.write().stored = number.checked_sub(1);
+    }
+}
+
+impl Health {
+    pub(crate) fn new(state: SyncState, storage: Storage, max_lag: u64) -> Self {
+        Self {
+            state,
+            storage,
+            max_lag,
+        }
+    }
+
+    /// Reports whether the storage and the block source are alive.
+    pub(crate) fn healthz(&self) -> HttpResponse {
+        let storage = match self.storage.query("SELECT 1;", &[]) {
+            Ok(_) => "ok".to_owned(),
+            Err(err) => format!("failed since {}", err),
+        };
+        let source = {
+            let status = self.state.inner.read();
+            if status.source_failed_cnt > 0 {
+                format!("failed {} times continuously", status.source_failed_cnt)
+            } else if status.tip.is_none() {
+                "not connected yet".to_owned()
+            } else {
+                "ok".to_owned()
+            }
+        };
+        let status_code = if storage == "ok" && source == "ok" {
+            200
+        } else {
+            503
+        };
+        text_response(
+            status_code,
+            format!("storage: {}\nsource: {}\n", storage, source),
+        )
+    }
+
+    /// Reports whether the stored tip is within the max lag of the tip of the block source.
+    pub(crate) fn readyz(&self) -> HttpResponse {
+        let status = self.state.inner.read();
+        match (status.tip, status.stored) {
+            (Some(tip), Some(stored)) => {
+                let lag = tip.saturating_sub(stored);
+                let status_code = if lag <= self.max_lag { 200 } else { 503 };
+                text_response(
+                    status_code,
+                    format!(
+                        "stored: {}\ntip: {}\nlag: {} (max {})\n",
+                        stored, tip, lag, self.max_lag
+                    ),
+                )
+            }
+            (None, _) => text_response(503, "the tip of the block source is unknown\n".to_owned()),
+            (Some(tip), None) => text_response(503, format!("no stored blocks\ntip: {}\n", tip)),
+        }
+    }
+}
@@ -0,0 +1,370 @@
+// Copyright (C) 2019-2020 Boyu Yang
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+use std::{fmt, sync::Arc};
+
+use jsonrpc_core::{Error as RpcError, IoHandler, Params, Result as RpcResult, Value};
+use kernel::{traits::Indexer as _, IndexerCell, IndexerTx, Storage};
+use parking_lot::Mutex;
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+use uckb_jsonrpc_core::types::{fixed::H256, packed, prelude::*, rpc};
+
+use super::{json_text_response, HttpResponse};
+
+/// The JSON-RPC methods of the CKB indexer: `get_tip`, `get_cells`, `get_transactions` and
+/// `get_cells_capacity`.
+pub(crate) struct IndexerRpc {
+    io: IoHandler,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "snake_case")]
+enum ScriptType {
+    Lock,
+    Type,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "snake_case")]
+enum Order {
+    Asc,
+    Desc,
+}
+
+#[derive(Deserialize)]
+struct SearchKey {
+    script: rpc::Script,
+    script_type: ScriptType,
+    filter: Option<SearchKeyFilter>,
+}
+
+#[derive(Deserialize, Default)]
+struct SearchKeyFilter {
+    script: Option<rpc::Script>,
+    output_data_len_range: Option<[rpc::Uint64; 2]>,
+    output_capacity_range: Option<[rpc::Uint64; 2]>,
+    block_range: Option<[rpc::BlockNumber; 2]>,
+}
+
+#[derive(Serialize)]
+struct Tip {
+    block_hash: H256,
+    block_number: rpc::BlockNumber,
+}
+
+#[derive(Serialize)]
+struct Cell {
+    output: rpc::CellOutput,
+    output_data: rpc::JsonBytes,
+    out_point: rpc::OutPoint,
+    block_number: rpc::BlockNumber,
+    tx_index: rpc::Uint32,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "snake_case")]
+enum IoType {
+    Input,
+    Output,
+}
+
+#[derive(Serialize)]
+struct Tx {
+    tx_hash: H256,
+    block_number: rpc::BlockNumber,
+    tx_index: rpc::Uint32,
+    io_index: rpc::Uint32,
+    io_type: IoType,
+}
+
+#[derive(Serialize)]
+struct CellsCapacity {
+    capacity: rpc::Capacity,
+    block_hash: H256,
+    block_number: rpc::BlockNumber,
+}
+
+#[derive(Serialize)]
+struct Pagination<T> {
+    objects: Vec<T>,
+    last_cursor: rpc::JsonBytes,
+}
+
+impl From<ScriptType> for kernel::ScriptType {
+    fn from(script_type: ScriptType) -> Self {
+        match script_type {
+            ScriptType::Lock => Self::Lock,
+            ScriptType::Type => Self::Type,
+        }
+    }
+}
+
+impl From<Order> for kernel::Order {
+    fn from(order: Order) -> Self {
+        match order {
+            Order::Asc => Self::Asc,
+            Order::Desc => Self::Desc,
+        }
+    }
+}
+
+fn range<T: Into<u64> + Copy>(range: Option<[T; 2]>) -> Option<(u64, u64)> {
+    range.map(|[start, end]| (start.into(), end.into()))
+}
+
+impl From<SearchKey> for kernel::SearchKey {
+    fn from(key: SearchKey) -> Self {
+        let filter = key.filter.unwrap_or_default();
+        Self {
+            script: key.script.into(),
+            script_type: key.script_type.into(),
+            filter_script: filter.script.map(Into::into),
+            output_data_len_range: range(filter.output_data_len_range),
+            output_capacity_range: range(filter.output_capacity_range),
+            block_range: range(filter.block_range),
+        }
+    }
+}
+
+impl From<IndexerCell> for Cell {
+    fn from(cell: IndexerCell) -> Self {
+        Self {
+            output: cell.output.into(),
+            output_data: rpc::JsonBytes::from_bytes(cell.output_data),
+            out_point: cell.out_point.into(),
+            block_number: cell.block_number.into(),
+            tx_index: cell.tx_index.into(),
+        }
+    }
+}
+
+impl From<IndexerTx> for Tx {
+    fn from(tx: IndexerTx) -> Self {
+        Self {
+            tx_hash: tx.tx_hash.unpack(),
+            block_number: tx.block_number.into(),
+            tx_index: tx.tx_index.into(),
+            io_index: tx.io_index.into(),
+            io_type: if tx.is_input {
+                IoType::Input
+            } else {
+                IoType::Output
+            },
+        }
+    }
+}
+
+// Parses the parameters by positions, the trailing optional parameters could be omitted.
+fn parse_params(params: Params, count: usize) -> RpcResult<Vec<Value>> {
+    let mut values = match params {
+        Params::Array(values) => values,
+        Params::None => Vec::new(),
+        Params::Map(_) => {
+            return Err(RpcError::invalid_params(
+                "parameters should be passed by positions",
+            ))
+        }
+    };
+    if values.len() > count {
+        return Err(RpcError::invalid_params(format!(
+            "expect at most {} parameters",
+            count
+        )));
+    }
+    values.resize(count, Value::Null);
+    Ok(values)
+}
+
+fn parse_param<T: DeserializeOwned>(value: Value, name: &str) -> RpcResult<T> {
+    serde_json::from_value(value)
+        .map_err(|err| RpcError::invalid_params(format!("invalid {}: {}", name, err)))
+}
+
+// The cursor of a cell is 16 bytes, the cursor of a transaction is 17 bytes.
+fn parse_page_params(
+    params: Params,
+    cursor_len: usize,
+) -> RpcResult<(kernel::SearchKey, kernel::Order, u32, Option<Vec<u8>>)> {
+    let mut values = parse_params(params, 4)?.into_iter();
+    let mut next = || values.next().unwrap_or(Value::Null);
+    let key: SearchKey = parse_param(next(), "search_key")?;
+    let order: Order = parse_param(next(), "order")?;
+    let limit: rpc::Uint32 = parse_param(next(), "limit")?;
+    let after: Option<rpc::JsonBytes> = parse_param(next(), "after_cursor")?;
+    let limit = limit.value();
+    if limit == 0 {
+        return Err(RpcError::invalid_params("limit should be greater than 0"));
+    }
+    if let Some(ref cursor) = after {
+        if cursor.len() != cursor_len {
+            return Err(RpcError::invalid_params(format!(
+                "incorrect after_cursor (length: {})",
+                cursor.len()
+            )));
+        }
+    }
+    Ok((
+        key.into(),
+        order.into(),
+        limit,
+        after.map(|cursor| cursor.as_bytes().to_vec()),
+    ))
+}
+
+fn internal_error<E: fmt::Display>(err: E) -> RpcError {
+    log::error!("failed to handle an indexer request since {}", err);
+    let mut error = RpcError::internal_error();
+    error.message = err.to_string();
+    error
+}
+
+fn to_value<T: Serialize>(value: T) -> RpcResult<Value> {
+    serde_json::to_value(value).map_err(internal_error)
+}
+
+fn tip(storage: &Storage) -> RpcResult<(packed::Byte32, u64)> {
+    storage
+        .indexer_tip()
+        .map_err(internal_error)?
+        .ok_or_else(|| internal_error("no stored blocks"))
+}
+
+impl IndexerRpc {
+    pub(crate) fn new(storage: Storage) -> Self {
+        let storage = Arc::new(Mutex::new(storage));
+        let mut io = IoHandler::new();
+        {
+            let storage = Arc::clone(&storage);
+            io.add_method("get_tip", move |params: Params| {
+                params.expect_no_params()?;
+                let (block_hash, block_number) = tip(&storage.lock())?;
+                to_value(Tip {
+                    block_hash: block_hash.unpack(),
+                    block_number: block_number.into(),
+                })
+            });
+        }
+        {
+            let storage = Arc::clone(&storage);
+            io.add_method("get_cells", move |params: Params| {
+                let (key, order, limit, after) = parse_page_params(params, 16)?;
+                let page = storage
+                    .lock()
+                    .indexer_cells(&key, order, limit, after.as_deref())
+                    .map_err(internal_error)?;
+                to_value(Pagination {
+                    objects: page.objects.into_iter().map(Cell::from).collect(),
+                    last_cursor: rpc::JsonBytes::from_vec(page.last_cursor),
+                })
+            });
+        }
+        {
+            let storage = Arc::clone(&storage);
+            io.add_method("get_transactions", move |params: Params| {
+                let (key, order, limit, after) = parse_page_params(params, 17)?;
+                let page = storage
+                    .lock()
+                    .indexer_transactions(&key, order, limit, after.as_deref())
+                    .map_err(internal_error)?;
+                to_value(Pagination {
+                    objects: page.objects.into_iter().map(Tx::from).collect(),
+                    last_cursor: rpc::JsonBytes::from_vec(page.last_cursor),
+                })
+            });
+        }
+        {
+            let storage = Arc::clone(&storage);
+            io.add_method("get_cells_capacity", move |params: Params| {
+                let mut values = parse_params(params, 1)?.into_iter();
+                let key: SearchKey =
+                    parse_param(values.next().unwrap_or(Value::Null), "search_key")?;
+                let storage = storage.lock();
+                let capacity = storage
+                    .indexer_cells_capacity(&key.into())
+                    .map_err(internal_error)?;
+                let (block_hash, block_number) = tip(&storage)?;
+                to_value(CellsCapacity {
+                    capacity: capacity.into(),
+                    block_hash: block_hash.unpack(),
+                    block_number: block_number.into(),
+                })
+            });
+        }
+        Self { io }
+    }
+
+    /// Handles a JSON-RPC request or a batch of requests.
+    pub(crate) fn handle(&self, body: &str) -> HttpResponse {
+        if let Some(response) = self.io.handle_request_sync(body) {
+            json_text_response(200, response)
+        } else {
+            // Only notifications, nothing to respond.
+            json_text_response(204, String::new())
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use jsonrpc_core::{Params, Value};
+    use serde_json::json;
+
+    use super::parse_page_params;
+
+    fn params(values: Vec<Value>) -> Params {
+        let search_key = json!({
+            "script": {
+                "code_hash": format!("0x{}", "00".repeat(32)),
+                "hash_type": "type",
+                "args": "0x",
+            },
+            "script_type": "lock",
+        });
+        let mut params = vec![search_key, json!("asc")];
+        params.extend(values);
+        Params::Array(params)
+    }
+
+    #[test]
+    fn omit_trailing_params() {
+        let (_, order, limit, after) = parse_page_params(params(vec![json!("0x10")]), 16).unwrap();
+        assert_eq!(order, kernel::Order::Asc);
+        assert_eq!(limit, 16);
+        assert_eq!(after, None);
+        let cursor = json!(format!("0x{}", "01".repeat(16)));
+        let values = vec![json!("0x10"), cursor];
+        let (_, _, _, after) = parse_page_params(params(values), 16).unwrap();
+        assert_eq!(after, Some(vec![1u8; 16]));
+        // The limit is required.
+        assert!(parse_page_params(params(Vec::new()), 16).is_err());
+        let values = vec![json!("0x10"), Value::Null, Value::Null];
+        assert!(parse_page_params(params(values), 16).is_err());
+    }
+
+    #[test]
+    fn reject_zero_limit() {
+        let err = parse_page_params(params(vec![json!("0x0")]), 16).unwrap_err();
+        assert_eq!(err.message, "limit should be greater than 0");
+    }
+
+    #[test]
+    fn reject_incorrect_cursors() {
+        for (len, cursor_len) in &[(16, 17), (17, 16), (15, 16), (0, 17)] {
+            let cursor = json!(format!("0x{}", "01".repeat(*len)));
+            let values = vec![json!("0x10"), cursor];
+            let err = parse_page_params(params(values), *cursor_len).unwrap_err();
+            assert_eq!(
+                err.message,
+                format!("incorrect after_cursor (length: {})", len)
+            );
+        }
+        let cursor = json!(format!("0x{}", "01".repeat(17)));
+        let values = vec![json!("0x10"), cursor];
+        assert!(parse_page_params(params(values), 17).is_ok());
+    }
+}
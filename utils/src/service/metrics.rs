@@ -0,0 +1,192 @@
+// Copyright (C) 2019-2020 Boyu Yang
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+use std::time::Duration;
+
+use kernel::traits::SyncListener;
+use prometheus::{
+    Encoder as _, Histogram, HistogramOpts, HistogramVec, IntCounter, IntGauge, Registry,
+    TextEncoder,
+};
+use uckb_jsonrpc_core::types::core;
+
+use super::{text_response, HttpResponse};
+use crate::error::Result;
+
+/// The Prometheus metrics of the sync process.
+#[derive(Clone)]
+pub(crate) struct Metrics {
+    registry: Registry,
+    stored_number: IntGauge,
+    tip_number: IntGauge,
+    gap: IntGauge,
+    blocks: IntCounter,
+    transactions: IntCounter,
+    cells: IntCounter,
+    rollbacks: IntCounter,
+    rollback_depth: Histogram,
+    source_errors: IntCounter,
+    source_failed_cnt: IntGauge,
+    storage_seconds: HistogramVec,
+}
+
+/// Updates the metrics by the events of the syncer.
+pub(crate) struct MetricsListener {
+    metrics: Metrics,
+    // The count of the continuous rollbacks.
+    rollback_depth: u64,
+}
+
+impl Metrics {
+    pub(crate) fn new() -> Result<Self> {
+        let registry = Registry::new_custom(Some("uckb_scanner".to_owned()), None)?;
+        let stored_number = IntGauge::new("stored_number", "The number of the stored tip block.")?;
+        let tip_number = IntGauge::new("tip_number", "The number of the tip block of the node.")?;
+        let gap = IntGauge::new(
+            "sync_gap",
+            "The number of blocks which the storage is behind the node.",
+        )?;
+        let blocks = IntCounter::new("inserted_blocks_total", "The inserted blocks.")?;
+        let transactions =
+            IntCounter::new("inserted_transactions_total", "The inserted transactions.")?;
+        let cells = IntCounter::new("inserted_cells_total", "The inserted cells.")?;
+        let rollbacks = IntCounter::new("rollbacks_total", "The blocks removed by rollbacks.")?;
+        let rollback_depth = Histogram::with_opts(
+            HistogramOpts::new(
+                "rollback_depth",
+                "The number of blocks removed by a reorganization.",
+            )
+            .buckets(vec![1.0, 2.0, 3.0, 5.0, 10.0, 20.0, 50.0, 100.0]),
+        )?;
+        let source_errors = IntCounter::new(
+            "source_errors_total",
+            "The failures to fetch data from the block source.",
+        )?;
+        let source_failed_cnt = IntGauge::new(
+            "source_continuous_failures",
+            "The count of the continuous failures to fetch data from the block source.",
+        )?;
+        let storage_seconds = HistogramVec::new(
+            HistogramOpts::new(
+                "storage_transaction_seconds",
+                "The latency of the storage transactions.",
+            )
+            .buckets(vec![
+                0.001, 0.005, 0.01, 0.025, 0.05, 0.1, 0.25, 0.5, 1.0, 2.5, 5.0, 10.0,
+            ]),
+            &["operation"],
+        )?;
+        registry.register(Box::new(stored_number.clone()))?;
+        registry.register(Box::new(tip_number.clone()))?;
+        registry.register(Box::new(gap.clone()))?;
+        registry.register(Box::new(blocks.clone()))?;
+        registry.register(Box::new(transactions.clone()))?;
+        registry.register(Box::new(cells.clone()))?;
+        registry.register(Box::new(rollbacks.clone()))?;
+        registry.register(Box::new(rollback_depth.clone()))?;
+        registry.register(Box::new(source_errors.clone()))?;
+        registry.register(Box::new(source_failed_cnt.clone()))?;
+        registry.register(Box::new(storage_seconds.clone()))?;
+        Ok(Self {
+            registry,
+            stored_number,
+            tip_number,
+            gap,
+            blocks,
+            transactions,
+            cells,
+            rollbacks,
+            rollback_depth,
+            source_errors,
+            source_failed_cnt,
+            storage_seconds,
+        })
+    }
+
+    pub(crate) fn listener(&self) -> MetricsListener {
+        MetricsListener {
+            metrics: self.clone(),
+            rollback_depth: 0,
+        }
+    }
+
+    /// Renders the metrics in the Prometheus text format.
+    pub(crate) fn render(&self) -> HttpResponse {
+        let encoder = TextEncoder::new();
+        let mut buffer = Vec::new();
+        if let Err(err) = encoder.encode(&self.registry.gather(), &mut buffer) {
+            log::error!("failed to encode metrics since {}", err);
+            return text_response(500, format!("{}\n", err));
+        }
+        let text = String::from_utf8_lossy(&buffer).into_owned();
+        text_response(200, text)
+    }
+
+    fn set_stored_number(&self, stored: Option<core::BlockNumber>) {
+        let stored = stored.map(|number| number as i64).unwrap_or(-1);
+        self.stored_number.set(stored);
+        self.gap.set(self.tip_number.get() - stored);
+    }
+}
+
+impl MetricsListener {
+    fn finish_rollback(&mut self) {
+        if self.rollback_depth > 0 {
+            self.metrics
+                .rollback_depth
+                .observe(self.rollback_depth as f64);
+            self.rollback_depth = 0;
+        }
+    }
+}
+
+impl SyncListener for MetricsListener {
+    fn on_tip_fetched(&mut self, tip: core::BlockNumber, stored: Option<core::BlockNumber>) {
+        self.metrics.source_failed_cnt.set(0);
+        self.metrics.tip_number.set(tip as i64);
+        self.metrics.set_stored_number(stored);
+    }
+
+    fn on_source_failed(&mut self, failed_cnt: u64) {
+        self.metrics.source_errors.inc();
+        self.metrics.source_failed_cnt.set(failed_cnt as i64);
+    }
+
+    fn on_block_inserted(&mut self, block: &core::BlockView, elapsed: Duration) {
+        self.finish_rollback();
+        self.metrics.blocks.inc();
+        self.metrics
+            .transactions
+            .inc_by(block.transactions().len() as u64);
+        let cells = block
+            .transactions()
+            .iter()
+            .map(|tx| tx.outputs().len() as u64)
+            .sum();
+        self.metrics.cells.inc_by(cells);
+        self.metrics
+            .storage_seconds
+            .with_label_values(&["insert_block"])
+            .observe(elapsed.as_secs_f64());
+        self.metrics.set_stored_number(Some(block.number()));
+    }
+
+    fn on_block_rolled_back(&mut self, number: core::BlockNumber, elapsed: Duration) {
+        self.rollback_depth += 1;
+        self.metrics.rollbacks.inc();
+        self.metrics
+            .storage_seconds
+            .with_label_values(&["remove_block"])
+            .observe(elapsed.as_secs_f64());
+        self.metrics.set_stored_number(number.checked_sub(1));
+    }
+
+    fn on_caught_up(&mut self, _tip: core::BlockNumber) {
+        self.finish_rollback();
+    }
+}
@@ -0,0 +1,106 @@
+// Copyright (C) 2019-2020 Boyu Yang
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+use std::{io::Cursor, net::SocketAddr, thread};
+
+use tiny_http::{Header, Response, Server};
+
+use crate::error::{Error, Result};
+
+mod api;
+mod graphql;
+mod health;
+mod indexer;
+mod metrics;
+
+pub(crate) use self::{
+    api::{parse_hash, parse_lock_hash, Api},
+    graphql::Graphql,
+    health::{Health, SyncState},
+    indexer::IndexerRpc,
+    metrics::Metrics,
+};
+
+pub(crate) type HttpResponse = Response<Cursor<Vec<u8>>>;
+
+/// The HTTP service for the operations of the sync process.
+pub(crate) struct Service {
+    metrics: Metrics,
+    health: Health,
+}
+
+impl Service {
+    pub(crate) fn new(metrics: Metrics, health: Health) -> Self {
+        Self { metrics, health }
+    }
+
+    /// Serves the requests in a background thread.
+    pub(crate) fn spawn(self, addr: SocketAddr) -> Result<()> {
+        let server = Server::http(addr).map_err(|err| Error::Http(err.to_string()))?;
+        log::info!("serve the http service on {}", addr);
+        thread::Builder::new()
+            .name("http-service".to_owned())
+            .spawn(move || {
+                for request in server.incoming_requests() {
+                    let path = request.url().split('?').next().unwrap_or_default();
+                    log::trace!("http request {} {}", request.method(), path);
+                    let response = self.handle(path);
+                    if let Err(err) = request.respond(response) {
+                        log::warn!("failed to respond a http request since {}", err);
+                    }
+                }
+            })?;
+        Ok(())
+    }
+
+    fn handle(&self, path: &str) -> HttpResponse {
+        match path {
+            "/metrics" => self.metrics.render(),
+            "/healthz" => self.health.healthz(),
+            "/readyz" => self.health.readyz(),
+            _ => text_response(404, "not found\n".to_owned()),
+        }
+    }
+}
+
+pub(crate) fn text_response(status_code: u16, text: String) -> HttpResponse {
+    let header = Header::from_bytes(&b"Content-Type"[..], &b"text/plain; charset=utf-8"[..])
+        .expect("a valid header");
+    Response::from_string(text)
+        .with_status_code(status_code)
+        .with_header(header)
+}
+
+pub(crate) fn html_response(status_code: u16, html: String) -> HttpResponse {
+    let header = Header::from_bytes(&b"Content-Type"[..], &b"text/html; charset=utf-8"[..])
+        .expect("a valid header");
+    Response::from_string(html)
+        .with_status_code(status_code)
+        .with_header(header)
+}
+
+pub(crate) fn json_response(status_code: u16, value: &serde_json::Value) -> HttpResponse {
+    json_text_response(status_code, value.to_string())
+}
+
+pub(crate) fn json_text_response(status_code: u16, text: String) -> HttpResponse {
+    let header =
+        Header::from_bytes(&b"Content-Type"[..], &b"application/json"[..]).expect("a valid header");
+    Response::from_string(text)
+        .with_status_code(status_code)
+        .with_header(header)
+}
+
+// A storage in memory, for the tests of the services.
+#[cfg(test)]
+fn memory_storage() -> kernel::Storage {
+    let rt = crate::subcmd::sync::initialize_runtime()
+        .map(std::sync::Arc::new)
+        .unwrap();
+    kernel::Storage::connect(rt, "sqlite::memory:").unwrap()
+}
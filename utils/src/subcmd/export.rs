@@ -0,0 +1,213 @@
+// Copyright (C) 2019-2020 Boyu Yang
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+use std::{
+    cmp, fs,
+    path::{Path, PathBuf},
+    sync::Arc,
+};
+
+use kernel::{traits::Export as _, ColumnType, Dataset, FromValue, Row, Storage};
+use parquet::{
+    basic::Compression,
+    column::writer::ColumnWriter,
+    data_type::ByteArray,
+    file::{
+        properties::WriterProperties,
+        writer::{FileWriter as _, SerializedFileWriter},
+    },
+    schema::parser::parse_message_type,
+};
+
+use super::sync::initialize_runtime;
+use crate::{
+    config::ExportArgs,
+    error::{Error, Result},
+};
+
+pub(crate) fn execute(args: ExportArgs) -> Result<()> {
+    let rt = initialize_runtime().map(Arc::new)?;
+    let storage = Storage::connect(Arc::clone(&rt), args.storage_uri())?;
+    let tip = if let Some(tip) = storage
+        .export_tip()?
+        .and_then(|tip| tip.checked_sub(args.confirmations()))
+    {
+        tip
+    } else {
+        log::info!("no confirmed blocks to export");
+        return Ok(());
+    };
+    log::info!("export confirmed blocks up to {}", tip);
+    let size = args.partition_size();
+    for dataset in args.datasets() {
+        let dir = args.output_dir().join(dataset.name());
+        fs::create_dir_all(&dir)?;
+        let mut start = 0;
+        let mut partial = None;
+        if let Some((first, last, path)) = last_partition(&dir)? {
+            start = last + 1;
+            // The last partition which doesn't end on a multiple of the partition size is
+            // replaced by the one which is extended with the new blocks, so the partitions are
+            // aligned to the multiples of the partition size.
+            if start % size != 0 && start <= tip {
+                start = first;
+                partial = Some(path);
+            }
+        }
+        while start <= tip {
+            let end = cmp::min((start / size + 1) * size - 1, tip);
+            let rows = storage.export(*dataset, start, end)?;
+            log::info!(
+                "export {} rows of {} in blocks [{}, {}]",
+                rows.len(),
+                dataset,
+                start,
+                end
+            );
+            write_partition(&dir, *dataset, start, end, &rows)?;
+            if let Some(path) = partial.take() {
+                log::info!("remove the replaced partition {}", path.display());
+                fs::remove_file(path)?;
+            }
+            start = end + 1;
+        }
+    }
+    Ok(())
+}
+
+fn partition_file_name(start: u64, end: u64) -> String {
+    format!("blocks-{:012}-{:012}.parquet", start, end)
+}
+
+// Returns the first and the last blocks of the last exported partition and its path, by the
+// names of the existed partitions.
+//
+// The partitions which start as the last one but end before it are left by an interrupted
+// replacement, they are removed.
+fn last_partition(dir: &Path) -> Result<Option<(u64, u64, PathBuf)>> {
+    let mut partitions = Vec::new();
+    for entry in fs::read_dir(dir)? {
+        let path = entry?.path();
+        let range_opt = path
+            .file_name()
+            .and_then(|name| name.to_str())
+            .and_then(|name| name.strip_prefix("blocks-"))
+            .and_then(|name| name.strip_suffix(".parquet"))
+            .and_then(|range| {
+                let mut numbers = range.split('-').map(|number| number.parse::<u64>().ok());
+                Some((numbers.next()??, numbers.next()??))
+            });
+        if let Some((start, end)) = range_opt {
+            partitions.push((start, end, path));
+        }
+    }
+    partitions.sort_by_key(|(_, end, _)| *end);
+    let last = partitions.pop();
+    if let Some((last_start, _, _)) = last {
+        for (start, _, path) in partitions {
+            if start == last_start {
+                log::info!("remove the replaced partition {}", path.display());
+                fs::remove_file(path)?;
+            }
+        }
+    }
+    Ok(last)
+}
+
+fn message_type(dataset: Dataset) -> String {
+    let fields = dataset
+        .columns()
+        .iter()
+        .map(|column| {
+            let repetition = if column.nullable {
+                "OPTIONAL"
+            } else {
+                "REQUIRED"
+            };
+            let physical = match column.type_ {
+                ColumnType::Integer => "INT64",
+                ColumnType::Bytes => "BYTE_ARRAY",
+            };
+            format!("    {} {} {};\n", repetition, physical, column.name)
+        })
+        .collect::<String>();
+    format!("message {} {{\n{}}}", dataset.name(), fields)
+}
+
+// Collects the values of a column and the definition levels if the column is nullable.
+fn column_values<T: FromValue>(
+    rows: &[Row],
+    index: usize,
+    nullable: bool,
+) -> Result<(Vec<T>, Option<Vec<i16>>)> {
+    let mut values = Vec::with_capacity(rows.len());
+    let mut def_levels = Vec::with_capacity(rows.len());
+    for row in rows {
+        if let Some(value) = row.try_get::<Option<T>>(index)? {
+            values.push(value);
+            def_levels.push(1);
+        } else if nullable {
+            def_levels.push(0);
+        } else {
+            return Err(Error::Unreachable(format!(
+                "null value in the required column {}",
+                index
+            )));
+        }
+    }
+    Ok((values, if nullable { Some(def_levels) } else { None }))
+}
+
+// Writes into a temporary file then renames it, so a partition is never partially written.
+fn write_partition(
+    dir: &Path,
+    dataset: Dataset,
+    start: u64,
+    end: u64,
+    rows: &[Row],
+) -> Result<PathBuf> {
+    let path = dir.join(partition_file_name(start, end));
+    let tmp_path = path.with_extension("parquet.tmp");
+    let schema = parse_message_type(&message_type(dataset)).map(Arc::new)?;
+    let props = WriterProperties::builder()
+        .set_compression(Compression::SNAPPY)
+        .build();
+    let file = fs::File::create(&tmp_path)?;
+    let mut writer = SerializedFileWriter::new(file, schema, Arc::new(props))?;
+    if !rows.is_empty() {
+        let mut row_group = writer.next_row_group()?;
+        for (index, column) in dataset.columns().iter().enumerate() {
+            let mut column_writer = row_group
+                .next_column()?
+                .ok_or_else(|| Error::Unreachable("no more columns".to_owned()))?;
+            match column_writer {
+                ColumnWriter::Int64ColumnWriter(ref mut typed) => {
+                    let (values, def_levels) = column_values::<i64>(rows, index, column.nullable)?;
+                    typed.write_batch(&values, def_levels.as_deref(), None)?;
+                }
+                ColumnWriter::ByteArrayColumnWriter(ref mut typed) => {
+                    let (values, def_levels) =
+                        column_values::<Vec<u8>>(rows, index, column.nullable)?;
+                    let values = values.into_iter().map(ByteArray::from).collect::<Vec<_>>();
+                    typed.write_batch(&values, def_levels.as_deref(), None)?;
+                }
+                _ => {
+                    return Err(Error::Unreachable(format!(
+                        "unexpected column type of {}",
+                        column.name
+                    )));
+                }
+            }
+            row_group.close_column(column_writer)?;
+        }
+        writer.close_row_group(row_group)?;
+    }
+    writer.close()?;
+    fs::rename(&tmp_path, &path)?;
+    Ok(path)
+}
@@ -0,0 +1,119 @@
+// Copyright (C) 2019-2020 Boyu Yang
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+use std::{
+    path::{Path, PathBuf},
+    sync::Arc,
+};
+
+use kernel::{traits::BaseData as _, Network, Registry, Report, Storage, Value};
+
+use super::sync::initialize_runtime;
+use crate::{
+    config::KnownScriptsArgs,
+    error::{Error, Result},
+    service::parse_hash,
+};
+
+const COLUMNS: &[&str] = &["name", "code_hash", "hash_type", "cell_deps"];
+
+pub(crate) fn execute(args: KnownScriptsArgs) -> Result<()> {
+    let storage = if let Some(uri) = args.storage_uri() {
+        let rt = initialize_runtime().map(Arc::new)?;
+        Some(Storage::connect(rt, uri)?)
+    } else {
+        None
+    };
+    let (network, registry) = load_registry(
+        storage.as_ref(),
+        args.network().copied(),
+        args.registry().map(PathBuf::as_path),
+    )?;
+    log::info!("the known scripts of {}", network);
+    let report = generate(&registry, args.search().map(String::as_str))?;
+    args.format().print(&report)
+}
+
+/// Chooses the network and loads the known scripts of it, and the scripts in the file if it
+/// is provided.
+///
+/// If the network is not set, it is detected from the genesis block in the storage, the
+/// mainnet is used if the genesis block is not stored or is not a known one.
+pub(crate) fn load_registry(
+    storage: Option<&Storage>,
+    network: Option<Network>,
+    file: Option<&Path>,
+) -> Result<(Network, Registry)> {
+    let network = if let Some(network) = network {
+        network
+    } else {
+        let genesis_hash = storage
+            .map(|storage| storage.block_hash(0))
+            .transpose()?
+            .flatten();
+        genesis_hash
+            .and_then(|hash| Registry::detect_network(&hash.raw_data()))
+            .unwrap_or_else(|| {
+                log::warn!("failed to detect the network, use mainnet");
+                Network::Mainnet
+            })
+    };
+    let mut registry = Registry::builtin(network);
+    if let Some(path) = file {
+        registry.load(path)?;
+    }
+    Ok((network, registry))
+}
+
+// Lists the known scripts, or only the ones which match the name, the code hash or the tx hash
+// of a cell dep, with their cell deps.
+fn generate(registry: &Registry, search: Option<&str>) -> Result<Report> {
+    let hash = search
+        .filter(|search| search.starts_with("0x"))
+        .map(parse_hash)
+        .transpose()?;
+    let mut report = Report::new(COLUMNS);
+    for script in registry.scripts() {
+        let cell_deps = registry
+            .cell_deps()
+            .iter()
+            .filter(|cell_dep| cell_dep.name() == script.name())
+            .collect::<Vec<_>>();
+        let matched = match (search, &hash) {
+            (None, _) => true,
+            (Some(_), Some(hash)) => {
+                script.code_hash() == hash
+                    || cell_deps.iter().any(|cell_dep| cell_dep.tx_hash() == hash)
+            }
+            (Some(name), None) => script.name() == name,
+        };
+        if !matched {
+            continue;
+        }
+        let cell_deps = cell_deps
+            .iter()
+            .map(|cell_dep| {
+                let dep_type = match cell_dep.dep_type() {
+                    0 => "code",
+                    1 => "dep_group",
+                    _ => return Err(Error::Unreachable("unknown dep type".to_owned())),
+                };
+                let tx_hash = faster_hex::hex_string(cell_dep.tx_hash())
+                    .map_err(|err| Error::Unreachable(err.to_string()))?;
+                Ok(format!("0x{}:{}:{}", tx_hash, cell_dep.index(), dep_type))
+            })
+            .collect::<Result<Vec<_>>>()?;
+        report.push(vec![
+            Value::Text(script.name().to_owned()),
+            Value::Bytes(script.code_hash().to_owned()),
+            Value::Integer(i64::from(script.hash_type())),
+            Value::Text(cell_deps.join(" ")),
+        ]);
+    }
+    Ok(report)
+}
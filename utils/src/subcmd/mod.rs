@@ -6,4 +6,10 @@
 // option. This file may not be copied, modified, or distributed
 // except according to those terms.
 
+pub(crate) mod export;
+pub(crate) mod known_scripts;
+pub(crate) mod query;
+pub(crate) mod rebuild_rollups;
+pub(crate) mod serve;
+pub(crate) mod stats;
 pub(crate) mod sync;
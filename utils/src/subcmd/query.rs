@@ -0,0 +1,21 @@
+// Copyright (C) 2019-2020 Boyu Yang
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+use std::sync::Arc;
+
+use kernel::{Report, Storage};
+
+use super::sync::initialize_runtime;
+use crate::{config::QueryArgs, error::Result};
+
+pub(crate) fn execute(args: QueryArgs) -> Result<()> {
+    let rt = initialize_runtime().map(Arc::new)?;
+    let storage = Storage::connect(Arc::clone(&rt), args.storage_uri())?;
+    let report = storage.query(args.sql(), &[]).map(Report::from)?;
+    args.format().print(&report)
+}
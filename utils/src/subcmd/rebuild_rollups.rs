@@ -0,0 +1,28 @@
+// Copyright (C) 2019-2020 Boyu Yang
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+use std::sync::Arc;
+
+use kernel::{
+    traits::{BaseData as _, Rollups as _},
+    Storage,
+};
+
+use super::sync::initialize_runtime;
+use crate::{config::RebuildRollupsArgs, error::Result};
+
+pub(crate) fn execute(args: RebuildRollupsArgs) -> Result<()> {
+    let rt = initialize_runtime().map(Arc::new)?;
+    let mut storage = Storage::connect(Arc::clone(&rt), args.storage_uri())?;
+    if let Some(tip) = storage.initialize()? {
+        log::info!("rebuild the rollups of the blocks up to {}", tip);
+    } else {
+        log::info!("no blocks are stored, the rollups are empty");
+    }
+    storage.rebuild_rollups().map_err(Into::into)
+}
@@ -0,0 +1,182 @@
+// Copyright (C) 2019-2020 Boyu Yang
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+use std::{io::Read as _, net::SocketAddr, path::PathBuf, sync::Arc, thread};
+
+use kernel::Storage;
+use serde_json::json;
+use tiny_http::{Method, Request, Server};
+
+use super::{known_scripts::load_registry, sync::initialize_runtime};
+use crate::{
+    config::ServeArgs,
+    error::{Error, Result},
+    service::{json_response, Api, Graphql, HttpResponse, IndexerRpc},
+};
+
+const GRAPHQL_PATH: &str = "/graphql";
+// The limit of the size of a request body, in bytes.
+const MAX_BODY_SIZE: u64 = 1024 * 1024;
+
+pub(crate) fn execute(args: ServeArgs) -> Result<()> {
+    let rt = initialize_runtime().map(Arc::new)?;
+    let connect = || Storage::connect(Arc::clone(&rt), args.storage_uri());
+    let (network, registry) = load_registry(
+        Some(&connect()?),
+        args.network().copied(),
+        args.registry().map(PathBuf::as_path),
+    )?;
+    log::info!("serve the chain of {}", network);
+    let mut handles = Vec::new();
+    {
+        let server = bind(args.listen())?;
+        log::info!("serve the rest api on {}", args.listen());
+        for id in 0..args.workers() {
+            let api = Api::new(connect()?, network, registry.clone());
+            let name = format!("api-worker-{}", id);
+            let handle = spawn_worker(&server, name, move |request| match request.method() {
+                Method::Get => api.handle(request.url()),
+                _ => json_response(405, &json!({ "error": "method not allowed" })),
+            })?;
+            handles.push(handle);
+        }
+    }
+    if let Some(addr) = args.indexer_listen() {
+        let server = bind(addr)?;
+        log::info!("serve the indexer rpc on {}", addr);
+        for id in 0..args.workers() {
+            let rpc = connect().map(IndexerRpc::new)?;
+            let name = format!("indexer-worker-{}", id);
+            let handle = spawn_worker(&server, name, move |request| match request.method() {
+                Method::Post => match read_body(request) {
+                    Ok(body) => rpc.handle(&body),
+                    Err(response) => response,
+                },
+                _ => json_response(405, &json!({ "error": "method not allowed" })),
+            })?;
+            handles.push(handle);
+        }
+    }
+    if let Some(addr) = args.graphql_listen() {
+        let server = bind(addr)?;
+        log::info!("serve the graphql api on {}", addr);
+        for id in 0..args.workers() {
+            let graphql = Graphql::new(connect()?, network);
+            let name = format!("graphql-worker-{}", id);
+            let handle = spawn_worker(&server, name, move |request| {
+                if request.url().split('?').next() != Some(GRAPHQL_PATH) {
+                    return json_response(404, &json!({ "error": "not found" }));
+                }
+                match request.method() {
+                    Method::Get => Graphql::graphiql(GRAPHQL_PATH),
+                    Method::Post => match read_body(request) {
+                        Ok(body) => graphql.handle(&body),
+                        Err(response) => response,
+                    },
+                    _ => json_response(405, &json!({ "error": "method not allowed" })),
+                }
+            })?;
+            handles.push(handle);
+        }
+    }
+    for handle in handles {
+        handle
+            .join()
+            .map_err(|_| Error::Unreachable("a worker panicked".to_owned()))?;
+    }
+    Ok(())
+}
+
+fn bind(addr: &SocketAddr) -> Result<Arc<Server>> {
+    Server::http(addr)
+        .map(Arc::new)
+        .map_err(|err| Error::Http(err.to_string()))
+}
+
+// Reads the body of the request, the body which is larger than the limit is rejected without
+// being read entirely.
+fn read_body(request: &mut Request) -> std::result::Result<String, HttpResponse> {
+    let too_large = || json_response(413, &json!({ "error": "payload too large" }));
+    if request.body_length().unwrap_or(0) as u64 > MAX_BODY_SIZE {
+        return Err(too_large());
+    }
+    let mut body = String::new();
+    match request
+        .as_reader()
+        .take(MAX_BODY_SIZE + 1)
+        .read_to_string(&mut body)
+    {
+        Ok(size) if size as u64 > MAX_BODY_SIZE => Err(too_large()),
+        Ok(_) => Ok(body),
+        Err(err) => Err(json_response(400, &json!({ "error": err.to_string() }))),
+    }
+}
+
+// Each worker handles the requests in its own thread, the workers of a server share requests.
+fn spawn_worker<F>(server: &Arc<Server>, name: String, handle: F) -> Result<thread::JoinHandle<()>>
+where
+    F: Fn(&mut Request) -> HttpResponse + Send + 'static,
+{
+    let server = Arc::clone(server);
+    thread::Builder::new()
+        .name(name)
+        .spawn(move || {
+            for mut request in server.incoming_requests() {
+                log::trace!("http request {} {}", request.method(), request.url());
+                let response = handle(&mut request);
+                if let Err(err) = request.respond(response) {
+                    log::warn!("failed to respond a http request since {}", err);
+                }
+            }
+        })
+        .map_err(Into::into)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::str::FromStr as _;
+
+    use tiny_http::{Header, Method, Request, TestRequest};
+
+    use super::{read_body, MAX_BODY_SIZE};
+
+    fn request(body: String, chunked: bool) -> Request {
+        let mut request = TestRequest::new().with_method(Method::Post);
+        let body = if chunked {
+            let header = Header::from_str("Transfer-Encoding: chunked").unwrap();
+            request = request.with_header(header);
+            format!("{:x}\r\n{}\r\n0\r\n\r\n", body.len(), body)
+        } else {
+            body
+        };
+        request.with_body(Box::leak(body.into_boxed_str())).into()
+    }
+
+    #[test]
+    fn read_body_with_length() {
+        let body = "a".repeat(MAX_BODY_SIZE as usize);
+        assert_eq!(
+            read_body(&mut request(body.clone(), false)).ok(),
+            Some(body)
+        );
+        let body = "a".repeat(MAX_BODY_SIZE as usize + 1);
+        let response = read_body(&mut request(body, false)).unwrap_err();
+        assert_eq!(response.status_code().0, 413);
+    }
+
+    #[test]
+    fn read_body_without_length() {
+        let body = "a".repeat(MAX_BODY_SIZE as usize);
+        let mut request_without_length = request(body.clone(), true);
+        assert_eq!(request_without_length.body_length(), None);
+        assert_eq!(read_body(&mut request_without_length).ok(), Some(body));
+        let body = "a".repeat(MAX_BODY_SIZE as usize + 1);
+        let response = read_body(&mut request(body, true)).unwrap_err();
+        assert_eq!(response.status_code().0, 413);
+    }
+}
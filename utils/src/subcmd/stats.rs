@@ -0,0 +1,237 @@
+// Copyright (C) 2019-2020 Boyu Yang
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+use std::{
+    path::PathBuf,
+    sync::Arc,
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use kernel::{
+    traits::{
+        Epochs as _, Explorer as _, Mining as _, Proposals as _, Rollups as _, ScriptUsage as _,
+        Stats as _, Udt as _,
+    },
+    Address, Excluded, Network, Registry, Report, Storage, Value,
+};
+
+use super::{known_scripts::load_registry, sync::initialize_runtime};
+use crate::{
+    config::{StatsArgs, StatsReport},
+    error::{Error, Result},
+    service::{parse_hash, parse_lock_hash},
+};
+
+pub(crate) fn execute(args: StatsArgs) -> Result<()> {
+    let rt = initialize_runtime().map(Arc::new)?;
+    let mut storage = Storage::connect(Arc::clone(&rt), args.storage_uri())?;
+    if args.refresh() {
+        storage.refresh_script_usage()?;
+    }
+    let (network, registry) = load_registry(
+        Some(&storage),
+        args.network().copied(),
+        args.registry().map(PathBuf::as_path),
+    )?;
+    let report = generate(&storage, args.report().clone(), network, &registry)?;
+    args.format().print(&report)
+}
+
+pub(crate) fn generate(
+    storage: &Storage,
+    report: StatsReport,
+    network: Network,
+    registry: &Registry,
+) -> Result<Report> {
+    let report = match report {
+        StatsReport::Summary => storage.summary(),
+        StatsReport::Supply { at, step } => {
+            let numbers = supply_heights(storage, at, step)?;
+            storage.supply(&numbers)
+        }
+        StatsReport::Richlist {
+            at,
+            limit,
+            excluded,
+            exclude_dao,
+        } => {
+            let excluded = parse_excluded(&excluded, exclude_dao, network, registry)?;
+            storage.richlist(at, limit, &excluded)
+        }
+        StatsReport::Distribution {
+            at,
+            excluded,
+            exclude_dao,
+        } => {
+            let excluded = parse_excluded(&excluded, exclude_dao, network, registry)?;
+            storage.distribution(at, &excluded)
+        }
+        StatsReport::Scripts => storage.script_usage(),
+        StatsReport::ScriptEpochs {
+            code_hash,
+            hash_type,
+        } => {
+            let code_hash = parse_hash(&code_hash)?;
+            let hash_type = match hash_type.as_str() {
+                "data" => 0,
+                "type" => 1,
+                _ => return Err(Error::Argument(format!("unknown hash type {}", hash_type))),
+            };
+            storage.script_usage_by_epoch(&code_hash, hash_type)
+        }
+        StatsReport::Tokens => storage.udt_tokens(),
+        StatsReport::TokenHolders { type_hash, limit } => {
+            let type_hash = parse_hash(&type_hash)?;
+            storage.udt_holders(&type_hash, limit)
+        }
+        StatsReport::TokenTransactions { type_hash, limit } => {
+            let type_hash = parse_hash(&type_hash)?;
+            storage.udt_transactions(&type_hash, limit)
+        }
+        StatsReport::BlockRewards { limit } => storage.block_rewards(limit),
+        StatsReport::MinerEpochs { epochs } => storage.miners_by_epoch(epochs),
+        StatsReport::MinerDays { days } => storage.miners_by_day(days),
+        StatsReport::Hashrate { epochs } => storage.hashrate(epochs),
+        StatsReport::Uncles { epochs } => storage.uncles_by_epoch(epochs),
+        StatsReport::UncleMiners { epochs } => storage.uncle_miners_by_epoch(epochs),
+        StatsReport::ProposalLatencies { epochs } => storage.proposal_latencies(epochs),
+        StatsReport::Proposals { epochs } => storage.proposals_by_epoch(epochs),
+        StatsReport::UncommittedProposals { limit } => storage.uncommitted_proposals(limit),
+        StatsReport::DuplicateProposals { epochs } => storage.duplicate_proposals(epochs),
+        StatsReport::IntervalEpochs { epochs } => storage.block_intervals_by_epoch(epochs),
+        StatsReport::IntervalDays { days } => storage.block_intervals_by_day(days),
+        StatsReport::TimestampAnomalies { limit } => {
+            let now = SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .map(|duration| duration.as_millis() as u64)
+                .unwrap_or_default();
+            storage.timestamp_anomalies(now, limit)
+        }
+        StatsReport::EpochDurations { epochs } => storage.epoch_durations(epochs),
+        StatsReport::Epochs { epochs } => storage.epoch_summaries(epochs),
+        StatsReport::RollupDays { days } => storage.rollups_by_day(days),
+        StatsReport::RollupHours { hours } => storage.rollups_by_hour(hours),
+    }?;
+    let report = with_names(report, registry)?;
+    with_addresses(report, network)
+}
+
+// The inputs are the names of known scripts, lock hashes or addresses, the deposits of NervosDAO
+// are excluded by the known script `dao`.
+fn parse_excluded(
+    inputs: &[String],
+    exclude_dao: bool,
+    network: Network,
+    registry: &Registry,
+) -> Result<Excluded> {
+    let mut excluded = Excluded::default();
+    let names =
+        inputs
+            .iter()
+            .map(String::as_str)
+            .chain(if exclude_dao { Some("dao") } else { None });
+    for input in names {
+        let scripts = registry.code_hashes(input);
+        if !scripts.is_empty() {
+            let scripts = scripts
+                .into_iter()
+                .map(|(code_hash, hash_type)| (code_hash.to_owned(), hash_type));
+            excluded.scripts.extend(scripts);
+        } else if exclude_dao && input == "dao" {
+            return Err(Error::Argument(format!(
+                "no known script dao for {}",
+                network
+            )));
+        } else {
+            excluded.lock_hashes.push(parse_lock_hash(input, network)?);
+        }
+    }
+    Ok(excluded)
+}
+
+// Appends the names of the scripts if the report has code hashes and hash types, the names of
+// unknown scripts are null.
+fn with_names(report: Report, registry: &Registry) -> Result<Report> {
+    let position = |name: &str| report.columns().iter().position(|column| column == name);
+    let (code_hash, hash_type) = match (position("code_hash"), position("hash_type")) {
+        (Some(code_hash), Some(hash_type)) => (code_hash, hash_type),
+        _ => return Ok(report),
+    };
+    let mut columns = report
+        .columns()
+        .iter()
+        .map(String::as_str)
+        .collect::<Vec<_>>();
+    columns.push("name");
+    let mut with_names = Report::new(&columns);
+    for row in report.rows() {
+        let name = match (&row[code_hash], &row[hash_type]) {
+            (Value::Bytes(code_hash), Value::Integer(hash_type)) => registry
+                .name(code_hash, *hash_type as u8)
+                .map(|name| Value::Text(name.to_owned()))
+                .unwrap_or(Value::Null),
+            _ => return Err(Error::Unreachable("a script has null fields".to_owned())),
+        };
+        let mut row = row.clone();
+        row.push(name);
+        with_names.push(row);
+    }
+    Ok(with_names)
+}
+
+// Appends the addresses of the locks if the report has the fields of lock scripts, the short
+// format is preferred.
+fn with_addresses(report: Report, network: Network) -> Result<Report> {
+    let position = |name: &str| report.columns().iter().position(|column| column == name);
+    let (code_hash, hash_type, args) = match (
+        position("code_hash"),
+        position("hash_type"),
+        position("args"),
+    ) {
+        (Some(code_hash), Some(hash_type), Some(args)) => (code_hash, hash_type, args),
+        _ => return Ok(report),
+    };
+    let mut columns = report
+        .columns()
+        .iter()
+        .map(String::as_str)
+        .collect::<Vec<_>>();
+    columns.push("address");
+    let mut with_addresses = Report::new(&columns);
+    for row in report.rows() {
+        let address = match (&row[code_hash], &row[hash_type], &row[args]) {
+            (Value::Bytes(code_hash), Value::Integer(hash_type), Value::Bytes(args)) => {
+                let address = Address::new(network, code_hash, *hash_type as u8, args)?;
+                address.to_short().unwrap_or_else(|| address.to_full())
+            }
+            _ => return Err(Error::Unreachable("a script has null fields".to_owned())),
+        };
+        let mut row = row.clone();
+        row.push(Value::Text(address));
+        with_addresses.push(row);
+    }
+    Ok(with_addresses)
+}
+
+// The multiples of the step before the end, and the end itself.
+fn supply_heights(storage: &Storage, at: Option<u64>, step: Option<u64>) -> Result<Vec<u64>> {
+    let end = if let Some(at) = at {
+        at
+    } else if let Some(tip) = storage.blocks(None, 1)?.first() {
+        tip.try_get::<i64>(0)? as u64
+    } else {
+        return Ok(Vec::new());
+    };
+    let mut numbers = if let Some(step) = step {
+        (0..end).step_by(step as usize).collect()
+    } else {
+        Vec::new()
+    };
+    numbers.push(end);
+    Ok(numbers)
+}
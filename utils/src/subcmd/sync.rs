@@ -7,104 +7,79 @@
 // except according to those terms.
 
 use std::{
-    cmp,
+    path::PathBuf,
     sync::{atomic, Arc},
-    thread,
-    time::Duration,
 };
 
 use jsonrpc_server_utils::tokio::runtime as runtime01;
-use kernel::{error::Error as KernelError, traits::BaseData as _, Storage};
+use kernel::{traits::BlockSource, FileSource, Storage, Syncer};
 use parking_lot::RwLock;
 use tokio::runtime;
 use uckb_jsonrpc_client::Client;
 
-use crate::{config::SyncArgs, error::Result};
-
-fn blocking_n_secs(n: u64) {
-    let wait_secs = Duration::from_secs(n);
-    thread::sleep(wait_secs);
-}
+use super::known_scripts::load_registry;
+use crate::{
+    config::{SyncArgs, SyncSource},
+    error::Result,
+    service::{Health, Metrics, Service, SyncState},
+};
 
 pub(crate) fn execute(args: SyncArgs) -> Result<()> {
     let rt = initialize_runtime().map(Arc::new)?;
-    let rt01 = initialize_runtime01().map(RwLock::new).map(Arc::new)?;
     let mut storage = Storage::connect(Arc::clone(&rt), args.storage_uri())?;
-    let client = {
-        let mut client = Client::new(Arc::clone(&rt), Arc::clone(&rt01));
-        client
-            .enable_http(args.jsonrpc_url())?
-            .enable_tcp(args.subscribe_socket())?;
-        client
-    };
-    let mut next = storage.initialize()?.map(|n| n + 1).unwrap_or(0);
-    log::info!("current storage has base data before height {}", next);
-    let mut retry_cnt = 0;
-    let mut failed_cnt = 0;
-    'new_turn: loop {
-        let tip = match client.get_tip_block_number() {
-            Ok(tip) => {
-                failed_cnt = 0;
-                tip
-            }
-            Err(err) => {
-                log::error!("failed to get tip block number since {}", err);
-                failed_cnt += 1;
-                let wait_secs = cmp::min(failed_cnt * failed_cnt, 90);
-                log::trace!("retry after {} secs", wait_secs);
-                blocking_n_secs(wait_secs);
-                continue 'new_turn;
-            }
-        };
-        log::info!("current tip number is {}", tip);
-        if tip < next {
-            retry_cnt += 1;
-            let wait_secs = cmp::min(retry_cnt, 10);
-            log::trace!("no new block, retry after {} secs", wait_secs);
-            blocking_n_secs(wait_secs);
-            continue 'new_turn;
-        } else {
-            retry_cnt = 0;
+    if args.network().is_some() || args.registry().is_some() {
+        let (network, registry) = load_registry(
+            Some(&storage),
+            args.network().copied(),
+            args.registry().map(PathBuf::as_path),
+        )?;
+        log::info!("track the tokens with the known scripts of {}", network);
+        storage.set_registry(registry);
+    }
+    match args.source() {
+        SyncSource::JsonRpc {
+            jsonrpc_url,
+            subscribe_socket,
+        } => {
+            let rt01 = initialize_runtime01().map(RwLock::new).map(Arc::new)?;
+            let client = {
+                let mut client = Client::new(Arc::clone(&rt), Arc::clone(&rt01));
+                client
+                    .enable_http(jsonrpc_url)?
+                    .enable_tcp(subscribe_socket)?;
+                client
+            };
+            build_syncer(&args, &rt, client, storage)?.run()?;
         }
-
-        let mut rollback_to = None;
-        let mut i = next;
-        'sync_block: while i <= tip {
-            log::info!("synchronize block {} ...", i);
-            match client.get_block_by_number(i, None) {
-                Ok(Some(block)) => {
-                    let result = storage.insert_block(&block);
-                    if let Err(KernelError::UnknownParentBlock { number, hash }) = result {
-                        log::warn!("rollback unknown parent block ({}, {:#x})", number, hash);
-                        storage.remove_block(number)?;
-                        rollback_to = Some(number);
-                        break;
-                    } else {
-                        i += 1;
-                        failed_cnt = 0;
-                        result?;
-                    }
-                }
-                Ok(None) => {
-                    failed_cnt = 0;
-                    break;
-                }
-                Err(err) => {
-                    log::error!("failed to get block number {} since {}", i, err);
-                    failed_cnt += 1;
-                    let wait_secs = cmp::min(failed_cnt * failed_cnt, 90);
-                    log::trace!("retry after {} secs", wait_secs);
-                    blocking_n_secs(wait_secs);
-                    continue 'sync_block;
-                }
-            }
+        SyncSource::File(path) => {
+            let source = FileSource::open(path)?;
+            let tip = build_syncer(&args, &rt, source, storage)?.run_to_tip()?;
+            log::info!("synchronized all blocks in the file, the tip is {}", tip);
         }
-        next = if let Some(rollback_to) = rollback_to {
-            rollback_to
-        } else {
-            tip + 1
+    }
+    Ok(())
+}
+
+fn build_syncer<S: BlockSource>(
+    args: &SyncArgs,
+    rt: &Arc<runtime::Runtime>,
+    source: S,
+    storage: Storage,
+) -> Result<Syncer<S>> {
+    let mut syncer = Syncer::new(source, storage);
+    if let Some(addr) = args.http_listen() {
+        let metrics = Metrics::new()?;
+        let state = SyncState::default();
+        syncer
+            .add_listener(metrics.listener())
+            .add_listener(state.listener());
+        let health = {
+            let storage = Storage::connect(Arc::clone(rt), args.storage_uri())?;
+            Health::new(state, storage, args.ready_max_lag())
         };
+        Service::new(metrics, health).spawn(*addr)?;
     }
+    Ok(syncer)
 }
 
 pub(crate) fn initialize_runtime() -> Result<runtime::Runtime> {